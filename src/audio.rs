@@ -0,0 +1,202 @@
+use crate::config::AudioConfig;
+use crate::models::IntervalType;
+use crate::utils::{expand_tilde, in_daily_window};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveTime};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Something worth a sound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cue {
+    Focus,
+    Idle,
+    /// Another `milestone_mins` of uninterrupted focus.
+    Milestone,
+}
+
+/// Plays short sounds on state transitions and focus milestones.
+#[derive(Debug)]
+pub struct AudioCues {
+    config: AudioConfig,
+    quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    /// Milestones already played for the current focus stretch.
+    milestones: i64,
+}
+
+impl AudioCues {
+    pub fn from_config(config: &AudioConfig) -> Result<Self> {
+        Ok(Self {
+            config: config.clone(),
+            quiet_hours: config.quiet_hours.as_ref().map(|q| q.parse()).transpose()?,
+            milestones: 0,
+        })
+    }
+
+    /// Decide which cue, if any, a tick calls for. `previous` is the state before
+    /// the tick and `state_start` when the current state began.
+    pub fn cue_for(
+        &mut self,
+        previous: Option<IntervalType>,
+        current: IntervalType,
+        state_start: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Option<Cue> {
+        let cue = if previous != Some(current) {
+            self.milestones = 0;
+            match (previous, current) {
+                // No cue when the session starts or resumes after being away
+                (None, _) | (Some(IntervalType::Away), _) => None,
+                (_, IntervalType::Focus) => Some(Cue::Focus),
+                (_, IntervalType::Idle) => Some(Cue::Idle),
+                // Locking the screen is a deliberate step away
+                (_, IntervalType::Away | IntervalType::Locked) => None,
+            }
+        } else {
+            let step = self.config.milestone_mins.filter(|m| *m > 0)? as i64;
+            let reached = (now - state_start).num_minutes() / step;
+            (current == IntervalType::Focus && reached > self.milestones).then(|| {
+                self.milestones = reached;
+                Cue::Milestone
+            })
+        };
+        cue.filter(|_| !self.is_quiet(now.time()))
+    }
+
+    fn is_quiet(&self, time: NaiveTime) -> bool {
+        self.quiet_hours
+            .is_some_and(|(start, end)| in_daily_window(time, start, end))
+    }
+
+    /// Start playing `cue` without waiting for it to finish.
+    pub fn play(&self, cue: Cue) -> Result<()> {
+        let sound = match cue {
+            Cue::Focus => &self.config.on_focus,
+            Cue::Idle => &self.config.on_idle,
+            Cue::Milestone => &self.config.on_milestone,
+        };
+        let Some(sound) = sound else {
+            return Ok(());
+        };
+        let path = sound_path(sound);
+        let mut child = player_command(&path, self.config.volume)
+            .spawn()
+            .with_context(|| format!("Failed to play {}", path.display()))?;
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// A bare name refers to a system sound (e.g. "Tink"); anything with a path
+/// separator or extension is a file.
+fn sound_path(sound: &str) -> PathBuf {
+    if sound.contains('/') || sound.contains('.') {
+        expand_tilde(sound)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(format!("/System/Library/Sounds/{}.aiff", sound))
+    } else {
+        PathBuf::from(format!(
+            "/usr/share/sounds/freedesktop/stereo/{}.oga",
+            sound
+        ))
+    }
+}
+
+fn player_command(path: &std::path::Path, volume: f32) -> Command {
+    let mut command;
+    if cfg!(target_os = "macos") {
+        command = Command::new("afplay");
+        command.arg("-v").arg(volume.to_string());
+    } else {
+        command = Command::new("paplay");
+        command.arg(format!("--volume={}", (volume * 65536.0) as u32));
+    }
+    command.arg(path);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuietHours;
+    use chrono::{Duration, TimeZone};
+
+    fn at(h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2023, 1, 2, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_cues_for_transitions_and_milestones() {
+        let mut audio = AudioCues::from_config(&AudioConfig {
+            milestone_mins: Some(25),
+            ..Default::default()
+        })
+        .unwrap();
+        let start = at(9, 0);
+
+        assert_eq!(audio.cue_for(None, IntervalType::Focus, start, start), None);
+        assert_eq!(
+            audio.cue_for(
+                Some(IntervalType::Focus),
+                IntervalType::Focus,
+                start,
+                start + Duration::minutes(24)
+            ),
+            None
+        );
+        assert_eq!(
+            audio.cue_for(
+                Some(IntervalType::Focus),
+                IntervalType::Focus,
+                start,
+                start + Duration::minutes(25)
+            ),
+            Some(Cue::Milestone)
+        );
+        // Played once per milestone
+        assert_eq!(
+            audio.cue_for(
+                Some(IntervalType::Focus),
+                IntervalType::Focus,
+                start,
+                start + Duration::minutes(26)
+            ),
+            None
+        );
+        assert_eq!(
+            audio.cue_for(
+                Some(IntervalType::Focus),
+                IntervalType::Idle,
+                at(10, 0),
+                at(10, 0)
+            ),
+            Some(Cue::Idle)
+        );
+        assert_eq!(
+            audio.cue_for(
+                Some(IntervalType::Away),
+                IntervalType::Focus,
+                at(11, 0),
+                at(11, 0)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_quiet_hours_wrap_midnight() {
+        let mut audio = AudioCues::from_config(&AudioConfig {
+            quiet_hours: Some(QuietHours {
+                start: "22:00".to_string(),
+                end: "07:00".to_string(),
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+        for (time, quiet) in [(at(23, 0), true), (at(6, 59), true), (at(7, 0), false)] {
+            let cue = audio.cue_for(Some(IntervalType::Focus), IntervalType::Idle, time, time);
+            assert_eq!(cue.is_none(), quiet, "at {}", time);
+        }
+    }
+}
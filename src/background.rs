@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// Work running on its own thread, such as a download, so that a stalled
+/// network never holds up recording. The tracker polls it on later ticks
+/// instead of waiting for it.
+pub struct Job<T> {
+    result: Receiver<Result<T>>,
+}
+
+impl<T: Send + 'static> Job<T> {
+    pub fn spawn(work: impl FnOnce() -> Result<T> + Send + 'static) -> Self {
+        let (sender, result) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(work());
+        });
+        Self { result }
+    }
+
+    /// The job's result once it has finished, `None` while it is running.
+    pub fn poll(&self) -> Option<Result<T>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow!("A background job stopped"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn wait<T: Send + 'static>(job: &Job<T>) -> Result<T> {
+        loop {
+            match job.poll() {
+                Some(result) => return result,
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_poll_waits_for_the_result() {
+        let (go, wait_for_go) = mpsc::channel::<()>();
+        let job = Job::spawn(move || {
+            wait_for_go.recv()?;
+            Ok(42)
+        });
+        assert!(job.poll().is_none());
+
+        go.send(()).unwrap();
+        assert_eq!(wait(&job).unwrap(), 42);
+
+        let failed = Job::<()>::spawn(|| panic!("the job panicked"));
+        assert!(wait(&failed).is_err());
+    }
+}
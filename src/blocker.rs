@@ -0,0 +1,284 @@
+use crate::config::BlockerConfig;
+use crate::hooks::Hook;
+use crate::models::IntervalType;
+use anyhow::{bail, Context, Result};
+use chrono::Duration;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const HOSTS_BEGIN: &str = "# BEGIN neflo focus block";
+const HOSTS_END: &str = "# END neflo focus block";
+
+/// Blocks distracting sites during long focus stretches and lifts the block on
+/// idle, away or when the session ends.
+#[derive(Debug)]
+pub struct Blocker {
+    config: BlockerConfig,
+    after: Duration,
+    /// Starts out true so the first update undoes a block left behind by a crash.
+    active: bool,
+    /// Blocking failed during the current focus stretch; not retried until the next.
+    failed: bool,
+    /// Lifting the block failed and was reported; retried quietly until it works.
+    release_failing: bool,
+    block_hook: Option<Hook>,
+    unblock_hook: Option<Hook>,
+}
+
+impl Blocker {
+    pub fn from_config(config: &BlockerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            after: Duration::minutes(config.after_mins as i64),
+            active: true,
+            failed: false,
+            release_failing: false,
+            block_hook: hook(&config.block_command, config),
+            unblock_hook: hook(&config.unblock_command, config),
+        }
+    }
+
+    /// Block once the current focus stretch reaches `after_mins`; unblock otherwise.
+    pub fn update(&mut self, kind: IntervalType, stretch: Duration) -> Result<()> {
+        let want = kind == IntervalType::Focus && stretch >= self.after;
+        if !want {
+            self.failed = false;
+            // Retried on every update until it succeeds, but reported once
+            return match self.release_when_due() {
+                Ok(()) => {
+                    self.release_failing = false;
+                    Ok(())
+                }
+                Err(_) if self.release_failing => Ok(()),
+                Err(err) => {
+                    self.release_failing = true;
+                    Err(err)
+                }
+            };
+        }
+        if !self.active && !self.failed {
+            self.active = true;
+            if let Err(err) = self.block() {
+                // Do not leave a half-applied block behind
+                self.failed = true;
+                self.release_when_due()?;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Lift the block if it may be in place, running `unblock_command` even
+    /// while it is backing off after a failure, as before the session ends.
+    pub fn release(&mut self) -> Result<()> {
+        self.lift(true)
+    }
+
+    /// Lift the block if it may be in place, unless `unblock_command` is
+    /// backing off after a failure; the block then stays recorded as active.
+    fn release_when_due(&mut self) -> Result<()> {
+        self.lift(false)
+    }
+
+    fn lift(&mut self, now: bool) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        if !self.config.sites.is_empty() {
+            self.write_hosts(false)?;
+        }
+        if let Some(hook) = &mut self.unblock_hook {
+            if now {
+                hook.run_now()?;
+            } else if !hook.run()? {
+                return Ok(());
+            }
+        }
+        self.active = false;
+        Ok(())
+    }
+
+    fn block(&mut self) -> Result<()> {
+        if !self.config.sites.is_empty() {
+            self.write_hosts(true)?;
+        }
+        if let Some(hook) = &mut self.block_hook {
+            if !hook.run()? {
+                bail!("block_command failed recently; not running it again yet");
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite the hosts file through `sudo -n tee`, so only that command needs
+    /// a passwordless sudo rule.
+    fn write_hosts(&self, block: bool) -> Result<()> {
+        let path = Path::new(&self.config.hosts_file);
+        let current = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let sites: &[String] = if block { &self.config.sites } else { &[] };
+        let updated = hosts_with_block(&current, sites);
+        if updated == current {
+            return Ok(());
+        }
+
+        let mut child = Command::new("sudo")
+            .args(["-n", "tee"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run sudo")?;
+        child
+            .stdin
+            .take()
+            .context("Failed to open sudo stdin")?
+            .write_all(updated.as_bytes())?;
+        if !child.wait()?.success() {
+            bail!(
+                "Could not update {}; allow `sudo -n tee {}` without a password",
+                path.display(),
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// `hosts` with Neflo's section replaced by entries for `sites`, or removed
+/// when `sites` is empty. Lines outside the section are kept as they are.
+fn hosts_with_block(hosts: &str, sites: &[String]) -> String {
+    let mut out = String::new();
+    let mut in_block = false;
+    for line in hosts.lines() {
+        match line.trim() {
+            HOSTS_BEGIN => in_block = true,
+            HOSTS_END => in_block = false,
+            _ if !in_block => {
+                out.push_str(line);
+                out.push('\n');
+            }
+            _ => {}
+        }
+    }
+    if !sites.is_empty() {
+        out.push_str(HOSTS_BEGIN);
+        out.push('\n');
+        for site in sites {
+            for host in [site.clone(), format!("www.{}", site)] {
+                out.push_str(&format!("0.0.0.0 {}\n::1 {}\n", host, host));
+            }
+        }
+        out.push_str(HOSTS_END);
+        out.push('\n');
+    }
+    out
+}
+
+fn hook(command: &Option<Vec<String>>, config: &BlockerConfig) -> Option<Hook> {
+    let command = command.clone()?;
+    Some(Hook::new(
+        command,
+        std::time::Duration::from_secs(config.timeout_secs),
+        config.env_allow.clone(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hosts_block_round_trip() {
+        let hosts = "127.0.0.1 localhost\n";
+        let sites = vec!["news.ycombinator.com".to_string()];
+
+        let blocked = hosts_with_block(hosts, &sites);
+        assert!(blocked.starts_with("127.0.0.1 localhost\n# BEGIN neflo focus block\n"));
+        assert!(blocked.contains("0.0.0.0 www.news.ycombinator.com\n"));
+        // Blocking again does not duplicate the section
+        assert_eq!(hosts_with_block(&blocked, &sites), blocked);
+        assert_eq!(hosts_with_block(&blocked, &[]), hosts);
+    }
+
+    #[test]
+    fn test_blocks_after_focus_and_undoes_on_idle() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let marker = dir.path().join("blocked");
+        let marker_str = marker.to_string_lossy().to_string();
+        let mut blocker = Blocker::from_config(&BlockerConfig {
+            after_mins: 25,
+            block_command: Some(vec!["touch".to_string(), marker_str.clone()]),
+            unblock_command: Some(vec!["rm".to_string(), "-f".to_string(), marker_str]),
+            ..Default::default()
+        });
+
+        blocker.update(IntervalType::Focus, Duration::minutes(5))?;
+        assert!(!marker.exists());
+        blocker.update(IntervalType::Focus, Duration::minutes(25))?;
+        assert!(marker.exists());
+        blocker.update(IntervalType::Idle, Duration::zero())?;
+        assert!(!marker.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failing_release_is_reported_once() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let hosts = dir.path().join("hosts");
+        let mut blocker = Blocker::from_config(&BlockerConfig {
+            sites: vec!["news.ycombinator.com".to_string()],
+            hosts_file: hosts.to_string_lossy().to_string(),
+            ..Default::default()
+        });
+
+        // The hosts file cannot be read
+        assert!(blocker
+            .update(IntervalType::Idle, Duration::zero())
+            .is_err());
+        blocker.update(IntervalType::Idle, Duration::zero())?;
+        assert!(blocker.active);
+
+        fs::write(&hosts, "127.0.0.1 localhost\n")?;
+        blocker.update(IntervalType::Idle, Duration::zero())?;
+        assert!(!blocker.active);
+        Ok(())
+    }
+
+    #[test]
+    fn test_final_release_does_not_wait_for_the_backoff() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let marker = dir.path().join("blocked");
+        // Fails for as long as the stuck file exists
+        let stuck = dir.path().join("stuck");
+        let unblock = format!(
+            "test ! -e {} && rm -f {}",
+            stuck.to_string_lossy(),
+            marker.to_string_lossy()
+        );
+        let mut blocker = Blocker::from_config(&BlockerConfig {
+            unblock_command: Some(vec!["sh".to_string(), "-c".to_string(), unblock]),
+            ..Default::default()
+        });
+        fs::write(&marker, "")?;
+        fs::write(&stuck, "")?;
+
+        assert!(blocker
+            .update(IntervalType::Idle, Duration::zero())
+            .is_err());
+        // Backing off: skipped quietly and still recorded as active
+        blocker.update(IntervalType::Idle, Duration::zero())?;
+        assert!(blocker.active);
+        // Still failing at exit is an error, not a silent success
+        assert!(blocker.release().is_err());
+
+        fs::remove_file(&stuck)?;
+        blocker.release()?;
+        assert!(!blocker.active);
+        assert!(!marker.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use crate::models::{Interval, IntervalType};
+use crate::utils::format_duration;
+use anyhow::{anyhow, Result};
+use chrono::Duration;
+use std::collections::BTreeMap;
+
+/// A break of `min` to `max` suggested after focusing for at least `after`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakRule {
+    pub after: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl BreakRule {
+    /// Parse a `[breaks]` entry such as `"50m" = "10m-15m"`; a single
+    /// duration suggests a break of exactly that length.
+    pub fn parse(after: &str, range: &str) -> Result<Self> {
+        let parse = |s: &str| -> Result<Duration> {
+            Ok(Duration::from_std(humantime::parse_duration(s.trim())?)?)
+        };
+        let (min, max) = match range.split_once('-') {
+            Some((min, max)) => (parse(min)?, parse(max)?),
+            None => (parse(range)?, parse(range)?),
+        };
+        if min > max {
+            return Err(anyhow!("break range '{}' ends before it starts", range));
+        }
+        Ok(Self {
+            after: parse(after)?,
+            min,
+            max,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        if self.min == self.max {
+            format_duration(self.min.num_seconds())
+        } else {
+            format!(
+                "{}–{}",
+                format_duration(self.min.num_seconds()),
+                format_duration(self.max.num_seconds())
+            )
+        }
+    }
+}
+
+/// The mapping used when the config has no `[breaks]` table.
+pub fn default_table() -> BTreeMap<String, String> {
+    [("25m", "5m"), ("50m", "10m-15m"), ("90m", "15m-20m")]
+        .into_iter()
+        .map(|(after, range)| (after.to_string(), range.to_string()))
+        .collect()
+}
+
+/// Parse the `[breaks]` table, shortest focus first.
+pub fn parse_all(table: &BTreeMap<String, String>) -> Result<Vec<BreakRule>> {
+    let mut rules = table
+        .iter()
+        .map(|(after, range)| BreakRule::parse(after, range))
+        .collect::<Result<Vec<_>>>()?;
+    rules.sort_by_key(|rule| rule.after);
+    Ok(rules)
+}
+
+/// Suggest a break when the newest interval is idle time that directly
+/// followed focus, picking the rule for the longest focus reached.
+/// `latest` yields one device's intervals, newest first.
+pub fn suggest<'a>(
+    rules: &[BreakRule],
+    mut latest: impl Iterator<Item = &'a Interval>,
+) -> Option<String> {
+    let (idle, focus) = (latest.next()?, latest.next()?);
+    if idle.kind != IntervalType::Idle || focus.kind != IntervalType::Focus {
+        return None;
+    }
+    let focused = focus.end - focus.start;
+    let rule = rules.iter().rev().find(|rule| focused >= rule.after)?;
+    Some(format!(
+        "You focused {} — consider a {} break",
+        format_duration(focused.num_minutes() * 60),
+        rule.describe()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_suggest_picks_longest_rule_reached() -> Result<()> {
+        let rules = parse_all(&default_table())?;
+        assert!(BreakRule::parse("50m", "15m-10m").is_err());
+
+        let start = Utc::now();
+        let focus = |mins| Interval {
+            end: start + Duration::minutes(mins),
+            ..Interval::new_at(IntervalType::Focus, start)
+        };
+        let idle = |mins| Interval::new_at(IntervalType::Idle, start + Duration::minutes(mins));
+
+        let newest_first = |intervals: &[Interval]| suggest(&rules, intervals.iter().rev());
+        assert_eq!(
+            newest_first(&[focus(72), idle(72)]).as_deref(),
+            Some("You focused 1h 12m — consider a 10m–15m break")
+        );
+        assert_eq!(
+            newest_first(&[focus(30), idle(30)]).as_deref(),
+            Some("You focused 30m — consider a 5m break")
+        );
+        assert_eq!(newest_first(&[focus(10), idle(10)]), None);
+        assert_eq!(newest_first(&[idle(0), focus(72)]), None);
+        Ok(())
+    }
+}
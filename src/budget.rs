@@ -0,0 +1,164 @@
+use crate::stats::SummaryStats;
+use crate::utils::format_duration;
+use anyhow::{anyhow, Result};
+use chrono::Duration;
+use std::collections::BTreeMap;
+
+/// Names that refer to the week's totals rather than a tag.
+pub const BUILT_IN: [&str; 3] = ["focus", "deep_work", "idle"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    AtLeast,
+    AtMost,
+}
+
+/// A weekly time budget such as "deep_work >= 15h" or "admin <= 4h".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub name: String,
+    pub bound: Bound,
+    pub amount: Duration,
+}
+
+impl Target {
+    /// Parse `spec`, a bound (`>=`, `≥`, `<=` or `≤`) followed by a duration.
+    pub fn parse(name: &str, spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (bound, rest) = [
+            (">=", Bound::AtLeast),
+            ("≥", Bound::AtLeast),
+            ("<=", Bound::AtMost),
+            ("≤", Bound::AtMost),
+        ]
+        .iter()
+        .find_map(|(prefix, bound)| spec.strip_prefix(prefix).map(|rest| (*bound, rest)))
+        .ok_or_else(|| anyhow!("expected \">= 15h\" or \"<= 4h\", got '{}'", spec))?;
+        let amount = Duration::from_std(humantime::parse_duration(rest.trim())?)?;
+        Ok(Self {
+            name: name.to_string(),
+            bound,
+            amount,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let bound = match self.bound {
+            Bound::AtLeast => "≥",
+            Bound::AtMost => "≤",
+        };
+        format!(
+            "{} {} {}",
+            self.name,
+            bound,
+            format_duration(self.amount.num_seconds())
+        )
+    }
+}
+
+/// Parse the `[weekly_targets]` table.
+pub fn parse_all(specs: &BTreeMap<String, String>) -> Result<Vec<Target>> {
+    specs
+        .iter()
+        .map(|(name, spec)| Target::parse(name, spec))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Standing {
+    /// Within an upper bound, or a lower bound already reached.
+    Met,
+    /// A lower bound not reached yet.
+    Under,
+    /// An upper bound exceeded.
+    Over,
+}
+
+impl Standing {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Standing::Met => "✓",
+            Standing::Under => "▼",
+            Standing::Over => "▲",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub target: Target,
+    pub actual: Duration,
+    pub standing: Standing,
+}
+
+impl Evaluation {
+    /// The time so far and how far it is from the bound, e.g. "12h, 3h to go".
+    pub fn progress(&self) -> String {
+        let actual = format_duration(self.actual.num_seconds());
+        let gap = format_duration((self.target.amount - self.actual).abs().num_seconds());
+        match self.standing {
+            Standing::Under => format!("{}, {} to go", actual, gap),
+            Standing::Over => format!("{}, {} over", actual, gap),
+            Standing::Met => actual,
+        }
+    }
+}
+
+/// Compare the week so far against `targets`. Built-in names read `week`;
+/// any other name reads the focus recorded under that tag in `by_tag`.
+pub fn evaluate(
+    targets: &[Target],
+    week: &SummaryStats,
+    by_tag: &BTreeMap<String, Duration>,
+) -> Vec<Evaluation> {
+    targets
+        .iter()
+        .map(|target| {
+            let actual = match target.name.as_str() {
+                "focus" => week.total_focus,
+                "deep_work" => week.total_deep_work,
+                "idle" => week.total_idle,
+                tag => by_tag.get(tag).copied().unwrap_or_else(Duration::zero),
+            };
+            let standing = match target.bound {
+                Bound::AtLeast if actual < target.amount => Standing::Under,
+                Bound::AtMost if actual > target.amount => Standing::Over,
+                _ => Standing::Met,
+            };
+            Evaluation {
+                target: target.clone(),
+                actual,
+                standing,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_evaluate() -> Result<()> {
+        let targets = vec![
+            Target::parse("deep_work", ">= 15h")?,
+            Target::parse("admin", "≤4h")?,
+            Target::parse("idle", "<= 2h 30m")?,
+        ];
+        assert_eq!(targets[2].amount, Duration::minutes(150));
+        assert!(Target::parse("admin", "4h").is_err());
+
+        let week = SummaryStats {
+            total_deep_work: Duration::hours(10),
+            total_idle: Duration::hours(1),
+            ..Default::default()
+        };
+        let by_tag = BTreeMap::from([("admin".to_string(), Duration::hours(5))]);
+        let standings: Vec<_> = evaluate(&targets, &week, &by_tag)
+            .iter()
+            .map(|e| e.standing)
+            .collect();
+        assert_eq!(standings, [Standing::Under, Standing::Over, Standing::Met]);
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use crate::stats::Stats;
+use crate::utils::format_duration;
+use chrono::Duration;
+
+/// What a full bar stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Scale {
+    /// The week's longest day, so the shape shows how days compare.
+    #[default]
+    Relative,
+    /// A fixed amount of time, so light and heavy weeks look different.
+    Fixed(Duration),
+}
+
+/// One weekday of the weekly activity chart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayBar {
+    /// Short weekday name, e.g. "Mon".
+    pub label: String,
+    pub focus_secs: i64,
+    pub idle_secs: i64,
+}
+
+/// Focus and idle for each day of the current week, Monday first.
+pub fn week_bars(stats: &Stats) -> Vec<DayBar> {
+    (0..7)
+        .map(|i| {
+            let date = stats.week_start + Duration::days(i);
+            let day = stats.daily_stats.get(&date).cloned().unwrap_or_default();
+            DayBar {
+                label: date.format("%a").to_string(),
+                focus_secs: day.total_focus.num_seconds(),
+                idle_secs: day.total_idle.num_seconds(),
+            }
+        })
+        .collect()
+}
+
+/// Seconds that fill a whole bar; at least 1 to allow dividing.
+pub fn full_bar_secs(bars: &[DayBar], scale: Scale) -> i64 {
+    let secs = match scale {
+        Scale::Relative => bars
+            .iter()
+            .map(|bar| bar.focus_secs + bar.idle_secs)
+            .max()
+            .unwrap_or(0),
+        Scale::Fixed(full) => full.num_seconds(),
+    };
+    secs.max(1)
+}
+
+/// Cells of a bar `length` cells long taken by (focus, idle). Days longer
+/// than a full bar are cut off at its end.
+pub fn stack(bar: &DayBar, full_bar_secs: i64, length: u16) -> (u16, u16) {
+    let cells = |secs: i64| (secs * length as i64 / full_bar_secs).min(length as i64) as u16;
+    let focus = cells(bar.focus_secs);
+    (focus, cells(bar.idle_secs).min(length - focus))
+}
+
+/// The day's focus time, plus its share of a fixed full bar, e.g. "2h 25%".
+pub fn label(bar: &DayBar, scale: Scale) -> String {
+    if bar.focus_secs <= 0 {
+        return String::new();
+    }
+    let focus = format_duration(bar.focus_secs);
+    match scale {
+        Scale::Relative => focus,
+        Scale::Fixed(full) => format!(
+            "{} {}%",
+            focus,
+            bar.focus_secs * 100 / full.num_seconds().max(1)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_scales_to_longest_day() {
+        let bars = [
+            DayBar {
+                label: "Mon".to_string(),
+                focus_secs: 3 * 3600,
+                idle_secs: 3600,
+            },
+            DayBar {
+                label: "Tue".to_string(),
+                focus_secs: 3600,
+                idle_secs: 0,
+            },
+        ];
+        let full = full_bar_secs(&bars, Scale::Relative);
+        assert_eq!(full, 4 * 3600);
+        assert_eq!(stack(&bars[0], full, 20), (15, 5));
+        assert_eq!(stack(&bars[1], full, 20), (5, 0));
+        assert_eq!(full_bar_secs(&[], Scale::Relative), 1);
+        assert_eq!(label(&bars[1], Scale::Relative), "1h");
+    }
+
+    #[test]
+    fn test_fixed_scale_cuts_long_days() {
+        let bar = DayBar {
+            label: "Mon".to_string(),
+            focus_secs: 8 * 3600,
+            idle_secs: 4 * 3600,
+        };
+        let scale = Scale::Fixed(Duration::hours(10));
+        let full = full_bar_secs(std::slice::from_ref(&bar), scale);
+        assert_eq!(stack(&bar, full, 10), (8, 2));
+        assert_eq!(label(&bar, scale), "8h 80%");
+    }
+}
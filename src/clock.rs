@@ -0,0 +1,52 @@
+use chrono::{DateTime, Local, Utc};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+/// Source of the current time, so time-dependent behavior can be tested.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+
+    fn local_now(&self) -> DateTime<Local> {
+        self.now().with_timezone(&Local)
+    }
+}
+
+/// The real wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A fixed instant, for computing values as of one moment.
+impl Clock for DateTime<Utc> {
+    fn now(&self) -> DateTime<Utc> {
+        *self
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test can keep one and hand the other to the code under test.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct FixedClock(Arc<Mutex<DateTime<Utc>>>);
+
+#[cfg(test)]
+impl FixedClock {
+    pub fn new(at: DateTime<Utc>) -> Self {
+        Self(Arc::new(Mutex::new(at)))
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
@@ -1,43 +1,1169 @@
-use anyhow::Result;
+use crate::breaks::{self, BreakRule};
+use crate::budget::{self, Target};
+use crate::chart;
+use crate::models::IntervalType;
+use crate::stats::{IdleMerge, StatsOptions};
+use crate::tracker::OverlapMode;
+use anyhow::{Context, Result};
+use chrono::{NaiveTime, Weekday};
+use ratatui::style::Color;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Config {
-    pub default_threshold_mins: u64,
+/// Config written on first run. Kept in sync with `Config::default()` by tests.
+const DEFAULT_CONFIG: &str = r#"# Neflo configuration
+
+# Minutes without keyboard/mouse input before you are considered idle.
+default_threshold_mins = 5
+
+# Count time as focus, even without input, while the screen is shared or a
+# presentation runs (macOS; detected by an app keeping the display awake).
+# presenting_is_focus = false
+
+# Record how many keystrokes and clicks per minute each focus interval had,
+# to tell heads-down typing from mostly reading (macOS). Privacy: only the
+# number of input events is counted; which keys were pressed, and what was
+# typed, is never read or stored.
+# track_intensity = false
+
+# Paint intervals that were a close call (e.g. idle just past the threshold)
+# with a lighter block in the dashboard's coverage bar.
+# shade_borderline = false
+
+# Focus stretches at least this many minutes long count as deep work.
+deep_work_mins = 25
+
+# Hours of activity that fill a bar in the weekly charts of the dashboard and
+# `neflo report`, which then also show each day's focus as a percentage of
+# it. Unset, bars are scaled to the busiest day of the week.
+# chart_scale_hours = 8
+
+# Minutes of focus a day counts as meeting your goal, marked in
+# `neflo report --month-calendar` together with streaks of such days.
+# daily_goal_mins = 240
+
+# Whether a running session celebrates reaching daily_goal_mins, with a
+# notification (respecting quiet hours) and a banner in the dashboard.
+# celebrate_goal = true
+
+# How overlapping intervals are handled when recording: "lenient" trims them
+# silently, "strict" additionally aborts debug builds so bugs surface early.
+# overlap_mode = "lenient"
+
+# Seconds without a sample (e.g. a short sleep or a stalled machine) after
+# which recording starts a new interval instead of stretching the current one,
+# and keeps the missing stretch as a gap. Up to 60; longer counts as away.
+# gap_secs = 10
+
+# Name recorded on every interval (defaults to the host name) and how idle
+# time is combined when several devices recorded overlapping intervals:
+# "intersection" counts idle only while no device was in focus, "union"
+# counts idle whenever any device was idle.
+# device_id = "work-laptop"
+# idle_merge = "intersection"
+
+# Optional operating window for `neflo start`. Flags passed on the command
+# line take precedence over these values.
+[schedule]
+# start_time = "09:00"   # 24h format (HH:MM)
+# end_time = "18:00"     # 24h format (HH:MM)
+# duration = "8h"        # e.g. 8h, 30m; takes precedence over start/end times
+# exit_on_end = false    # close the dashboard once the session ends
+# exit_countdown_secs = 10  # how long the final summary stays up before exiting
+
+# Write a Markdown summary of the current week while a session is running.
+# Relative paths are resolved against the data directory and {week_start} is
+# replaced with the Monday of the reported week.
+# [weekly_report]
+# path = "reports/week-{week_start}.md"
+# weekday = "Sun"
+# time = "21:00"
+
+# Append a summary line to a daily note (e.g. an Obsidian vault) every day.
+# Placeholders: {{date}}, {{focus}}, {{idle}}, {{interruptions}}, {{deep_work}},
+# {{top_tag}} (the [space_names] name with the most focus that day).
+# [integrations.daily_note]
+# path = "~/Notes/Daily/{{date}}.md"
+# template = "- Neflo: {{focus}} focus, {{interruptions}} interruptions"
+# time = "18:00"
+
+# Play short sounds when you drift into idle or get back to focus, and
+# optionally after every `milestone_mins` of uninterrupted focus. Sounds are
+# system sound names (e.g. "Tink") or paths to audio files; leave one out to
+# silence that cue.
+# [audio]
+# on_idle = "Tink"
+# on_focus = "Pop"
+# on_milestone = "Glass"
+# milestone_mins = 50
+# volume = 0.5             # 0.0 to 1.0
+# quiet_hours = { start = "22:00", end = "08:00" }
+
+# Publish the tracker state and today's totals to an MQTT broker as retained
+# messages, with Home Assistant discovery. Requires `mosquitto_pub`.
+# [mqtt]
+# host = "localhost"
+# port = 1883
+# username = "neflo"
+# password = "secret"
+# topic_prefix = "neflo"
+# discovery_prefix = "homeassistant"
+
+# Stream state changes and, every minute, today's totals as JSON to WebSocket
+# clients of ws://<bind>:<port>/ws while a session runs, e.g. for a live
+# dashboard or a streaming overlay.
+# [websocket]
+# bind = "127.0.0.1"
+# port = 8787
+
+# Block distracting sites once a focus stretch lasts `after_mins`, and lift the
+# block on idle, away or when the session ends. `sites` are added to the hosts
+# file through `sudo -n tee`, which needs a passwordless sudo rule; the
+# commands can call any other blocker instead (or as well). They are stopped
+# after `timeout_secs` and only see PATH, HOME, USER, LANG, TMPDIR and the
+# variables in `env_allow`.
+# [blocker]
+# after_mins = 25
+# sites = ["news.ycombinator.com", "reddit.com"]
+# block_command = ["open", "focus://focus"]
+# unblock_command = ["open", "focus://unfocus"]
+# timeout_secs = 10
+# env_allow = []
+
+# Send a desktop notification when you stay idle for `after_mins` during a
+# session, then every `every_mins` until you are back, at most `max_per_day`
+# times a day.
+# [idle_reminder]
+# after_mins = 15
+# every_mins = 10
+# max_per_day = 5
+
+# Desktop notifications, such as idle reminders. `enabled = false` turns them
+# all off. During `quiet_hours` they are held back and, with `summarize`, sent
+# as a single summary once quiet hours end.
+[notifications]
+# enabled = true
+# quiet_hours = { start = "22:00", end = "08:00" }
+# summarize = true
+
+# Treat public holidays as days off: they are left out of trends and goal
+# streaks, and a session with a scheduled start time does not record on them.
+# Set `url` to an iCalendar feed or `country` to a two-letter country code
+# (looked up on date.nager.at). Downloaded with `curl` when a session starts
+# and once a day after that.
+# [holidays]
+# country = "DE"
+# url = "https://example.com/holidays.ics"
+
+# How often idle time is sampled and the dashboard's numbers recomputed.
+# Keys are still read every 100ms. With `battery_saver`, sample at most every
+# 5 seconds and save every 5 minutes instead of every 30 seconds while on
+# battery.
+[power]
+# sample_secs = 1
+# battery_saver = false
+
+# The session log (state changes and errors of `neflo start`, shown by
+# `neflo logs`) is kept in the `logs` directory next to the lock file. It moves
+# to a new file every day or once it reaches `max_kb`; older files are deleted
+# after `keep_days`.
+[logs]
+# max_kb = 1024
+# keep_days = 14
+
+# A running session warns once, in the error log, when db.json grows past
+# `warn_mb` megabytes or holds more than `warn_intervals` intervals, since
+# every save rewrites the whole file. `neflo db stats` shows the figures.
+[database]
+# warn_mb = 20
+# warn_intervals = 100000
+
+# Names for macOS desktop spaces in `neflo report`, keyed by the space id
+# shown there.
+[space_names]
+# "3" = "coding"
+# "4" = "comms"
+
+# Weekly time budgets, flagged in `neflo report` and the dashboard when over
+# or under. Keys are "focus", "deep_work", "idle", or a space name from
+# [space_names] (counting focus on that space); values are ">= <duration>"
+# or "<= <duration>".
+[weekly_targets]
+# deep_work = ">= 15h"
+# comms = "<= 4h"
+
+# Break suggestions shown in the dashboard when focus gives way to idle:
+# after focusing for at least the key, suggest a break of the value (a single
+# duration or a "min-max" range). An empty [breaks] table turns them off.
+# [breaks]
+# "25m" = "5m"
+# "50m" = "10m-15m"
+# "90m" = "15m-20m"
+
+# Rename and recolor interval kinds in the dashboard and reports. Colors are
+# terminal color names ("red", "lightblue", "darkgray"), hex values (#rrggbb)
+# or a 0-255 palette index.
+# [kinds.idle]
+# label = "Interrupted"
+# color = "red"
+"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScheduleConfig {
     pub start_time: Option<String>,
     pub end_time: Option<String>,
     pub duration: Option<String>,
+    pub exit_on_end: bool,
+    pub exit_countdown_secs: u64,
 }
 
-impl Default for Config {
+impl Default for ScheduleConfig {
     fn default() -> Self {
         Self {
-            default_threshold_mins: 5,
             start_time: None,
             end_time: None,
             duration: None,
+            exit_on_end: false,
+            exit_countdown_secs: 10,
         }
     }
 }
 
-pub fn load_config() -> Result<Config> {
-    let mut path =
-        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-    path.push(".neflo");
-    if !path.exists() {
-        fs::create_dir_all(&path)?;
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WeeklyReportConfig {
+    pub path: String,
+    #[serde(default = "default_report_weekday")]
+    pub weekday: String,
+    #[serde(default = "default_report_time")]
+    pub time: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DailyNoteConfig {
+    pub path: String,
+    #[serde(default = "default_daily_note_template")]
+    pub template: String,
+    #[serde(default = "default_daily_note_time")]
+    pub time: String,
+}
+
+fn default_daily_note_template() -> String {
+    "- Neflo: {{focus}} focus, {{interruptions}} interruptions".to_string()
+}
+
+fn default_daily_note_time() -> String {
+    "18:00".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl QuietHours {
+    pub fn parse(&self) -> Result<(NaiveTime, NaiveTime)> {
+        Ok((
+            NaiveTime::parse_from_str(&self.start, "%H:%M")?,
+            NaiveTime::parse_from_str(&self.end, "%H:%M")?,
+        ))
+    }
+
+    /// Validation messages for start and end, with keys under `prefix`.
+    fn issues(&self, prefix: &str) -> Vec<String> {
+        [("start", &self.start), ("end", &self.end)]
+            .into_iter()
+            .filter(|(_, value)| NaiveTime::parse_from_str(value, "%H:%M").is_err())
+            .map(|(key, value)| {
+                format!(
+                    "{}.quiet_hours.{} must be HH:MM in 24h format (e.g. \"22:00\"), got '{}'",
+                    prefix, key, value
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub on_idle: Option<String>,
+    pub on_focus: Option<String>,
+    pub on_milestone: Option<String>,
+    pub milestone_mins: Option<u64>,
+    pub volume: f32,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            on_idle: Some("Tink".to_string()),
+            on_focus: Some("Pop".to_string()),
+            on_milestone: Some("Glass".to_string()),
+            milestone_mins: None,
+            volume: 0.5,
+            quiet_hours: None,
+        }
     }
-    path.push("config.json");
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            topic_prefix: "neflo".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BlockerConfig {
+    pub after_mins: u64,
+    pub sites: Vec<String>,
+    pub hosts_file: String,
+    pub block_command: Option<Vec<String>>,
+    pub unblock_command: Option<Vec<String>>,
+    /// Commands still running after this long are stopped.
+    pub timeout_secs: u64,
+    /// Environment variables passed to the commands besides PATH, HOME,
+    /// USER, LANG and TMPDIR.
+    pub env_allow: Vec<String>,
+}
+
+impl Default for BlockerConfig {
+    fn default() -> Self {
+        Self {
+            after_mins: 25,
+            sites: Vec::new(),
+            hosts_file: "/etc/hosts".to_string(),
+            block_command: None,
+            unblock_command: None,
+            timeout_secs: 10,
+            env_allow: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct IdleReminderConfig {
+    pub after_mins: u64,
+    pub every_mins: u64,
+    pub max_per_day: u32,
+}
+
+impl Default for IdleReminderConfig {
+    fn default() -> Self {
+        Self {
+            after_mins: 15,
+            every_mins: 10,
+            max_per_day: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    pub enabled: bool,
+    pub quiet_hours: Option<QuietHours>,
+    pub summarize: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            quiet_hours: None,
+            summarize: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    pub bind: String,
+    pub port: u16,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1".to_string(),
+            port: 8787,
+        }
+    }
+}
+
+/// Where public holidays come from; set one of the two.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct HolidaysConfig {
+    /// An iCalendar (.ics) feed.
+    pub url: Option<String>,
+    /// An ISO 3166-1 alpha-2 country code, e.g. "DE".
+    pub country: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub sample_secs: u64,
+    pub battery_saver: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LogsConfig {
+    pub max_kb: u64,
+    pub keep_days: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub warn_mb: u64,
+    pub warn_intervals: usize,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            warn_mb: 20,
+            warn_intervals: 100_000,
+        }
+    }
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            max_kb: 1024,
+            keep_days: 14,
+        }
+    }
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            sample_secs: 1,
+            battery_saver: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct KindStyle {
+    pub label: Option<String>,
+    pub color: Option<String>,
+}
+
+/// Display names and colors of interval kinds.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct KindsConfig {
+    pub focus: KindStyle,
+    pub idle: KindStyle,
+    pub away: KindStyle,
+    pub locked: KindStyle,
+}
+
+impl KindsConfig {
+    pub fn style(&self, kind: IntervalType) -> &KindStyle {
+        match kind {
+            IntervalType::Focus => &self.focus,
+            IntervalType::Idle => &self.idle,
+            IntervalType::Away => &self.away,
+            IntervalType::Locked => &self.locked,
+        }
+    }
+
+    pub fn label(&self, kind: IntervalType) -> &str {
+        self.style(kind).label.as_deref().unwrap_or(match kind {
+            IntervalType::Focus => "Focus",
+            IntervalType::Idle => "Idle",
+            IntervalType::Away => "Away",
+            IntervalType::Locked => "Locked",
+        })
+    }
+
+    pub fn color(&self, kind: IntervalType) -> Color {
+        self.style(kind)
+            .color
+            .as_deref()
+            .and_then(|c| Color::from_str(c).ok())
+            .unwrap_or(match kind {
+                IntervalType::Focus => Color::Green,
+                IntervalType::Idle => Color::Yellow,
+                IntervalType::Away => Color::DarkGray,
+                IntervalType::Locked => Color::Blue,
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct IntegrationsConfig {
+    pub daily_note: Option<DailyNoteConfig>,
+}
+
+fn default_report_weekday() -> String {
+    "Sun".to_string()
+}
 
+fn default_report_time() -> String {
+    "21:00".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub default_threshold_mins: u64,
+    pub presenting_is_focus: bool,
+    pub track_intensity: bool,
+    pub shade_borderline: bool,
+    pub deep_work_mins: u64,
+    pub daily_goal_mins: Option<u64>,
+    pub celebrate_goal: bool,
+    pub chart_scale_hours: Option<u64>,
+    pub schedule: ScheduleConfig,
+    pub overlap_mode: OverlapMode,
+    pub gap_secs: u64,
+    pub weekly_report: Option<WeeklyReportConfig>,
+    pub integrations: IntegrationsConfig,
+    pub device_id: Option<String>,
+    pub idle_merge: IdleMerge,
+    pub audio: Option<AudioConfig>,
+    pub idle_reminder: Option<IdleReminderConfig>,
+    pub notifications: NotificationsConfig,
+    pub mqtt: Option<MqttConfig>,
+    pub websocket: Option<WebSocketConfig>,
+    pub blocker: Option<BlockerConfig>,
+    pub holidays: Option<HolidaysConfig>,
+    pub power: PowerConfig,
+    pub logs: LogsConfig,
+    pub database: DatabaseConfig,
+    pub space_names: BTreeMap<String, String>,
+    pub weekly_targets: BTreeMap<String, String>,
+    pub breaks: BTreeMap<String, String>,
+    pub kinds: KindsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default_threshold_mins: 5,
+            presenting_is_focus: false,
+            track_intensity: false,
+            shade_borderline: false,
+            deep_work_mins: 25,
+            daily_goal_mins: None,
+            celebrate_goal: true,
+            chart_scale_hours: None,
+            schedule: ScheduleConfig::default(),
+            overlap_mode: OverlapMode::default(),
+            gap_secs: 10,
+            weekly_report: None,
+            integrations: IntegrationsConfig::default(),
+            device_id: None,
+            idle_merge: IdleMerge::default(),
+            audio: None,
+            idle_reminder: None,
+            notifications: NotificationsConfig::default(),
+            mqtt: None,
+            websocket: None,
+            blocker: None,
+            holidays: None,
+            power: PowerConfig::default(),
+            logs: LogsConfig::default(),
+            database: DatabaseConfig::default(),
+            space_names: BTreeMap::new(),
+            weekly_targets: BTreeMap::new(),
+            breaks: breaks::default_table(),
+            kinds: KindsConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Check value-level constraints that serde cannot express, returning one
+    /// human-readable message per problem.
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+
+        if self.default_threshold_mins == 0 {
+            issues.push("default_threshold_mins must be at least 1, got 0".to_string());
+        }
+        if self.deep_work_mins == 0 {
+            issues.push("deep_work_mins must be at least 1, got 0".to_string());
+        }
+        if self.chart_scale_hours == Some(0) {
+            issues.push("chart_scale_hours must be at least 1, got 0".to_string());
+        }
+        if self.daily_goal_mins == Some(0) {
+            issues.push("daily_goal_mins must be at least 1, got 0".to_string());
+        }
+        for (name, spec) in &self.weekly_targets {
+            if let Err(err) = Target::parse(name, spec) {
+                issues.push(format!("weekly_targets.{}: {}", name, err));
+            }
+            if !budget::BUILT_IN.contains(&name.as_str())
+                && !self.space_names.values().any(|tag| tag == name)
+            {
+                issues.push(format!(
+                    "weekly_targets.{} must be one of {} or a name from [space_names]",
+                    name,
+                    budget::BUILT_IN.join(", ")
+                ));
+            }
+        }
+        for (after, range) in &self.breaks {
+            if let Err(err) = BreakRule::parse(after, range) {
+                issues.push(format!("breaks.\"{}\": {}", after, err));
+            }
+        }
+        if self.power.sample_secs == 0 {
+            issues.push("power.sample_secs must be at least 1, got 0".to_string());
+        }
+        if !(1..=60).contains(&self.gap_secs) {
+            issues.push(format!(
+                "gap_secs must be between 1 and 60, got {}",
+                self.gap_secs
+            ));
+        } else if self.gap_secs <= self.power.sample_secs {
+            issues.push(format!(
+                "gap_secs must be longer than power.sample_secs ({}), got {}",
+                self.power.sample_secs, self.gap_secs
+            ));
+        }
+        if self.logs.max_kb == 0 {
+            issues.push("logs.max_kb must be at least 1, got 0".to_string());
+        }
+        if self.database.warn_mb == 0 {
+            issues.push("database.warn_mb must be at least 1, got 0".to_string());
+        }
+        if self.database.warn_intervals == 0 {
+            issues.push("database.warn_intervals must be at least 1, got 0".to_string());
+        }
+        if let Some(quiet) = &self.notifications.quiet_hours {
+            issues.extend(quiet.issues("notifications"));
+        }
+        for (key, value) in [
+            ("schedule.start_time", &self.schedule.start_time),
+            ("schedule.end_time", &self.schedule.end_time),
+        ] {
+            if let Some(value) = value {
+                if NaiveTime::parse_from_str(value, "%H:%M").is_err() {
+                    issues.push(format!(
+                        "{} must be HH:MM in 24h format (e.g. \"09:00\"), got '{}'",
+                        key, value
+                    ));
+                }
+            }
+        }
+        if let Some(duration) = &self.schedule.duration {
+            if humantime::parse_duration(duration).is_err() {
+                issues.push(format!(
+                    "schedule.duration must be a duration such as \"8h\" or \"30m\", got '{}'",
+                    duration
+                ));
+            }
+        }
+
+        if let Some(report) = &self.weekly_report {
+            if report.weekday.parse::<Weekday>().is_err() {
+                issues.push(format!(
+                    "weekly_report.weekday must be a day of the week such as \"Sun\", got '{}'",
+                    report.weekday
+                ));
+            }
+            if NaiveTime::parse_from_str(&report.time, "%H:%M").is_err() {
+                issues.push(format!(
+                    "weekly_report.time must be HH:MM in 24h format (e.g. \"21:00\"), got '{}'",
+                    report.time
+                ));
+            }
+        }
+
+        if let Some(note) = &self.integrations.daily_note {
+            if NaiveTime::parse_from_str(&note.time, "%H:%M").is_err() {
+                issues.push(format!(
+                    "integrations.daily_note.time must be HH:MM in 24h format (e.g. \"18:00\"), got '{}'",
+                    note.time
+                ));
+            }
+        }
+
+        if let Some(audio) = &self.audio {
+            if !(0.0..=1.0).contains(&audio.volume) {
+                issues.push(format!(
+                    "audio.volume must be between 0.0 and 1.0, got {}",
+                    audio.volume
+                ));
+            }
+            if audio.milestone_mins == Some(0) {
+                issues.push("audio.milestone_mins must be at least 1, got 0".to_string());
+            }
+            if let Some(quiet) = &audio.quiet_hours {
+                issues.extend(quiet.issues("audio"));
+            }
+        }
+
+        if let Some(mqtt) = &self.mqtt {
+            if mqtt.host.is_empty() {
+                issues.push("mqtt.host must not be empty".to_string());
+            }
+            for (key, value) in [
+                ("mqtt.topic_prefix", &mqtt.topic_prefix),
+                ("mqtt.discovery_prefix", &mqtt.discovery_prefix),
+            ] {
+                if value.is_empty() || value.contains(['+', '#']) {
+                    issues.push(format!(
+                        "{} must be a non-empty topic without wildcards, got '{}'",
+                        key, value
+                    ));
+                }
+            }
+        }
+
+        if let Some(holidays) = &self.holidays {
+            match (&holidays.url, &holidays.country) {
+                (Some(_), Some(_)) | (None, None) => {
+                    issues.push("holidays needs exactly one of `url` or `country`".to_string())
+                }
+                (None, Some(country))
+                    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) =>
+                {
+                    issues.push(format!(
+                        "holidays.country must be a two-letter country code such as \"DE\", got '{}'",
+                        country
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(websocket) = &self.websocket {
+            if websocket.port == 0 {
+                issues.push("websocket.port must be between 1 and 65535, got 0".to_string());
+            }
+        }
+
+        if let Some(blocker) = &self.blocker {
+            if blocker.sites.is_empty() && blocker.block_command.is_none() {
+                issues.push(
+                    "blocker needs `sites` or a `block_command`, otherwise nothing is blocked"
+                        .to_string(),
+                );
+            }
+            for (key, command) in [
+                ("blocker.block_command", &blocker.block_command),
+                ("blocker.unblock_command", &blocker.unblock_command),
+            ] {
+                if command.as_ref().is_some_and(|c| c.is_empty()) {
+                    issues.push(format!("{} must not be empty", key));
+                }
+            }
+            if blocker.timeout_secs == 0 {
+                issues.push("blocker.timeout_secs must be at least 1".to_string());
+            }
+            if let Some(site) = blocker
+                .sites
+                .iter()
+                .find(|s| s.is_empty() || s.contains(char::is_whitespace))
+            {
+                issues.push(format!(
+                    "blocker.sites entries must be host names, got '{}'",
+                    site
+                ));
+            }
+        }
+
+        if let Some(reminder) = &self.idle_reminder {
+            for (key, value) in [
+                ("idle_reminder.after_mins", reminder.after_mins),
+                ("idle_reminder.every_mins", reminder.every_mins),
+            ] {
+                if value == 0 {
+                    issues.push(format!("{} must be at least 1, got 0", key));
+                }
+            }
+        }
+
+        for (name, style) in [
+            ("focus", &self.kinds.focus),
+            ("idle", &self.kinds.idle),
+            ("away", &self.kinds.away),
+            ("locked", &self.kinds.locked),
+        ] {
+            if style.label.as_deref().is_some_and(|l| l.trim().is_empty()) {
+                issues.push(format!("kinds.{}.label must not be empty", name));
+            }
+            if let Some(color) = &style.color {
+                if Color::from_str(color).is_err() {
+                    issues.push(format!(
+                        "kinds.{}.color must be a color name such as \"red\", \"#rrggbb\" or 0-255, got '{}'",
+                        name, color
+                    ));
+                }
+            }
+        }
+
+        if self
+            .device_id
+            .as_deref()
+            .is_some_and(|id| id.trim().is_empty())
+        {
+            issues.push("device_id must not be empty".to_string());
+        }
+
+        issues
+    }
+
+    pub fn chart_scale(&self) -> chart::Scale {
+        match self.chart_scale_hours {
+            Some(hours) => chart::Scale::Fixed(chrono::Duration::hours(hours as i64)),
+            None => chart::Scale::Relative,
+        }
+    }
+
+    /// Length of the scheduled working day: `schedule.duration`, or the time
+    /// from `schedule.start_time` to `schedule.end_time`.
+    pub fn workday(&self) -> Option<chrono::Duration> {
+        let schedule = &self.schedule;
+        if let Some(duration) = &schedule.duration {
+            return chrono::Duration::from_std(humantime::parse_duration(duration).ok()?).ok();
+        }
+        let time =
+            |value: &Option<String>| NaiveTime::parse_from_str(value.as_deref()?, "%H:%M").ok();
+        let (start, end) = (time(&schedule.start_time)?, time(&schedule.end_time)?);
+        (end > start).then(|| end - start)
+    }
+
+    /// The desktop spaces given the name `tag` in `[space_names]`, lowest first.
+    pub fn tag_spaces(&self, tag: &str) -> Result<Vec<u64>> {
+        let mut spaces: Vec<u64> = self
+            .space_names
+            .iter()
+            .filter(|(_, name)| *name == tag)
+            .filter_map(|(space, _)| space.parse().ok())
+            .collect();
+        if spaces.is_empty() {
+            anyhow::bail!("No space is named '{}' in [space_names].", tag);
+        }
+        spaces.sort_unstable();
+        Ok(spaces)
+    }
+
+    pub fn stats_options(&self) -> StatsOptions {
+        StatsOptions {
+            idle_merge: self.idle_merge,
+            deep_work_min: chrono::Duration::minutes(self.deep_work_mins as i64),
+        }
+    }
+}
+
+/// The flat `config.json` layout used before TOML support.
+#[derive(Serialize, Deserialize)]
+struct LegacyConfig {
+    default_threshold_mins: u64,
+    start_time: Option<String>,
+    end_time: Option<String>,
+    duration: Option<String>,
+}
+
+impl From<LegacyConfig> for Config {
+    fn from(legacy: LegacyConfig) -> Self {
+        Self {
+            default_threshold_mins: legacy.default_threshold_mins,
+            schedule: ScheduleConfig {
+                start_time: legacy.start_time,
+                end_time: legacy.end_time,
+                duration: legacy.duration,
+                ..ScheduleConfig::default()
+            },
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&Config> for LegacyConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            default_threshold_mins: config.default_threshold_mins,
+            start_time: config.schedule.start_time.clone(),
+            end_time: config.schedule.end_time.clone(),
+            duration: config.schedule.duration.clone(),
+        }
+    }
+}
+
+/// Default location of the config file inside the data directory.
+pub fn default_config_path(base_dir: &Path) -> PathBuf {
+    base_dir.join("config.toml")
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+fn parse_config(path: &Path, data: &str) -> Result<Config> {
+    if is_json(path) {
+        let legacy: LegacyConfig = serde_json::from_str(data)?;
+        Ok(legacy.into())
+    } else {
+        Ok(toml::from_str(data)?)
+    }
+}
+
+/// Load the config at `path`, creating it if missing.
+///
+/// A legacy `config.json` next to a missing `config.toml` is migrated: its
+/// values are written to the TOML file and the JSON file is renamed to
+/// `config.json.bak`. Paths ending in `.json` are still read as JSON.
+pub fn load_config(path: &Path) -> Result<Config> {
     if !path.exists() {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let legacy_path = path.with_extension("json");
+        if !is_json(path) && legacy_path.exists() {
+            return migrate_legacy(&legacy_path, path);
+        }
+
         let config = Config::default();
-        let data = serde_json::to_string_pretty(&config)?;
-        fs::write(&path, data)?;
+        let data = if is_json(path) {
+            serde_json::to_string_pretty(&LegacyConfig::from(&config))?
+        } else {
+            DEFAULT_CONFIG.to_string()
+        };
+        fs::write(path, data)?;
         return Ok(config);
     }
 
-    let data = fs::read_to_string(&path)?;
-    let config = serde_json::from_str(&data)?;
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let config = parse_config(path, &data)
+        .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+    let issues = config.validate();
+    if !issues.is_empty() {
+        anyhow::bail!(
+            "Invalid config file {}:\n  - {}",
+            path.display(),
+            issues.join("\n  - ")
+        );
+    }
+
     Ok(config)
 }
+
+fn migrate_legacy(legacy_path: &Path, path: &Path) -> Result<Config> {
+    let data = fs::read_to_string(legacy_path)?;
+    let config = parse_config(legacy_path, &data)?;
+
+    let toml_data = format!(
+        "# Neflo configuration (migrated from {})\n\n{}",
+        legacy_path.display(),
+        toml::to_string_pretty(&config)?
+    );
+    fs::write(path, toml_data)?;
+    fs::rename(legacy_path, legacy_path.with_extension("json.bak"))?;
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_default_template_matches_default() -> Result<()> {
+        let config: Config = toml::from_str(DEFAULT_CONFIG)?;
+        assert_eq!(config, Config::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_creates_default() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("nested").join("config.toml");
+
+        let config = load_config(&path)?;
+        assert_eq!(config.default_threshold_mins, 5);
+        assert!(path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_toml() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "default_threshold_mins = 12\n\n[schedule]\nstart_time = \"09:00\" # morning\n",
+        )?;
+
+        let config = load_config(&path)?;
+        assert_eq!(config.default_threshold_mins, 12);
+        assert_eq!(config.schedule.start_time.as_deref(), Some("09:00"));
+        assert_eq!(config.schedule.end_time, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_legacy_json_path() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("neflo.json");
+        fs::write(
+            &path,
+            r#"{"default_threshold_mins": 12, "start_time": "09:00", "end_time": null, "duration": null}"#,
+        )?;
+
+        let config = load_config(&path)?;
+        assert_eq!(config.default_threshold_mins, 12);
+        assert_eq!(config.schedule.start_time.as_deref(), Some("09:00"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_reports_bad_values() {
+        let config = Config {
+            default_threshold_mins: 0,
+            schedule: ScheduleConfig {
+                start_time: Some("09:00".to_string()),
+                end_time: Some("5pm".to_string()),
+                duration: Some("forever".to_string()),
+                ..ScheduleConfig::default()
+            },
+            ..Config::default()
+        };
+
+        let issues = config.validate();
+        assert_eq!(issues.len(), 3);
+        assert!(issues[0].contains("default_threshold_mins"));
+        assert!(issues[1].contains("schedule.end_time must be HH:MM"));
+        assert!(issues[1].contains("'5pm'"));
+        assert!(issues[2].contains("schedule.duration"));
+    }
+
+    #[test]
+    fn test_weekly_targets_validation() {
+        let config: Config = toml::from_str(
+            "[space_names]\n\"3\" = \"comms\"\n\n[weekly_targets]\ndeep_work = \">= 15h\"\ncomms = \"<= 4h\"\nadmin = \"<= 4h\"\nfocus = \"20h\"\n",
+        )
+        .unwrap();
+        let issues = config.validate();
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].contains("weekly_targets.admin must be one of"));
+        assert!(issues[1].contains("weekly_targets.focus: expected"));
+    }
+
+    #[test]
+    fn test_audio_section_defaults_and_validation() {
+        let config: Config = toml::from_str("[audio]\nvolume = 1.5\n").unwrap();
+        let audio = config.audio.as_ref().unwrap();
+        assert_eq!(audio.on_idle.as_deref(), Some("Tink"));
+        assert_eq!(audio.milestone_mins, None);
+
+        let issues = config.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].contains("audio.volume"));
+    }
+
+    #[test]
+    fn test_kinds_labels_and_colors() {
+        let config: Config =
+            toml::from_str("[kinds.idle]\nlabel = \"Interrupted\"\ncolor = \"red\"\n").unwrap();
+        assert_eq!(config.kinds.label(IntervalType::Idle), "Interrupted");
+        assert_eq!(config.kinds.color(IntervalType::Idle), Color::Red);
+        assert_eq!(config.kinds.label(IntervalType::Focus), "Focus");
+        assert_eq!(config.kinds.color(IntervalType::Focus), Color::Green);
+        assert!(config.validate().is_empty());
+
+        let config: Config = toml::from_str("[kinds.focus]\ncolor = \"mauve\"\n").unwrap();
+        let issues = config.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].contains("kinds.focus.color"));
+    }
+
+    #[test]
+    fn test_load_config_invalid_value() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[schedule]\nend_time = \"5pm\"\n")?;
+
+        let err = load_config(&path).unwrap_err().to_string();
+        assert!(err.contains("schedule.end_time must be HH:MM"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_syntax_error_mentions_line() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "default_threshold_mins = 5\n[schedule\n")?;
+
+        let err = format!("{:#}", load_config(&path).unwrap_err());
+        assert!(err.contains("Failed to parse config file"));
+        assert!(err.contains("line 2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_migrates_json() -> Result<()> {
+        let dir = tempdir()?;
+        let json_path = dir.path().join("config.json");
+        let toml_path = dir.path().join("config.toml");
+        fs::write(
+            &json_path,
+            r#"{"default_threshold_mins": 7, "start_time": null, "end_time": "18:00", "duration": "8h"}"#,
+        )?;
+
+        let config = load_config(&toml_path)?;
+        assert_eq!(config.default_threshold_mins, 7);
+        assert_eq!(config.schedule.end_time.as_deref(), Some("18:00"));
+        assert!(toml_path.exists());
+        assert!(!json_path.exists());
+        assert!(dir.path().join("config.json.bak").exists());
+
+        // Loading again reads the migrated TOML file
+        let reloaded = load_config(&toml_path)?;
+        assert_eq!(reloaded, config);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use crate::tracker::ResetScope;
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::Path;
+
+/// Dropped in the state directory by `neflo stop`/`neflo reset` for a running session to pick up.
+const CONTROL_REQUEST_FILE: &str = "control.request";
+
+/// Something another `neflo` process asks the running session to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Request {
+    /// Save and exit.
+    Stop,
+    /// Remove recorded intervals.
+    Reset(ResetScope),
+}
+
+impl Request {
+    fn as_str(self) -> &'static str {
+        match self {
+            Request::Stop => "stop",
+            Request::Reset(ResetScope::Session) => "reset:session",
+            Request::Reset(ResetScope::Today) => "reset:today",
+            Request::Reset(ResetScope::All) => "reset",
+        }
+    }
+}
+
+/// Ask the session whose state lives in `state_dir` to carry out `request`.
+pub fn send(state_dir: &Path, request: Request) -> Result<()> {
+    fs::write(state_dir.join(CONTROL_REQUEST_FILE), request.as_str())?;
+    Ok(())
+}
+
+/// Consume a pending request, if any.
+pub fn take(state_dir: &Path) -> Result<Option<Request>> {
+    let path = state_dir.join(CONTROL_REQUEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let request = fs::read_to_string(&path)?;
+    fs::remove_file(&path)?;
+    match request.trim() {
+        "stop" => Ok(Some(Request::Stop)),
+        "reset:session" => Ok(Some(Request::Reset(ResetScope::Session))),
+        "reset:today" => Ok(Some(Request::Reset(ResetScope::Today))),
+        "reset" => Ok(Some(Request::Reset(ResetScope::All))),
+        other => bail!("Unknown control request '{}'", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_request_round_trip() -> Result<()> {
+        let dir = tempdir()?;
+        assert_eq!(take(dir.path())?, None);
+
+        send(dir.path(), Request::Stop)?;
+        send(dir.path(), Request::Reset(ResetScope::Today))?;
+        // The latest request wins and is consumed by the first read
+        assert_eq!(take(dir.path())?, Some(Request::Reset(ResetScope::Today)));
+        assert_eq!(take(dir.path())?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use crate::stats::DayStats;
+use crate::utils::format_duration;
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Substitute `{{placeholder}}`s in `template` with values for `date`, whose
+/// space name with the most focus is `top_tag`. Unknown placeholders are left
+/// untouched.
+pub fn render(template: &str, date: NaiveDate, stats: &DayStats, top_tag: Option<&str>) -> String {
+    template
+        .replace("{{date}}", &date.to_string())
+        .replace(
+            "{{focus}}",
+            &format_duration(stats.total_focus.num_seconds()),
+        )
+        .replace("{{idle}}", &format_duration(stats.total_idle.num_seconds()))
+        .replace("{{interruptions}}", &stats.idle_sessions.to_string())
+        .replace(
+            "{{deep_work}}",
+            &format_duration(stats.total_deep_work.num_seconds()),
+        )
+        .replace("{{top_tag}}", top_tag.unwrap_or_default())
+}
+
+/// Append `text` as its own line to the note at `path`, creating it if needed.
+pub fn append(path: &Path, text: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let needs_newline = fs::read_to_string(path)
+        .map(|existing| !existing.is_empty() && !existing.ends_with('\n'))
+        .unwrap_or(false);
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if needs_newline {
+        writeln!(file)?;
+    }
+    writeln!(file, "{}", text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render_placeholders() {
+        let stats = DayStats {
+            total_focus: Duration::minutes(250),
+            total_idle: Duration::minutes(40),
+            total_locked: Duration::zero(),
+            focus_sessions: 6,
+            idle_sessions: 5,
+            total_deep_work: Duration::minutes(180),
+            deep_work_sessions: 3,
+            focus_onset: None,
+            off: None,
+            input_events: 0.0,
+            intensity_minutes: 0.0,
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+
+        let line = render(
+            "- {{date}}: focus {{focus}} ({{deep_work}} deep) on {{top_tag}}, idle {{idle}}, {{interruptions}} interruptions {{other}}",
+            date,
+            &stats,
+            Some("coding"),
+        );
+
+        assert_eq!(
+            line,
+            "- 2024-05-13: focus 4h 10m (3h deep) on coding, idle 40m, 5 interruptions {{other}}"
+        );
+        // Without named spaces the tag is left empty
+        assert_eq!(render("top: {{top_tag}}", date, &stats, None), "top: ");
+    }
+
+    #[test]
+    fn test_append_adds_line() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("vault").join("2024-05-13.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, "# Monday")?;
+
+        append(&path, "- focus 4h")?;
+        append(&path, "- focus 5h")?;
+
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "# Monday\n- focus 4h\n- focus 5h\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use crate::storage::Storage;
+use anyhow::{anyhow, bail, Result};
+use chrono::NaiveDate;
+
+/// Parse a day range as `FROM..TO` (both inclusive) or a single date.
+pub fn parse_range(value: &str) -> Result<(NaiveDate, NaiveDate)> {
+    let date = |s: &str| {
+        NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+            .map_err(|_| anyhow!("Invalid date '{}': expected YYYY-MM-DD", s))
+    };
+    let (from, to) = match value.split_once("..") {
+        Some((from, to)) => (date(from)?, date(to)?),
+        None => (date(value)?, date(value)?),
+    };
+    if to < from {
+        bail!("{} ends before it starts", value);
+    }
+    Ok((from, to))
+}
+
+/// Mark the days from `from` to `to` as off and persist them.
+pub fn record(storage: &Storage, from: NaiveDate, to: NaiveDate, reason: &str) -> Result<()> {
+    let mut db = storage.load()?;
+    db.mark_off(from, to, reason);
+    storage.save(&db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse_range() -> Result<()> {
+        assert_eq!(parse_range("2024-06-03..2024-06-07")?, (date(3), date(7)));
+        assert_eq!(parse_range("2024-06-03")?, (date(3), date(3)));
+        assert!(parse_range("2024-06-07..2024-06-03").is_err());
+        assert!(parse_range("next week").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_marks_each_day() -> Result<()> {
+        let dir = tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        record(&storage, date(3), date(5), "vacation")?;
+        record(&storage, date(5), date(5), "sick")?;
+
+        let db = storage.load()?;
+        let days: Vec<_> = db
+            .days_off
+            .iter()
+            .map(|(d, reason)| (*d, reason.as_str()))
+            .collect();
+        assert_eq!(
+            days,
+            [
+                (date(3), "vacation"),
+                (date(4), "vacation"),
+                (date(5), "sick")
+            ]
+        );
+        Ok(())
+    }
+}
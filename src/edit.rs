@@ -0,0 +1,115 @@
+use crate::models::{Interval, IntervalType};
+use crate::period::Period;
+use crate::storage::Storage;
+use crate::trash;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+
+/// Move the time spent on `spaces` during `period` to `space`, keeping the
+/// spans replaced in the trash for `neflo undo`. Returns the time moved.
+pub fn retag(
+    storage: &Storage,
+    period: Period,
+    spaces: &[u64],
+    space: u64,
+    now: DateTime<Utc>,
+) -> Result<Duration> {
+    let mut db = storage.load()?;
+    let replaced = db.retag_spaces(period.start(), period.end(), spaces, space);
+    let moved = replaced
+        .iter()
+        .fold(Duration::zero(), |sum, s| sum + (s.end - s.start));
+    let entry = trash::Entry {
+        spaces: replaced,
+        ..Default::default()
+    };
+    stash(storage, "retag", &entry, now)?;
+    storage.save(&db)?;
+    Ok(moved)
+}
+
+/// Remove the intervals of `kind` (all kinds when `None`) during `period`
+/// into the trash for `neflo undo`. Returns what was removed.
+pub fn delete(
+    storage: &Storage,
+    period: Period,
+    kind: Option<IntervalType>,
+    now: DateTime<Utc>,
+) -> Result<Vec<Interval>> {
+    let mut db = storage.load()?;
+    let entry = trash::Entry {
+        intervals: db.remove_intervals_between(period.start(), period.end(), kind),
+        ..Default::default()
+    };
+    stash(storage, "delete", &entry, now)?;
+    storage.save(&db)?;
+    Ok(entry.intervals)
+}
+
+/// Keep `entry` in the trash unless it is empty or this is a dry run.
+fn stash(storage: &Storage, action: &str, entry: &trash::Entry, now: DateTime<Utc>) -> Result<()> {
+    let empty = entry.intervals.is_empty() && entry.spaces.is_empty();
+    if let (false, false, Some(dir)) = (empty, storage.dry_run, storage.trash_dir()) {
+        trash::save(&dir, action, entry, now)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SpaceSpan;
+    use chrono::{Local, NaiveDate, TimeZone};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_edits_keep_what_they_replace_in_the_trash() -> Result<()> {
+        let dir = tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let at = |h| {
+            Local
+                .from_local_datetime(&day.and_hms_opt(h, 0, 0).unwrap())
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let mut db = storage.load()?;
+        db.intervals = vec![
+            Interval {
+                end: at(11),
+                ..Interval::new_at(IntervalType::Focus, at(9))
+            },
+            Interval {
+                end: at(12),
+                ..Interval::new_at(IntervalType::Idle, at(11))
+            },
+        ];
+        db.spaces = vec![SpaceSpan {
+            start: at(9),
+            end: at(12),
+            space: 3,
+        }];
+        storage.save(&db)?;
+
+        let moved = retag(&storage, Period::Day(day), &[3], 2, at(13))?;
+        assert_eq!(moved, Duration::hours(3));
+        assert_eq!(storage.load()?.spaces[0].space, 2);
+
+        let deleted = delete(&storage, Period::Day(day), Some(IntervalType::Idle), at(14))?;
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(storage.load()?.intervals.len(), 1);
+
+        // Undone newest first
+        let trash_dir = storage.trash_dir().unwrap();
+        let (path, entry) = trash::latest(&trash_dir)?.unwrap();
+        assert_eq!(entry.intervals, deleted);
+        std::fs::remove_file(path)?;
+        let (_, entry) = trash::latest(&trash_dir)?.unwrap();
+        assert_eq!(entry.spaces, db.spaces);
+
+        // Nothing matched, nothing to undo
+        delete(&storage, Period::Day(day.pred_opt().unwrap()), None, at(15))?;
+        assert_eq!(trash::latest(&trash_dir)?.unwrap().1, entry);
+        Ok(())
+    }
+}
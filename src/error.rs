@@ -0,0 +1,40 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Failures the user can do something about. They travel inside
+/// `anyhow::Error`; callers that react to one use `downcast_ref::<Error>()`.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(
+        "{} is not a valid Neflo database ({source}); move it aside to start a new one, \
+         or restore it from a copy",
+        path.display()
+    )]
+    CorruptDatabase {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error(
+        "Another instance of Neflo{} is already running. Please close it before starting a new one, \
+         or use `neflo start --observe` to watch it.",
+        owner.as_ref().map(|owner| format!(" ({})", owner)).unwrap_or_default()
+    )]
+    Locked { owner: Option<String> },
+    #[error(
+        "Permission denied for {}; check who owns it, or point --data-dir at a directory you can write",
+        path.display()
+    )]
+    PermissionDenied { path: PathBuf },
+}
+
+/// `err` from touching `path`, as [`Error::PermissionDenied`] when that is what it was.
+pub fn io(path: &Path, err: io::Error) -> anyhow::Error {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => Error::PermissionDenied {
+            path: path.to_path_buf(),
+        }
+        .into(),
+        _ => err.into(),
+    }
+}
@@ -0,0 +1,305 @@
+use crate::models::{Database, Interval, IntervalType, PowerSource};
+use crate::stats::{merge_devices, StatsOptions};
+use crate::utils::year_week;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Bumped whenever columns are added, removed or change meaning.
+pub const CSV_SCHEMA_VERSION: u32 = 6;
+
+/// One CSV row. Field order defines the column order.
+#[derive(Serialize, Deserialize)]
+struct CsvRecord {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    kind: IntervalType,
+    device_id: Option<String>,
+    /// Added in version 2; missing in older files.
+    #[serde(default)]
+    power: Option<PowerSource>,
+    /// Added in version 3; the database owner, ignored on import.
+    #[serde(default)]
+    user: Option<String>,
+    /// Added in version 4; missing in older files.
+    #[serde(default)]
+    confidence: Option<f32>,
+    /// Added in version 5; derived from `start`, ignored on import.
+    #[serde(default)]
+    iso_week: Option<u32>,
+    #[serde(default)]
+    year_week: Option<String>,
+    /// Added in version 6; missing in older files.
+    #[serde(default)]
+    intensity: Option<f32>,
+}
+
+/// (name, type, description) of every column, in order.
+const CSV_COLUMNS: [(&str, &str, &str); 10] = [
+    ("start", "RFC 3339 timestamp", "Interval start in UTC"),
+    ("end", "RFC 3339 timestamp", "Interval end in UTC"),
+    ("kind", "enum", "Interval kind"),
+    (
+        "device_id",
+        "string, optional",
+        "Device that recorded the interval; empty if unknown",
+    ),
+    (
+        "power",
+        "enum, optional",
+        "Power source when the interval started: battery or ac; empty if unknown",
+    ),
+    (
+        "user",
+        "string, optional",
+        "OS user the database belongs to; empty if unknown",
+    ),
+    (
+        "confidence",
+        "number, optional",
+        "How clearly the interval was on its side of the idle threshold, from 0 to 1; empty if unknown",
+    ),
+    (
+        "iso_week",
+        "integer",
+        "ISO 8601 week number of the local start date, from 1 to 53",
+    ),
+    (
+        "year_week",
+        "string",
+        "ISO 8601 year and week of the local start date, e.g. 2024-W09",
+    ),
+    (
+        "intensity",
+        "number, optional",
+        "Keystrokes and clicks per minute of a focus interval, with track_intensity; empty if not sampled",
+    ),
+];
+
+impl CsvRecord {
+    fn new(interval: &Interval, user: Option<&str>) -> Self {
+        let date = interval.start.with_timezone(&Local).date_naive();
+        Self {
+            start: interval.start,
+            end: interval.end,
+            kind: interval.kind,
+            device_id: interval.device_id.clone(),
+            power: interval.power,
+            user: user.map(str::to_string),
+            confidence: interval.confidence,
+            iso_week: Some(date.iso_week().week()),
+            year_week: Some(year_week(date)),
+            intensity: interval.intensity,
+        }
+    }
+}
+
+impl From<CsvRecord> for Interval {
+    fn from(record: CsvRecord) -> Self {
+        Self {
+            start: record.start,
+            end: record.end,
+            kind: record.kind,
+            device_id: record.device_id,
+            power: record.power,
+            confidence: record.confidence,
+            intensity: record.intensity,
+        }
+    }
+}
+
+pub fn write_csv<W: Write>(db: &Database, writer: W) -> Result<()> {
+    write_csv_rows(&db.intervals, db.meta.owner.as_deref(), writer)
+}
+
+/// Write `intervals` in the export format, labelled with `user`.
+pub fn write_csv_rows<W: Write>(
+    intervals: &[Interval],
+    user: Option<&str>,
+    writer: W,
+) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for interval in intervals {
+        writer.serialize(CsvRecord::new(interval, user))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_csv<R: Read>(reader: R) -> Result<Vec<Interval>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut intervals = Vec::new();
+    for (idx, record) in reader.deserialize::<CsvRecord>().enumerate() {
+        let record = record.map_err(|e| anyhow::anyhow!("Invalid CSV row {}: {}", idx + 2, e))?;
+        intervals.push(record.into());
+    }
+    Ok(intervals)
+}
+
+/// HealthKit category the records map to in Apple Health.
+const MINDFUL_SESSION: &str = "HKCategoryTypeIdentifierMindfulSession";
+
+/// A focus block in the shape of a mindful-minutes sample, for Shortcuts'
+/// "Log Health Sample" action.
+#[derive(Serialize)]
+struct HealthRecord {
+    category: &'static str,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    minutes: i64,
+    /// ISO 8601 week of the start, as a number and as a year-week key.
+    iso_week: u32,
+    year_week: String,
+}
+
+/// Write every deep-work block (focus lasting at least `options.deep_work_min`)
+/// as a JSON array of mindful-session records.
+pub fn write_health_json<W: Write>(db: &Database, options: &StatsOptions, writer: W) -> Result<()> {
+    let records: Vec<HealthRecord> = merge_devices(&db.intervals, options.idle_merge)
+        .into_iter()
+        .filter(|i| i.kind == IntervalType::Focus && i.end - i.start >= options.deep_work_min)
+        .map(|i| HealthRecord {
+            category: MINDFUL_SESSION,
+            start: i.start.with_timezone(&Local),
+            end: i.end.with_timezone(&Local),
+            minutes: (i.end - i.start).num_minutes(),
+            iso_week: i.start.with_timezone(&Local).iso_week().week(),
+            year_week: year_week(i.start.with_timezone(&Local).date_naive()),
+        })
+        .collect();
+    serde_json::to_writer_pretty(writer, &records)?;
+    Ok(())
+}
+
+/// Describe the CSV columns, with enum values taken from the data model.
+pub fn csv_schema() -> String {
+    let kinds: Vec<String> = IntervalType::ALL
+        .iter()
+        .map(|kind| {
+            serde_json::to_value(kind)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut out = format!(
+        "Neflo CSV schema (version {})\n\n{:<10} {:<20} {}\n",
+        CSV_SCHEMA_VERSION, "Column", "Type", "Description"
+    );
+    for (name, ty, description) in CSV_COLUMNS {
+        let description = if name == "kind" {
+            format!("{}: one of {}", description, kinds.join(", "))
+        } else {
+            description.to_string()
+        };
+        out.push_str(&format!("{:<10} {:<20} {}\n", name, ty, description));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn sample_db() -> Database {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap() + Duration::nanoseconds(123);
+        Database {
+            intervals: vec![
+                Interval {
+                    start: t,
+                    end: t + Duration::minutes(25),
+                    kind: IntervalType::Focus,
+                    device_id: Some("laptop, 13\"".to_string()),
+                    power: Some(PowerSource::Battery),
+                    confidence: Some(0.85),
+                    intensity: Some(42.5),
+                },
+                Interval {
+                    start: t + Duration::minutes(25),
+                    end: t + Duration::minutes(30),
+                    kind: IntervalType::Idle,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+                Interval {
+                    start: t + Duration::minutes(30),
+                    end: t + Duration::hours(2),
+                    kind: IntervalType::Away,
+                    device_id: Some("laptop".to_string()),
+                    power: Some(PowerSource::Ac),
+                    confidence: None,
+                    intensity: None,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_csv_round_trip() -> Result<()> {
+        let mut db = sample_db();
+        db.meta.owner = Some("alice".to_string());
+        let mut buf = Vec::new();
+        write_csv(&db, &mut buf)?;
+        // A Sunday in the first days of January belongs to the last week of the year before
+        assert!(String::from_utf8(buf.clone())?.contains(",battery,alice,0.85,52,2022-W52,42.5\n"));
+
+        let intervals = read_csv(buf.as_slice())?;
+        assert_eq!(intervals, db.intervals);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_header_matches_schema() -> Result<()> {
+        let mut buf = Vec::new();
+        write_csv(&sample_db(), &mut buf)?;
+        let header = String::from_utf8(buf)?.lines().next().unwrap().to_string();
+
+        let names: Vec<&str> = CSV_COLUMNS.iter().map(|c| c.0).collect();
+        assert_eq!(header, names.join(","));
+        assert!(csv_schema().contains("one of Focus, Idle, Locked, Away"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_health_json_keeps_long_focus_blocks() -> Result<()> {
+        let options = StatsOptions {
+            deep_work_min: Duration::minutes(20),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        write_health_json(&sample_db(), &options, &mut buf)?;
+
+        let records: serde_json::Value = serde_json::from_slice(&buf)?;
+        let records = records.as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["category"], MINDFUL_SESSION);
+        assert_eq!(records[0]["minutes"], 25);
+        assert_eq!(records[0]["year_week"], "2022-W52");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_csv_version_1() -> Result<()> {
+        let data =
+            "start,end,kind,device_id\n2023-01-01T10:00:00Z,2023-01-01T10:05:00Z,Focus,laptop\n";
+        let intervals = read_csv(data.as_bytes())?;
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].power, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_csv_reports_row() {
+        let data = "start,end,kind,device_id\n2023-01-01T10:00:00Z,2023-01-01T10:05:00Z,Napping,\n";
+        let err = read_csv(data.as_bytes()).unwrap_err().to_string();
+        assert!(err.contains("row 2"));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::models::{Database, Dirty};
+use std::collections::{HashMap, HashSet};
+
+/// One-off repairs of data written by older versions, each returning whether
+/// it changed anything, in the order they were added. A database records how
+/// many it has had in `meta.fixups`, so new ones go at the end and existing
+/// ones must never be reordered or removed.
+const FIXUPS: &[fn(&mut Database) -> bool] = &[
+    // Samples taken twice in the same instant left empty intervals behind
+    remove_zero_length,
+    // Before 0.2 an idle interval backdated to the last input could start
+    // inside the focus interval it ended, instead of cutting it short
+    resolve_overlaps,
+];
+
+/// Number of fixups a database created now needs no more of.
+pub const LATEST: u32 = FIXUPS.len() as u32;
+
+/// Run the fixups `db` has not had yet, and mark it for a full save when any
+/// of them changed something so the record of them sticks.
+pub fn apply(db: &mut Database) {
+    let done = db.meta.fixups as usize;
+    let Some(pending) = FIXUPS.get(done..) else {
+        return;
+    };
+    let mut changed = false;
+    for fixup in pending {
+        changed |= fixup(db);
+    }
+    db.meta.fixups = LATEST;
+    if changed {
+        db.mark_dirty(Dirty::Full);
+    }
+}
+
+/// Drop empty intervals, except each device's latest, which may have just
+/// been opened.
+fn remove_zero_length(db: &mut Database) -> bool {
+    let latest: HashSet<usize> = db
+        .intervals
+        .iter()
+        .enumerate()
+        .map(|(idx, i)| (&i.device_id, idx))
+        .collect::<HashMap<_, _>>()
+        .into_values()
+        .collect();
+    let before = db.intervals.len();
+    let mut idx = 0;
+    db.intervals.retain(|i| {
+        idx += 1;
+        i.end > i.start || latest.contains(&(idx - 1))
+    });
+    db.intervals.len() != before
+}
+
+fn resolve_overlaps(db: &mut Database) -> bool {
+    let before = db.intervals.clone();
+    db.resolve_overlaps();
+    db.intervals != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DbMeta, Interval, IntervalType};
+    use chrono::{DateTime, Duration, Utc};
+
+    #[test]
+    fn test_fixups_run_once() {
+        let start = "2024-05-13T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let interval = |kind, from: i64, to: i64| Interval {
+            end: start + Duration::minutes(to),
+            ..Interval::new_at(kind, start + Duration::minutes(from))
+        };
+        let old = || Database {
+            intervals: vec![
+                interval(IntervalType::Focus, 0, 30),
+                interval(IntervalType::Idle, 25, 40),
+                interval(IntervalType::Focus, 40, 40),
+                interval(IntervalType::Idle, 40, 40),
+            ],
+            ..Default::default()
+        };
+
+        let mut db = old();
+        apply(&mut db);
+        assert_eq!(
+            db.intervals,
+            [
+                interval(IntervalType::Focus, 0, 25),
+                interval(IntervalType::Idle, 25, 40),
+                // The latest interval may be the one just opened
+                interval(IntervalType::Idle, 40, 40),
+            ]
+        );
+        assert_eq!(db.meta.fixups, LATEST);
+        assert_eq!(db.dirty, Dirty::Full);
+
+        // A database that already had them is left as it is
+        let mut db = Database {
+            meta: DbMeta {
+                fixups: LATEST,
+                ..Default::default()
+            },
+            ..old()
+        };
+        apply(&mut db);
+        assert_eq!(db.intervals.len(), 4);
+        assert_eq!(db.dirty, Dirty::Clean);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::background::Job;
+use crate::config::HolidaysConfig;
+use crate::models::Database;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+use serde::Deserialize;
+use std::process::Command;
+
+/// Hours between downloads of the calendar.
+const REFRESH_HOURS: i64 = 24;
+/// Hours before a failed download is tried again.
+const RETRY_HOURS: i64 = 1;
+/// Public holidays by country, as JSON: `<COUNTRY_API>/<year>/<country code>`.
+const COUNTRY_API: &str = "https://date.nager.at/api/v3/PublicHolidays";
+
+#[derive(Clone)]
+enum Source {
+    /// An iCalendar feed.
+    Ics(String),
+    /// An ISO 3166-1 country code looked up in `COUNTRY_API`.
+    Country(String),
+}
+
+/// Keeps the days off in sync with a public holidays calendar while a session
+/// runs. Downloads go through `curl`, on a background thread.
+pub struct Holidays {
+    source: Source,
+    next_refresh: Option<DateTime<Utc>>,
+    download: Option<Job<Vec<(NaiveDate, String)>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CountryHoliday {
+    date: NaiveDate,
+    local_name: String,
+}
+
+impl Holidays {
+    pub fn from_config(config: &HolidaysConfig) -> Option<Self> {
+        let source = match (&config.url, &config.country) {
+            (Some(url), _) => Source::Ics(url.clone()),
+            (None, Some(country)) => Source::Country(country.to_uppercase()),
+            (None, None) => return None,
+        };
+        Some(Self {
+            source,
+            next_refresh: None,
+            download: None,
+        })
+    }
+
+    /// Start downloading the calendar when due, and once a download has
+    /// finished mark its holidays as days off, returning whether any day was
+    /// added. Never waits for the download.
+    pub fn refresh(&mut self, db: &mut Database, now: DateTime<Utc>) -> Result<bool> {
+        if let Some(download) = &self.download {
+            let Some(result) = download.poll() else {
+                return Ok(false);
+            };
+            self.download = None;
+            let holidays = result?;
+            self.next_refresh = Some(now + Duration::hours(REFRESH_HOURS));
+            return Ok(db.add_holidays(holidays) > 0);
+        }
+        if self.next_refresh.is_some_and(|next| now < next) {
+            return Ok(false);
+        }
+        self.next_refresh = Some(now + Duration::hours(RETRY_HOURS));
+        let source = self.source.clone();
+        let year = now.with_timezone(&Local).year();
+        self.download = Some(Job::spawn(move || fetch(&source, year)));
+        Ok(false)
+    }
+}
+
+fn fetch(source: &Source, year: i32) -> Result<Vec<(NaiveDate, String)>> {
+    match source {
+        Source::Ics(url) => Ok(parse_ics(&download(url)?)),
+        Source::Country(code) => {
+            let mut holidays = Vec::new();
+            // Next year too, so the turn of the year is covered
+            for year in [year, year + 1] {
+                let json = download(&format!("{}/{}/{}", COUNTRY_API, year, code))?;
+                holidays.extend(parse_country(&json)?);
+            }
+            Ok(holidays)
+        }
+    }
+}
+
+fn download(url: &str) -> Result<String> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", "10", url])
+        .output()
+        .context("Failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "Downloading holidays from {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// The days of each event in an iCalendar feed, with the event's summary.
+/// All-day events end the day before their `DTEND`, as the standard says.
+pub fn parse_ics(text: &str) -> Vec<(NaiveDate, String)> {
+    // Long lines are folded onto continuation lines starting with a space or tab
+    let unfolded = text
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let date = |value: &str| NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok();
+
+    let mut days = Vec::new();
+    let (mut start, mut end, mut summary) = (None, None, String::new());
+    for line in unfolded.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.split(';').next().unwrap_or_default() {
+            "BEGIN" if value == "VEVENT" => (start, end, summary) = (None, None, String::new()),
+            "DTSTART" => start = date(value),
+            // A timed end falls on the day it names
+            "DTEND" if value.contains('T') => end = date(value),
+            "DTEND" => end = date(value).and_then(|d| d.pred_opt()),
+            "SUMMARY" => summary = value.replace("\\,", ",").replace("\\;", ";"),
+            "END" if value == "VEVENT" => {
+                if let Some(start) = start {
+                    let end = end.unwrap_or(start).max(start);
+                    for day in start.iter_days().take_while(|day| *day <= end) {
+                        days.push((day, summary.clone()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    days
+}
+
+fn parse_country(json: &str) -> Result<Vec<(NaiveDate, String)>> {
+    let holidays: Vec<CountryHoliday> = serde_json::from_str(json)?;
+    Ok(holidays
+        .into_iter()
+        .map(|h| (h.date, h.local_name))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20241225\r\n\
+            DTEND;VALUE=DATE:20241227\r\n\
+            SUMMARY:Christmas\\, Boxing\r\n  Day\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20240501\r\n\
+            SUMMARY:Labour Day\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        assert_eq!(
+            parse_ics(ics),
+            [
+                (date(12, 25), "Christmas, Boxing Day".to_string()),
+                (date(12, 26), "Christmas, Boxing Day".to_string()),
+                (date(5, 1), "Labour Day".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_country() -> Result<()> {
+        let json = r#"[{"date": "2024-10-03", "localName": "Tag der Deutschen Einheit", "name": "German Unity Day", "countryCode": "DE"}]"#;
+        assert_eq!(
+            parse_country(json)?,
+            [(date(10, 3), "Tag der Deutschen Einheit".to_string())]
+        );
+        Ok(())
+    }
+}
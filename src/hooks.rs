@@ -0,0 +1,154 @@
+use anyhow::{bail, Context, Result};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Variables every hook gets, on top of `env_allow`.
+const BASE_ENV: &[&str] = &["PATH", "HOME", "USER", "LANG", "TMPDIR"];
+/// Pause after the first failure; it doubles with every further failure.
+const BACKOFF_SECS: u64 = 30;
+const MAX_BACKOFF_SECS: u64 = 600;
+/// How often a running hook is checked for having exited.
+const POLL_MILLIS: u64 = 10;
+
+/// A user-configured command, run with a time limit and only the environment
+/// it is allowed to see. After a failure it is skipped for a while, so a broken
+/// command is not retried on every sample. Hooks run one at a time, on the
+/// thread that needs them.
+#[derive(Debug)]
+pub struct Hook {
+    command: Vec<String>,
+    timeout: Duration,
+    env_allow: Vec<String>,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl Hook {
+    pub fn new(command: Vec<String>, timeout: Duration, env_allow: Vec<String>) -> Self {
+        Self {
+            command,
+            timeout,
+            env_allow,
+            failures: 0,
+            retry_at: None,
+        }
+    }
+
+    /// Run the command, or skip it while backing off after a failure. Returns
+    /// whether it ran.
+    pub fn run(&mut self) -> Result<bool> {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return Ok(false);
+        }
+        self.run_now()?;
+        Ok(true)
+    }
+
+    /// Run the command even while backing off, for a run that cannot wait,
+    /// such as undoing something before exiting.
+    pub fn run_now(&mut self) -> Result<()> {
+        match self.run_once() {
+            Ok(()) => {
+                self.failures = 0;
+                self.retry_at = None;
+                Ok(())
+            }
+            Err(err) => {
+                let backoff = (BACKOFF_SECS << self.failures.min(16)).min(MAX_BACKOFF_SECS);
+                self.failures += 1;
+                self.retry_at = Some(Instant::now() + Duration::from_secs(backoff));
+                Err(err.context(format!(
+                    "{} failed; trying again in {}s at the earliest",
+                    self.command.join(" "),
+                    backoff
+                )))
+            }
+        }
+    }
+
+    fn run_once(&self) -> Result<()> {
+        let Some((program, args)) = self.command.split_first() else {
+            return Ok(());
+        };
+        let allowed = BASE_ENV
+            .iter()
+            .copied()
+            .chain(self.env_allow.iter().map(String::as_str));
+        // Captured so the command cannot draw over the dashboard
+        let mut child = Command::new(program)
+            .args(args)
+            .env_clear()
+            .envs(allowed.filter_map(|name| Some((name, std::env::var_os(name)?))))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", program))?;
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!(
+                    "{} did not finish within {}s and was stopped",
+                    self.command.join(" "),
+                    self.timeout.as_secs_f64()
+                );
+            }
+            thread::sleep(Duration::from_millis(POLL_MILLIS));
+        };
+        if !status.success() {
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr);
+            }
+            bail!(
+                "{} exited with {}: {}",
+                self.command.join(" "),
+                status,
+                stderr.trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn test_environment_is_limited_to_the_allowlist() -> Result<()> {
+        std::env::set_var("NEFLO_HOOK_TEST_SECRET", "1");
+        std::env::set_var("NEFLO_HOOK_TEST_ALLOWED", "1");
+        let mut hook = Hook::new(
+            sh(r#"test -z "$NEFLO_HOOK_TEST_SECRET" && test -n "$NEFLO_HOOK_TEST_ALLOWED""#),
+            Duration::from_secs(5),
+            vec!["NEFLO_HOOK_TEST_ALLOWED".to_string()],
+        );
+        assert!(hook.run()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_slow_hook_is_stopped_and_failures_back_off() {
+        let mut hook = Hook::new(sh("sleep 5"), Duration::from_millis(100), Vec::new());
+        let started = Instant::now();
+        let err = hook.run().unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(format!("{:#}", err).contains("did not finish"));
+
+        // Skipped until the backoff has passed, unless it cannot wait
+        assert!(!hook.run().unwrap());
+        assert!(hook.run_now().is_err());
+    }
+}
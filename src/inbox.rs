@@ -0,0 +1,200 @@
+use crate::models::{Database, Interval, Marker};
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// An event dropped into the inbox by an external tool.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum InboxEvent {
+    /// A complete interval, in the same shape as stored in `db.json`.
+    Interval(Interval),
+    /// A note appended to the journal of `date`.
+    Note { date: NaiveDate, text: String },
+    /// A timestamped annotation.
+    Marker(Marker),
+    /// Days taken off, from `from` to `to` inclusive.
+    Off {
+        from: NaiveDate,
+        to: NaiveDate,
+        reason: String,
+    },
+}
+
+/// A file may hold a single event or a list of events.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InboxFile {
+    One(InboxEvent),
+    Many(Vec<InboxEvent>),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct IngestSummary {
+    pub intervals: usize,
+    pub notes: usize,
+    pub markers: usize,
+    pub days_off: usize,
+    /// Files that could not be parsed; they are renamed to `*.rejected`.
+    pub rejected: Vec<PathBuf>,
+}
+
+impl IngestSummary {
+    pub fn changed(&self) -> bool {
+        self.intervals > 0 || self.notes > 0 || self.markers > 0 || self.days_off > 0
+    }
+}
+
+/// Append-only drop-box directory: external tools write `*.json` files (ideally
+/// via a temporary name and a rename) which are ingested and then deleted.
+pub struct Inbox {
+    dir: PathBuf,
+}
+
+impl Inbox {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Drop `events` into the inbox as one file, writing under a temporary name
+    /// first so it is never ingested half-written.
+    pub fn submit(&self, events: &[InboxEvent]) -> Result<PathBuf> {
+        let name = format!(
+            "{}-{}",
+            Utc::now().format("%Y%m%dT%H%M%S%.9f"),
+            std::process::id()
+        );
+        let tmp = self.dir.join(format!("{}.json.tmp", name));
+        let path = self.dir.join(format!("{}.json", name));
+        fs::write(&tmp, serde_json::to_string(events)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    /// Apply every pending event file to `db` in file name order.
+    pub fn ingest(&self, db: &mut Database) -> Result<IngestSummary> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+
+        let mut summary = IngestSummary::default();
+        for path in files {
+            let parsed = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| Ok(serde_json::from_str::<InboxFile>(&data)?));
+            let events = match parsed {
+                Ok(InboxFile::One(event)) => vec![event],
+                Ok(InboxFile::Many(events)) => events,
+                Err(_) => {
+                    let rejected = path.with_extension("json.rejected");
+                    fs::rename(&path, &rejected)?;
+                    summary.rejected.push(rejected);
+                    continue;
+                }
+            };
+
+            let mut intervals = Vec::new();
+            for event in events {
+                match event {
+                    InboxEvent::Interval(interval) => intervals.push(interval),
+                    InboxEvent::Note { date, text } => {
+                        db.add_journal_entry(date, &text);
+                        summary.notes += 1;
+                    }
+                    InboxEvent::Marker(marker) => {
+                        db.add_marker(marker);
+                        summary.markers += 1;
+                    }
+                    InboxEvent::Off { from, to, reason } => {
+                        db.mark_off(from, to, &reason);
+                        summary.days_off += 1;
+                    }
+                }
+            }
+            summary.intervals += db.merge_intervals(intervals);
+            fs::remove_file(&path)?;
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::IntervalType;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_ingest_events() -> Result<()> {
+        let dir = tempdir()?;
+        let inbox_dir = dir.path().join("inbox");
+        let inbox = Inbox::new(inbox_dir.clone())?;
+        fs::write(
+            inbox_dir.join("001-meeting.json"),
+            r#"{"type": "interval", "start": "2024-05-13T09:00:00Z", "end": "2024-05-13T09:30:00Z", "kind": "Idle", "device_id": "calendar"}"#,
+        )?;
+        fs::write(
+            inbox_dir.join("002-notes.json"),
+            r#"[{"type": "note", "date": "2024-05-13", "text": "deployed v2"}]"#,
+        )?;
+        fs::write(inbox_dir.join("003-partial.json.tmp"), "{")?;
+
+        let mut db = Database::default();
+        let summary = inbox.ingest(&mut db)?;
+
+        assert_eq!(summary.intervals, 1);
+        assert_eq!(summary.notes, 1);
+        assert!(summary.rejected.is_empty());
+        assert_eq!(db.intervals[0].kind, IntervalType::Idle);
+        assert_eq!(db.intervals[0].device_id.as_deref(), Some("calendar"));
+        let date = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        assert_eq!(db.journal[&date], "deployed v2");
+
+        // Processed files are removed, files still being written are left alone
+        assert!(!inbox_dir.join("001-meeting.json").exists());
+        assert!(inbox_dir.join("003-partial.json.tmp").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_rejects_invalid_files() -> Result<()> {
+        let dir = tempdir()?;
+        let inbox_dir = dir.path().join("inbox");
+        let inbox = Inbox::new(inbox_dir.clone())?;
+        fs::write(inbox_dir.join("bad.json"), r#"{"type": "unknown"}"#)?;
+
+        let mut db = Database::default();
+        let summary = inbox.ingest(&mut db)?;
+
+        assert!(!summary.changed());
+        assert_eq!(summary.rejected, vec![inbox_dir.join("bad.json.rejected")]);
+        assert!(summary.rejected[0].exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_submitted_marker_is_ingested() -> Result<()> {
+        let dir = tempdir()?;
+        let inbox = Inbox::new(dir.path().join("inbox"))?;
+        let marker = Marker {
+            at: "2024-05-13T14:32:00Z".parse()?,
+            text: "prod incident".to_string(),
+        };
+        inbox.submit(&[InboxEvent::Marker(marker.clone())])?;
+
+        let mut db = Database::default();
+        let summary = inbox.ingest(&mut db)?;
+
+        assert_eq!(summary.markers, 1);
+        assert_eq!(db.markers, vec![marker]);
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use crate::storage::Storage;
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Ask for a reflection note on the terminal. Returns `None` when stdin is not
+/// interactive or the user leaves the note empty.
+pub fn prompt_for_note() -> Result<Option<String>> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(None);
+    }
+
+    print!("Add a note for today (leave empty to skip): ");
+    io::stdout().flush()?;
+
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    let note = line.trim();
+    Ok((!note.is_empty()).then(|| note.to_string()))
+}
+
+/// Append `note` to the journal entry of `date` and persist it.
+pub fn record(storage: &Storage, date: NaiveDate, note: &str) -> Result<()> {
+    let mut db = storage.load()?;
+    db.add_journal_entry(date, note);
+    storage.save(&db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_record_persists_note() -> Result<()> {
+        let dir = tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+
+        record(&storage, date, "deep work on the importer")?;
+
+        let db = storage.load()?;
+        assert_eq!(db.journal[&date], "deep work on the importer");
+
+        Ok(())
+    }
+}
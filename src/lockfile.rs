@@ -0,0 +1,102 @@
+use crate::error::Error;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use fd_lock::{RwLock, RwLockWriteGuard};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Guards the database of one data directory; kept in its state directory.
+const LOCK_FILE: &str = "neflo.lock";
+
+/// Open the lock file in `state_dir` without touching what the holder wrote to it.
+pub fn open(state_dir: &Path) -> Result<RwLock<File>> {
+    let lock_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(state_dir.join(LOCK_FILE))?;
+    Ok(RwLock::new(lock_file))
+}
+
+/// Take the lock and record this process as its holder, or explain who holds it.
+pub fn acquire<'a>(
+    lock: &'a mut RwLock<File>,
+    state_dir: &Path,
+) -> Result<RwLockWriteGuard<'a, File>> {
+    let guard = lock.try_write().map_err(|_| already_running(state_dir))?;
+    let mut file: &File = &guard;
+    file.set_len(0)?;
+    writeln!(file, "{} {}", std::process::id(), Local::now().to_rfc3339())?;
+    Ok(guard)
+}
+
+/// Like [`acquire`], but `None` when a session holds the lock, so the caller
+/// can hand its work to that session instead.
+pub fn try_acquire<'a>(
+    lock: &'a mut RwLock<File>,
+    state_dir: &Path,
+) -> Result<Option<RwLockWriteGuard<'a, File>>> {
+    match acquire(lock, state_dir) {
+        Ok(guard) => Ok(Some(guard)),
+        Err(err) if matches!(err.downcast_ref(), Some(Error::Locked { .. })) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether a session currently holds the lock in `state_dir`.
+pub fn is_held(state_dir: &Path) -> Result<bool> {
+    let mut lock = open(state_dir)?;
+    let held = lock.try_write().is_err();
+    Ok(held)
+}
+
+fn already_running(state_dir: &Path) -> Error {
+    let owner = fs::read_to_string(state_dir.join(LOCK_FILE))
+        .ok()
+        .and_then(|contents| describe_owner(&contents));
+    Error::Locked { owner }
+}
+
+/// Turn the "<pid> <rfc3339 start>" written by [`acquire`] into a readable description.
+fn describe_owner(contents: &str) -> Option<String> {
+    let (pid, started) = contents.trim().split_once(' ')?;
+    let pid: u32 = pid.parse().ok()?;
+    let started = DateTime::parse_from_rfc3339(started).ok()?;
+    Some(format!(
+        "PID {}, started {}",
+        pid,
+        started.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_second_acquire_names_the_holder() -> Result<()> {
+        let dir = tempdir()?;
+        let mut lock = open(dir.path())?;
+        let _guard = acquire(&mut lock, dir.path())?;
+        assert!(is_held(dir.path())?);
+
+        let mut second = open(dir.path())?;
+        let err = acquire(&mut second, dir.path()).unwrap_err().to_string();
+        assert!(
+            err.contains(&format!("(PID {}, started ", std::process::id())),
+            "{}",
+            err
+        );
+        assert!(try_acquire(&mut second, dir.path())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_owner_ignores_garbage() {
+        assert_eq!(describe_owner(""), None);
+        assert_eq!(describe_owner("not a pid"), None);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::config::LogsConfig;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The file being written; rotated files are named `neflo-<time>.log`.
+const CURRENT: &str = "neflo.log";
+
+/// The session log: one "<rfc3339> <message>" line per state change or
+/// error, moved to a new file every day or once it grows past `max_bytes`.
+#[derive(Debug, Clone)]
+pub struct Log {
+    dir: PathBuf,
+    max_bytes: u64,
+    keep: Duration,
+}
+
+impl Log {
+    pub fn new(dir: PathBuf, config: &LogsConfig) -> Self {
+        Self {
+            dir,
+            max_bytes: config.max_kb * 1024,
+            keep: Duration::days(config.keep_days as i64),
+        }
+    }
+
+    pub fn write(&self, at: DateTime<Local>, message: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(CURRENT);
+        if let Ok(metadata) = fs::metadata(&path) {
+            let written: DateTime<Local> = metadata.modified()?.into();
+            if metadata.len() >= self.max_bytes || written.date_naive() != at.date_naive() {
+                self.rotate(&path, written, at)?;
+            }
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{} {}", at.to_rfc3339(), message)?;
+        Ok(())
+    }
+
+    /// Move the current file aside, named after when it was last written,
+    /// and delete files older than `keep`.
+    fn rotate(&self, path: &Path, written: DateTime<Local>, now: DateTime<Local>) -> Result<()> {
+        let name = format!("neflo-{}.log", written.format("%Y-%m-%dT%H%M%S%.3f"));
+        fs::rename(path, self.dir.join(name))?;
+        for file in files(&self.dir)? {
+            let modified: DateTime<Local> = fs::metadata(&file)?.modified()?.into();
+            if now - modified > self.keep {
+                fs::remove_file(file)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Log lines written at or after `since`, oldest first, from all files in `dir`.
+pub fn read(dir: &Path, since: Option<DateTime<Local>>) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for file in files(dir)? {
+        for line in fs::read_to_string(file)?.lines() {
+            let Some(at) = line
+                .split_once(' ')
+                .and_then(|(at, _)| DateTime::parse_from_rfc3339(at).ok())
+            else {
+                continue;
+            };
+            if since.is_none_or(|since| at >= since) {
+                lines.push((at, line.to_string()));
+            }
+        }
+    }
+    lines.sort_by_key(|(at, _)| *at);
+    Ok(lines.into_iter().map(|(_, line)| line).collect())
+}
+
+/// The log files in `dir`, current and rotated.
+fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name.starts_with("neflo") && name.ends_with(".log") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::local_midnight;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rotates_when_full_and_reads_across_files() -> Result<()> {
+        let dir = tempdir()?;
+        let log = Log::new(
+            dir.path().to_path_buf(),
+            &LogsConfig {
+                max_kb: 1,
+                keep_days: 14,
+            },
+        );
+        let start = Local::now();
+        let long = "x".repeat(600);
+        for second in 0..3 {
+            log.write(start + Duration::seconds(second), &long)?;
+        }
+        assert_eq!(files(dir.path())?.len(), 2);
+
+        let all = read(dir.path(), None)?;
+        assert_eq!(all.len(), 3);
+        assert!(all[0].starts_with(&start.to_rfc3339()));
+        let recent = read(dir.path(), Some(start + Duration::seconds(1)))?;
+        assert_eq!(recent.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotates_on_a_new_day() -> Result<()> {
+        let dir = tempdir()?;
+        let log = Log::new(dir.path().to_path_buf(), &LogsConfig::default());
+        log.write(Local::now(), "today")?;
+        let tomorrow = local_midnight(Local::now().date_naive() + Duration::days(1))
+            .unwrap()
+            .with_timezone(&Local);
+        log.write(tomorrow, "tomorrow")?;
+        assert_eq!(files(dir.path())?.len(), 2);
+        Ok(())
+    }
+}
@@ -1,26 +1,83 @@
+mod audio;
+mod background;
+mod blocker;
+mod breaks;
+mod budget;
+mod chart;
+mod clock;
 mod config;
+mod control;
+mod daily_note;
+mod days_off;
+mod edit;
+mod error;
+mod export;
+mod fixups;
+mod holidays;
+mod hooks;
+mod inbox;
+mod journal;
+mod lockfile;
+mod logs;
+mod markers;
 mod models;
+mod mqtt;
+mod notify;
+mod overlay;
+mod paths;
+mod period;
+mod permissions;
+mod query;
+mod reminder;
 mod report;
+mod scheduler;
+mod session;
+mod standup;
 mod stats;
+mod status;
 mod storage;
+mod suggest;
 mod system;
 mod tracker;
+mod trash;
 mod tui;
 mod update;
 mod utils;
+mod websocket;
 
 use anyhow::Result;
+use chrono::Local;
 use clap::{Parser, Subcommand};
-use fd_lock::RwLock;
+use paths::Paths;
 use report::Reporter;
-use std::fs::OpenOptions;
+use scheduler::Scheduler;
+use std::fs::File;
+use std::path::PathBuf;
 use storage::Storage;
-use tracker::Tracker;
+use tracker::{ResetScope, Tracker};
 
 #[derive(Parser)]
 #[command(name = "neflo")]
 #[command(about = "A simple focus and idle time tracker for macOS", long_about = None)]
 struct Cli {
+    /// Directory holding the database, config and lock file (defaults to the XDG
+    /// directories on Linux and ~/.neflo elsewhere)
+    #[arg(long, global = true, env = "NEFLO_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+    /// Keep data and config in neflo-data/ beside the executable (also enabled by a
+    /// neflo.portable file there); --data-dir still takes precedence
+    #[arg(long, global = true)]
+    portable: bool,
+    /// Path to the config file (defaults to config.toml in the data directory)
+    #[arg(long, global = true, env = "NEFLO_CONFIG")]
+    config: Option<PathBuf>,
+    /// Print what import, reset, undo, edit, annotate, mark-off, journal or
+    /// migrate-paths would change, without writing anything
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Color console output: auto (a terminal without NO_COLOR set), always or never
+    #[arg(long, global = true, value_enum, default_value_t = utils::ColorChoice::Auto)]
+    color: utils::ColorChoice,
     #[command(subcommand)]
     command: Commands,
 }
@@ -41,63 +98,1093 @@ enum Commands {
         /// Session duration (e.g. 8h, 30m)
         #[arg(short, long)]
         duration: Option<String>,
+        /// Exit automatically once the session ends
+        #[arg(long)]
+        exit_on_end: bool,
+        /// Focus to reach this session (e.g. 4h), separate from the daily goal
+        #[arg(long)]
+        goal: Option<humantime::Duration>,
+        /// Watch a session running elsewhere in a read-only view
+        #[arg(long, conflicts_with_all = ["threshold", "start_time", "end_time", "duration", "exit_on_end", "goal"])]
+        observe: bool,
+        /// Show only the header and a large timer of the current state, for a
+        /// small always-on window
+        #[arg(long)]
+        compact: bool,
     },
     /// Generate a report of focus/idle time
-    Report,
+    Report {
+        /// Show how likely you are to be focused at each hour of the day instead
+        #[arg(long)]
+        profile_hours: bool,
+        /// Number of past weeks the hourly profile covers
+        #[arg(long, default_value_t = 4, requires = "profile_hours")]
+        weeks: i64,
+        /// Print this month as a Markdown calendar with daily goals and streaks
+        #[arg(long, conflicts_with = "profile_hours")]
+        month_calendar: bool,
+        /// Hours that fill a bar of the weekly chart, instead of the busiest day
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        scale_hours: Option<u64>,
+        /// List these days instead of the current week: today, yesterday,
+        /// this-week, last-week, this-month, last-month, a date or a range
+        /// (YYYY-MM-DD..YYYY-MM-DD)
+        #[arg(long, conflicts_with_all = ["profile_hours", "month_calendar"])]
+        period: Option<String>,
+        /// List the days with the most of this first
+        #[arg(long, value_enum, conflicts_with_all = ["profile_hours", "month_calendar"])]
+        sort: Option<stats::DaySort>,
+        /// List only the first N days
+        #[arg(long, requires = "sort")]
+        top: Option<usize>,
+    },
+    /// Export all intervals as CSV
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+    },
+    /// Import intervals from a CSV file produced by `export`
+    Import {
+        /// CSV file to import
+        file: PathBuf,
+    },
+    /// Print the current state and today's focus, e.g. for a status bar
+    Status {
+        #[arg(long, value_enum, default_value_t = StatusFormat::Text)]
+        format: StatusFormat,
+    },
+    /// Show the session log
+    Logs {
+        /// Only lines from this long ago, e.g. 1h
+        #[arg(long)]
+        since: Option<humantime::Duration>,
+    },
+    /// Keep a file updated with the current state and session focus, for OBS
+    Overlay {
+        /// File to write; .html makes a self-reloading page, anything else one line of text
+        #[arg(long)]
+        out: PathBuf,
+        /// How often to update it
+        #[arg(long, default_value = "5s")]
+        every: humantime::Duration,
+    },
+    /// Print the intervals matching the given filters
+    Query {
+        /// Only intervals of this kind
+        #[arg(long, value_enum)]
+        kind: Option<models::IntervalType>,
+        /// First local date to include (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        /// Last local date to include (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<chrono::NaiveDate>,
+        /// Only these days: today, yesterday, this-week, last-week, this-month,
+        /// last-month, a date or a range (YYYY-MM-DD..YYYY-MM-DD)
+        #[arg(long, conflicts_with_all = ["since", "until"])]
+        period: Option<String>,
+        /// Only intervals recorded by this device
+        #[arg(long)]
+        device: Option<String>,
+        /// Only time spent on desktop spaces with this name from [space_names]
+        #[arg(long)]
+        tag: Option<String>,
+        /// Leave out intervals shorter than this, e.g. 25m
+        #[arg(long)]
+        min_duration: Option<humantime::Duration>,
+        #[arg(long, value_enum, default_value_t = QueryFormat::Json)]
+        format: QueryFormat,
+    },
+    /// Print the focus blocks of the last day you worked, for a standup chat
+    Standup {
+        /// Leave out focus blocks shorter than this
+        #[arg(long, default_value = "10m")]
+        min_duration: humantime::Duration,
+    },
+    /// Work out when today's focus reaches a target at the current pace
+    When {
+        /// Today's remaining focus toward `daily_goal_mins` from the config
+        #[arg(long, required_unless_present = "focus", conflicts_with = "focus")]
+        goal_remaining: bool,
+        /// A total focus time for today to reach instead, e.g. 6h
+        #[arg(long)]
+        focus: Option<humantime::Duration>,
+    },
+    /// Print the CSV export/import schema
+    Schema,
+    /// Suggest the best deep-work windows based on your history
+    Suggest,
+    /// Add a note to today's journal (prompts when no text is given)
+    Journal {
+        /// The note to record
+        note: Option<String>,
+    },
+    /// Add a timestamped marker, e.g. "prod incident", shown on the timeline and in reports
+    Annotate {
+        /// The marker text
+        text: String,
+        /// When it happened: HH:MM today or an RFC 3339 timestamp (default: now)
+        #[arg(long)]
+        at: Option<String>,
+    },
+    /// Mark days as off (vacation, sick days) so stats and goals skip them
+    MarkOff {
+        /// The days: FROM..TO (inclusive) or a single date, as YYYY-MM-DD
+        range: String,
+        /// Why the days are off
+        #[arg(long, default_value = "off")]
+        reason: String,
+    },
     /// Update neflo to the latest version
-    SelfUpdate,
+    SelfUpdate {
+        /// Restart a running session into the new version without losing it
+        #[arg(long)]
+        restart: bool,
+        /// Go back to the version replaced by the last update
+        #[arg(long)]
+        rollback: bool,
+        /// Replace the binary even if Homebrew or Nix installed it
+        #[arg(long)]
+        force: bool,
+    },
+    /// Save and end the running session
+    Stop,
+    /// Erase recorded intervals, including those of a running session
+    Reset {
+        /// Required, to avoid erasing data by accident
+        #[arg(long)]
+        confirm: bool,
+        /// What to erase
+        #[arg(long, value_enum, default_value_t = ResetScope::All)]
+        scope: ResetScope,
+    },
+    /// Restore what the most recent reset or edit removed or replaced
+    Undo,
+    /// Change recorded data in bulk; `neflo undo` reverts each edit
+    Edit {
+        #[command(subcommand)]
+        action: EditAction,
+    },
+    /// Check the macOS privacy permissions Neflo can use
+    Permissions {
+        /// Open System Settings for each missing permission
+        #[arg(long)]
+        open: bool,
+    },
+    /// Check how Neflo reads the system on this machine
+    Doctor,
+    /// Inspect the database
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    /// Move data and config from ~/.neflo to the XDG base directories
+    MigratePaths,
+    /// Inspect the configuration file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum StatusFormat {
+    /// One human-readable line
+    Text,
+    /// All fields as JSON, e.g. for an Übersicht widget
+    Json,
+    /// Item properties for `sketchybar --set`
+    Sketchybar,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum QueryFormat {
+    /// The intervals as a JSON array
+    Json,
+    /// The intervals in the export CSV format
+    Csv,
+    /// The number of intervals and their total duration
+    Total,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ExportFormat {
+    /// All intervals, re-importable with `neflo import`
+    Csv,
+    /// Deep-work blocks as mindful-session JSON for Apple Health
+    Health,
+}
+
+#[derive(Subcommand)]
+enum EditAction {
+    /// Move time spent on the spaces of one tag to another tag
+    Retag {
+        /// Name from [space_names] the time is currently on
+        #[arg(long)]
+        from_tag: String,
+        /// Name from [space_names] to move it to (its lowest space if it has several)
+        #[arg(long)]
+        to_tag: String,
+        /// The days: today, yesterday, this-week, last-week, this-month,
+        /// last-month, a date or a range (YYYY-MM-DD..YYYY-MM-DD)
+        #[arg(long)]
+        range: String,
+    },
+    /// Delete the intervals recorded during the given days
+    Delete {
+        /// The days: today, yesterday, this-week, last-week, this-month,
+        /// last-month, a date or a range (YYYY-MM-DD..YYYY-MM-DD)
+        #[arg(long)]
+        range: String,
+        /// Only intervals of this kind
+        #[arg(long, value_enum)]
+        kind: Option<models::IntervalType>,
+        /// Required, to avoid deleting data by accident
+        #[arg(long)]
+        confirm: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Show how large the database is and what it holds
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Check the config file for syntax errors and invalid values
+    Validate,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = config::load_config()?;
-    let storage = Storage::new()?;
+    let data_dir = match cli.data_dir {
+        Some(dir) => Some(dir),
+        None => Storage::portable_dir(&std::env::current_exe()?, cli.portable),
+    };
+    let paths = Paths::resolve(data_dir, cli.config)?;
+    let config_path = paths.config_file.clone();
+    let dry_run = cli.dry_run;
+    let color = cli.color;
+    if dry_run
+        && !matches!(
+            cli.command,
+            Commands::Import { .. }
+                | Commands::Reset { .. }
+                | Commands::Undo
+                | Commands::Edit { .. }
+                | Commands::Annotate { .. }
+                | Commands::MarkOff { .. }
+                | Commands::Journal { .. }
+                | Commands::MigratePaths
+        )
+    {
+        anyhow::bail!("--dry-run only applies to commands that change data");
+    }
+
+    if let Commands::MigratePaths = cli.command {
+        let home =
+            dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+        let legacy = paths::legacy_dir(&home);
+        if paths::legacy_in_use(&legacy) {
+            anyhow::bail!(
+                "A session is running from {}. Please close it before migrating.",
+                legacy.display()
+            );
+        }
+        let target = Paths::xdg(&home, |var| std::env::var_os(var));
+        if dry_run {
+            let moves = target.planned_moves(&legacy)?;
+            if moves.is_empty() {
+                println!("Nothing to migrate from {}.", legacy.display());
+            }
+            for (from, to) in moves {
+                println!("Would move {} to {}", from.display(), to.display());
+            }
+            return Ok(());
+        }
+        let moved = target.migrate_from(&legacy)?;
+        if moved.is_empty() {
+            println!("Nothing to migrate from {}.", legacy.display());
+        } else {
+            for path in moved {
+                println!("Moved {}", path.display());
+            }
+        }
+        return Ok(());
+    }
+
+    if let Commands::Config {
+        action: ConfigAction::Validate,
+    } = cli.command
+    {
+        if !config_path.exists() {
+            println!(
+                "No config file at {}; defaults will be used.",
+                config_path.display()
+            );
+            return Ok(());
+        }
+        config::load_config(&config_path)?;
+        println!("Config file {} is valid.", config_path.display());
+        return Ok(());
+    }
+
+    let config = config::load_config(&config_path)?;
+    let device_id = config.device_id.clone().unwrap_or_else(system::device_name);
+    let mut storage = Storage::new(&paths.data_dir);
+    storage.device = Some(device_id.clone());
+    storage.user = Some(system::user_name());
+    storage.dry_run = dry_run;
 
     match cli.command {
+        Commands::Start {
+            observe: true,
+            compact,
+            ..
+        } => {
+            let mut tracker =
+                Tracker::new(storage, config.default_threshold_mins, None, None, None)?;
+            tracker.stats_options = config.stats_options();
+            let ui = tui::UiState {
+                observe: true,
+                compact,
+                kinds: config.kinds.clone(),
+                targets: budget::parse_all(&config.weekly_targets)?,
+                space_names: config.space_names.clone(),
+                chart_scale: config.chart_scale(),
+                shade_borderline: config.shade_borderline,
+                workday: config.workday(),
+                ..Default::default()
+            };
+            tui::run_tui(&mut tracker, &paths.state_dir, ui)?;
+        }
         Commands::Start {
             threshold,
             start_time,
             end_time,
             duration,
+            exit_on_end,
+            goal,
+            observe: false,
+            compact,
         } => {
-            let base_dir = Storage::get_base_dir()?;
-            let lock_path = base_dir.join("neflo.lock");
-            let lock_file = OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(lock_path)?;
-
-            let mut lock = RwLock::new(lock_file);
-            let _guard = lock.try_write().map_err(|_| {
-                anyhow::anyhow!("Another instance of Neflo is already running. Please close it before starting a new one.")
-            })?;
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            let _guard = lockfile::acquire(&mut lock, &paths.state_dir)?;
 
             let threshold = threshold.unwrap_or(config.default_threshold_mins);
-            let start_time = start_time.or(config.start_time);
-            let end_time = end_time.or(config.end_time);
-            let duration = duration.or(config.duration);
+            let start_time = start_time.or(config.schedule.start_time.clone());
+            let end_time = end_time.or(config.schedule.end_time.clone());
+            let duration = duration.or(config.schedule.duration.clone());
 
             let mut tracker =
                 Tracker::new(storage.clone(), threshold, start_time, end_time, duration)?;
+            tracker.overlap_mode = config.overlap_mode;
+            tracker.gap_threshold = chrono::Duration::seconds(config.gap_secs as i64);
+            tracker.session_goal = goal.map(|goal| utils::chrono_duration(goal.into()));
+            tracker.daily_goal = config
+                .daily_goal_mins
+                .filter(|_| config.celebrate_goal)
+                .map(|mins| chrono::Duration::minutes(mins as i64));
+            tracker.device_id = Some(device_id);
+            tracker.stats_options = config.stats_options();
+            tracker.inbox = Some(inbox::Inbox::new(paths.data_dir.join("inbox"))?);
+            tracker.battery_saver = config.power.battery_saver;
+            tracker.sample_secs = config.power.sample_secs as i64;
+            tracker.idle_reminder = config
+                .idle_reminder
+                .as_ref()
+                .map(reminder::IdleReminder::from_config);
+            tracker.notifier = notify::Notifier::from_config(&config.notifications)?;
+            tracker.mqtt = config
+                .mqtt
+                .as_ref()
+                .zip(tracker.device_id.as_deref())
+                .map(|(mqtt, device)| mqtt::MqttPublisher::new(mqtt, device))
+                .transpose()?;
+            tracker.blocker = config.blocker.as_ref().map(blocker::Blocker::from_config);
+            tracker.websocket = config
+                .websocket
+                .as_ref()
+                .map(websocket::EventStream::bind)
+                .transpose()?;
+            tracker.holidays = config
+                .holidays
+                .as_ref()
+                .and_then(holidays::Holidays::from_config);
+            tracker.audio = config
+                .audio
+                .as_ref()
+                .map(audio::AudioCues::from_config)
+                .transpose()?;
+            tracker.scheduler =
+                Scheduler::from_config(&config, &paths.data_dir, Local::now().naive_local())?;
+            if let Some(session_start) = update::resumed_session_start() {
+                tracker.run_start_time = session_start;
+                // Announced before the restart
+                tracker.session_goal_met = tracker.session_goal.is_some_and(|goal| {
+                    stats::session_focus(&tracker.db, session_start, tracker.device_id.as_deref())
+                        >= goal
+                });
+            }
+            tracker.record_session();
 
-            tui::run_tui(&mut tracker)?;
+            let end = tui::run_tui(
+                &mut tracker,
+                &paths.state_dir,
+                tui::UiState {
+                    kinds: config.kinds.clone(),
+                    targets: budget::parse_all(&config.weekly_targets)?,
+                    space_names: config.space_names.clone(),
+                    breaks: breaks::parse_all(&config.breaks)?,
+                    chart_scale: config.chart_scale(),
+                    detect_presenting: config.presenting_is_focus,
+                    compact,
+                    track_intensity: config.track_intensity,
+                    shade_borderline: config.shade_borderline,
+                    workday: config.workday(),
+                    log: Some(logs::Log::new(paths.state_dir.join("logs"), &config.logs)),
+                    quota: Some(config.database.clone()),
+                    exit_after: (exit_on_end || config.schedule.exit_on_end).then(|| {
+                        chrono::Duration::seconds(config.schedule.exit_countdown_secs as i64)
+                    }),
+                    ..Default::default()
+                },
+            )?;
 
-            // Final save
-            tracker.storage.save(&tracker.db)?;
+            // Never leave sites blocked after exiting, and save even if unblocking fails
+            if !matches!(end, session::SessionEnd::Restart(_)) {
+                tracker.end_session(chrono::Utc::now());
+            }
+            let released = tracker.release_blocker();
+            match (tracker.storage.save(&tracker.db), released) {
+                (Ok(()), result) | (result, Ok(())) => result?,
+                (Err(save), Err(release)) => anyhow::bail!("{:#}; {:#}", save, release),
+            }
+
+            if let session::SessionEnd::Restart(exe) = &end {
+                // The lock file is opened close-on-exec, so the new process can take it
+                update::restart(exe, tracker.run_start_time)?;
+            }
 
             // Report
             println!("\nSession ended automatically or by user.");
-            let reporter = Reporter::new(storage);
+            // Unattended and remotely stopped runs should not block on the journal prompt
+            if end == session::SessionEnd::Quit {
+                if let Some(note) = journal::prompt_for_note()? {
+                    journal::record(&storage, Local::now().date_naive(), &note)?;
+                }
+            }
+            let mut reporter = Reporter::new(storage, config.stats_options());
+            reporter.space_names = config.space_names.clone();
+            reporter.targets = budget::parse_all(&config.weekly_targets)?;
+            reporter.kinds = config.kinds.clone();
+            reporter.default_threshold_mins = config.default_threshold_mins;
+            reporter.chart_scale = config.chart_scale();
+            reporter.workday = config.workday();
             reporter.report()?;
+            if let Some(mqtt) = &tracker.mqtt {
+                mqtt.publish_offline()?;
+            }
         }
-        Commands::Report => {
-            let reporter = Reporter::new(storage);
-            reporter.report()?;
+        Commands::Report {
+            profile_hours,
+            weeks,
+            month_calendar,
+            scale_hours,
+            period,
+            sort,
+            top,
+        } => {
+            let mut reporter = Reporter::new(storage, config.stats_options());
+            reporter.space_names = config.space_names.clone();
+            reporter.targets = budget::parse_all(&config.weekly_targets)?;
+            reporter.kinds = config.kinds.clone();
+            reporter.default_threshold_mins = config.default_threshold_mins;
+            reporter.chart_scale = match scale_hours {
+                Some(hours) => chart::Scale::Fixed(chrono::Duration::hours(hours as i64)),
+                None => config.chart_scale(),
+            };
+            reporter.workday = config.workday();
+            reporter.period = period
+                .map(|period| period::Period::parse(&period, Local::now().date_naive()))
+                .transpose()?;
+            reporter.sort = sort;
+            reporter.top = top;
+            reporter.color = color.enabled();
+            if profile_hours {
+                reporter.profile_hours(weeks)?;
+            } else if month_calendar {
+                reporter.month_calendar(
+                    config
+                        .daily_goal_mins
+                        .map(|mins| chrono::Duration::minutes(mins as i64)),
+                )?;
+            } else {
+                reporter.report()?;
+            }
+        }
+        Commands::Export { output, format } => {
+            let db = storage.load()?;
+            let writer: Box<dyn std::io::Write> = match output {
+                Some(path) => Box::new(File::create(path)?),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            match format {
+                ExportFormat::Csv => export::write_csv(&db, writer)?,
+                ExportFormat::Health => {
+                    export::write_health_json(&db, &config.stats_options(), writer)?
+                }
+            }
+        }
+        Commands::Import { file } => {
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            // Nothing is written in a dry run, so a running session is no obstacle
+            let _guard = if dry_run {
+                None
+            } else {
+                Some(lockfile::acquire(&mut lock, &paths.state_dir)?)
+            };
+
+            let imported = export::read_csv(File::open(&file)?)?;
+            let total = imported.len();
+            let mut db = storage.load()?;
+            let added = db.merge_intervals(imported);
+            storage.save(&db)?;
+            if dry_run {
+                return Ok(());
+            }
+            println!(
+                "Imported {} of {} intervals ({} duplicates skipped).",
+                added,
+                total,
+                total - added
+            );
+        }
+        Commands::Logs { since } => {
+            let since = since
+                .map(|since| chrono::Duration::from_std(since.into()))
+                .transpose()?
+                .map(|since| Local::now() - since);
+            for line in logs::read(&paths.state_dir.join("logs"), since)? {
+                println!("{}", line);
+            }
+        }
+        Commands::Status { format } => {
+            let running = lockfile::is_held(&paths.state_dir)?;
+            let status = status::Status::new(
+                &storage.load()?,
+                running,
+                &config.stats_options(),
+                &config.kinds,
+                &clock::SystemClock,
+            );
+            match format {
+                StatusFormat::Text => println!("{}", status.text()),
+                StatusFormat::Json => println!("{}", serde_json::to_string(&status)?),
+                StatusFormat::Sketchybar => println!("{}", status.sketchybar()),
+            }
+        }
+        Commands::Overlay { out, every } => {
+            let format = overlay::Format::from_path(&out);
+            println!(
+                "Updating {} every {}; press Ctrl-C to stop.",
+                out.display(),
+                every
+            );
+            let mut last = String::new();
+            loop {
+                let running = lockfile::is_held(&paths.state_dir)?;
+                let contents = overlay::Overlay::new(
+                    &storage.load()?,
+                    running,
+                    &config.stats_options(),
+                    &config.kinds,
+                    &clock::SystemClock,
+                )
+                .render(format, every.as_secs().max(1));
+                if contents != last {
+                    overlay::write(&out, &contents)?;
+                    last = contents;
+                }
+                std::thread::sleep(every.into());
+            }
+        }
+        Commands::Standup { min_duration } => {
+            let db = storage.load()?;
+            let today = Local::now().date_naive();
+            let Some(date) = stats::last_active_day(&db, today) else {
+                println!("No focus recorded before today.");
+                return Ok(());
+            };
+            let blocks = stats::focus_blocks(
+                &db,
+                date,
+                chrono::Duration::from_std(min_duration.into())?,
+                &config.stats_options(),
+                &config.space_names,
+            );
+            println!("{}", standup::render(&db, date, &blocks));
+        }
+        Commands::Query {
+            kind,
+            since,
+            until,
+            period,
+            device,
+            tag,
+            min_duration,
+            format,
+        } => {
+            let spaces = tag.map(|tag| config.tag_spaces(&tag)).transpose()?;
+            let (since, until) = match period {
+                Some(period) => {
+                    let period = period::Period::parse(&period, Local::now().date_naive())?;
+                    (Some(period.first()), Some(period.last()))
+                }
+                None => (since, until),
+            };
+            let query = query::Query {
+                kind,
+                since,
+                until,
+                device,
+                spaces,
+                min_duration: min_duration
+                    .map(|d| chrono::Duration::from_std(d.into()))
+                    .transpose()?,
+            };
+            let db = storage.load()?;
+            let intervals = query.run(&db);
+            match format {
+                QueryFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&intervals)?)
+                }
+                QueryFormat::Csv => export::write_csv_rows(
+                    &intervals,
+                    db.meta.owner.as_deref(),
+                    std::io::stdout().lock(),
+                )?,
+                QueryFormat::Total => {
+                    let total = intervals
+                        .iter()
+                        .fold(chrono::Duration::zero(), |sum, i| sum + (i.end - i.start));
+                    println!(
+                        "{} intervals, {}",
+                        intervals.len(),
+                        utils::format_duration(total.num_seconds())
+                    );
+                }
+            }
+        }
+        Commands::When {
+            goal_remaining: _,
+            focus,
+        } => {
+            let target = match focus {
+                Some(focus) => chrono::Duration::from_std(focus.into())?,
+                None => match config.daily_goal_mins {
+                    Some(mins) => chrono::Duration::minutes(mins as i64),
+                    None => anyhow::bail!(
+                        "Set daily_goal_mins in {} or pass --focus.",
+                        config_path.display()
+                    ),
+                },
+            };
+            let db = storage.load()?;
+            let now = chrono::Utc::now();
+            let focus_so_far = stats::calculate_stats(&db, None, &config.stats_options(), &now)
+                .today_summary
+                .total_focus;
+            let target_text = utils::format_minutes(target);
+            match stats::focus_eta(&db, focus_so_far, target, now) {
+                stats::FocusEta::Reached => println!(
+                    "{} reached: {} of focus today.",
+                    target_text,
+                    utils::format_minutes(focus_so_far)
+                ),
+                stats::FocusEta::At(at)
+                    if at.with_timezone(&Local).date_naive() != Local::now().date_naive() =>
+                {
+                    println!(
+                        "{} of focus to go; at today's pace you do not reach {} before midnight.",
+                        utils::format_minutes(target - focus_so_far),
+                        target_text
+                    )
+                }
+                stats::FocusEta::At(at) => println!(
+                    "{} of focus to go; at today's pace you reach {} at {}.",
+                    utils::format_minutes(target - focus_so_far),
+                    target_text,
+                    at.with_timezone(&Local).format("%H:%M")
+                ),
+                stats::FocusEta::Unknown => {
+                    println!("No focus recorded today yet; {} to go.", target_text)
+                }
+            }
+        }
+        Commands::Schema => {
+            print!("{}", export::csv_schema());
+        }
+        Commands::Suggest => {
+            let db = storage.load()?;
+            let windows = suggest::suggest_from_history(&db, chrono::Utc::now());
+            println!("{}", suggest::describe(&windows));
+        }
+        Commands::Journal { note } => {
+            let note = match note {
+                Some(note) => Some(note),
+                None => journal::prompt_for_note()?,
+            };
+            let Some(note) = note else {
+                println!("No note recorded.");
+                return Ok(());
+            };
+            let date = Local::now().date_naive();
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            let guard = lockfile::try_acquire(&mut lock, &paths.state_dir)?;
+            if dry_run {
+                journal::record(&storage, date, &note)?;
+            } else if guard.is_some() {
+                journal::record(&storage, date, &note)?;
+                println!("Journal entry saved.");
+            } else {
+                // The running session owns the database; hand the note over
+                inbox::Inbox::new(paths.data_dir.join("inbox"))?
+                    .submit(&[inbox::InboxEvent::Note { date, text: note }])?;
+                println!("Journal entry sent to the running session.");
+            }
+        }
+        Commands::Annotate { text, at } => {
+            let at = match at {
+                Some(at) => markers::parse_at(&at, Local::now())?,
+                None => chrono::Utc::now(),
+            };
+            let marker = models::Marker { at, text };
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            let guard = lockfile::try_acquire(&mut lock, &paths.state_dir)?;
+            if dry_run {
+                markers::record(&storage, marker)?;
+            } else if guard.is_some() {
+                markers::record(&storage, marker)?;
+                println!("Marker added.");
+            } else {
+                // The running session owns the database; hand the marker over
+                inbox::Inbox::new(paths.data_dir.join("inbox"))?
+                    .submit(&[inbox::InboxEvent::Marker(marker)])?;
+                println!("Marker sent to the running session.");
+            }
+        }
+        Commands::MarkOff { range, reason } => {
+            let (from, to) = days_off::parse_range(&range)?;
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            let guard = lockfile::try_acquire(&mut lock, &paths.state_dir)?;
+            if dry_run {
+                days_off::record(&storage, from, to, &reason)?;
+            } else if guard.is_some() {
+                days_off::record(&storage, from, to, &reason)?;
+                println!("Marked {} as {}.", range, reason);
+            } else {
+                // The running session owns the database; hand the days over
+                inbox::Inbox::new(paths.data_dir.join("inbox"))?
+                    .submit(&[inbox::InboxEvent::Off { from, to, reason }])?;
+                println!("Days off sent to the running session.");
+            }
+        }
+        Commands::SelfUpdate {
+            restart,
+            rollback,
+            force,
+        } => {
+            let exe = std::env::current_exe()?;
+            if let (false, Some(manager)) = (force, update::PackageManager::owning(&exe)) {
+                anyhow::bail!(
+                    "neflo was installed with {:?}, which would break if it were replaced; {} instead, \
+                     or pass --force to replace it anyway.",
+                    manager,
+                    manager.instructions()
+                );
+            }
+            let changed = if rollback {
+                let version =
+                    update::rollback(&paths.data_dir, &exe, self_update::cargo_crate_version!())?;
+                println!("Rolled back to version {}.", version);
+                true
+            } else {
+                update::update(&paths.data_dir, &exe)?
+            };
+            if changed && restart {
+                if lockfile::is_held(&paths.state_dir)? {
+                    update::request_restart(&paths.state_dir, &exe)?;
+                    println!("Asked the running session to restart into the new version.");
+                } else {
+                    println!("No running session to restart.");
+                }
+            }
+        }
+        Commands::Stop => {
+            if lockfile::is_held(&paths.state_dir)? {
+                control::send(&paths.state_dir, control::Request::Stop)?;
+                println!("Asked the running session to stop.");
+            } else {
+                println!("No running session to stop.");
+            }
+        }
+        Commands::Reset { confirm, scope } => {
+            if !confirm && !dry_run {
+                anyhow::bail!(
+                    "This erases recorded intervals; run `neflo reset --confirm` to proceed."
+                );
+            }
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            let guard = lockfile::try_acquire(&mut lock, &paths.state_dir)?;
+            if guard.is_some() || dry_run {
+                if scope == ResetScope::Session {
+                    anyhow::bail!(match guard {
+                        Some(_) => "No running session; use `--scope today` or `--scope all`.",
+                        None =>
+                            "--dry-run cannot preview the running session's reset; \
+                                 use `--scope today` or `--scope all`.",
+                    });
+                }
+                let mut tracker =
+                    Tracker::new(storage, config.default_threshold_mins, None, None, None)?;
+                let removed = tracker.reset(scope, chrono::Utc::now())?;
+                if dry_run {
+                    return Ok(());
+                }
+                println!(
+                    "Moved {} intervals to the trash; `neflo undo` restores them.",
+                    removed
+                );
+            } else {
+                control::send(&paths.state_dir, control::Request::Reset(scope))?;
+                println!("Asked the running session to reset.");
+            }
+        }
+        Commands::Undo => {
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            let guard = lockfile::try_acquire(&mut lock, &paths.state_dir)?;
+            let trash_dir = storage
+                .trash_dir()
+                .ok_or_else(|| anyhow::anyhow!("This storage keeps no trash"))?;
+            let Some((file, entry)) = trash::latest(&trash_dir)? else {
+                println!("Nothing to undo.");
+                return Ok(());
+            };
+            if dry_run || guard.is_some() {
+                let mut db = storage.load()?;
+                db.merge_intervals(entry.intervals.clone());
+                db.put_spaces(entry.spaces.clone());
+                storage.save(&db)?;
+                if dry_run {
+                    return Ok(());
+                }
+            } else if !entry.spaces.is_empty() {
+                // The inbox only takes intervals
+                anyhow::bail!(
+                    "A session is running; stop it with `neflo stop` before undoing a retag."
+                );
+            } else {
+                // The running session owns the database; hand the intervals over
+                let events: Vec<_> = entry
+                    .intervals
+                    .iter()
+                    .cloned()
+                    .map(inbox::InboxEvent::Interval)
+                    .collect();
+                inbox::Inbox::new(paths.data_dir.join("inbox"))?.submit(&events)?;
+            }
+            std::fs::remove_file(&file)?;
+            if entry.spaces.is_empty() {
+                println!(
+                    "Restored {} intervals from {}.",
+                    entry.intervals.len(),
+                    file.display()
+                );
+            } else {
+                println!(
+                    "Restored {} space spans from {}.",
+                    entry.spaces.len(),
+                    file.display()
+                );
+            }
+        }
+        Commands::Edit { action } => {
+            let mut lock = lockfile::open(&paths.state_dir)?;
+            // Nothing is written in a dry run, so a running session is no obstacle
+            let _guard = if dry_run {
+                None
+            } else {
+                Some(lockfile::acquire(&mut lock, &paths.state_dir)?)
+            };
+            let today = Local::now().date_naive();
+            match action {
+                EditAction::Retag {
+                    from_tag,
+                    to_tag,
+                    range,
+                } => {
+                    let period = period::Period::parse(&range, today)?;
+                    let spaces = config.tag_spaces(&from_tag)?;
+                    let space = config.tag_spaces(&to_tag)?[0];
+                    let moved = edit::retag(&storage, period, &spaces, space, chrono::Utc::now())?;
+                    if dry_run {
+                        return Ok(());
+                    }
+                    if moved.is_zero() {
+                        println!("Nothing was recorded on '{}' in {}.", from_tag, range);
+                    } else {
+                        println!(
+                            "Moved {} from '{}' to '{}'; `neflo undo` moves it back.",
+                            utils::format_duration(moved.num_seconds()),
+                            from_tag,
+                            to_tag
+                        );
+                    }
+                }
+                EditAction::Delete {
+                    range,
+                    kind,
+                    confirm,
+                } => {
+                    if !confirm && !dry_run {
+                        anyhow::bail!(
+                            "This deletes recorded intervals; run `neflo edit delete --confirm` to proceed."
+                        );
+                    }
+                    let period = period::Period::parse(&range, today)?;
+                    let removed = edit::delete(&storage, period, kind, chrono::Utc::now())?;
+                    if dry_run {
+                        return Ok(());
+                    }
+                    println!(
+                        "Moved {} intervals to the trash; `neflo undo` restores them.",
+                        removed.len()
+                    );
+                }
+            }
+        }
+        Commands::Doctor => {
+            let db = storage.load()?;
+            let violations = db.validate();
+            let meta = db.meta;
+            let unknown = || "unknown".to_string();
+            println!(
+                "Database: owned by {}, schema version {}, created {}, last written by neflo {} on {}",
+                meta.owner.unwrap_or_else(unknown),
+                meta.schema_version,
+                meta.created_at.map_or_else(unknown, |t| {
+                    t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+                }),
+                meta.app_version.unwrap_or_else(unknown),
+                meta.device.unwrap_or_else(unknown)
+            );
+            match violations.len() {
+                0 => println!("Intervals: {} recorded, consistent", db.intervals.len()),
+                count => {
+                    println!("Intervals: {} problems", count);
+                    for violation in violations.iter().take(10) {
+                        println!("  {}", violation);
+                    }
+                }
+            }
+            if !db.gaps.is_empty() {
+                println!("Gaps: {} pauses longer than gap_secs", db.gaps.len());
+            }
+            let detector = system::IdleDetector::new();
+            let source = detector.source();
+            println!("Idle detection: {}", source.name());
+            let reading = |value: Option<f64>| {
+                value.map_or("unavailable".to_string(), |secs| {
+                    format!("{:.1}s idle", secs)
+                })
+            };
+            if let Some(monitor) = detector.dbus_monitor() {
+                println!(
+                    "  {}: {}",
+                    monitor.name(),
+                    reading(system::dbus_idle_time(monitor))
+                );
+            } else if source != system::IdleSource::Unavailable {
+                println!(
+                    "  {}: {}",
+                    system::IdleSource::EventSource.name(),
+                    reading(system::event_source_idle_time())
+                );
+                println!(
+                    "  {}: {}",
+                    system::IdleSource::HidIdleTime.name(),
+                    reading(system::hid_idle_time())
+                );
+                println!(
+                    "  A running session switches to {} if {} returns the same value for {} seconds.",
+                    system::IdleSource::HidIdleTime.name(),
+                    system::IdleSource::EventSource.name(),
+                    system::FROZEN_SECS
+                );
+            }
+        }
+        Commands::Db {
+            action: DbAction::Stats,
+        } => {
+            let db = storage.load()?;
+            let limits = &config.database;
+            let bytes = storage.size();
+            println!(
+                "File: {}, {:.1} MB (warning above {} MB)",
+                storage.path().display(),
+                bytes as f64 / 1_000_000.0,
+                limits.warn_mb
+            );
+            let oldest = db.intervals.iter().map(|i| i.start).min();
+            println!(
+                "Intervals: {} (warning above {}){}",
+                db.intervals.len(),
+                limits.warn_intervals,
+                oldest.map_or(String::new(), |t| format!(
+                    ", oldest from {}",
+                    t.with_timezone(&Local).format("%Y-%m-%d")
+                ))
+            );
+            for (what, count) in [
+                ("Space spans", db.spaces.len()),
+                ("Gaps", db.gaps.len()),
+                ("Markers", db.markers.len()),
+                ("Sessions", db.sessions.len()),
+                ("Journal entries", db.journal.len()),
+                ("Days off", db.days_off.len()),
+            ] {
+                println!("{}: {}", what, count);
+            }
+            if let Some(warning) = storage::quota_warning(limits, bytes, db.intervals.len()) {
+                println!("{}", warning);
+            }
+        }
+        Commands::Permissions { open } => {
+            for permission in permissions::Permission::ALL {
+                match permission.status() {
+                    permissions::Status::Granted => println!("{}: granted", permission.name()),
+                    permissions::Status::NotApplicable => {
+                        println!("{}: not needed on this platform", permission.name())
+                    }
+                    permissions::Status::Missing => {
+                        println!("{}: missing", permission.name());
+                        println!("  {}", permission.explain());
+                        if open {
+                            permission.open_settings()?;
+                        }
+                    }
+                }
+            }
         }
-        Commands::SelfUpdate => {
-            update::update()?;
+        Commands::Config { .. } | Commands::MigratePaths => {
+            unreachable!("handled before loading the config")
         }
     }
 
@@ -0,0 +1,78 @@
+use crate::models::Marker;
+use crate::storage::Storage;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveTime, Utc};
+
+/// Parse `--at` as a time of day (HH:MM) on the date of `now`, or as a full
+/// RFC 3339 timestamp.
+pub fn parse_at(value: &str, now: DateTime<Local>) -> Result<DateTime<Utc>> {
+    if let Ok(time) = NaiveTime::parse_from_str(value, "%H:%M") {
+        return now
+            .date_naive()
+            .and_time(time)
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .ok_or_else(|| anyhow!("{} does not exist today (daylight saving change)", value));
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| {
+            anyhow!(
+                "Invalid time '{}': expected HH:MM or an RFC 3339 timestamp",
+                value
+            )
+        })
+}
+
+/// Add `marker` to the database and persist it.
+pub fn record(storage: &Storage, marker: Marker) -> Result<()> {
+    let mut db = storage.load()?;
+    db.add_marker(marker);
+    storage.save(&db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_at() -> Result<()> {
+        let now = Local.with_ymd_and_hms(2024, 5, 13, 16, 0, 0).unwrap();
+        let expected = Local.with_ymd_and_hms(2024, 5, 13, 14, 32, 0).unwrap();
+        assert_eq!(parse_at("14:32", now)?, expected.with_timezone(&Utc));
+        assert_eq!(
+            parse_at("2024-05-13T14:32:00Z", now)?,
+            "2024-05-13T14:32:00Z".parse::<DateTime<Utc>>()?
+        );
+        assert!(parse_at("2pm", now).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_keeps_markers_ordered() -> Result<()> {
+        let dir = tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        for (time, text) in [
+            ("2024-05-13T15:00:00Z", "deploy"),
+            ("2024-05-13T14:32:00Z", "incident"),
+        ] {
+            record(
+                &storage,
+                Marker {
+                    at: at(time),
+                    text: text.to_string(),
+                },
+            )?;
+        }
+
+        let db = storage.load()?;
+        let texts: Vec<_> = db.markers.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, ["incident", "deploy"]);
+        Ok(())
+    }
+}
@@ -1,30 +1,1155 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
 pub enum IntervalType {
     Focus,
     Idle,
+    /// Time the tracker could not observe, e.g. while the machine was asleep.
+    Away,
+    /// The screen was locked, or another user's session was on the display.
+    Locked,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl IntervalType {
+    pub const ALL: [IntervalType; 4] = [
+        IntervalType::Focus,
+        IntervalType::Idle,
+        IntervalType::Locked,
+        IntervalType::Away,
+    ];
+
+    /// Which kind wins where a device's intervals from different sources
+    /// overlap, highest first: observed time beats unobserved time, focus
+    /// beats idle, and idle beats locked.
+    const PRIORITY: [IntervalType; 4] = [
+        IntervalType::Focus,
+        IntervalType::Idle,
+        IntervalType::Locked,
+        IntervalType::Away,
+    ];
+}
+
+/// A broken invariant of the interval timeline, found by `Database::validate`.
+/// `index` is the position of the offending interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    EndsBeforeStart {
+        index: usize,
+    },
+    /// Starts before the previous interval of its device.
+    OutOfOrder {
+        index: usize,
+    },
+    /// Starts before the previous interval of its device ends.
+    Overlap {
+        index: usize,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::EndsBeforeStart { index } => {
+                write!(f, "interval {} ends before it starts", index)
+            }
+            Violation::OutOfOrder { index } => write!(
+                f,
+                "interval {} starts before the previous one of its device",
+                index
+            ),
+            Violation::Overlap { index } => write!(
+                f,
+                "interval {} overlaps the previous one of its device",
+                index
+            ),
+        }
+    }
+}
+
+/// What saving a database over another would change, shown by `--dry-run`
+/// instead of writing.
+#[derive(Debug, PartialEq)]
+pub struct ChangePlan {
+    pub added: Vec<Interval>,
+    pub removed: Vec<Interval>,
+    pub markers_added: usize,
+    pub markers_removed: usize,
+    /// Desktop space spans, which a retag replaces.
+    pub spaces_added: usize,
+    pub spaces_removed: usize,
+    /// Dates whose day-off entry would be added, removed or changed.
+    pub days_off_changed: Vec<NaiveDate>,
+    /// Dates whose journal entry would be added, removed or changed.
+    pub journal_changed: Vec<NaiveDate>,
+    /// Data fixups and the schema version the save would record.
+    pub fixups: (u32, u32),
+    pub schema_version: (u32, u32),
+}
+
+impl ChangePlan {
+    pub fn between(before: &Database, after: &Database) -> Self {
+        let missing_from = |a: &[Interval], b: &[Interval]| -> Vec<Interval> {
+            a.iter().filter(|i| !b.contains(i)).cloned().collect()
+        };
+        let changed_dates = |a: &BTreeMap<NaiveDate, String>, b: &BTreeMap<NaiveDate, String>| {
+            let dates: BTreeSet<&NaiveDate> = a.keys().chain(b.keys()).collect();
+            dates
+                .into_iter()
+                .filter(|date| a.get(*date) != b.get(*date))
+                .copied()
+                .collect()
+        };
+        Self {
+            added: missing_from(&after.intervals, &before.intervals),
+            removed: missing_from(&before.intervals, &after.intervals),
+            markers_added: after
+                .markers
+                .iter()
+                .filter(|m| !before.markers.contains(m))
+                .count(),
+            markers_removed: before
+                .markers
+                .iter()
+                .filter(|m| !after.markers.contains(m))
+                .count(),
+            spaces_added: after
+                .spaces
+                .iter()
+                .filter(|s| !before.spaces.contains(s))
+                .count(),
+            spaces_removed: before
+                .spaces
+                .iter()
+                .filter(|s| !after.spaces.contains(s))
+                .count(),
+            days_off_changed: changed_dates(&before.days_off, &after.days_off),
+            journal_changed: changed_dates(&before.journal, &after.journal),
+            fixups: (before.meta.fixups, after.meta.fixups),
+            schema_version: (before.meta.schema_version, DB_SCHEMA_VERSION),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.markers_added == 0
+            && self.markers_removed == 0
+            && self.spaces_added == 0
+            && self.spaces_removed == 0
+            && self.days_off_changed.is_empty()
+            && self.journal_changed.is_empty()
+            && self.fixups.0 == self.fixups.1
+            && self.schema_version.0 == self.schema_version.1
+    }
+}
+
+impl fmt::Display for ChangePlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Nothing would change.");
+        }
+        let mut lines = Vec::new();
+        let span = |intervals: &[Interval]| {
+            let start = intervals.iter().map(|i| i.start).min();
+            let end = intervals.iter().map(|i| i.end).max();
+            match start.zip(end) {
+                Some((start, end)) => format!(
+                    " ({} to {})",
+                    start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    end.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                ),
+                None => String::new(),
+            }
+        };
+        for (verb, intervals) in [("add", &self.added), ("remove", &self.removed)] {
+            if !intervals.is_empty() {
+                lines.push(format!(
+                    "Would {} {} intervals{}",
+                    verb,
+                    intervals.len(),
+                    span(intervals)
+                ));
+            }
+        }
+        for (verb, count) in [
+            ("add", self.markers_added),
+            ("remove", self.markers_removed),
+        ] {
+            if count > 0 {
+                lines.push(format!("Would {} {} markers", verb, count));
+            }
+        }
+        for (verb, count) in [("add", self.spaces_added), ("remove", self.spaces_removed)] {
+            if count > 0 {
+                lines.push(format!("Would {} {} space spans", verb, count));
+            }
+        }
+        for (what, dates) in [
+            ("days off", &self.days_off_changed),
+            ("journal entries", &self.journal_changed),
+        ] {
+            if let (Some(first), Some(last)) = (dates.first(), dates.last()) {
+                lines.push(format!(
+                    "Would change {} {} ({} to {})",
+                    dates.len(),
+                    what,
+                    first,
+                    last
+                ));
+            }
+        }
+        if self.fixups.0 != self.fixups.1 {
+            lines.push(format!(
+                "Would record data fixups {} to {}",
+                self.fixups.0 + 1,
+                self.fixups.1
+            ));
+        }
+        if self.schema_version.0 != self.schema_version.1 {
+            lines.push(format!(
+                "Would upgrade the schema from version {} to {}",
+                self.schema_version.0, self.schema_version.1
+            ));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Where the machine drew power from when an interval started.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerSource {
+    Battery,
+    Ac,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Interval {
     pub start: DateTime<Utc>,
     pub end: DateTime<Utc>,
     pub kind: IntervalType,
+    /// Machine that recorded the interval; `None` for data from before
+    /// device tracking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    /// Power source when the interval started; `None` if unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<PowerSource>,
+    /// How decisively the idle threshold put the interval on its side, from
+    /// 0 (borderline) to 1; `None` for away time and older or imported data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+    /// Keystrokes and clicks per minute of a focus interval, with
+    /// `track_intensity`; `None` when not sampled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intensity: Option<f32>,
 }
 
+/// Intervals with a confidence below this were a close call.
+pub const BORDERLINE_CONFIDENCE: f32 = 0.2;
+
 impl Interval {
     pub fn new_at(kind: IntervalType, at: DateTime<Utc>) -> Self {
         Self {
             start: at,
             end: at,
             kind,
+            device_id: None,
+            power: None,
+            confidence: None,
+            intensity: None,
         }
     }
+
+    pub fn is_borderline(&self) -> bool {
+        self.confidence
+            .is_some_and(|confidence| confidence < BORDERLINE_CONFIDENCE)
+    }
+}
+
+/// A stretch of time spent on one desktop space (macOS Mission Control space).
+/// Recorded separately from intervals so that switching spaces does not split
+/// focus sessions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpaceSpan {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub space: u64,
+}
+
+/// A stretch within a session in which no sample arrived for longer than the
+/// gap threshold, so nothing was recorded for it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Gap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+}
+
+/// A point-in-time annotation (e.g. "prod incident"), separate from intervals.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub at: DateTime<Utc>,
+    pub text: String,
+}
+
+/// The settings a tracking session ran with, so days tracked with different
+/// thresholds or schedules can be told apart later.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub start: DateTime<Utc>,
+    /// Unset while the session runs, or if it crashed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
+    pub threshold_mins: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<NaiveTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<NaiveTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_mins: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+}
+
+/// Unsaved changes in a [`Database`], ordered by how much needs writing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Dirty {
+    #[default]
+    Clean,
+    /// Only the end of the latest interval moved.
+    Tail,
+    /// Anything else changed; the whole file must be rewritten.
+    Full,
+}
+
+/// Layout version of `db.json`, bumped on incompatible changes.
+pub const DB_SCHEMA_VERSION: u32 = 1;
+
+/// Describes the database file itself, to diagnose files written by other
+/// versions or devices. Written by `Storage` on every save.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct DbMeta {
+    /// 0 for files written before the metadata existed.
+    pub schema_version: u32,
+    /// Unknown for files written before the metadata existed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Version of neflo that last wrote the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    /// Device that last wrote the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// OS user the data belongs to, set by the first save that knows it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// How many of the data fixups in `fixups.rs` have been applied.
+    pub fixups: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Database {
+    /// Replaced with the writer's metadata on save.
+    #[serde(default, skip_serializing)]
+    pub meta: DbMeta,
     pub intervals: Vec<Interval>,
+    /// Free-form reflection notes keyed by local date.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub journal: BTreeMap<NaiveDate, String>,
+    /// Active desktop space over time, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spaces: Vec<SpaceSpan>,
+    /// Unrecorded stretches within sessions, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gaps: Vec<Gap>,
+    /// Annotations added with `neflo annotate`, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub markers: Vec<Marker>,
+    /// Tracking sessions and their settings, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sessions: Vec<SessionRecord>,
+    /// Days taken off (vacation, sick days) with their reason; left out of
+    /// averages, streaks and daily goals.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub days_off: BTreeMap<NaiveDate, String>,
+    /// Changes since the last save; maintained by the tracker.
+    #[serde(skip)]
+    pub dirty: Dirty,
+}
+
+impl Database {
+    /// Record a change, keeping the most significant pending one.
+    pub fn mark_dirty(&mut self, dirty: Dirty) {
+        self.dirty = self.dirty.max(dirty);
+    }
+
+    /// Append a note to the journal entry of `date`, one note per line.
+    pub fn add_journal_entry(&mut self, date: NaiveDate, note: &str) {
+        let note = note.trim();
+        if note.is_empty() {
+            return;
+        }
+        self.journal
+            .entry(date)
+            .and_modify(|entry| {
+                entry.push('\n');
+                entry.push_str(note);
+            })
+            .or_insert_with(|| note.to_string());
+    }
+
+    /// Mark every day from `from` to `to` as off for `reason`, replacing the
+    /// reason of days already marked.
+    pub fn mark_off(&mut self, from: NaiveDate, to: NaiveDate, reason: &str) {
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            self.days_off.insert(date, reason.to_string());
+        }
+    }
+
+    /// Mark `holidays` as off, keeping days already marked as they are.
+    /// Returns the number of days added.
+    pub fn add_holidays(
+        &mut self,
+        holidays: impl IntoIterator<Item = (NaiveDate, String)>,
+    ) -> usize {
+        let before = self.days_off.len();
+        for (date, name) in holidays {
+            self.days_off.entry(date).or_insert(name);
+        }
+        self.days_off.len() - before
+    }
+
+    /// Insert `marker`, keeping markers ordered by time.
+    pub fn add_marker(&mut self, marker: Marker) {
+        let idx = self.markers.partition_point(|m| m.at <= marker.at);
+        self.markers.insert(idx, marker);
+    }
+
+    /// Remove all recorded time from `since` on (everything when `None`) and
+    /// return it. Intervals spanning `since` are split there.
+    pub fn remove_intervals_since(&mut self, since: Option<DateTime<Utc>>) -> Vec<Interval> {
+        let Some(since) = since else {
+            return std::mem::take(&mut self.intervals);
+        };
+        let mut removed = Vec::new();
+        self.intervals.retain_mut(|interval| {
+            if interval.end <= since {
+                true
+            } else if interval.start >= since {
+                removed.push(interval.clone());
+                false
+            } else {
+                removed.push(Interval {
+                    start: since,
+                    ..interval.clone()
+                });
+                interval.end = since;
+                true
+            }
+        });
+        removed
+    }
+
+    /// Remove the recorded time of `kind` (any kind when `None`) between
+    /// `from` and `to` and return it. Intervals sticking out of the range are
+    /// cut at its edges, keeping the parts outside.
+    pub fn remove_intervals_between(
+        &mut self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        kind: Option<IntervalType>,
+    ) -> Vec<Interval> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for interval in std::mem::take(&mut self.intervals) {
+            if interval.end <= from
+                || interval.start >= to
+                || kind.is_some_and(|kind| interval.kind != kind)
+            {
+                kept.push(interval);
+                continue;
+            }
+            if interval.start < from {
+                kept.push(Interval {
+                    end: from,
+                    ..interval.clone()
+                });
+            }
+            removed.push(Interval {
+                start: interval.start.max(from),
+                end: interval.end.min(to),
+                ..interval.clone()
+            });
+            if interval.end > to {
+                kept.push(Interval {
+                    start: to,
+                    ..interval
+                });
+            }
+        }
+        self.intervals = kept;
+        if !removed.is_empty() {
+            self.mark_dirty(Dirty::Full);
+        }
+        removed
+    }
+
+    /// Move the time spent on `spaces` between `from` and `to` to `space`,
+    /// returning the spans replaced, cut to the range, so that
+    /// [`Database::put_spaces`] can restore them.
+    pub fn retag_spaces(
+        &mut self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        spaces: &[u64],
+        space: u64,
+    ) -> Vec<SpaceSpan> {
+        let replaced: Vec<SpaceSpan> = self
+            .spaces
+            .iter()
+            .filter(|span| spaces.contains(&span.space) && span.space != space)
+            .map(|span| SpaceSpan {
+                start: span.start.max(from),
+                end: span.end.min(to),
+                space: span.space,
+            })
+            .filter(|span| span.end > span.start)
+            .collect();
+        self.put_spaces(
+            replaced
+                .iter()
+                .map(|span| SpaceSpan {
+                    space,
+                    ..span.clone()
+                })
+                .collect(),
+        );
+        replaced
+    }
+
+    /// Insert `spans`, cutting the spans they overlap around them.
+    pub fn put_spaces(&mut self, spans: Vec<SpaceSpan>) {
+        if spans.is_empty() {
+            return;
+        }
+        for span in spans {
+            let mut kept = Vec::new();
+            for old in std::mem::take(&mut self.spaces) {
+                if old.end <= span.start || old.start >= span.end {
+                    kept.push(old);
+                    continue;
+                }
+                if old.start < span.start {
+                    kept.push(SpaceSpan {
+                        end: span.start,
+                        ..old.clone()
+                    });
+                }
+                if old.end > span.end {
+                    kept.push(SpaceSpan {
+                        start: span.end,
+                        ..old
+                    });
+                }
+            }
+            kept.push(span);
+            self.spaces = kept;
+        }
+        self.spaces.sort_by_key(|span| span.start);
+        self.mark_dirty(Dirty::Full);
+    }
+
+    /// Whether every interval has `end >= start` and each device's intervals
+    /// are ordered without overlapping their neighbours. Intervals of
+    /// different devices may overlap.
+    pub fn is_consistent(&self) -> bool {
+        self.validate().is_empty()
+    }
+
+    /// Every break of the rules `is_consistent` checks, in interval order.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut previous: HashMap<&Option<String>, &Interval> = HashMap::new();
+        for (index, interval) in self.intervals.iter().enumerate() {
+            if interval.end < interval.start {
+                violations.push(Violation::EndsBeforeStart { index });
+            }
+            if let Some(prev) = previous.insert(&interval.device_id, interval) {
+                if interval.start < prev.start {
+                    violations.push(Violation::OutOfOrder { index });
+                } else if interval.start < prev.end {
+                    violations.push(Violation::Overlap { index });
+                }
+            }
+        }
+        violations
+    }
+
+    /// Remove overlaps by letting newer intervals of the same device take
+    /// precedence: earlier intervals are truncated at the start of the ones
+    /// that follow them, and dropped entirely if nothing of them remains.
+    pub fn resolve_overlaps(&mut self) {
+        let mut boundaries: HashMap<Option<String>, DateTime<Utc>> = HashMap::new();
+        for interval in self.intervals.iter_mut().rev() {
+            let boundary = boundaries.get(&interval.device_id).copied();
+            if let Some(boundary) = boundary {
+                if interval.end > boundary {
+                    interval.end = boundary;
+                }
+            }
+            if interval.end >= interval.start {
+                let next = boundary.map_or(interval.start, |b| b.min(interval.start));
+                boundaries.insert(interval.device_id.clone(), next);
+            }
+        }
+        self.intervals.retain(|i| i.end >= i.start);
+    }
+
+    /// Add intervals from another source (an import, the inbox, the trash) and
+    /// bring the timeline into canonical form. Returns how many of them added
+    /// time not already recorded with the same kind.
+    pub fn merge_intervals(&mut self, incoming: Vec<Interval>) -> usize {
+        let mut known: HashMap<(&Option<String>, IntervalType), Vec<&Interval>> = HashMap::new();
+        for interval in &self.intervals {
+            known
+                .entry((&interval.device_id, interval.kind))
+                .or_default()
+                .push(interval);
+        }
+        for intervals in known.values_mut() {
+            intervals.sort_by_key(|i| i.start);
+        }
+        let added = incoming
+            .iter()
+            .filter(|new| {
+                let Some(same) = known.get(&(&new.device_id, new.kind)) else {
+                    return true;
+                };
+                // Same-kind intervals of one device do not overlap, so only the
+                // last one starting no later can contain `new`
+                let idx = same.partition_point(|i| i.start <= new.start);
+                idx == 0 || same[idx - 1].end < new.end
+            })
+            .count();
+
+        let mut intervals = std::mem::take(&mut self.intervals);
+        intervals.extend(incoming);
+        self.intervals = canonicalize(intervals);
+        added
+    }
+
+    /// Number of distinct devices that recorded intervals.
+    pub fn device_count(&self) -> usize {
+        let mut devices: Vec<&Option<String>> =
+            self.intervals.iter().map(|i| &i.device_id).collect();
+        devices.sort();
+        devices.dedup();
+        devices.len()
+    }
+}
+
+/// A stretch of time from start to end.
+type Range = (DateTime<Utc>, DateTime<Utc>);
+
+/// Between overlapping intervals of the same kind, a known power source wins.
+const POWER_PRIORITY: [Option<PowerSource>; 3] =
+    [Some(PowerSource::Ac), Some(PowerSource::Battery), None];
+
+/// Bring intervals from any number of sources into canonical form. Per
+/// device, overlapping intervals of the same kind and power source are
+/// coalesced, and where they overlap others the kind with the higher
+/// `IntervalType::PRIORITY` (then `POWER_PRIORITY`) wins and the other is cut
+/// around it. Intervals that merely touch stay separate, so
+/// session counts do not change. The result is ordered by start and does not
+/// depend on the order of the input; canonicalizing it again changes nothing.
+pub fn canonicalize(intervals: Vec<Interval>) -> Vec<Interval> {
+    let mut by_device: BTreeMap<Option<String>, Vec<Interval>> = BTreeMap::new();
+    for interval in intervals.into_iter().filter(|i| i.end >= i.start) {
+        by_device
+            .entry(interval.device_id.clone())
+            .or_default()
+            .push(interval);
+    }
+
+    let mut result = Vec::new();
+    for (_, mut intervals) in by_device {
+        intervals.sort_by_key(canonical_order);
+        // Time claimed by higher-priority kinds, as disjoint sorted ranges
+        let mut claimed: Vec<Range> = Vec::new();
+        let mut device_result = Vec::new();
+        for (kind, power) in IntervalType::PRIORITY
+            .iter()
+            .flat_map(|kind| POWER_PRIORITY.iter().map(move |power| (*kind, *power)))
+        {
+            let groups = coalesce(
+                intervals
+                    .iter()
+                    .filter(|i| i.kind == kind && i.power == power),
+            );
+            for group in &groups {
+                device_result.extend(subtract(group, &claimed));
+            }
+            claimed.extend(groups.iter().map(|g| (g.start, g.end)));
+            claimed = union(claimed);
+        }
+        // An empty interval inside recorded time of any kind carries nothing
+        device_result.retain(|i| i.end > i.start || !inside(i.start, &claimed));
+        result.extend(device_result);
+    }
+    result.sort_by_key(canonical_order);
+    result
+}
+
+/// Total order used to make merging independent of input order.
+fn canonical_order(
+    interval: &Interval,
+) -> (DateTime<Utc>, DateTime<Utc>, Option<String>, usize, u8) {
+    let priority = IntervalType::PRIORITY
+        .iter()
+        .position(|k| *k == interval.kind)
+        .unwrap_or_default();
+    let power = match interval.power {
+        None => 0,
+        Some(PowerSource::Battery) => 1,
+        Some(PowerSource::Ac) => 2,
+    };
+    (
+        interval.start,
+        interval.end,
+        interval.device_id.clone(),
+        priority,
+        power,
+    )
+}
+
+/// Merge overlapping intervals, already sorted by `canonical_order`.
+fn coalesce<'a>(intervals: impl Iterator<Item = &'a Interval>) -> Vec<Interval> {
+    let mut groups: Vec<Interval> = Vec::new();
+    for interval in intervals {
+        match groups.last_mut() {
+            Some(group)
+                if interval.start < group.end
+                    || (interval.start == group.start && interval.end == group.end) =>
+            {
+                group.end = group.end.max(interval.end);
+            }
+            _ => groups.push(interval.clone()),
+        }
+    }
+    groups
+}
+
+/// The parts of `interval` outside the `claimed` ranges. An empty interval
+/// survives unless it lies strictly inside a claimed range.
+fn subtract(interval: &Interval, claimed: &[Range]) -> Vec<Interval> {
+    if interval.start == interval.end {
+        return if inside(interval.start, claimed) {
+            Vec::new()
+        } else {
+            vec![interval.clone()]
+        };
+    }
+    let mut pieces = Vec::new();
+    let mut from = interval.start;
+    for &(start, end) in claimed {
+        if end <= from {
+            continue;
+        }
+        if start >= interval.end {
+            break;
+        }
+        if start > from {
+            pieces.push(Interval {
+                start: from,
+                end: start,
+                ..interval.clone()
+            });
+        }
+        from = end;
+        if from >= interval.end {
+            return pieces;
+        }
+    }
+    pieces.push(Interval {
+        start: from,
+        ..interval.clone()
+    });
+    pieces
+}
+
+/// Whether `at` lies strictly inside one of `ranges`.
+fn inside(at: DateTime<Utc>, ranges: &[Range]) -> bool {
+    ranges.iter().any(|&(start, end)| start < at && at < end)
+}
+
+/// Sort and merge overlapping or touching ranges, dropping empty ones.
+fn union(mut ranges: Vec<Range>) -> Vec<Range> {
+    ranges.retain(|(start, end)| end > start);
+    ranges.sort();
+    let mut merged: Vec<Range> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use proptest::prelude::*;
+
+    fn interval(kind: IntervalType, start: DateTime<Utc>, secs: i64) -> Interval {
+        Interval {
+            start,
+            end: start + Duration::seconds(secs),
+            kind,
+            device_id: None,
+            power: None,
+            confidence: None,
+            intensity: None,
+        }
+    }
+
+    #[test]
+    fn test_change_plan_lists_what_differs() {
+        let t = Utc.with_ymd_and_hms(2024, 5, 13, 10, 0, 0).unwrap();
+        let date = t.date_naive();
+        let before = Database {
+            intervals: vec![Interval::new_at(IntervalType::Focus, t)],
+            ..Default::default()
+        };
+        let mut after = Database {
+            intervals: vec![Interval::new_at(IntervalType::Idle, t)],
+            ..Default::default()
+        };
+        after.days_off.insert(date, "vacation".to_string());
+        after.meta.schema_version = DB_SCHEMA_VERSION;
+
+        let plan = ChangePlan::between(&before, &after);
+        assert_eq!(plan.added, after.intervals);
+        assert_eq!(plan.removed, before.intervals);
+        assert_eq!(plan.days_off_changed, [date]);
+        assert!(plan.journal_changed.is_empty());
+        assert!(plan.to_string().contains("Would remove 1 intervals"));
+
+        let unchanged = ChangePlan::between(&after, &after);
+        assert!(unchanged.is_empty());
+        assert_eq!(unchanged.to_string(), "Nothing would change.");
+    }
+
+    #[test]
+    fn test_validate_names_each_violation() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let db = Database {
+            intervals: vec![
+                interval(IntervalType::Focus, t, 300),
+                interval(IntervalType::Idle, t + Duration::seconds(60), 60),
+                interval(IntervalType::Focus, t, 60),
+                interval(IntervalType::Idle, t + Duration::seconds(900), -60),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            db.validate(),
+            [
+                Violation::Overlap { index: 1 },
+                Violation::OutOfOrder { index: 2 },
+                Violation::EndsBeforeStart { index: 3 },
+            ]
+        );
+        assert_eq!(
+            db.validate()[1].to_string(),
+            "interval 2 starts before the previous one of its device"
+        );
+    }
+
+    #[test]
+    fn test_remove_intervals_since_splits_spanning_interval() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let mut db = Database {
+            intervals: vec![
+                interval(IntervalType::Focus, t, 600),
+                interval(IntervalType::Idle, t + Duration::seconds(600), 60),
+            ],
+            ..Default::default()
+        };
+
+        let removed = db.remove_intervals_since(Some(t + Duration::seconds(300)));
+
+        assert_eq!(db.intervals, vec![interval(IntervalType::Focus, t, 300)]);
+        assert_eq!(
+            removed,
+            vec![
+                interval(IntervalType::Focus, t + Duration::seconds(300), 300),
+                interval(IntervalType::Idle, t + Duration::seconds(600), 60),
+            ]
+        );
+        assert_eq!(db.remove_intervals_since(None).len(), 1);
+        assert!(db.intervals.is_empty());
+    }
+
+    #[test]
+    fn test_remove_intervals_between_keeps_the_outside() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let mut db = Database {
+            intervals: vec![
+                interval(IntervalType::Focus, t, 600),
+                interval(IntervalType::Idle, t + Duration::seconds(600), 60),
+                interval(IntervalType::Focus, t + Duration::seconds(660), 600),
+            ],
+            ..Default::default()
+        };
+
+        let from = t + Duration::seconds(300);
+        let to = t + Duration::seconds(900);
+        let removed = db.remove_intervals_between(from, to, Some(IntervalType::Focus));
+
+        assert_eq!(
+            removed,
+            vec![
+                interval(IntervalType::Focus, from, 300),
+                interval(IntervalType::Focus, t + Duration::seconds(660), 240),
+            ]
+        );
+        assert_eq!(
+            db.intervals,
+            vec![
+                interval(IntervalType::Focus, t, 300),
+                interval(IntervalType::Idle, t + Duration::seconds(600), 60),
+                interval(IntervalType::Focus, to, 360),
+            ]
+        );
+        assert_eq!(db.dirty, Dirty::Full);
+        assert_eq!(db.remove_intervals_between(from, to, None).len(), 1);
+    }
+
+    #[test]
+    fn test_retag_spaces_round_trip() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let span = |from: i64, to: i64, space| SpaceSpan {
+            start: t + Duration::minutes(from),
+            end: t + Duration::minutes(to),
+            space,
+        };
+        let original = vec![span(0, 60, 3), span(60, 90, 1), span(90, 150, 3)];
+        let mut db = Database {
+            spaces: original.clone(),
+            ..Default::default()
+        };
+
+        let replaced = db.retag_spaces(
+            t + Duration::minutes(30),
+            t + Duration::minutes(120),
+            &[3],
+            2,
+        );
+
+        assert_eq!(replaced, [span(30, 60, 3), span(90, 120, 3)]);
+        assert_eq!(
+            db.spaces,
+            [
+                span(0, 30, 3),
+                span(30, 60, 2),
+                span(60, 90, 1),
+                span(90, 120, 2),
+                span(120, 150, 3),
+            ]
+        );
+
+        // Putting the replaced spans back undoes the retag, apart from the cuts
+        db.put_spaces(replaced);
+        let covered: Vec<(u64, i64)> = db
+            .spaces
+            .iter()
+            .map(|s| (s.space, (s.end - s.start).num_minutes()))
+            .collect();
+        assert_eq!(covered, [(3, 30), (3, 30), (1, 30), (3, 30), (3, 30)]);
+    }
+
+    #[test]
+    fn test_resolve_overlaps_truncates_earlier() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let mut db = Database {
+            intervals: vec![
+                interval(IntervalType::Focus, t, 300),
+                interval(IntervalType::Idle, t + Duration::seconds(60), 60),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(db.validate(), [Violation::Overlap { index: 1 }]);
+
+        db.resolve_overlaps();
+
+        assert!(db.is_consistent());
+        assert_eq!(db.intervals.len(), 2);
+        assert_eq!(db.intervals[0].end, t + Duration::seconds(60));
+    }
+
+    #[test]
+    fn test_resolve_overlaps_drops_shadowed() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let mut db = Database {
+            intervals: vec![
+                interval(IntervalType::Focus, t, 60),
+                interval(IntervalType::Idle, t + Duration::seconds(120), 60),
+                interval(IntervalType::Focus, t + Duration::seconds(30), 10),
+            ],
+            ..Default::default()
+        };
+
+        db.resolve_overlaps();
+
+        assert!(db.is_consistent());
+        assert_eq!(db.intervals.len(), 2);
+        assert_eq!(db.intervals[0].end, t + Duration::seconds(30));
+        assert_eq!(db.intervals[1].start, t + Duration::seconds(30));
+    }
+
+    #[test]
+    fn test_interval_type_all_is_exhaustive() {
+        for kind in IntervalType::ALL {
+            // Adding a variant without listing it in ALL fails to compile here
+            match kind {
+                IntervalType::Focus
+                | IntervalType::Idle
+                | IntervalType::Away
+                | IntervalType::Locked => {}
+            }
+        }
+        assert_eq!(IntervalType::ALL.len(), 4);
+    }
+
+    #[test]
+    fn test_add_journal_entry_appends() {
+        let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut db = Database::default();
+
+        db.add_journal_entry(date, "  shipped the parser ");
+        db.add_journal_entry(date, "");
+        db.add_journal_entry(date, "too many meetings");
+
+        assert_eq!(db.journal.len(), 1);
+        assert_eq!(db.journal[&date], "shipped the parser\ntoo many meetings");
+    }
+
+    #[test]
+    fn test_overlaps_between_devices_are_kept() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let mut laptop = interval(IntervalType::Focus, t, 300);
+        laptop.device_id = Some("laptop".to_string());
+        let mut desktop = interval(IntervalType::Idle, t + Duration::seconds(60), 60);
+        desktop.device_id = Some("desktop".to_string());
+        let mut db = Database {
+            intervals: vec![laptop, desktop],
+            ..Default::default()
+        };
+
+        assert!(db.is_consistent());
+        db.resolve_overlaps();
+
+        assert_eq!(db.intervals.len(), 2);
+        assert_eq!(db.intervals[0].end, t + Duration::seconds(300));
+        assert_eq!(db.device_count(), 2);
+    }
+
+    #[test]
+    fn test_merge_intervals_coalesces_and_prefers_focus() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let mut db = Database {
+            intervals: vec![interval(IntervalType::Idle, t, 600)],
+            ..Default::default()
+        };
+
+        let added = db.merge_intervals(vec![
+            interval(IntervalType::Idle, t, 300),
+            interval(IntervalType::Idle, t + Duration::seconds(500), 300),
+            interval(IntervalType::Focus, t + Duration::seconds(200), 100),
+        ]);
+
+        assert_eq!(added, 2);
+        assert_eq!(
+            db.intervals,
+            vec![
+                interval(IntervalType::Idle, t, 200),
+                interval(IntervalType::Focus, t + Duration::seconds(200), 100),
+                interval(IntervalType::Idle, t + Duration::seconds(300), 500),
+            ]
+        );
+    }
+
+    fn interval_strategy() -> impl Strategy<Value = Interval> {
+        (
+            prop::option::of(prop_oneof![Just("a"), Just("b")]),
+            prop::sample::select(IntervalType::ALL.to_vec()),
+            prop::option::of(prop_oneof![
+                Just(PowerSource::Battery),
+                Just(PowerSource::Ac)
+            ]),
+            0i64..120,
+            0i64..30,
+        )
+            .prop_map(|(device, kind, power, start, len)| {
+                let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+                Interval {
+                    start: t + Duration::minutes(start),
+                    end: t + Duration::minutes(start + len),
+                    kind,
+                    device_id: device.map(str::to_string),
+                    power,
+                    confidence: None,
+                    intensity: None,
+                }
+            })
+    }
+
+    /// Covered time per device, as merged ranges.
+    fn coverage(intervals: &[Interval]) -> BTreeMap<Option<String>, Vec<Range>> {
+        let mut ranges: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for i in intervals {
+            ranges
+                .entry(i.device_id.clone())
+                .or_default()
+                .push((i.start, i.end));
+        }
+        ranges
+            .into_iter()
+            .map(|(device, r)| (device, union(r)))
+            .filter(|(_, r)| !r.is_empty())
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_canonicalize_is_consistent_and_idempotent(
+            intervals in prop::collection::vec(interval_strategy(), 0..40)
+        ) {
+            let canonical = canonicalize(intervals.clone());
+            let db = Database { intervals: canonical.clone(), ..Default::default() };
+            prop_assert!(db.is_consistent());
+            prop_assert_eq!(canonicalize(canonical.clone()), canonical.clone());
+            prop_assert_eq!(coverage(&canonical), coverage(&intervals));
+        }
+
+        #[test]
+        fn prop_merge_ignores_source_order(
+            a in prop::collection::vec(interval_strategy(), 0..20),
+            b in prop::collection::vec(interval_strategy(), 0..20),
+        ) {
+            let mut ab = Database { intervals: canonicalize(a.clone()), ..Default::default() };
+            ab.merge_intervals(b.clone());
+            let mut ba = Database { intervals: canonicalize(b), ..Default::default() };
+            ba.merge_intervals(a);
+            prop_assert_eq!(ab.intervals, ba.intervals);
+        }
+    }
 }
@@ -0,0 +1,297 @@
+use crate::config::MqttConfig;
+use crate::models::IntervalType;
+use crate::stats::SummaryStats;
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+
+/// Publishes the tracker state and today's totals as retained MQTT messages,
+/// with Home Assistant discovery so the entities appear without YAML.
+/// Messages are sent with `mosquitto_pub`.
+#[derive(Debug)]
+pub struct MqttPublisher {
+    config: MqttConfig,
+    device: String,
+    /// Topic-safe form of `device`, used in topics and entity ids.
+    node: String,
+    announced: bool,
+    password: Option<PasswordFile>,
+}
+
+impl MqttPublisher {
+    pub fn new(config: &MqttConfig, device: &str) -> Result<Self> {
+        let node = device
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Ok(Self {
+            config: config.clone(),
+            device: device.to_string(),
+            node,
+            announced: false,
+            password: config
+                .password
+                .as_deref()
+                .map(PasswordFile::create)
+                .transpose()?,
+        })
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}/{}", self.config.topic_prefix, self.node, name)
+    }
+
+    /// Publish the current state ("focus", "idle", "locked" or "away").
+    pub fn publish_state(&mut self, kind: IntervalType) -> Result<()> {
+        self.announce()?;
+        let state = format!("{:?}", kind).to_lowercase();
+        detach(self.publish(&self.topic("state"), &state)?);
+        Ok(())
+    }
+
+    /// Publish today's totals as JSON.
+    pub fn publish_today(&mut self, today: &SummaryStats) -> Result<()> {
+        self.announce()?;
+        let payload = json!({
+            "focus_mins": today.total_focus.num_minutes(),
+            "idle_mins": today.total_idle.num_minutes(),
+            "deep_work_mins": today.total_deep_work.num_minutes(),
+            "interruptions": today.idle_count,
+        });
+        detach(self.publish(&self.topic("today"), &payload.to_string())?);
+        Ok(())
+    }
+
+    /// Mark the entities unavailable, waiting for the message to go out since
+    /// this is called right before exiting.
+    pub fn publish_offline(&self) -> Result<()> {
+        self.publish(&self.topic("availability"), "offline")?
+            .wait()?;
+        Ok(())
+    }
+
+    /// Send the discovery configs and mark the entities available, once.
+    fn announce(&mut self) -> Result<()> {
+        if self.announced {
+            return Ok(());
+        }
+        for (topic, payload) in self.discovery() {
+            detach(self.publish(&topic, &payload)?);
+        }
+        detach(self.publish(&self.topic("availability"), "online")?);
+        self.announced = true;
+        Ok(())
+    }
+
+    /// Home Assistant discovery topics and payloads for each entity.
+    fn discovery(&self) -> Vec<(String, String)> {
+        let device = json!({
+            "identifiers": [format!("neflo_{}", self.node)],
+            "name": format!("Neflo ({})", self.device),
+        });
+        let entities = [
+            (
+                "binary_sensor",
+                "in_flow",
+                json!({
+                    "name": "In flow",
+                    "state_topic": self.topic("state"),
+                    "value_template": "{{ 'ON' if value == 'focus' else 'OFF' }}",
+                    "icon": "mdi:brain",
+                }),
+            ),
+            (
+                "sensor",
+                "state",
+                json!({
+                    "name": "State",
+                    "state_topic": self.topic("state"),
+                }),
+            ),
+            (
+                "sensor",
+                "focus_today",
+                json!({
+                    "name": "Focus today",
+                    "state_topic": self.topic("today"),
+                    "value_template": "{{ value_json.focus_mins }}",
+                    "unit_of_measurement": "min",
+                }),
+            ),
+            (
+                "sensor",
+                "interruptions_today",
+                json!({
+                    "name": "Interruptions today",
+                    "state_topic": self.topic("today"),
+                    "value_template": "{{ value_json.interruptions }}",
+                }),
+            ),
+        ];
+        entities
+            .into_iter()
+            .map(|(component, object_id, mut payload)| {
+                payload["unique_id"] = json!(format!("neflo_{}_{}", self.node, object_id));
+                payload["availability_topic"] = json!(self.topic("availability"));
+                payload["device"] = device.clone();
+                (
+                    format!(
+                        "{}/{}/neflo_{}/{}/config",
+                        self.config.discovery_prefix, component, self.node, object_id
+                    ),
+                    payload.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn publish(&self, topic: &str, payload: &str) -> Result<Child> {
+        let mut command = publish_command(&self.config, topic, payload);
+        if let Some(password) = &self.password {
+            command.env("XDG_CONFIG_HOME", &password.dir);
+        }
+        command.spawn().context("Failed to run mosquitto_pub")
+    }
+}
+
+/// Reap the publisher in the background so ticks never wait on the broker.
+fn detach(mut child: Child) {
+    std::thread::spawn(move || child.wait());
+}
+
+/// A directory only this user can read, holding a `mosquitto_pub` options file
+/// with the broker password. `mosquitto_pub` finds it through
+/// `XDG_CONFIG_HOME`, so the password never shows in the process list.
+/// Removed on drop.
+#[derive(Debug)]
+struct PasswordFile {
+    dir: PathBuf,
+}
+
+impl PasswordFile {
+    fn create(password: &str) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "neflo-mqtt-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let file = Self { dir };
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(file.dir.join("mosquitto_pub"))?
+            .write_all(format!("-P {}\n", password).as_bytes())?;
+        Ok(file)
+    }
+}
+
+impl Drop for PasswordFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The `mosquitto_pub` command for one message. The password is not part of
+/// it; see [`PasswordFile`].
+fn publish_command(config: &MqttConfig, topic: &str, payload: &str) -> Command {
+    let mut command = Command::new("mosquitto_pub");
+    command
+        .arg("-h")
+        .arg(&config.host)
+        .arg("-p")
+        .arg(config.port.to_string());
+    if let Some(username) = &config.username {
+        command.arg("-u").arg(username);
+    }
+    command
+        .args(["-r", "-q", "1", "-t", topic, "-m", payload])
+        .stdout(std::process::Stdio::null());
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_discovery_topics_and_payloads() {
+        let publisher = MqttPublisher::new(&MqttConfig::default(), "Anna's MacBook").unwrap();
+        let discovery = publisher.discovery();
+
+        assert_eq!(publisher.topic("state"), "neflo/anna_s_macbook/state");
+        assert_eq!(
+            discovery[0].0,
+            "homeassistant/binary_sensor/neflo_anna_s_macbook/in_flow/config"
+        );
+        let payload: serde_json::Value = serde_json::from_str(&discovery[0].1).unwrap();
+        assert_eq!(payload["unique_id"], "neflo_anna_s_macbook_in_flow");
+        assert_eq!(payload["state_topic"], "neflo/anna_s_macbook/state");
+        assert_eq!(payload["device"]["name"], "Neflo (Anna's MacBook)");
+    }
+
+    #[test]
+    fn test_publish_command_is_retained() {
+        let config = MqttConfig {
+            username: Some("neflo".to_string()),
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        let command = publish_command(&config, "neflo/mac/state", "focus");
+        let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            args,
+            [
+                "-h",
+                "localhost",
+                "-p",
+                "1883",
+                "-u",
+                "neflo",
+                "-r",
+                "-q",
+                "1",
+                "-t",
+                "neflo/mac/state",
+                "-m",
+                "focus"
+            ]
+        );
+    }
+    #[test]
+    fn test_password_is_kept_off_the_command_line() -> Result<()> {
+        let config = MqttConfig {
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        let publisher = MqttPublisher::new(&config, "mac")?;
+        let dir = publisher.password.as_ref().unwrap().dir.clone();
+        let options = dir.join("mosquitto_pub");
+        assert_eq!(fs::read_to_string(&options)?, "-P hunter2\n");
+        assert_eq!(fs::metadata(&options)?.permissions().mode() & 0o777, 0o600);
+
+        let command = publish_command(&config, "neflo/mac/state", "focus");
+        assert!(!command
+            .get_args()
+            .any(|arg| arg.to_string_lossy().contains("hunter2")));
+
+        drop(publisher);
+        assert!(!dir.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config::NotificationsConfig;
+use crate::utils::in_daily_window;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveTime};
+use std::process::Command;
+
+/// Sends notifications on behalf of every feature, honouring the global switch
+/// and quiet hours. Notifications during quiet hours are dropped, or queued and
+/// sent as one summary once quiet hours end.
+#[derive(Debug)]
+pub struct Notifier {
+    enabled: bool,
+    quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    summarize: bool,
+    /// Messages held back during the current quiet hours.
+    queued: Vec<String>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            quiet_hours: None,
+            summarize: false,
+            queued: Vec::new(),
+        }
+    }
+}
+
+impl Notifier {
+    pub fn from_config(config: &NotificationsConfig) -> Result<Self> {
+        Ok(Self {
+            enabled: config.enabled,
+            quiet_hours: config.quiet_hours.as_ref().map(|q| q.parse()).transpose()?,
+            summarize: config.summarize,
+            queued: Vec::new(),
+        })
+    }
+
+    /// Show `message` now, or hold it back during quiet hours.
+    pub fn notify(&mut self, message: &str, now: DateTime<Local>) -> Result<()> {
+        match self.admit(message, now) {
+            Some(message) => send("Neflo", &message),
+            None => Ok(()),
+        }
+    }
+
+    /// Send the summary of held-back messages once quiet hours are over; call
+    /// regularly.
+    pub fn flush(&mut self, now: DateTime<Local>) -> Result<()> {
+        match self.summary(now) {
+            Some(summary) => send("Neflo", &summary),
+            None => Ok(()),
+        }
+    }
+
+    /// The message to show right away, if any, queueing it during quiet hours.
+    fn admit(&mut self, message: &str, now: DateTime<Local>) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        if self.is_quiet(now.time()) {
+            if self.summarize {
+                self.queued.push(message.to_string());
+            }
+            return None;
+        }
+        Some(message.to_string())
+    }
+
+    fn summary(&mut self, now: DateTime<Local>) -> Option<String> {
+        if self.queued.is_empty() || self.is_quiet(now.time()) {
+            return None;
+        }
+        let queued = std::mem::take(&mut self.queued);
+        Some(match queued.as_slice() {
+            [only] => format!("During quiet hours: {}", only),
+            _ => format!(
+                "{} notifications during quiet hours, the last: {}",
+                queued.len(),
+                queued[queued.len() - 1]
+            ),
+        })
+    }
+
+    fn is_quiet(&self, time: NaiveTime) -> bool {
+        self.quiet_hours
+            .is_some_and(|(start, end)| in_daily_window(time, start, end))
+    }
+}
+
+/// Show a desktop notification (Notification Center on macOS, `notify-send`
+/// elsewhere) without waiting for it to be dismissed.
+pub fn send(title: &str, body: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        ));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg(title).arg(body);
+        command
+    };
+    let mut child = command.spawn().context("Failed to show notification")?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuietHours;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_quiet_hours_queue_and_summarize() {
+        let mut notifier = Notifier::from_config(&NotificationsConfig {
+            quiet_hours: Some(QuietHours {
+                start: "22:00".to_string(),
+                end: "08:00".to_string(),
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+        let at = |day: u32, h: u32| Local.with_ymd_and_hms(2024, 5, day, h, 0, 0).unwrap();
+
+        assert_eq!(notifier.admit("a", at(1, 21)).as_deref(), Some("a"));
+        assert_eq!(notifier.admit("b", at(1, 23)), None);
+        assert_eq!(notifier.admit("c", at(2, 7)), None);
+        assert_eq!(notifier.summary(at(2, 7)), None);
+        assert_eq!(
+            notifier.summary(at(2, 8)).as_deref(),
+            Some("2 notifications during quiet hours, the last: c")
+        );
+        assert_eq!(notifier.summary(at(2, 9)), None);
+
+        notifier.summarize = false;
+        notifier.admit("d", at(2, 23));
+        assert_eq!(notifier.summary(at(3, 9)), None);
+
+        notifier.enabled = false;
+        assert_eq!(notifier.admit("e", at(3, 12)), None);
+    }
+
+    #[test]
+    fn test_applescript_string_escapes_quotes() {
+        assert_eq!(applescript_string(r#"say "hi""#), r#""say \"hi\"""#);
+    }
+}
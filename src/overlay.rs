@@ -0,0 +1,163 @@
+use crate::clock::Clock;
+use crate::config::KindsConfig;
+use crate::models::Database;
+use crate::stats::{calculate_stats, StatsOptions};
+use crate::status::Status;
+use crate::utils::{color_hex, format_minutes};
+use anyhow::Result;
+use chrono::Duration;
+use std::fs;
+use std::path::Path;
+
+/// How the overlay file is written, picked from its extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One line, for an OBS text source reading from a file.
+    Text,
+    /// A small page for an OBS browser source, reloading itself.
+    Html,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                Format::Html
+            }
+            _ => Format::Text,
+        }
+    }
+}
+
+/// The current state and the running session's focus, for streaming overlays.
+pub struct Overlay {
+    pub status: Status,
+    /// Focus since the running session started; zero without one.
+    pub session_focus: Duration,
+}
+
+impl Overlay {
+    pub fn new(
+        db: &Database,
+        running: bool,
+        options: &StatsOptions,
+        kinds: &KindsConfig,
+        clock: &dyn Clock,
+    ) -> Self {
+        let session_start = db
+            .sessions
+            .last()
+            .filter(|session| running && session.end.is_none())
+            .map(|session| session.start);
+        let session_focus = match session_start {
+            Some(start) => {
+                calculate_stats(db, Some(start), options, clock)
+                    .session_summary
+                    .total_focus
+            }
+            None => Duration::zero(),
+        };
+        Self {
+            status: Status::new(db, running, options, kinds, clock),
+            session_focus,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        if !self.status.running {
+            return self.status.label.clone();
+        }
+        format!(
+            "{} · {} focus this session",
+            self.status.label,
+            format_minutes(self.session_focus)
+        )
+    }
+
+    /// A transparent page showing `text` in the state's color, reloading
+    /// every `refresh_secs`.
+    pub fn html(&self, refresh_secs: u64) -> String {
+        let text = self
+            .text()
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+             <meta http-equiv=\"refresh\" content=\"{}\">\
+             <style>body {{ background: transparent; margin: 0; font: bold 32px sans-serif; color: white; }}</style>\
+             </head>\n<body><span style=\"color: {}\">●</span> {}</body></html>\n",
+            refresh_secs,
+            color_hex(&self.status.color),
+            text
+        )
+    }
+
+    pub fn render(&self, format: Format, refresh_secs: u64) -> String {
+        match format {
+            Format::Text => self.text() + "\n",
+            Format::Html => self.html(refresh_secs),
+        }
+    }
+}
+
+/// Replace `path` with `contents` in one step, so OBS never reads half a file.
+pub fn write(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Interval, IntervalType, SessionRecord};
+    use chrono::{Local, TimeZone, Utc};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_overlay_shows_state_and_session_focus() -> Result<()> {
+        let start = Local
+            .with_ymd_and_hms(2024, 5, 13, 9, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let db = Database {
+            intervals: vec![Interval {
+                end: start + Duration::minutes(42),
+                ..Interval::new_at(IntervalType::Focus, start)
+            }],
+            sessions: vec![SessionRecord {
+                start,
+                end: None,
+                threshold_mins: 5,
+                start_time: None,
+                end_time: None,
+                duration_mins: None,
+                device_id: None,
+            }],
+            ..Default::default()
+        };
+        let now = start + Duration::minutes(42);
+        let kinds = KindsConfig::default();
+        let options = StatsOptions::default();
+
+        let overlay = Overlay::new(&db, true, &options, &kinds, &now);
+        assert_eq!(overlay.text(), "Focus · 42m focus this session");
+        assert!(overlay
+            .html(5)
+            .contains("<span style=\"color: #00cd00\">●</span> Focus · 42m focus this session"));
+
+        let stopped = Overlay::new(&db, false, &options, &kinds, &now);
+        assert_eq!(stopped.text(), "Not running");
+
+        let dir = tempdir()?;
+        let path = dir.path().join("overlay.html");
+        assert_eq!(Format::from_path(&path), Format::Html);
+        assert_eq!(Format::from_path(Path::new("overlay.txt")), Format::Text);
+        write(&path, &overlay.render(Format::Html, 5))?;
+        assert!(fs::read_to_string(&path)?.contains("content=\"5\""));
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use crate::config;
+use crate::storage::Storage;
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const XDG_VARS: [&str; 3] = ["XDG_DATA_HOME", "XDG_CONFIG_HOME", "XDG_STATE_HOME"];
+
+fn env_var(name: &str) -> Option<OsString> {
+    std::env::var_os(name)
+}
+
+/// Where neflo keeps its files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paths {
+    /// Database, inbox and generated reports.
+    pub data_dir: PathBuf,
+    pub config_file: PathBuf,
+    /// Lock file and other runtime state.
+    pub state_dir: PathBuf,
+}
+
+impl Paths {
+    /// Everything in one directory: the legacy `~/.neflo` layout, also used for
+    /// `--data-dir` and portable mode.
+    pub fn single(dir: PathBuf) -> Self {
+        Self {
+            config_file: config::default_config_path(&dir),
+            state_dir: dir.clone(),
+            data_dir: dir,
+        }
+    }
+
+    /// XDG base directories. Unset or relative variables fall back to the spec
+    /// defaults.
+    pub fn xdg(home: &Path, env: impl Fn(&str) -> Option<OsString>) -> Self {
+        let base = |var: &str, default: &str| {
+            env(var)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+                .unwrap_or_else(|| home.join(default))
+                .join("neflo")
+        };
+        Self {
+            data_dir: base("XDG_DATA_HOME", ".local/share"),
+            config_file: base("XDG_CONFIG_HOME", ".config").join("config.toml"),
+            state_dir: base("XDG_STATE_HOME", ".local/state"),
+        }
+    }
+
+    /// The XDG layout is the default on Linux. Elsewhere it is used once any
+    /// `XDG_*_HOME` variable is set or the data was moved there with `migrate-paths`.
+    fn xdg_enabled(&self, env: impl Fn(&str) -> Option<OsString>, linux: bool) -> bool {
+        linux
+            || XDG_VARS.iter().any(|var| env(var).is_some())
+            || self.data_dir.join("db.json").exists()
+    }
+
+    /// Resolve the paths for this run, migrating a legacy `~/.neflo` directory to
+    /// the XDG layout the first time it is used.
+    pub fn resolve(data_dir: Option<PathBuf>, config_file: Option<PathBuf>) -> Result<Self> {
+        let mut paths = match data_dir {
+            Some(dir) => Self::single(Storage::get_base_dir(Some(dir))?),
+            None => {
+                let home = dirs::home_dir()
+                    .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+                let legacy = legacy_dir(&home);
+                let xdg = Self::xdg(&home, env_var);
+                if !xdg.xdg_enabled(env_var, cfg!(target_os = "linux")) {
+                    Self::single(Storage::get_base_dir(None)?)
+                } else if legacy_in_use(&legacy) {
+                    // A session started before the upgrade keeps its directory until it ends
+                    Self::single(legacy)
+                } else {
+                    xdg.migrate_from(&legacy)?;
+                    xdg.create_dirs()?;
+                    xdg
+                }
+            }
+        };
+        if let Some(config_file) = config_file {
+            paths.config_file = config_file;
+        }
+        Ok(paths)
+    }
+
+    pub fn create_dirs(&self) -> Result<()> {
+        fs::create_dir_all(&self.data_dir)?;
+        fs::create_dir_all(&self.state_dir)?;
+        if let Some(parent) = self.config_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+
+    /// The `(from, to)` moves `migrate_from` would make, without touching anything.
+    pub fn planned_moves(&self, legacy: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+        if !legacy.is_dir() || self.data_dir.join("db.json").exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries: Vec<_> = fs::read_dir(legacy)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        entries.sort();
+        let mut moves = Vec::new();
+        for from in entries {
+            let name = from.file_name().unwrap_or_default().to_string_lossy();
+            let to = if name == "neflo.lock" {
+                continue;
+            } else if name.starts_with("config.") {
+                self.config_file.with_file_name(name.as_ref())
+            } else {
+                self.data_dir.join(name.as_ref())
+            };
+            if !to.exists() {
+                moves.push((from, to));
+            }
+        }
+        Ok(moves)
+    }
+
+    /// Move the files of a legacy single-directory layout into these paths.
+    /// Does nothing when `legacy` does not exist or the data was already moved.
+    /// Returns the files that were moved.
+    pub fn migrate_from(&self, legacy: &Path) -> Result<Vec<PathBuf>> {
+        if !legacy.is_dir() || self.data_dir.join("db.json").exists() {
+            return Ok(Vec::new());
+        }
+        self.create_dirs()?;
+
+        // Recreated in the state directory on demand
+        let lock = legacy.join("neflo.lock");
+        if lock.exists() {
+            fs::remove_file(lock)?;
+        }
+        let mut moved = Vec::new();
+        for (from, to) in self.planned_moves(legacy)? {
+            fs::rename(&from, &to).with_context(|| {
+                format!("Failed to move {} to {}", from.display(), to.display())
+            })?;
+            moved.push(to);
+        }
+
+        // Leave the directory in place if anything could not be moved
+        if fs::read_dir(legacy)?.next().is_none() {
+            fs::remove_dir(legacy)?;
+        }
+        Ok(moved)
+    }
+}
+
+/// The directory used before XDG support.
+pub fn legacy_dir(home: &Path) -> PathBuf {
+    home.join(".neflo")
+}
+
+/// Whether a running session holds the lock in a legacy directory.
+pub fn legacy_in_use(legacy: &Path) -> bool {
+    let Ok(file) = fs::File::open(legacy.join("neflo.lock")) else {
+        return false;
+    };
+    let mut lock = fd_lock::RwLock::new(file);
+    let in_use = lock.try_write().is_err();
+    in_use
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_xdg_paths() {
+        let home = Path::new("/home/me");
+        let env = |var: &str| (var == "XDG_CONFIG_HOME").then(|| OsString::from("/etc/me"));
+        let paths = Paths::xdg(home, env);
+        assert_eq!(paths.data_dir, home.join(".local/share/neflo"));
+        assert_eq!(
+            paths.config_file,
+            PathBuf::from("/etc/me/neflo/config.toml")
+        );
+        assert_eq!(paths.state_dir, home.join(".local/state/neflo"));
+
+        // Relative values are ignored, as the spec requires
+        let relative = Paths::xdg(home, |_| Some(OsString::from("relative")));
+        assert_eq!(relative.data_dir, home.join(".local/share/neflo"));
+
+        // Opt-in outside Linux
+        assert!(paths.xdg_enabled(env, false));
+        assert!(!paths.xdg_enabled(|_| None, false));
+        assert!(paths.xdg_enabled(|_| None, true));
+    }
+
+    #[test]
+    fn test_migrate_from_legacy() -> Result<()> {
+        let dir = tempdir()?;
+        let legacy = dir.path().join(".neflo");
+        fs::create_dir_all(legacy.join("inbox"))?;
+        fs::write(legacy.join("db.json"), "{}")?;
+        fs::write(legacy.join("config.toml"), "")?;
+        fs::write(legacy.join("neflo.lock"), "")?;
+
+        let paths = Paths::xdg(dir.path(), |_| None);
+        let planned = paths.planned_moves(&legacy)?;
+        assert_eq!(planned.len(), 3);
+        assert!(legacy.join("db.json").exists());
+        let moved = paths.migrate_from(&legacy)?;
+        assert_eq!(
+            moved,
+            planned.into_iter().map(|(_, to)| to).collect::<Vec<_>>()
+        );
+
+        assert_eq!(moved.len(), 3);
+        assert!(paths.data_dir.join("db.json").exists());
+        assert!(paths.data_dir.join("inbox").is_dir());
+        assert!(paths.config_file.exists());
+        assert!(!legacy.exists());
+
+        // Already migrated
+        assert!(paths.migrate_from(&legacy)?.is_empty());
+        Ok(())
+    }
+}
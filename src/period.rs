@@ -0,0 +1,181 @@
+use crate::utils::resolve_local;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+
+/// A run of whole local days that statistics, reports and queries are
+/// bucketed by. Weeks start on Monday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day(NaiveDate),
+    /// The week containing the date.
+    Week(NaiveDate),
+    /// The month containing the date.
+    Month(NaiveDate),
+    /// From the first date to the last, both included.
+    Custom(NaiveDate, NaiveDate),
+}
+
+impl Period {
+    /// Parse `today`, `yesterday`, `this-week`, `last-week`, `this-month`,
+    /// `last-month`, a date (YYYY-MM-DD) or a range (YYYY-MM-DD..YYYY-MM-DD).
+    pub fn parse(value: &str, today: NaiveDate) -> Result<Self> {
+        let date = |value: &str| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| anyhow!("Invalid date '{}': expected YYYY-MM-DD", value))
+        };
+        Ok(match value {
+            "today" => Self::Day(today),
+            "yesterday" => Self::Day(today).previous(),
+            "this-week" => Self::Week(today),
+            "last-week" => Self::Week(today).previous(),
+            "this-month" => Self::Month(today),
+            "last-month" => Self::Month(today).previous(),
+            _ => match value.split_once("..") {
+                Some((first, last)) => {
+                    let (first, last) = (date(first)?, date(last)?);
+                    if last < first {
+                        anyhow::bail!("The range {} ends before it starts", value);
+                    }
+                    Self::Custom(first, last)
+                }
+                None => Self::Day(date(value).map_err(|_| {
+                    anyhow!(
+                        "Invalid period '{}': expected today, yesterday, this-week, last-week, \
+                         this-month, last-month, a date or a range of dates",
+                        value
+                    )
+                })?),
+            },
+        })
+    }
+
+    pub fn first(&self) -> NaiveDate {
+        match *self {
+            Self::Day(date) => date,
+            Self::Week(date) => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Self::Month(date) => date.with_day(1).unwrap_or(date),
+            Self::Custom(first, _) => first,
+        }
+    }
+
+    pub fn last(&self) -> NaiveDate {
+        match *self {
+            Self::Day(date) => date,
+            Self::Week(_) => self.first() + Duration::days(6),
+            Self::Month(date) => {
+                let next = date
+                    .with_day(1)
+                    .and_then(|first| first.checked_add_months(chrono::Months::new(1)));
+                next.and_then(|next| next.pred_opt()).unwrap_or(date)
+            }
+            Self::Custom(_, last) => last,
+        }
+    }
+
+    /// The period of the same kind just before this one; a custom range
+    /// moves back by its own length.
+    pub fn previous(&self) -> Self {
+        let first = self.first();
+        match *self {
+            Self::Day(date) => Self::Day(date - Duration::days(1)),
+            Self::Week(_) => Self::Week(first - Duration::days(7)),
+            Self::Month(_) => Self::Month(first - Duration::days(1)),
+            Self::Custom(first, last) => {
+                let length = last - first + Duration::days(1);
+                Self::Custom(first - length, last - length)
+            }
+        }
+    }
+
+    /// Local midnight at the start of the period, as UTC.
+    pub fn start(&self) -> DateTime<Utc> {
+        self.bounds_in(&Local).0
+    }
+
+    /// Local midnight after the period's last day, as UTC.
+    pub fn end(&self) -> DateTime<Utc> {
+        self.bounds_in(&Local).1
+    }
+
+    /// The instants from local midnight at the start of the period to local
+    /// midnight after it, in `tz`. Days with a clock change are 23 or 25
+    /// hours long; a midnight the clocks skip starts the day when they jump.
+    pub fn bounds_in<Tz: TimeZone>(&self, tz: &Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+        let after = self.last().succ_opt().unwrap_or(NaiveDate::MAX);
+        (
+            resolve_local(tz, self.first(), NaiveTime::MIN),
+            resolve_local(tz, after, NaiveTime::MIN),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_week_and_month_boundaries() {
+        // Sunday belongs to the week that started the Monday before
+        let sunday = date(2024, 3, 3);
+        assert_eq!(Period::Week(sunday).first(), date(2024, 2, 26));
+        assert_eq!(Period::Week(sunday).last(), sunday);
+        assert_eq!(Period::Week(date(2024, 3, 4)).first(), date(2024, 3, 4));
+
+        assert_eq!(Period::Month(date(2024, 2, 10)).last(), date(2024, 2, 29));
+        assert_eq!(Period::Month(date(2023, 2, 10)).last(), date(2023, 2, 28));
+        assert_eq!(Period::Month(date(2024, 12, 31)).last(), date(2024, 12, 31));
+        assert_eq!(
+            Period::Month(date(2024, 1, 15)).previous(),
+            Period::Month(date(2023, 12, 31))
+        );
+        assert_eq!(
+            Period::Week(date(2024, 1, 3)).previous().first(),
+            date(2023, 12, 25)
+        );
+        assert_eq!(
+            Period::Custom(date(2024, 1, 8), date(2024, 1, 10)).previous(),
+            Period::Custom(date(2024, 1, 5), date(2024, 1, 7))
+        );
+    }
+
+    #[test]
+    fn test_bounds_are_local_midnights() {
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let (start, end) = Period::Day(date(2024, 3, 31)).bounds_in(&tz);
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 3, 30, 22, 0, 0).unwrap());
+        assert_eq!(end - start, Duration::days(1));
+
+        let (start, end) = Period::Week(date(2024, 3, 31)).bounds_in(&tz);
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 3, 24, 22, 0, 0).unwrap());
+        assert_eq!(end, Utc.with_ymd_and_hms(2024, 3, 31, 22, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let today = date(2024, 3, 6);
+        assert_eq!(
+            Period::parse("yesterday", today)?,
+            Period::Day(date(2024, 3, 5))
+        );
+        assert_eq!(
+            Period::parse("last-week", today)?.first(),
+            date(2024, 2, 26)
+        );
+        assert_eq!(
+            Period::parse("last-month", today)?.last(),
+            date(2024, 2, 29)
+        );
+        assert_eq!(
+            Period::parse("2024-01-01..2024-01-31", today)?,
+            Period::Custom(date(2024, 1, 1), date(2024, 1, 31))
+        );
+        assert!(Period::parse("2024-01-31..2024-01-01", today).is_err());
+        assert!(Period::parse("fortnight", today).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{bail, Context, Result};
+
+/// macOS privacy permissions that features may depend on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Permission {
+    Accessibility,
+    ScreenRecording,
+}
+
+/// Features relying on a permission fall back to doing without it when `Missing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub enum Status {
+    Granted,
+    Missing,
+    /// The platform has no such permission; features work without it.
+    NotApplicable,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 2] = [Permission::Accessibility, Permission::ScreenRecording];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Permission::Accessibility => "Accessibility",
+            Permission::ScreenRecording => "Screen Recording",
+        }
+    }
+
+    /// What Neflo would use the permission for.
+    pub fn purpose(self) -> &'static str {
+        match self {
+            Permission::Accessibility => "reading the frontmost app and its window titles",
+            Permission::ScreenRecording => "reading window titles of other apps",
+        }
+    }
+
+    /// Deep link to the matching pane of System Settings > Privacy & Security.
+    pub fn settings_url(self) -> &'static str {
+        match self {
+            Permission::Accessibility => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility"
+            }
+            Permission::ScreenRecording => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture"
+            }
+        }
+    }
+
+    pub fn status(self) -> Status {
+        #[cfg(target_os = "macos")]
+        {
+            #[link(name = "ApplicationServices", kind = "framework")]
+            extern "C" {
+                fn AXIsProcessTrusted() -> bool;
+            }
+            #[link(name = "CoreGraphics", kind = "framework")]
+            extern "C" {
+                fn CGPreflightScreenCaptureAccess() -> bool;
+            }
+            // Both only query the current grant and never show a prompt
+            let granted = unsafe {
+                match self {
+                    Permission::Accessibility => AXIsProcessTrusted(),
+                    Permission::ScreenRecording => CGPreflightScreenCaptureAccess(),
+                }
+            };
+            if granted {
+                Status::Granted
+            } else {
+                Status::Missing
+            }
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            Status::NotApplicable
+        }
+    }
+
+    /// Explain a missing grant and how to fix it.
+    pub fn explain(self) -> String {
+        format!(
+            "{} permission is missing; Neflo needs it for {}. Grant it in System Settings > \
+             Privacy & Security > {} (`neflo permissions --open`), then restart Neflo.",
+            self.name(),
+            self.purpose(),
+            self.name()
+        )
+    }
+
+    /// Open the System Settings pane where the permission is granted.
+    pub fn open_settings(self) -> Result<()> {
+        if !cfg!(target_os = "macos") {
+            bail!("{} permission only exists on macOS", self.name());
+        }
+        let status = std::process::Command::new("open")
+            .arg(self.settings_url())
+            .status()
+            .context("Failed to open System Settings")?;
+        if !status.success() {
+            bail!("Failed to open System Settings for {}", self.name());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_urls_point_to_privacy_panes() {
+        for permission in Permission::ALL {
+            assert!(permission
+                .settings_url()
+                .starts_with("x-apple.systempreferences:com.apple.preference.security?Privacy_"));
+            assert!(permission.explain().contains(permission.name()));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::models::{Database, Interval, IntervalType};
+use crate::period::Period;
+use chrono::{Duration, NaiveDate};
+
+/// Which intervals `neflo query` returns. Unset fields match everything.
+#[derive(Debug, Default)]
+pub struct Query {
+    pub kind: Option<IntervalType>,
+    /// First local date to include.
+    pub since: Option<NaiveDate>,
+    /// Last local date to include.
+    pub until: Option<NaiveDate>,
+    pub device: Option<String>,
+    /// Desktop spaces of a tag; intervals are cut to the time spent on them.
+    pub spaces: Option<Vec<u64>>,
+    pub min_duration: Option<Duration>,
+}
+
+impl Query {
+    /// Matching intervals, oldest first, cut to the date range and tag.
+    pub fn run(&self, db: &Database) -> Vec<Interval> {
+        let from = self.since.map(|date| Period::Day(date).start());
+        let to = self.until.map(|date| Period::Day(date).end());
+        let mut matches: Vec<Interval> = db
+            .intervals
+            .iter()
+            .filter(|i| self.kind.is_none_or(|kind| i.kind == kind))
+            .filter(|i| {
+                self.device
+                    .as_deref()
+                    .is_none_or(|device| i.device_id.as_deref() == Some(device))
+            })
+            .map(|i| Interval {
+                start: from.map_or(i.start, |from| i.start.max(from)),
+                end: to.map_or(i.end, |to| i.end.min(to)),
+                ..i.clone()
+            })
+            .filter(|i| i.end > i.start)
+            .flat_map(|i| self.cut_to_spaces(db, i))
+            .filter(|i| self.min_duration.is_none_or(|min| i.end - i.start >= min))
+            .collect();
+        matches.sort_by_key(|i| i.start);
+        matches
+    }
+
+    fn cut_to_spaces(&self, db: &Database, interval: Interval) -> Vec<Interval> {
+        let Some(spaces) = &self.spaces else {
+            return vec![interval];
+        };
+        db.spaces
+            .iter()
+            .filter(|span| spaces.contains(&span.space))
+            .map(|span| Interval {
+                start: interval.start.max(span.start),
+                end: interval.end.min(span.end),
+                ..interval.clone()
+            })
+            .filter(|i| i.end > i.start)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SpaceSpan;
+    use chrono::{DateTime, Local, TimeZone, Utc};
+
+    #[test]
+    fn test_query_filters_and_cuts() {
+        let at = |day: u32, h: u32| -> DateTime<Utc> {
+            Local
+                .with_ymd_and_hms(2024, 5, day, h, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let interval = |start, end, kind| Interval {
+            end,
+            ..Interval::new_at(kind, start)
+        };
+        let db = Database {
+            intervals: vec![
+                interval(at(1, 9), at(1, 11), IntervalType::Focus),
+                interval(at(1, 11), at(1, 12), IntervalType::Idle),
+                interval(at(2, 9), at(2, 10), IntervalType::Focus),
+            ],
+            spaces: vec![SpaceSpan {
+                start: at(1, 10),
+                end: at(2, 9),
+                space: 3,
+            }],
+            ..Default::default()
+        };
+
+        let focus = Query {
+            kind: Some(IntervalType::Focus),
+            since: Some(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(focus.run(&db), [db.intervals[2].clone()]);
+
+        let on_space = Query {
+            kind: Some(IntervalType::Focus),
+            spaces: Some(vec![3]),
+            ..Default::default()
+        };
+        let cut = on_space.run(&db);
+        assert_eq!(cut.len(), 1);
+        assert_eq!((cut[0].start, cut[0].end), (at(1, 10), at(1, 11)));
+
+        let long = Query {
+            min_duration: Some(Duration::minutes(90)),
+            ..Default::default()
+        };
+        assert_eq!(long.run(&db).len(), 1);
+    }
+}
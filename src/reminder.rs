@@ -0,0 +1,89 @@
+use crate::config::IdleReminderConfig;
+use crate::utils::chrono_duration;
+use chrono::{DateTime, Duration, Local, NaiveDate};
+
+/// Escalating reminder while the user stays idle during a tracked session.
+#[derive(Debug)]
+pub struct IdleReminder {
+    after: Duration,
+    every: Duration,
+    max_per_day: u32,
+    day: Option<NaiveDate>,
+    sent_today: u32,
+    /// When the last reminder of the current idle stretch was sent.
+    last_sent: Option<DateTime<Local>>,
+}
+
+impl IdleReminder {
+    pub fn from_config(config: &IdleReminderConfig) -> Self {
+        Self {
+            after: Duration::minutes(config.after_mins as i64),
+            every: Duration::minutes(config.every_mins as i64),
+            max_per_day: config.max_per_day,
+            day: None,
+            sent_today: 0,
+            last_sent: None,
+        }
+    }
+
+    /// Given the current idle time, return the reminder text if one is due.
+    pub fn check(&mut self, idle: std::time::Duration, now: DateTime<Local>) -> Option<String> {
+        if self.day != Some(now.date_naive()) {
+            self.day = Some(now.date_naive());
+            self.sent_today = 0;
+        }
+
+        let idle = chrono_duration(idle);
+        if idle < self.after {
+            // Activity resumed: the next idle stretch starts from scratch
+            self.last_sent = None;
+            return None;
+        }
+        if self.sent_today >= self.max_per_day
+            || self.last_sent.is_some_and(|t| now - t < self.every)
+        {
+            return None;
+        }
+
+        self.last_sent = Some(now);
+        self.sent_today += 1;
+        Some(format!("Away for {} minutes", idle.num_minutes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_reminder_escalates_and_caps_per_day() {
+        let mut reminder = IdleReminder::from_config(&IdleReminderConfig {
+            after_mins: 15,
+            every_mins: 10,
+            max_per_day: 3,
+        });
+        let t = Local.with_ymd_and_hms(2024, 5, 13, 10, 0, 0).unwrap();
+        let mins = |m: i64| t + Duration::minutes(m);
+        let idle = |m: u64| std::time::Duration::from_secs(m * 60);
+
+        assert_eq!(reminder.check(idle(14), t), None);
+        assert_eq!(
+            reminder.check(idle(15), mins(1)).as_deref(),
+            Some("Away for 15 minutes")
+        );
+        assert_eq!(reminder.check(idle(20), mins(6)), None);
+        assert_eq!(
+            reminder.check(idle(25), mins(11)).as_deref(),
+            Some("Away for 25 minutes")
+        );
+
+        // Back to work, then idle again: reminded right away, until the daily cap
+        assert_eq!(reminder.check(idle(0), mins(12)), None);
+        assert!(reminder.check(idle(15), mins(30)).is_some());
+        assert_eq!(reminder.check(idle(60), mins(60)), None);
+
+        // The cap resets the next day
+        assert!(reminder.check(idle(15), t + Duration::days(1)).is_some());
+    }
+}
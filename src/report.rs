@@ -1,16 +1,63 @@
-use crate::stats::calculate_stats;
+use crate::budget::{self, Target};
+use crate::chart;
+use crate::clock::{Clock, SystemClock};
+use crate::config::KindsConfig;
+use crate::models::{Database, IntervalType, SessionRecord};
+use crate::period::Period;
+use crate::stats::{
+    average_focus_onset, calculate_stats, focus_by_space, focus_by_tag, focus_profile, focus_trend,
+    goal_streaks, ranked_days, utilization, DaySort, DayStats, Stats, StatsOptions, TrendDirection,
+};
 use crate::storage::Storage;
-use crate::utils::format_duration;
+use crate::utils::{format_bar, format_duration, format_percent, month_weeks, paint, year_week};
 use anyhow::Result;
-use chrono::Duration;
+use chrono::{Datelike, Duration, Local, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
 
 pub struct Reporter {
     storage: Storage,
+    options: StatsOptions,
+    /// Display names for desktop space ids, keyed by the id as a string.
+    pub space_names: BTreeMap<String, String>,
+    pub kinds: KindsConfig,
+    /// Sessions on days using another threshold are listed with their settings.
+    pub default_threshold_mins: u64,
+    pub targets: Vec<Target>,
+    pub chart_scale: chart::Scale,
+    /// Scheduled working time per day, which utilization is measured against.
+    pub workday: Option<Duration>,
+    /// Days to list instead of the current week.
+    pub period: Option<Period>,
+    /// Rank the listed days instead of showing them in date order.
+    pub sort: Option<DaySort>,
+    /// List only this many days.
+    pub top: Option<usize>,
+    /// Color focus and idle in the kinds' colors (see `ColorChoice`).
+    pub color: bool,
 }
 
 impl Reporter {
-    pub fn new(storage: Storage) -> Self {
-        Self { storage }
+    pub fn new(storage: Storage, options: StatsOptions) -> Self {
+        Self {
+            storage,
+            options,
+            space_names: BTreeMap::new(),
+            kinds: KindsConfig::default(),
+            default_threshold_mins: 5,
+            targets: Vec::new(),
+            chart_scale: chart::Scale::default(),
+            workday: None,
+            period: None,
+            sort: None,
+            top: None,
+            color: false,
+        }
+    }
+
+    /// The kinds to color output with, if it is colored.
+    fn colors(&self) -> Option<&KindsConfig> {
+        self.color.then_some(&self.kinds)
     }
 
     pub fn report(&self) -> Result<()> {
@@ -20,23 +67,24 @@ impl Reporter {
             return Ok(());
         }
 
-        let stats_data = calculate_stats(&db, None);
+        let stats_data = calculate_stats(&db, None, &self.options, &SystemClock);
+        let focus = self.kinds.label(IntervalType::Focus);
+        let idle = self.kinds.label(IntervalType::Idle);
+        let locked = self.kinds.label(IntervalType::Locked);
 
         println!("Neflo Report");
         println!("============");
 
-        let mut week_total_focus = Duration::zero();
-        let mut week_total_idle = Duration::zero();
-        let mut week_focus_sessions = 0;
-        let mut week_idle_sessions = 0;
-
-        let week_end = stats_data.week_start + Duration::days(6);
-
-        for (date, stats) in &stats_data.daily_stats {
-            if *date < stats_data.week_start || *date > week_end {
-                continue;
-            }
+        let week = Period::Week(stats_data.today);
+        let week_end = week.last();
 
+        let days = ranked_days(
+            &stats_data.daily_stats,
+            self.period.unwrap_or(week),
+            self.sort,
+            self.top,
+        );
+        for (date, stats) in &days {
             let is_today = *date == stats_data.today;
             let date_str = if is_today {
                 format!("{} (Today)", date)
@@ -45,20 +93,71 @@ impl Reporter {
             };
 
             println!("\nDate: {}", date_str);
+            if let Some(reason) = &stats.off {
+                println!("  Off:               {}", reason);
+            }
             println!(
-                "  Focus Time:        {}",
+                "  {}{}",
+                painted(
+                    format!("{:<19}", format!("{} Time:", focus)),
+                    IntervalType::Focus,
+                    self.colors()
+                ),
                 format_duration(stats.total_focus.num_seconds())
             );
             println!(
-                "  Idle Time:         {}",
+                "  {}{}",
+                painted(
+                    format!("{:<19}", format!("{} Time:", idle)),
+                    IntervalType::Idle,
+                    self.colors()
+                ),
                 format_duration(stats.total_idle.num_seconds())
             );
+            if stats.total_locked > Duration::zero() {
+                println!(
+                    "  {}{}",
+                    painted(
+                        format!("{:<19}", format!("{} Time:", locked)),
+                        IntervalType::Locked,
+                        self.colors()
+                    ),
+                    format_duration(stats.total_locked.num_seconds())
+                );
+            }
             println!("  Interruptions:     {}", stats.idle_sessions);
+            println!(
+                "  Deep Work:         {} ({} sessions)",
+                format_duration(stats.total_deep_work.num_seconds()),
+                stats.deep_work_sessions
+            );
+            if let Some(onset) = stats.focus_onset {
+                println!(
+                    "  Focus Onset:       {}",
+                    format_duration(onset.num_seconds())
+                );
+            }
+            if let Some(intensity) = stats.intensity() {
+                println!("  Intensity:         {:.0} events/min", intensity);
+            }
+            if let Some((workday, share)) = self.workday.and_then(|workday| {
+                Some((
+                    workday,
+                    utilization(&stats_data.daily_stats, *date, *date, workday)?,
+                ))
+            }) {
+                println!(
+                    "  Utilization:       {} of {}",
+                    format_percent(share),
+                    format_duration(workday.num_seconds())
+                );
+            }
 
             if stats.focus_sessions > 0 {
                 let avg_focus = stats.total_focus / (stats.focus_sessions as i32);
                 println!(
-                    "  Avg Focus Session: {}",
+                    "  {:<19}{}",
+                    format!("Avg {} Session:", focus),
                     format_duration(avg_focus.num_seconds())
                 );
             }
@@ -70,41 +169,742 @@ impl Reporter {
                 );
             }
 
-            week_total_focus += stats.total_focus;
-            week_total_idle += stats.total_idle;
-            week_focus_sessions += stats.focus_sessions;
-            week_idle_sessions += stats.idle_sessions;
+            let sessions: Vec<_> = db
+                .sessions
+                .iter()
+                .filter(|s| s.start.with_timezone(&Local).date_naive() == *date)
+                .collect();
+            if sessions
+                .iter()
+                .any(|s| s.threshold_mins != self.default_threshold_mins)
+            {
+                println!("  Sessions:");
+                for session in sessions {
+                    println!("    {}", describe_session(session));
+                }
+            }
+
+            let markers: Vec<_> = db
+                .markers
+                .iter()
+                .filter(|m| m.at.with_timezone(&Local).date_naive() == *date)
+                .collect();
+            if !markers.is_empty() {
+                println!("  Markers:");
+                for marker in markers {
+                    println!(
+                        "    {}  {}",
+                        marker.at.with_timezone(&Local).format("%H:%M"),
+                        marker.text
+                    );
+                }
+            }
+
+            if let Some(note) = db.journal.get(date) {
+                println!("  Journal:");
+                for line in note.lines() {
+                    println!("    {}", line);
+                }
+            }
         }
 
         println!(
-            "\nWeekly Summary (Starting Monday {})",
+            "\nWeekly Summary ({}, Starting Monday {})",
+            year_week(stats_data.week_start),
             stats_data.week_start
         );
         println!("-------------------------------------------");
+        let summary = &stats_data.week_summary;
         println!(
-            "Total Focus Time:    {}",
-            format_duration(week_total_focus.num_seconds())
+            "{}{}",
+            painted(
+                format!("{:<21}", format!("Total {} Time:", focus)),
+                IntervalType::Focus,
+                self.colors()
+            ),
+            format_duration(summary.total_focus.num_seconds())
         );
         println!(
-            "Total Idle Time:     {}",
-            format_duration(week_total_idle.num_seconds())
+            "{}{}",
+            painted(
+                format!("{:<21}", format!("Total {} Time:", idle)),
+                IntervalType::Idle,
+                self.colors()
+            ),
+            format_duration(summary.total_idle.num_seconds())
         );
-        println!("Total Interruptions: {}", week_idle_sessions);
-        if week_focus_sessions > 0 {
-            let avg_focus = week_total_focus / (week_focus_sessions as i32);
+        if summary.total_locked > Duration::zero() {
             println!(
-                "Avg Focus Session:   {}",
+                "{}{}",
+                painted(
+                    format!("{:<21}", format!("Total {} Time:", locked)),
+                    IntervalType::Locked,
+                    self.colors()
+                ),
+                format_duration(summary.total_locked.num_seconds())
+            );
+        }
+        println!("Total Interruptions: {}", summary.idle_count);
+        println!(
+            "Total Deep Work:     {} ({} sessions)",
+            format_duration(summary.total_deep_work.num_seconds()),
+            summary.deep_work_count
+        );
+        if let Some(share) = self.workday.and_then(|workday| {
+            utilization(
+                &stats_data.daily_stats,
+                stats_data.week_start,
+                week_end,
+                workday,
+            )
+        }) {
+            println!("Utilization:         {}", format_percent(share));
+        }
+        if let Some(onset) =
+            average_focus_onset(&stats_data.daily_stats, stats_data.week_start, week_end)
+        {
+            println!(
+                "Avg Focus Onset:     {}",
+                format_duration(onset.num_seconds())
+            );
+        }
+        if summary.focus_count > 0 {
+            let avg_focus = summary.total_focus / (summary.focus_count as i32);
+            println!(
+                "{:<21}{}",
+                format!("Avg {} Session:", focus),
                 format_duration(avg_focus.num_seconds())
             );
         }
-        if week_idle_sessions > 0 {
-            let avg_idle = week_total_idle / (week_idle_sessions as i32);
+        if summary.idle_count > 0 {
+            let avg_idle = summary.total_idle / (summary.idle_count as i32);
             println!(
                 "Avg Interruption:    {}",
                 format_duration(avg_idle.num_seconds())
             );
         }
 
+        println!(
+            "\nActivity - Current Week ({}, {})",
+            painted(
+                format!("{} {}", FOCUS_CELL, focus),
+                IntervalType::Focus,
+                self.colors()
+            ),
+            painted(
+                format!("{} {}", IDLE_CELL, idle),
+                IntervalType::Idle,
+                self.colors()
+            )
+        );
+        println!("-------------------------------------------");
+        for line in weekly_chart(&stats_data, self.chart_scale, self.colors()) {
+            println!("{}", line);
+        }
+        let intensity = intensity_chart(&stats_data, self.colors());
+        if !intensity.is_empty() {
+            println!("\nIntensity - Current Week (keystrokes and clicks per minute of focus)");
+            println!("-------------------------------------------");
+            for line in intensity {
+                println!("{}", line);
+            }
+        }
+
+        let trends: Vec<_> = [7, 28]
+            .into_iter()
+            .filter_map(|days| {
+                Some((
+                    days,
+                    focus_trend(&stats_data.daily_stats, stats_data.today, days)?,
+                ))
+            })
+            .collect();
+        if !trends.is_empty() {
+            println!("\n{} Trend (completed days)", focus);
+            println!("-------------------------------------------");
+            for (days, trend) in trends {
+                let direction = match trend.direction {
+                    TrendDirection::Up => "trending up",
+                    TrendDirection::Down => "trending down",
+                    TrendDirection::Flat => "steady",
+                };
+                println!(
+                    "{:<12}{} ± {}, {}",
+                    format!("{}-day avg:", days),
+                    format_duration(trend.average.num_seconds()),
+                    format_duration(trend.std_dev.num_seconds()),
+                    direction
+                );
+            }
+        }
+
+        let week_start = week.start();
+        let by_space = focus_by_space(&db, week_start, &self.options);
+        if let Some(max_focus) = by_space.values().max() {
+            println!("\nFocus by Space (this week)");
+            println!("--------------------------");
+            for (space, focus) in &by_space {
+                let label = match self.space_names.get(&space.to_string()) {
+                    Some(name) => name.clone(),
+                    None => format!("Space {}", space),
+                };
+                println!(
+                    "{:<12} {:<20} {}",
+                    label,
+                    format_bar(focus.num_seconds(), max_focus.num_seconds(), 20),
+                    format_duration(focus.num_seconds())
+                );
+            }
+        }
+
+        if !self.targets.is_empty() {
+            let by_tag = focus_by_tag(&db, week_start, &self.options, &self.space_names);
+            println!("\nWeekly Targets (this week)");
+            println!("--------------------------");
+            for evaluation in budget::evaluate(&self.targets, &stats_data.week_summary, &by_tag) {
+                println!(
+                    "{} {:<22} {}",
+                    evaluation.standing.symbol(),
+                    evaluation.target.describe(),
+                    evaluation.progress()
+                );
+            }
+        }
+
+        if stats_data.focus_on_battery + stats_data.focus_on_ac > Duration::zero() {
+            println!(
+                "\nFocus on the go:     {} on battery, {} docked (all recorded days)",
+                format_duration(stats_data.focus_on_battery.num_seconds()),
+                format_duration(stats_data.focus_on_ac.num_seconds())
+            );
+        }
+
+        let max_interruptions = stats_data
+            .hourly_interruptions
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0);
+        if max_interruptions > 0 {
+            println!("\nInterruptions by Hour (all recorded days)");
+            println!("-------------------------------------------");
+            for (hour, count) in stats_data.hourly_interruptions.iter().enumerate() {
+                if *count == 0 {
+                    continue;
+                }
+                println!(
+                    "{:02}:00  {:<20} {}",
+                    hour,
+                    format_bar(*count as i64, max_interruptions as i64, 20),
+                    count
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Print how likely each hour of the day was spent in focus over the last
+    /// `weeks` weeks, and the hours least likely to be.
+    pub fn month_calendar(&self, goal: Option<Duration>) -> Result<()> {
+        let db = self.storage.load()?;
+        print!(
+            "{}",
+            month_calendar_markdown(&db, &self.options, goal, &SystemClock)
+        );
+        Ok(())
+    }
+
+    pub fn profile_hours(&self, weeks: i64) -> Result<()> {
+        let db = self.storage.load()?;
+        let profile = focus_profile(&db, Utc::now() - Duration::weeks(weeks), Utc::now());
+        let hours: Vec<usize> = (0..24).filter(|h| profile.hour_ratio(*h) > 0.0).collect();
+        let (Some(first), Some(last)) = (hours.first(), hours.last()) else {
+            println!("No focus recorded in the last {} weeks.", weeks);
+            return Ok(());
+        };
+
+        let focus = self.kinds.label(IntervalType::Focus);
+        println!(
+            "{} by Hour of Day (last {} weeks, {} days with data)",
+            focus, weeks, profile.days
+        );
+        println!("-------------------------------------------");
+        for hour in *first..=*last {
+            let percent = (profile.hour_ratio(hour) * 100.0).round() as i64;
+            println!(
+                "{:02}:00  {:<20} {:>3}%",
+                hour,
+                format_bar(percent, 100, 20),
+                percent
+            );
+        }
+
+        let mut lowest: Vec<usize> = (*first..=*last).collect();
+        lowest.sort_by(|a, b| profile.hour_ratio(*a).total_cmp(&profile.hour_ratio(*b)));
+        lowest.truncate(3);
+        lowest.sort();
+        let lowest: Vec<String> = lowest.iter().map(|h| format!("{:02}:00", h)).collect();
+        println!(
+            "\nLowest-{} hours: {}",
+            focus.to_lowercase(),
+            lowest.join(", ")
+        );
         Ok(())
     }
 }
+
+/// One line describing when a session ran and the settings it used.
+fn describe_session(session: &SessionRecord) -> String {
+    let local = |t: chrono::DateTime<Utc>| t.with_timezone(&Local).format("%H:%M").to_string();
+    let end = session.end.map(local).unwrap_or_default();
+    let mut out = format!(
+        "{}–{:<5}  threshold {}m",
+        local(session.start),
+        end,
+        session.threshold_mins
+    );
+    if let Some(mins) = session.duration_mins {
+        let _ = write!(out, ", duration {}", format_duration(mins * 60));
+    } else {
+        if let Some(start_time) = session.start_time {
+            let _ = write!(out, ", from {}", start_time.format("%H:%M"));
+        }
+        if let Some(end_time) = session.end_time {
+            let _ = write!(out, ", until {}", end_time.format("%H:%M"));
+        }
+    }
+    out
+}
+
+/// `text` in the color of `kind`, when `colors` are given.
+fn painted(text: String, kind: IntervalType, colors: Option<&KindsConfig>) -> String {
+    match colors {
+        Some(kinds) => paint(&text, kinds.color(kind), true),
+        None => text,
+    }
+}
+
+const FOCUS_CELL: char = '█';
+const IDLE_CELL: char = '░';
+/// Cells of the longest day's bar in the weekly chart.
+const CHART_WIDTH: u16 = 30;
+
+/// One bar per weekday of the current week for the intensity of its focus,
+/// scaled to the most intense day; empty if no day has any.
+fn intensity_chart(stats: &Stats, colors: Option<&KindsConfig>) -> Vec<String> {
+    let week = Period::Week(stats.today);
+    let days: Vec<(NaiveDate, Option<f64>)> = week
+        .first()
+        .iter_days()
+        .take_while(|date| *date <= week.last())
+        .map(|date| {
+            (
+                date,
+                stats.daily_stats.get(&date).and_then(DayStats::intensity),
+            )
+        })
+        .collect();
+    let max = days.iter().filter_map(|(_, i)| *i).fold(0.0, f64::max);
+    if max <= 0.0 {
+        return Vec::new();
+    }
+    days.iter()
+        .map(|(date, intensity)| match intensity {
+            Some(intensity) => {
+                let bar = format_bar(
+                    intensity.round() as i64,
+                    max.round() as i64,
+                    CHART_WIDTH as usize,
+                );
+                let padding = CHART_WIDTH as usize - bar.chars().count();
+                format!(
+                    "{} {}{} {:.0}/min",
+                    date.format("%a"),
+                    painted(bar, IntervalType::Focus, colors),
+                    " ".repeat(padding),
+                    intensity
+                )
+            }
+            None => date.format("%a").to_string(),
+        })
+        .collect()
+}
+
+/// The dashboard's weekly chart as text: one stacked bar per weekday, focus
+/// then idle, followed by the day's focus time.
+fn weekly_chart(stats: &Stats, scale: chart::Scale, colors: Option<&KindsConfig>) -> Vec<String> {
+    let bars = chart::week_bars(stats);
+    let full = chart::full_bar_secs(&bars, scale);
+    bars.iter()
+        .map(|bar| {
+            let (focus, idle) = chart::stack(bar, full, CHART_WIDTH);
+            // Padded by hand: escape codes would count towards a width
+            format!(
+                "{} {}{}{} {}",
+                bar.label,
+                painted(
+                    FOCUS_CELL.to_string().repeat(focus as usize),
+                    IntervalType::Focus,
+                    colors
+                ),
+                painted(
+                    IDLE_CELL.to_string().repeat(idle as usize),
+                    IntervalType::Idle,
+                    colors
+                ),
+                " ".repeat(CHART_WIDTH.saturating_sub(focus + idle) as usize),
+                chart::label(bar, scale),
+            )
+            .trim_end()
+            .to_string()
+        })
+        .collect()
+}
+
+/// Render the current month as a printable Markdown calendar: focus per day,
+/// with days that reached `goal` ticked and the goal streaks summarized.
+pub fn month_calendar_markdown(
+    db: &Database,
+    options: &StatsOptions,
+    goal: Option<Duration>,
+    clock: &dyn Clock,
+) -> String {
+    let stats_data = calculate_stats(db, None, options, clock);
+    let today = stats_data.today;
+    let weeks = month_weeks(today);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# Neflo {}\n", today.format("%B %Y"));
+    let _ = writeln!(out, "| Mon | Tue | Wed | Thu | Fri | Sat | Sun |");
+    let _ = writeln!(out, "|-----|-----|-----|-----|-----|-----|-----|");
+    let mut month_focus = Duration::zero();
+    let mut days_met = 0;
+    let mut days_off = 0;
+    for week in &weeks {
+        let cells: Vec<String> = week
+            .iter()
+            .map(|date| {
+                let Some(date) = date else {
+                    return String::new();
+                };
+                let mut cell = format!("**{}**", date.day());
+                if let Some(stats) = stats_data.daily_stats.get(date) {
+                    if let Some(reason) = &stats.off {
+                        if *date <= today {
+                            days_off += 1;
+                        }
+                        let _ = write!(cell, "<br>_{}_", reason);
+                        return cell;
+                    }
+                    month_focus += stats.total_focus;
+                    if goal.is_some_and(|goal| stats.total_focus >= goal) {
+                        days_met += 1;
+                        cell.push_str(" ✓");
+                    }
+                    let _ = write!(
+                        cell,
+                        "<br>{}",
+                        format_duration(stats.total_focus.num_minutes() * 60)
+                    );
+                }
+                cell
+            })
+            .collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+
+    let _ = writeln!(out, "\n## Summary\n");
+    let _ = writeln!(
+        out,
+        "- **Total Focus Time**: {}",
+        format_duration(month_focus.num_seconds())
+    );
+    if let Some(goal) = goal {
+        let first = Period::Month(today).first();
+        let streaks = goal_streaks(&stats_data.daily_stats, first, today, goal);
+        let _ = writeln!(
+            out,
+            "- **Daily Goal**: {} focus, met on {} of {} days",
+            format_duration(goal.num_seconds()),
+            days_met,
+            today.day() - days_off
+        );
+        let _ = writeln!(out, "- **Current Streak**: {} days", streaks.current);
+        let _ = writeln!(out, "- **Longest Streak**: {} days", streaks.longest);
+    }
+    out
+}
+
+/// Render the current week as a Markdown document, suitable for saving to disk.
+pub fn weekly_markdown(db: &Database, options: &StatsOptions, kinds: &KindsConfig) -> String {
+    let stats_data = calculate_stats(db, None, options, &SystemClock);
+    let focus = kinds.label(IntervalType::Focus);
+    let idle = kinds.label(IntervalType::Idle);
+    let week_end = Period::Week(stats_data.today).last();
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# Neflo Weekly Report {} ({} to {})\n",
+        year_week(stats_data.week_start),
+        stats_data.week_start,
+        week_end
+    );
+    let _ = writeln!(out, "| Date | {} | {} | Interruptions |", focus, idle);
+    let _ = writeln!(out, "|------|-------|------|---------------|");
+    for (date, stats) in stats_data
+        .daily_stats
+        .range(stats_data.week_start..=week_end)
+    {
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            date.format("%a %Y-%m-%d"),
+            format_duration(stats.total_focus.num_seconds()),
+            format_duration(stats.total_idle.num_seconds()),
+            stats.idle_sessions
+        );
+    }
+
+    let week = &stats_data.week_summary;
+    let _ = writeln!(out, "\n## Summary\n");
+    let _ = writeln!(
+        out,
+        "- **Total {} Time**: {}",
+        focus,
+        format_duration(week.total_focus.num_seconds())
+    );
+    let _ = writeln!(
+        out,
+        "- **Total {} Time**: {}",
+        idle,
+        format_duration(week.total_idle.num_seconds())
+    );
+    if week.total_locked > Duration::zero() {
+        let _ = writeln!(
+            out,
+            "- **Total {} Time**: {}",
+            kinds.label(IntervalType::Locked),
+            format_duration(week.total_locked.num_seconds())
+        );
+    }
+    let _ = writeln!(out, "- **Total Interruptions**: {}", week.idle_count);
+    let _ = writeln!(
+        out,
+        "- **Total Deep Work**: {} ({} sessions)",
+        format_duration(week.total_deep_work.num_seconds()),
+        week.deep_work_count
+    );
+
+    let notes: Vec<_> = db.journal.range(stats_data.week_start..=week_end).collect();
+    if !notes.is_empty() {
+        let _ = writeln!(out, "\n## Journal\n");
+        for (date, note) in notes {
+            let _ = writeln!(out, "### {}\n\n{}\n", date.format("%a %Y-%m-%d"), note);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Interval, IntervalType};
+    use chrono::{Local, TimeZone, Utc};
+
+    #[test]
+    fn test_describe_session() {
+        let start = Local
+            .with_ymd_and_hms(2024, 5, 13, 9, 2, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut session = SessionRecord {
+            start,
+            end: Some(start + Duration::minutes(523)),
+            threshold_mins: 10,
+            start_time: None,
+            end_time: chrono::NaiveTime::from_hms_opt(18, 0, 0),
+            duration_mins: None,
+            device_id: None,
+        };
+        assert_eq!(
+            describe_session(&session),
+            "09:02–17:45  threshold 10m, until 18:00"
+        );
+
+        session.end = None;
+        session.duration_mins = Some(240);
+        assert_eq!(
+            describe_session(&session),
+            "09:02–       threshold 10m, duration 4h"
+        );
+    }
+
+    #[test]
+    fn test_weekly_markdown_includes_today_and_journal() {
+        let today = Local::now().date_naive();
+        let start = today
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut db = Database {
+            intervals: vec![Interval {
+                start,
+                end: start + Duration::minutes(25),
+                kind: IntervalType::Focus,
+                device_id: None,
+                power: None,
+                confidence: None,
+                intensity: None,
+            }],
+            ..Default::default()
+        };
+        db.add_journal_entry(today, "wrote the scheduler");
+
+        let md = weekly_markdown(&db, &StatsOptions::default(), &KindsConfig::default());
+
+        assert!(md.starts_with("# Neflo Weekly Report"));
+        assert!(md.contains(&today.format("%a %Y-%m-%d").to_string()));
+        assert!(md.contains("- **Total Focus Time**: 25m"));
+        assert!(md.contains("- **Total Deep Work**: 25m (1 sessions)"));
+        assert!(md.contains("wrote the scheduler"));
+    }
+
+    #[test]
+    fn test_month_calendar_marks_goal_days_and_streaks() {
+        let at = |day: u32| {
+            Local
+                .with_ymd_and_hms(2024, 5, day, 9, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let focus = |day: u32, mins: i64| Interval {
+            end: at(day) + Duration::minutes(mins),
+            ..Interval::new_at(IntervalType::Focus, at(day))
+        };
+        let mut db = Database {
+            intervals: vec![
+                focus(1, 130),
+                focus(2, 125),
+                focus(3, 60),
+                focus(13, 120),
+                focus(14, 150),
+            ],
+            ..Default::default()
+        };
+        db.mark_off(at(6).date_naive(), at(10).date_naive(), "vacation");
+        let now = at(14) + Duration::hours(4);
+
+        let md = month_calendar_markdown(
+            &db,
+            &StatsOptions::default(),
+            Some(Duration::hours(2)),
+            &now,
+        );
+
+        assert!(md.starts_with("# Neflo May 2024"));
+        // May 2024 starts on a Wednesday
+        assert!(md.contains(
+            "|  |  | **1** ✓<br>2h 10m | **2** ✓<br>2h 5m | **3**<br>1h | **4** | **5** |"
+        ));
+        assert!(md.contains("| **6**<br>_vacation_ |"));
+        // Days off are not counted against the goal
+        assert!(md.contains("- **Daily Goal**: 2h focus, met on 4 of 9 days"));
+        assert!(md.contains("- **Current Streak**: 2 days"));
+        assert!(md.contains("- **Longest Streak**: 2 days"));
+    }
+
+    #[test]
+    fn test_weekly_chart_stacks_focus_and_idle() {
+        let monday = Local
+            .with_ymd_and_hms(2024, 5, 13, 9, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let db = Database {
+            intervals: vec![
+                Interval {
+                    end: monday + Duration::hours(2),
+                    ..Interval::new_at(IntervalType::Focus, monday)
+                },
+                Interval {
+                    end: monday + Duration::minutes(150),
+                    ..Interval::new_at(IntervalType::Idle, monday + Duration::hours(2))
+                },
+            ],
+            ..Default::default()
+        };
+        let now = monday + Duration::days(1);
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &now);
+
+        let chart = weekly_chart(&stats, chart::Scale::Relative, None);
+        assert_eq!(chart.len(), 7);
+        assert_eq!(
+            chart[0],
+            format!("Mon {}{} 2h", "█".repeat(24), "░".repeat(6))
+        );
+        assert_eq!(chart[1], "Tue");
+
+        let fixed = weekly_chart(&stats, chart::Scale::Fixed(Duration::hours(5)), None);
+        assert_eq!(
+            fixed[0],
+            format!(
+                "Mon {}{}{} 2h 40%",
+                "█".repeat(12),
+                "░".repeat(3),
+                " ".repeat(15)
+            )
+        );
+
+        // Colors only add escape codes around the cells
+        let kinds = KindsConfig::default();
+        let colored = weekly_chart(&stats, chart::Scale::Relative, Some(&kinds));
+        assert!(colored[0].starts_with("Mon \x1b["));
+        let mut plain = colored[0].clone();
+        while let Some(start) = plain.find('\x1b') {
+            let end = start + plain[start..].find('m').unwrap();
+            plain.replace_range(start..=end, "");
+        }
+        assert_eq!(plain, chart[0]);
+    }
+
+    #[test]
+    fn test_intensity_chart_covers_sampled_focus_only() {
+        let monday = Local
+            .with_ymd_and_hms(2024, 5, 13, 9, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let focus = |start, minutes, intensity| Interval {
+            end: start + Duration::minutes(minutes),
+            intensity,
+            ..Interval::new_at(IntervalType::Focus, start)
+        };
+        let mut db = Database {
+            intervals: vec![
+                focus(monday, 60, Some(40.0)),
+                focus(monday + Duration::hours(2), 60, None),
+                focus(monday + Duration::days(1), 30, Some(20.0)),
+            ],
+            ..Default::default()
+        };
+        let now = monday + Duration::days(2);
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &now);
+
+        let chart = intensity_chart(&stats, None);
+        assert_eq!(chart[0], format!("Mon {} 40/min", "█".repeat(30)));
+        assert_eq!(
+            chart[1],
+            format!("Tue {}{} 20/min", "█".repeat(15), " ".repeat(15))
+        );
+        assert_eq!(chart[2], "Wed");
+
+        for interval in &mut db.intervals {
+            interval.intensity = None;
+        }
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &now);
+        assert!(intensity_chart(&stats, None).is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::clock::SystemClock;
+use crate::config::{Config, DailyNoteConfig, KindsConfig, WeeklyReportConfig};
+use crate::daily_note;
+use crate::models::Database;
+use crate::period::Period;
+use crate::report::weekly_markdown;
+use crate::stats::{calculate_stats, top_tag, StatsOptions};
+use crate::utils::expand_tilde;
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Time-based jobs that run while a tracking session is active.
+#[derive(Default)]
+pub struct Scheduler {
+    pub weekly_report: Option<WeeklyReportTask>,
+    pub daily_note: Option<DailyNoteTask>,
+    pub options: StatsOptions,
+    pub kinds: KindsConfig,
+    pub space_names: BTreeMap<String, String>,
+}
+
+impl Scheduler {
+    pub fn from_config(config: &Config, base_dir: &Path, now: NaiveDateTime) -> Result<Self> {
+        Ok(Self {
+            weekly_report: config
+                .weekly_report
+                .as_ref()
+                .map(|c| WeeklyReportTask::new(c, base_dir, now))
+                .transpose()?,
+            daily_note: config
+                .integrations
+                .daily_note
+                .as_ref()
+                .map(|c| DailyNoteTask::new(c, base_dir, now))
+                .transpose()?,
+            options: config.stats_options(),
+            kinds: config.kinds.clone(),
+            space_names: config.space_names.clone(),
+        })
+    }
+
+    /// Run every job whose scheduled time has passed since its last run.
+    pub fn poll(&mut self, db: &Database, now: NaiveDateTime) -> Result<()> {
+        if let Some(task) = &mut self.weekly_report {
+            task.poll(db, now, &self.options, &self.kinds)?;
+        }
+        if let Some(task) = &mut self.daily_note {
+            task.poll(db, now, &self.options, &self.space_names)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the weekly Markdown report once per week at a configured local time.
+pub struct WeeklyReportTask {
+    weekday: Weekday,
+    time: NaiveTime,
+    path_template: PathBuf,
+    last_run: NaiveDateTime,
+}
+
+impl WeeklyReportTask {
+    /// Create the task; occurrences at or before `now` are considered done so a
+    /// session started on Monday does not immediately write last week's report.
+    pub fn new(config: &WeeklyReportConfig, base_dir: &Path, now: NaiveDateTime) -> Result<Self> {
+        let weekday = config
+            .weekday
+            .parse::<Weekday>()
+            .map_err(|_| anyhow::anyhow!("Invalid weekly_report.weekday '{}'", config.weekday))?;
+        let time = NaiveTime::parse_from_str(&config.time, "%H:%M")?;
+        Ok(Self {
+            weekday,
+            time,
+            path_template: base_dir.join(expand_tilde(&config.path)),
+            last_run: last_occurrence(weekday, time, now),
+        })
+    }
+
+    /// Write the report if a scheduled occurrence has passed since the last run,
+    /// returning the path written to.
+    pub fn poll(
+        &mut self,
+        db: &Database,
+        now: NaiveDateTime,
+        options: &StatsOptions,
+        kinds: &KindsConfig,
+    ) -> Result<Option<PathBuf>> {
+        let due = last_occurrence(self.weekday, self.time, now);
+        if due <= self.last_run {
+            return Ok(None);
+        }
+        self.last_run = due;
+
+        let week_start = Period::Week(now.date()).first();
+        let path = PathBuf::from(
+            self.path_template
+                .to_string_lossy()
+                .replace("{week_start}", &week_start.to_string()),
+        );
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, weekly_markdown(db, options, kinds))?;
+        Ok(Some(path))
+    }
+}
+
+/// Appends a rendered summary line to a daily note once per day.
+pub struct DailyNoteTask {
+    time: NaiveTime,
+    path_template: PathBuf,
+    template: String,
+    last_run: NaiveDateTime,
+}
+
+impl DailyNoteTask {
+    pub fn new(config: &DailyNoteConfig, base_dir: &Path, now: NaiveDateTime) -> Result<Self> {
+        let time = NaiveTime::parse_from_str(&config.time, "%H:%M")?;
+        Ok(Self {
+            time,
+            path_template: base_dir.join(expand_tilde(&config.path)),
+            template: config.template.clone(),
+            last_run: last_daily_occurrence(time, now),
+        })
+    }
+
+    /// Append today's summary if the configured time has passed since the last
+    /// run, returning the note written to.
+    pub fn poll(
+        &mut self,
+        db: &Database,
+        now: NaiveDateTime,
+        options: &StatsOptions,
+        space_names: &BTreeMap<String, String>,
+    ) -> Result<Option<PathBuf>> {
+        let due = last_daily_occurrence(self.time, now);
+        if due <= self.last_run {
+            return Ok(None);
+        }
+        self.last_run = due;
+
+        let date = due.date();
+        let stats = calculate_stats(db, None, options, &SystemClock)
+            .daily_stats
+            .get(&date)
+            .cloned()
+            .unwrap_or_default();
+        let path = PathBuf::from(
+            self.path_template
+                .to_string_lossy()
+                .replace("{{date}}", &date.to_string()),
+        );
+        let top_tag = top_tag(db, date, options, space_names);
+        let line = daily_note::render(&self.template, date, &stats, top_tag.as_deref());
+        daily_note::append(&path, &line)?;
+        Ok(Some(path))
+    }
+}
+
+/// The most recent `time` that is not after `now`.
+fn last_daily_occurrence(time: NaiveTime, now: NaiveDateTime) -> NaiveDateTime {
+    let candidate = now.date().and_time(time);
+    if candidate > now {
+        candidate - Duration::days(1)
+    } else {
+        candidate
+    }
+}
+
+/// The most recent `weekday` at `time` that is not after `now`.
+fn last_occurrence(weekday: Weekday, time: NaiveTime, now: NaiveDateTime) -> NaiveDateTime {
+    let days_back = (7 + now.weekday().num_days_from_monday() as i64
+        - weekday.num_days_from_monday() as i64)
+        % 7;
+    let candidate = (now.date() - Duration::days(days_back)).and_time(time);
+    if candidate > now {
+        candidate - Duration::days(7)
+    } else {
+        candidate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use tempfile::tempdir;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_last_occurrence() {
+        let time = NaiveTime::from_hms_opt(21, 0, 0).unwrap();
+        // 2024-05-12 is a Sunday
+        assert_eq!(
+            last_occurrence(Weekday::Sun, time, at(2024, 5, 12, 21, 30)),
+            at(2024, 5, 12, 21, 0)
+        );
+        assert_eq!(
+            last_occurrence(Weekday::Sun, time, at(2024, 5, 12, 20, 59)),
+            at(2024, 5, 5, 21, 0)
+        );
+        assert_eq!(
+            last_occurrence(Weekday::Sun, time, at(2024, 5, 15, 9, 0)),
+            at(2024, 5, 12, 21, 0)
+        );
+    }
+
+    #[test]
+    fn test_daily_note_appends_once_per_day() -> Result<()> {
+        let dir = tempdir()?;
+        let config = DailyNoteConfig {
+            path: "vault/{{date}}.md".to_string(),
+            template: "- Focus: {{focus}} ({{interruptions}} interruptions)".to_string(),
+            time: "18:00".to_string(),
+        };
+        let db = Database::default();
+        let options = StatsOptions::default();
+        let mut task = DailyNoteTask::new(&config, dir.path(), at(2024, 5, 13, 9, 0))?;
+
+        assert!(task
+            .poll(&db, at(2024, 5, 13, 17, 59), &options, &BTreeMap::new())?
+            .is_none());
+        let written = task.poll(&db, at(2024, 5, 13, 18, 0), &options, &BTreeMap::new())?;
+        assert!(task
+            .poll(&db, at(2024, 5, 13, 18, 1), &options, &BTreeMap::new())?
+            .is_none());
+
+        let expected = dir.path().join("vault/2024-05-13.md");
+        assert_eq!(written, Some(expected.clone()));
+        assert_eq!(
+            fs::read_to_string(expected)?,
+            "- Focus: 0s (0 interruptions)\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_poll_writes_once_per_occurrence() -> Result<()> {
+        let dir = tempdir()?;
+        let config = WeeklyReportConfig {
+            path: "reports/week-{week_start}.md".to_string(),
+            weekday: "Sun".to_string(),
+            time: "21:00".to_string(),
+        };
+        let db = Database::default();
+        let options = StatsOptions::default();
+        let kinds = KindsConfig::default();
+        let mut task = WeeklyReportTask::new(&config, dir.path(), at(2024, 5, 13, 9, 0))?;
+
+        assert!(task
+            .poll(&db, at(2024, 5, 19, 20, 59), &options, &kinds)?
+            .is_none());
+
+        let written = task.poll(&db, at(2024, 5, 19, 21, 0), &options, &kinds)?;
+        let expected = dir.path().join("reports/week-2024-05-13.md");
+        assert_eq!(written, Some(expected.clone()));
+        assert!(expected.exists());
+
+        assert!(task
+            .poll(&db, at(2024, 5, 19, 21, 5), &options, &kinds)?
+            .is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use crate::config::DatabaseConfig;
+use crate::control::{self, Request};
+use crate::logs::Log;
+use crate::storage::quota_warning;
+use crate::system::{
+    active_space, input_event_count, power_source, presenting, screen_locked, screen_saver_running,
+    IdleDetector, IdleSample, SleepDetector, FROZEN_SECS,
+};
+use crate::tracker::{ResetScope, Tracker};
+use crate::update::take_restart_request;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::{Duration as StdDuration, Instant};
+
+/// How often the power source is checked.
+const POWER_POLL_SECS: i64 = 60;
+/// How often screen sharing is checked, with `Options::detect_presenting`.
+const PRESENTING_POLL_SECS: i64 = 10;
+/// How often the screen saver is checked. Finding it takes a process, unlike
+/// the lock check done on every sample.
+const SCREEN_SAVER_POLL_SECS: i64 = 10;
+/// How often the database size is checked against `Options::quota`.
+const QUOTA_POLL_SECS: i64 = 3600;
+/// How long the tracking thread waits for a command before doing its rounds.
+const POLL_MILLIS: u64 = 100;
+
+/// Why a session ended.
+#[derive(Debug, PartialEq)]
+pub enum SessionEnd {
+    Quit,
+    /// The session reached its end time and `exit_on_end` closed the dashboard.
+    Ended,
+    /// `neflo stop` asked the session to exit.
+    Stopped,
+    /// `self-update --restart` asked to continue the session in this binary.
+    Restart(PathBuf),
+}
+
+/// What the front end asks of the tracking thread.
+#[derive(Debug)]
+pub enum Command {
+    Reset(ResetScope),
+    Quit,
+}
+
+/// What the tracking thread tells the front end.
+#[derive(Debug)]
+pub enum Update {
+    /// The database changed; statistics need recomputing.
+    Changed,
+    /// A failed operation or a notice, to be logged rather than end the session.
+    Error(anyhow::Error),
+    /// Idle detection got stuck with no fallback (or recovered); nothing is
+    /// recorded while it is stuck.
+    IdleFrozen(bool),
+    /// Idle time was read.
+    IdleSampled(IdleSample),
+    /// The session has ended and closes at this time.
+    ExitAt(DateTime<Utc>),
+    End(SessionEnd),
+}
+
+/// How the tracking thread runs a session.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Poll for screen sharing so that presenting counts as focus.
+    pub detect_presenting: bool,
+    /// Count input events on every sample, for the intensity of focus.
+    pub track_intensity: bool,
+    /// Close the session this long after it ends.
+    pub exit_after: Option<Duration>,
+    /// Where state changes and errors are logged.
+    pub log: Option<Log>,
+    /// Warn once when the database outgrows these levels.
+    pub quota: Option<DatabaseConfig>,
+}
+
+/// Record a session until it ends or a `Command::Quit` arrives: sample idle
+/// time, tick, save and run scheduled tasks. Meant for its own thread; the
+/// tracker is only locked while it is being worked on, so a front end
+/// sharing it can skip frames instead of waiting on a slow save.
+pub fn run(
+    tracker: &Mutex<&mut Tracker>,
+    state_dir: &Path,
+    options: Options,
+    commands: Receiver<Command>,
+    updates: Sender<Update>,
+) {
+    let log = |message: String| {
+        if let Some(log) = &options.log {
+            if let Err(err) = log.write(Local::now(), &message) {
+                let _ = updates.send(Update::Error(
+                    err.context("Failed to write the session log"),
+                ));
+            }
+        }
+    };
+    let send = |update: Update| {
+        match &update {
+            Update::Error(err) => log(format!("error: {:#}", err)),
+            Update::End(end) => log(format!("session ended: {:?}", end)),
+            _ => {}
+        }
+        // The front end may already be gone while the session winds down
+        let _ = updates.send(update);
+    };
+    let report = |result: Result<()>| {
+        if let Err(err) = result {
+            send(Update::Error(err));
+        }
+    };
+    let mut last_power_poll: Option<DateTime<Utc>> = None;
+    let mut last_presenting_poll: Option<DateTime<Utc>> = None;
+    let mut last_screen_saver_poll: Option<DateTime<Utc>> = None;
+    let mut last_quota_poll: Option<DateTime<Utc>> = None;
+    let mut quota_warned = false;
+    let mut idle = IdleDetector::new();
+    let mut idle_frozen = false;
+    let mut sleep = SleepDetector::default();
+    let mut exit_at: Option<DateTime<Utc>> = None;
+    log("session started".to_string());
+    loop {
+        let command = match commands.recv_timeout(StdDuration::from_millis(POLL_MILLIS)) {
+            Ok(command) => Some(command),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return log("session ended: Quit".to_string()),
+        };
+        let mut guard = tracker.lock().unwrap();
+        let tracker: &mut Tracker = &mut guard;
+        match command {
+            Some(Command::Quit) => return log("session ended: Quit".to_string()),
+            Some(Command::Reset(scope)) => {
+                report(tracker.reset(scope, tracker.now()).map(|_| ()));
+                send(Update::Changed);
+            }
+            None => {}
+        }
+
+        match take_restart_request(state_dir) {
+            Ok(Some(exe)) => return send(Update::End(SessionEnd::Restart(exe))),
+            Ok(None) => {}
+            Err(err) => send(Update::Error(err)),
+        }
+        match control::take(state_dir) {
+            Ok(Some(Request::Stop)) => return send(Update::End(SessionEnd::Stopped)),
+            Ok(Some(Request::Reset(scope))) => {
+                report(tracker.reset(scope, tracker.now()).map(|_| ()));
+                send(Update::Changed);
+            }
+            Ok(None) => {}
+            Err(err) => send(Update::Error(err)),
+        }
+
+        let now = tracker.now();
+        if last_power_poll.is_none_or(|t| now - t >= Duration::seconds(POWER_POLL_SECS)) {
+            last_power_poll = Some(now);
+            tracker.power = power_source();
+        }
+        if options.detect_presenting
+            && last_presenting_poll
+                .is_none_or(|t| now - t >= Duration::seconds(PRESENTING_POLL_SECS))
+        {
+            last_presenting_poll = Some(now);
+            tracker.presenting = presenting();
+        }
+
+        if let (Some(quota), false) = (&options.quota, quota_warned) {
+            if last_quota_poll.is_none_or(|t| now - t >= Duration::seconds(QUOTA_POLL_SECS)) {
+                last_quota_poll = Some(now);
+                let bytes = tracker.storage.size();
+                if let Some(warning) = quota_warning(quota, bytes, tracker.db.intervals.len()) {
+                    // Once a session is enough
+                    quota_warned = true;
+                    send(Update::Error(anyhow::anyhow!(warning)));
+                }
+            }
+        }
+        report(tracker.run_scheduled_tasks(now));
+        if tracker.should_stop(now) {
+            if !tracker.session_ended_saved {
+                report(tracker.release_blocker());
+                // Retried on the next iteration if the save fails
+                let result = tracker.storage.save(&tracker.db);
+                tracker.session_ended_saved = result.is_ok();
+                report(result);
+            }
+            if tracker.session_ended_saved {
+                if let Some(after) = options.exit_after {
+                    let at = *exit_at.get_or_insert_with(|| {
+                        send(Update::ExitAt(now + after));
+                        now + after
+                    });
+                    if now >= at {
+                        return send(Update::End(SessionEnd::Ended));
+                    }
+                }
+            }
+        } else if tracker.should_track(now)
+            && tracker
+                .last_tick
+                .is_none_or(|t| now - t >= tracker.tick_interval())
+        {
+            tracker.slept += sleep.slept(Instant::now(), now);
+            if options.track_intensity {
+                tracker.input_events = input_event_count();
+            }
+            let locked = screen_locked();
+            if locked != tracker.screen_locked {
+                tracker.screen_locked = locked;
+                log(format!("screen locked: {}", locked));
+            }
+            if last_screen_saver_poll
+                .is_none_or(|t| now - t >= Duration::seconds(SCREEN_SAVER_POLL_SECS))
+            {
+                last_screen_saver_poll = Some(now);
+                let saver = screen_saver_running();
+                if saver != tracker.screen_saver {
+                    tracker.screen_saver = saver;
+                    log(format!("screen saver: {}", saver));
+                }
+            }
+            // Idle time read behind the lock screen or in another user's
+            // session is meaningless and can look frozen, so it is not read
+            let idle_time = if tracker.screen_inactive() {
+                StdDuration::ZERO
+            } else {
+                let source = idle.source();
+                let idle_time = idle.idle_time();
+                if idle.source() != source {
+                    send(Update::Error(anyhow::anyhow!(
+                        "{} returned the same idle time for too long; idle time now comes from {}",
+                        source.name(),
+                        idle.source().name()
+                    )));
+                }
+                if idle.is_frozen() != idle_frozen {
+                    idle_frozen = idle.is_frozen();
+                    if idle_frozen {
+                        send(Update::Error(anyhow::anyhow!(
+                            "{} returned the same idle time for {}s and there is no fallback; recording paused",
+                            idle.source().name(),
+                            FROZEN_SECS
+                        )));
+                    }
+                    send(Update::IdleFrozen(idle_frozen));
+                }
+                send(Update::IdleSampled(IdleSample {
+                    source: idle.source(),
+                    idle: idle_time,
+                    at: now,
+                }));
+                idle_time
+            };
+            // A frozen reading would record endless focus; the gap becomes away time
+            if !idle_frozen || tracker.screen_inactive() {
+                tracker.space = active_space();
+                let before = tracker.last_kind_seen;
+                report(tracker.tick(idle_time, now));
+                if let Some(kind) = tracker.last_kind_seen.filter(|kind| before != Some(*kind)) {
+                    log(format!("state: {:?}", kind).to_lowercase());
+                }
+                send(Update::Changed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+    use std::sync::mpsc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_commands_and_stop_request_reach_the_session() -> Result<()> {
+        let dir = tempdir()?;
+        let mut tracker = Tracker::new(Storage::in_memory(), 5, None, None, None)?;
+        let (commands, command_rx) = mpsc::channel();
+        let (update_tx, updates) = mpsc::channel();
+        commands.send(Command::Reset(ResetScope::All))?;
+        control::send(dir.path(), Request::Stop)?;
+
+        run(
+            &Mutex::new(&mut tracker),
+            dir.path(),
+            Options::default(),
+            command_rx,
+            update_tx,
+        );
+
+        let updates: Vec<Update> = updates.try_iter().collect();
+        assert!(updates.iter().any(|u| matches!(u, Update::Changed)));
+        assert!(matches!(
+            updates.last(),
+            Some(Update::End(SessionEnd::Stopped))
+        ));
+        Ok(())
+    }
+}
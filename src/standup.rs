@@ -0,0 +1,94 @@
+use crate::models::Database;
+use crate::stats::FocusBlock;
+use crate::utils::format_minutes;
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+
+/// `date`'s focus blocks, markers and journal notes as a compact bullet list,
+/// in chronological order, ready to paste into a standup chat.
+pub fn render(db: &Database, date: NaiveDate, blocks: &[FocusBlock]) -> String {
+    let total = blocks
+        .iter()
+        .fold(Duration::zero(), |sum, b| sum + (b.end - b.start));
+    let mut entries: Vec<(DateTime<Utc>, String)> = blocks
+        .iter()
+        .map(|block| {
+            let mut line = format!(
+                "- {}-{} {}",
+                clock_time(block.start),
+                clock_time(block.end),
+                format_minutes(block.end - block.start)
+            );
+            if !block.tags.is_empty() {
+                line.push_str(&format!(" ({})", block.tags.join(", ")));
+            }
+            (block.start, line)
+        })
+        .collect();
+    entries.extend(
+        db.markers
+            .iter()
+            .filter(|m| m.at.with_timezone(&Local).date_naive() == date)
+            .map(|m| (m.at, format!("- {} {}", clock_time(m.at), m.text))),
+    );
+    entries.sort_by_key(|(at, _)| *at);
+
+    let mut lines = vec![format!(
+        "{}: {} focus",
+        date.format("%A %Y-%m-%d"),
+        format_minutes(total)
+    )];
+    lines.extend(entries.into_iter().map(|(_, line)| line));
+    if let Some(note) = db.journal.get(&date) {
+        lines.extend(note.lines().map(|line| format!("- Note: {}", line)));
+    }
+    lines.join("\n")
+}
+
+fn clock_time(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local).format("%H:%M").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Marker;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_render_in_chronological_order() {
+        let at = |h: u32, m: u32| {
+            Local
+                .with_ymd_and_hms(2024, 5, 10, h, m, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let mut db = Database::default();
+        db.add_marker(Marker {
+            at: at(10, 50),
+            text: "prod incident".to_string(),
+        });
+        db.add_journal_entry(date, "shipped the importer");
+        let blocks = [
+            FocusBlock {
+                start: at(9, 5),
+                end: at(10, 40),
+                tags: vec!["coding".to_string(), "comms".to_string()],
+            },
+            FocusBlock {
+                start: at(11, 0),
+                end: at(11, 30),
+                tags: Vec::new(),
+            },
+        ];
+
+        assert_eq!(
+            render(&db, date, &blocks),
+            "Friday 2024-05-10: 2h 5m focus\n\
+             - 09:05-10:40 1h 35m (coding, comms)\n\
+             - 10:50 prod incident\n\
+             - 11:00-11:30 30m\n\
+             - Note: shipped the importer"
+        );
+    }
+}
@@ -1,21 +1,131 @@
-use crate::models::{Database, IntervalType};
-use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+use crate::clock::Clock;
+use crate::models::{Database, Interval, IntervalType, PowerSource};
+use crate::period::Period;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 
+/// How idle time is combined when several devices recorded overlapping intervals.
+/// Focus time is always the union across devices.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleMerge {
+    /// Idle only counts while no device was in focus.
+    #[default]
+    Intersection,
+    /// Idle counts whenever any device was idle, even if another was in focus.
+    Union,
+}
+
+/// Settings that change how statistics are computed.
+#[derive(Debug, Clone, Copy)]
+pub struct StatsOptions {
+    pub idle_merge: IdleMerge,
+    /// Focus intervals at least this long count as deep work.
+    pub deep_work_min: Duration,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self {
+            idle_merge: IdleMerge::default(),
+            deep_work_min: Duration::minutes(25),
+        }
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct DayStats {
     pub total_focus: Duration,
     pub total_idle: Duration,
+    /// Time behind the lock screen; neither idle nor an interruption.
+    pub total_locked: Duration,
     pub focus_sessions: u32,
     pub idle_sessions: u32,
+    /// Focus in intervals of at least `StatsOptions::deep_work_min`.
+    pub total_deep_work: Duration,
+    pub deep_work_sessions: u32,
+    /// Time from the scheduled start, or the day's first activity, to the
+    /// first deep-work block; `None` on days without deep work.
+    pub focus_onset: Option<Duration>,
+    /// Why the day was taken off, if it was.
+    pub off: Option<String>,
+    /// Input events of the focus intervals that counted them, and the
+    /// minutes of focus those cover.
+    pub input_events: f64,
+    pub intensity_minutes: f64,
 }
 
-#[derive(Default, Clone, Debug)]
+impl DayStats {
+    /// Keystrokes and clicks per minute of focus, on days they were counted.
+    pub fn intensity(&self) -> Option<f64> {
+        (self.intensity_minutes > 0.0).then(|| self.input_events / self.intensity_minutes)
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct SummaryStats {
     pub total_focus: Duration,
     pub total_idle: Duration,
+    pub total_locked: Duration,
     pub focus_count: u32,
     pub idle_count: u32,
+    pub total_deep_work: Duration,
+    pub deep_work_count: u32,
+}
+
+impl SummaryStats {
+    /// The totals of the days in `period`. Day and week figures are both
+    /// derived from `daily_stats` so they cannot disagree.
+    pub fn over(daily_stats: &BTreeMap<NaiveDate, DayStats>, period: Period) -> Self {
+        let mut summary = Self::default();
+        for day in daily_stats
+            .range(period.first()..=period.last())
+            .map(|(_, day)| day)
+        {
+            summary.total_focus += day.total_focus;
+            summary.total_idle += day.total_idle;
+            summary.total_locked += day.total_locked;
+            summary.focus_count += day.focus_sessions;
+            summary.idle_count += day.idle_sessions;
+            summary.total_deep_work += day.total_deep_work;
+            summary.deep_work_count += day.deep_work_sessions;
+        }
+        summary
+    }
+}
+
+/// What days are ranked by; the most comes first.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum DaySort {
+    Focus,
+    Idle,
+    Interruptions,
+}
+
+/// The recorded days of `period`, in date order or ranked by `sort`, and cut
+/// to the first `top`. Days that rank equal stay in date order.
+pub fn ranked_days(
+    daily_stats: &BTreeMap<NaiveDate, DayStats>,
+    period: Period,
+    sort: Option<DaySort>,
+    top: Option<usize>,
+) -> Vec<(NaiveDate, &DayStats)> {
+    let mut days: Vec<(NaiveDate, &DayStats)> = daily_stats
+        .range(period.first()..=period.last())
+        .map(|(date, day)| (*date, day))
+        .collect();
+    match sort {
+        Some(DaySort::Focus) => days.sort_by_key(|(_, day)| std::cmp::Reverse(day.total_focus)),
+        Some(DaySort::Idle) => days.sort_by_key(|(_, day)| std::cmp::Reverse(day.total_idle)),
+        Some(DaySort::Interruptions) => {
+            days.sort_by_key(|(_, day)| std::cmp::Reverse(day.idle_sessions))
+        }
+        None => {}
+    }
+    days.truncate(top.unwrap_or(days.len()));
+    days
 }
 
 pub struct Stats {
@@ -23,25 +133,54 @@ pub struct Stats {
     pub session_summary: SummaryStats,
     pub today_summary: SummaryStats,
     pub week_summary: SummaryStats,
+    /// Number of idle intervals (interruptions) starting in each local hour of the day.
+    pub hourly_interruptions: [u32; 24],
+    /// Focus time by the power source it started on (all recorded days); intervals
+    /// without power information are left out.
+    pub focus_on_battery: Duration,
+    pub focus_on_ac: Duration,
     pub today: NaiveDate,
     pub week_start: NaiveDate,
 }
 
-pub fn calculate_stats(db: &Database, run_start_time: Option<DateTime<Utc>>) -> Stats {
-    let now_local = Local::now();
-    let today = now_local.date_naive();
-
-    // Find the start of the current week (Monday)
-    let days_from_monday = now_local.weekday().num_days_from_monday();
-    let week_start = today - Duration::days(days_from_monday as i64);
-    let week_end = week_start + Duration::days(6);
+pub fn calculate_stats(
+    db: &Database,
+    run_start_time: Option<DateTime<Utc>>,
+    options: &StatsOptions,
+    clock: &dyn Clock,
+) -> Stats {
+    let today = clock.local_now().date_naive();
 
     let mut daily_stats: BTreeMap<NaiveDate, DayStats> = BTreeMap::new();
     let mut session_summary = SummaryStats::default();
-    let mut today_summary = SummaryStats::default();
-    let mut week_summary = SummaryStats::default();
+    let mut hourly_interruptions = [0u32; 24];
+    let mut focus_on_battery = Duration::zero();
+    let mut focus_on_ac = Duration::zero();
+    let mut first_activity: BTreeMap<NaiveDate, DateTime<Utc>> = BTreeMap::new();
+    let mut first_deep_work: BTreeMap<NaiveDate, DateTime<Utc>> = BTreeMap::new();
 
-    for interval in &db.intervals {
+    // Per device: merged timelines no longer know which machine was docked,
+    // or how much input it had
+    for interval in db
+        .intervals
+        .iter()
+        .filter(|i| i.kind == IntervalType::Focus)
+    {
+        match interval.power {
+            Some(PowerSource::Battery) => focus_on_battery += interval.end - interval.start,
+            Some(PowerSource::Ac) => focus_on_ac += interval.end - interval.start,
+            None => {}
+        }
+        if let Some(intensity) = interval.intensity {
+            let date = interval.start.with_timezone(&Local).date_naive();
+            let minutes = (interval.end - interval.start).num_seconds() as f64 / 60.0;
+            let stats = daily_stats.entry(date).or_default();
+            stats.input_events += intensity as f64 * minutes;
+            stats.intensity_minutes += minutes;
+        }
+    }
+
+    for interval in timeline(db, options.idle_merge).iter() {
         let start_local = interval.start.with_timezone(&Local);
         let date = start_local.date_naive();
         let duration = interval.end - interval.start;
@@ -50,59 +189,698 @@ pub fn calculate_stats(db: &Database, run_start_time: Option<DateTime<Utc>>) ->
         }
 
         let stats = daily_stats.entry(date).or_default();
+        if interval.kind != IntervalType::Away {
+            keep_earliest(&mut first_activity, date, interval.start);
+        }
         match interval.kind {
             IntervalType::Focus => {
                 stats.total_focus += duration;
                 stats.focus_sessions += 1;
+                if duration >= options.deep_work_min {
+                    stats.total_deep_work += duration;
+                    stats.deep_work_sessions += 1;
+                    keep_earliest(&mut first_deep_work, date, interval.start);
+                }
             }
             IntervalType::Idle => {
                 stats.total_idle += duration;
                 stats.idle_sessions += 1;
+                hourly_interruptions[start_local.hour() as usize] += 1;
             }
+            IntervalType::Locked => stats.total_locked += duration,
+            IntervalType::Away => {}
         }
 
         if let Some(run_start) = run_start_time {
             if interval.start >= run_start {
-                update_summary(&mut session_summary, interval.kind, duration);
+                update_summary(&mut session_summary, interval.kind, duration, options);
             }
         }
+    }
 
-        if date == today {
-            update_summary(&mut today_summary, interval.kind, duration);
-        }
+    for (date, reason) in &db.days_off {
+        daily_stats.entry(*date).or_default().off = Some(reason.clone());
+    }
 
-        if date >= week_start && date <= week_end {
-            update_summary(&mut week_summary, interval.kind, duration);
+    for (date, deep_work_start) in first_deep_work {
+        let reference = scheduled_start(db, date).or(first_activity.get(&date).copied());
+        if let (Some(stats), Some(reference)) = (daily_stats.get_mut(&date), reference) {
+            stats.focus_onset = Some((deep_work_start - reference).max(Duration::zero()));
         }
     }
 
     Stats {
+        today_summary: SummaryStats::over(&daily_stats, Period::Day(today)),
+        week_summary: SummaryStats::over(&daily_stats, Period::Week(today)),
         daily_stats,
         session_summary,
-        today_summary,
-        week_summary,
+        hourly_interruptions,
+        focus_on_battery,
+        focus_on_ac,
         today,
-        week_start,
+        week_start: Period::Week(today).first(),
+    }
+}
+
+/// The database's intervals, merged across devices when more than one recorded data.
+fn timeline(db: &Database, idle_merge: IdleMerge) -> Cow<'_, [Interval]> {
+    if db.device_count() > 1 {
+        Cow::Owned(merge_devices(&db.intervals, idle_merge))
+    } else {
+        Cow::Borrowed(&db.intervals)
+    }
+}
+
+/// Combine intervals recorded by several devices into a single timeline:
+/// focus is the union of all devices' focus, idle follows `idle_merge`, locked
+/// time counts where no device was in use or idle, and touching intervals of
+/// the same kind are coalesced. Away time is dropped.
+pub fn merge_devices(intervals: &[Interval], idle_merge: IdleMerge) -> Vec<Interval> {
+    // (time, focus delta, idle delta, locked delta)
+    let mut events: Vec<(DateTime<Utc>, i32, i32, i32)> = Vec::new();
+    for interval in intervals.iter().filter(|i| i.end > i.start) {
+        match interval.kind {
+            IntervalType::Focus => {
+                events.push((interval.start, 1, 0, 0));
+                events.push((interval.end, -1, 0, 0));
+            }
+            IntervalType::Idle => {
+                events.push((interval.start, 0, 1, 0));
+                events.push((interval.end, 0, -1, 0));
+            }
+            IntervalType::Locked => {
+                events.push((interval.start, 0, 0, 1));
+                events.push((interval.end, 0, 0, -1));
+            }
+            IntervalType::Away => {}
+        }
+    }
+    events.sort_by_key(|e| e.0);
+
+    let mut merged: Vec<Interval> = Vec::new();
+    let mut push = |kind: IntervalType, start: DateTime<Utc>, end: DateTime<Utc>| {
+        if let Some(last) = merged.iter_mut().rev().find(|i| i.kind == kind) {
+            if last.end == start {
+                last.end = end;
+                return;
+            }
+        }
+        merged.push(Interval {
+            start,
+            end,
+            ..Interval::new_at(kind, start)
+        });
+    };
+
+    let (mut focus_active, mut idle_active, mut locked_active) = (0, 0, 0);
+    for (idx, (time, focus_delta, idle_delta, locked_delta)) in events.iter().enumerate() {
+        focus_active += focus_delta;
+        idle_active += idle_delta;
+        locked_active += locked_delta;
+        let Some((next, _, _, _)) = events.get(idx + 1) else {
+            break;
+        };
+        if next == time {
+            continue;
+        }
+        if focus_active > 0 {
+            push(IntervalType::Focus, *time, *next);
+        }
+        if idle_active > 0 && (focus_active == 0 || idle_merge == IdleMerge::Union) {
+            push(IntervalType::Idle, *time, *next);
+        }
+        if locked_active > 0 && focus_active == 0 && idle_active == 0 {
+            push(IntervalType::Locked, *time, *next);
+        }
+    }
+
+    merged.sort_by_key(|i| i.start);
+    merged
+}
+
+/// Number of half-hour slots in a day used by [`FocusProfile`].
+pub const SLOTS_PER_DAY: usize = 48;
+const SLOT_SECS: i64 = 1800;
+
+/// Focus time per half-hour slot of the local day, summed over a range of days.
+#[derive(Clone, Debug)]
+pub struct FocusProfile {
+    pub focus_secs: [i64; SLOTS_PER_DAY],
+    /// Number of distinct local days that contributed any data.
+    pub days: u32,
+}
+
+impl FocusProfile {
+    /// Average fraction of `slot` spent in focus across the contributing days.
+    pub fn focus_ratio(&self, slot: usize) -> f64 {
+        if self.days == 0 {
+            return 0.0;
+        }
+        self.focus_secs[slot] as f64 / (self.days as i64 * SLOT_SECS) as f64
+    }
+
+    /// Average fraction of the local `hour` (0-23) spent in focus.
+    pub fn hour_ratio(&self, hour: usize) -> f64 {
+        (self.focus_ratio(hour * 2) + self.focus_ratio(hour * 2 + 1)) / 2.0
+    }
+}
+
+/// Build a half-hour focus profile from the intervals between `since` and
+/// `until`, splitting intervals that cross slot boundaries. Idle time counts
+/// towards the number of observed days but not towards focus.
+pub fn focus_profile(db: &Database, since: DateTime<Utc>, until: DateTime<Utc>) -> FocusProfile {
+    let mut focus_secs = [0i64; SLOTS_PER_DAY];
+    let mut days = std::collections::BTreeSet::new();
+
+    let intervals = timeline(db, IdleMerge::Intersection);
+    for interval in intervals
+        .iter()
+        .filter(|i| i.end > since && i.start < until)
+    {
+        if interval.kind == IntervalType::Away {
+            continue;
+        }
+        for (date, slot, secs) in slot_chunks(interval.start.max(since), interval.end.min(until)) {
+            days.insert(date);
+            if interval.kind == IntervalType::Focus {
+                focus_secs[slot] += secs;
+            }
+        }
+    }
+
+    FocusProfile {
+        focus_secs,
+        days: days.len() as u32,
+    }
+}
+
+/// Split `from..to` at half-hour slot boundaries into (local date, slot, seconds).
+fn slot_chunks(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<(NaiveDate, usize, i64)> {
+    let mut chunks = Vec::new();
+    let mut cursor = from;
+    while cursor < to {
+        let local = cursor.with_timezone(&Local);
+        let secs_into_day = local.num_seconds_from_midnight() as i64;
+        let slot_end = cursor + Duration::seconds(SLOT_SECS - secs_into_day % SLOT_SECS);
+        let chunk_end = slot_end.min(to);
+        chunks.push((
+            local.date_naive(),
+            (secs_into_day / SLOT_SECS) as usize,
+            (chunk_end - cursor).num_seconds(),
+        ));
+        cursor = chunk_end;
+    }
+    chunks
+}
+
+/// Today's focus by `until` if the rest of the day goes like the same half
+/// hours did on average over the `lookback_days` before today. `None` with
+/// fewer than `min_days` days of history.
+pub fn forecast_focus(
+    db: &Database,
+    focus_so_far: Duration,
+    now: DateTime<Utc>,
+    until: DateTime<Utc>,
+    lookback_days: i64,
+    min_days: u32,
+) -> Option<Duration> {
+    let midnight = local_midnight(now.with_timezone(&Local).date_naive())?;
+    let profile = focus_profile(db, midnight - Duration::days(lookback_days), midnight);
+    if profile.days < min_days {
+        return None;
     }
+    let expected: f64 = slot_chunks(now, until)
+        .into_iter()
+        .map(|(_, slot, secs)| profile.focus_ratio(slot) * secs as f64)
+        .sum();
+    Some(focus_so_far + Duration::seconds(expected as i64))
+}
+
+/// When today's focus reaches a target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FocusEta {
+    Reached,
+    At(DateTime<Utc>),
+    /// No focus today yet, so there is no pace to extrapolate.
+    Unknown,
+}
+
+/// When today's focus will reach `target` if it keeps growing at today's
+/// pace: the focus so far over the time since the day's first activity.
+pub fn focus_eta(
+    db: &Database,
+    focus_so_far: Duration,
+    target: Duration,
+    now: DateTime<Utc>,
+) -> FocusEta {
+    if focus_so_far >= target {
+        return FocusEta::Reached;
+    }
+    let Some(midnight) = local_midnight(now.with_timezone(&Local).date_naive()) else {
+        return FocusEta::Unknown;
+    };
+    let day_start = db
+        .intervals
+        .iter()
+        .filter(|i| i.kind != IntervalType::Away && i.end > midnight && i.start < now)
+        .map(|i| i.start.max(midnight))
+        .min();
+    match day_start {
+        Some(start) if focus_so_far > Duration::zero() && now > start => {
+            let elapsed = (now - start).num_seconds() as f64;
+            let pace = focus_so_far.num_seconds() as f64 / elapsed;
+            let remaining = (target - focus_so_far).num_seconds() as f64;
+            FocusEta::At(now + Duration::seconds((remaining / pace).ceil() as i64))
+        }
+        _ => FocusEta::Unknown,
+    }
+}
+
+/// Split `from..to` into `cells` equal cells and report the kind recorded for
+/// most of each one, or `None` where less than half the cell was recorded.
+pub fn coverage(
+    db: &Database,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    cells: usize,
+    options: &StatsOptions,
+) -> Vec<Option<IntervalType>> {
+    cover(timeline(db, options.idle_merge).iter(), from, to, cells)
+}
+
+/// Like [`coverage`], counting only borderline intervals: a cell is `Some`
+/// where borderline time of one kind fills most of it.
+pub fn borderline_coverage(
+    db: &Database,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    cells: usize,
+    options: &StatsOptions,
+) -> Vec<Option<IntervalType>> {
+    let intervals = timeline(db, options.idle_merge);
+    cover(
+        intervals.iter().filter(|i| i.is_borderline()),
+        from,
+        to,
+        cells,
+    )
 }
 
-fn update_summary(summary: &mut SummaryStats, kind: IntervalType, duration: Duration) {
+fn cover<'a>(
+    intervals: impl Iterator<Item = &'a Interval>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    cells: usize,
+) -> Vec<Option<IntervalType>> {
+    if cells == 0 || to <= from {
+        return Vec::new();
+    }
+    let cell_secs = (to - from).num_seconds() as f64 / cells as f64;
+    // Seconds per cell for each kind, indexed like IntervalType::ALL
+    let mut secs = vec![[0f64; IntervalType::ALL.len()]; cells];
+
+    for interval in intervals.filter(|i| i.end > from && i.start < to) {
+        let kind_idx = IntervalType::ALL
+            .iter()
+            .position(|k| *k == interval.kind)
+            .unwrap_or_default();
+        let start = (interval.start.max(from) - from).num_seconds() as f64;
+        let end = (interval.end.min(to) - from).num_seconds() as f64;
+        let first = (start / cell_secs) as usize;
+        let last = ((end / cell_secs).ceil() as usize).min(cells);
+        for (cell, cell_secs_by_kind) in secs.iter_mut().enumerate().take(last).skip(first) {
+            let cell_start = cell as f64 * cell_secs;
+            let overlap = end.min(cell_start + cell_secs) - start.max(cell_start);
+            if overlap > 0.0 {
+                cell_secs_by_kind[kind_idx] += overlap;
+            }
+        }
+    }
+
+    secs.iter()
+        .map(|by_kind| {
+            let (idx, best) =
+                by_kind.iter().enumerate().fold(
+                    (0, 0f64),
+                    |acc, (i, s)| if *s > acc.1 { (i, *s) } else { acc },
+                );
+            let recorded: f64 = by_kind.iter().sum();
+            (recorded * 2.0 >= cell_secs && best > 0.0).then(|| IntervalType::ALL[idx])
+        })
+        .collect()
+}
+
+/// Focus time after `since`, split by the desktop space that was active.
+/// Focus while no space was recorded is left out.
+pub fn focus_by_space(
+    db: &Database,
+    since: DateTime<Utc>,
+    options: &StatsOptions,
+) -> BTreeMap<u64, Duration> {
+    focus_by_space_between(db, since, DateTime::<Utc>::MAX_UTC, options)
+}
+
+fn focus_by_space_between(
+    db: &Database,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    options: &StatsOptions,
+) -> BTreeMap<u64, Duration> {
+    let mut by_space = BTreeMap::new();
+    let intervals = timeline(db, options.idle_merge);
+    let spans: Vec<_> = db
+        .spaces
+        .iter()
+        .filter(|s| s.end > since && s.start < until)
+        .collect();
+    for interval in intervals
+        .iter()
+        .filter(|i| i.kind == IntervalType::Focus && i.end > since && i.start < until)
+    {
+        for span in &spans {
+            let start = interval.start.max(span.start).max(since);
+            let end = interval.end.min(span.end).min(until);
+            if end > start {
+                *by_space.entry(span.space).or_insert_with(Duration::zero) += end - start;
+            }
+        }
+    }
+    by_space
+}
+
+/// Focus time after `since` per space name from `space_names`, the tags that
+/// weekly targets refer to. Spaces without a name are left out.
+pub fn focus_by_tag(
+    db: &Database,
+    since: DateTime<Utc>,
+    options: &StatsOptions,
+    space_names: &BTreeMap<String, String>,
+) -> BTreeMap<String, Duration> {
+    let mut by_tag = BTreeMap::new();
+    for (space, focus) in focus_by_space(db, since, options) {
+        if let Some(name) = space_names.get(&space.to_string()) {
+            *by_tag.entry(name.clone()).or_insert_with(Duration::zero) += focus;
+        }
+    }
+    by_tag
+}
+
+/// The name from `space_names` with the most focus on local `date`, if any
+/// named space saw focus that day.
+pub fn top_tag(
+    db: &Database,
+    date: NaiveDate,
+    options: &StatsOptions,
+    space_names: &BTreeMap<String, String>,
+) -> Option<String> {
+    let day = Period::Day(date);
+    let mut by_tag: BTreeMap<&String, Duration> = BTreeMap::new();
+    for (space, focus) in focus_by_space_between(db, day.start(), day.end(), options) {
+        if let Some(name) = space_names.get(&space.to_string()) {
+            *by_tag.entry(name).or_insert_with(Duration::zero) += focus;
+        }
+    }
+    by_tag
+        .into_iter()
+        .max_by_key(|(_, focus)| *focus)
+        .map(|(name, _)| name.clone())
+}
+
+/// An uninterrupted stretch of focus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusBlock {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Names from `space_names` of the spaces used during the block, most used first.
+    pub tags: Vec<String>,
+}
+
+/// Focus intervals at least `min` long that started on local `date`, oldest first.
+pub fn focus_blocks(
+    db: &Database,
+    date: NaiveDate,
+    min: Duration,
+    options: &StatsOptions,
+    space_names: &BTreeMap<String, String>,
+) -> Vec<FocusBlock> {
+    let mut blocks: Vec<FocusBlock> = timeline(db, options.idle_merge)
+        .iter()
+        .filter(|i| {
+            i.kind == IntervalType::Focus
+                && i.start.with_timezone(&Local).date_naive() == date
+                && i.end - i.start >= min
+        })
+        .map(|i| {
+            let mut by_tag: BTreeMap<&String, Duration> = BTreeMap::new();
+            for span in &db.spaces {
+                let overlap = i.end.min(span.end) - i.start.max(span.start);
+                if let Some(name) = space_names.get(&span.space.to_string()) {
+                    if overlap > Duration::zero() {
+                        *by_tag.entry(name).or_insert_with(Duration::zero) += overlap;
+                    }
+                }
+            }
+            let mut tags: Vec<_> = by_tag.into_iter().collect();
+            tags.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+            FocusBlock {
+                start: i.start,
+                end: i.end,
+                tags: tags.into_iter().map(|(name, _)| name.clone()).collect(),
+            }
+        })
+        .collect();
+    blocks.sort_by_key(|b| b.start);
+    blocks
+}
+
+/// The latest local day before `today` with any focus recorded, e.g. Friday
+/// when asked on a Monday.
+pub fn last_active_day(db: &Database, today: NaiveDate) -> Option<NaiveDate> {
+    db.intervals
+        .iter()
+        .filter(|i| i.kind == IntervalType::Focus && i.end > i.start)
+        .map(|i| i.start.with_timezone(&Local).date_naive())
+        .filter(|date| *date < today)
+        .max()
+}
+
+fn keep_earliest(
+    earliest: &mut BTreeMap<NaiveDate, DateTime<Utc>>,
+    date: NaiveDate,
+    at: DateTime<Utc>,
+) {
+    let entry = earliest.entry(date).or_insert(at);
+    *entry = (*entry).min(at);
+}
+
+/// The `start_time` of the first session begun on `date` that had one.
+fn scheduled_start(db: &Database, date: NaiveDate) -> Option<DateTime<Utc>> {
+    db.sessions
+        .iter()
+        .filter(|s| s.start.with_timezone(&Local).date_naive() == date)
+        .find_map(|s| s.start_time)
+        .and_then(|time| Local.from_local_datetime(&date.and_time(time)).earliest())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Focus as a share of the scheduled working time over the days from `from`
+/// to `to`. Only days with recorded activity that were not taken off count,
+/// each as one `workday`.
+pub fn utilization(
+    daily_stats: &BTreeMap<NaiveDate, DayStats>,
+    from: NaiveDate,
+    to: NaiveDate,
+    workday: Duration,
+) -> Option<f64> {
+    let worked: Vec<_> = daily_stats
+        .range(from..=to)
+        .map(|(_, stats)| stats)
+        .filter(|stats| {
+            stats.off.is_none() && stats.total_focus + stats.total_idle > Duration::zero()
+        })
+        .collect();
+    if worked.is_empty() || workday <= Duration::zero() {
+        return None;
+    }
+    let focus: i64 = worked.iter().map(|s| s.total_focus.num_seconds()).sum();
+    Some(focus as f64 / (workday.num_seconds() * worked.len() as i64) as f64)
+}
+
+/// Mean focus onset over the working days from `from` to `to` that had deep work.
+pub fn average_focus_onset(
+    daily_stats: &BTreeMap<NaiveDate, DayStats>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Option<Duration> {
+    let onsets: Vec<Duration> = daily_stats
+        .range(from..=to)
+        .filter(|(_, stats)| stats.off.is_none())
+        .filter_map(|(_, stats)| stats.focus_onset)
+        .collect();
+    let total = onsets.iter().fold(Duration::zero(), |sum, d| sum + *d);
+    (!onsets.is_empty()).then(|| total / onsets.len() as i32)
+}
+
+/// Runs of consecutive days on which focus reached a daily goal.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Streaks {
+    /// Ending today, or yesterday while today's goal is still open.
+    pub current: u32,
+    pub longest: u32,
+}
+
+/// Goal streaks over the days from `from` to `to`, the latter being today.
+/// Days off are skipped rather than breaking a streak.
+pub fn goal_streaks(
+    daily_stats: &BTreeMap<NaiveDate, DayStats>,
+    from: NaiveDate,
+    to: NaiveDate,
+    goal: Duration,
+) -> Streaks {
+    let met = |date: &NaiveDate| {
+        daily_stats
+            .get(date)
+            .is_some_and(|stats| stats.total_focus >= goal)
+    };
+    let mut streaks = Streaks::default();
+    let mut run = 0;
+    for date in from.iter_days().take_while(|date| *date <= to) {
+        if daily_stats
+            .get(&date)
+            .is_some_and(|stats| stats.off.is_some())
+        {
+            // Days off neither extend nor break a streak
+            continue;
+        }
+        if met(&date) {
+            run += 1;
+            streaks.longest = streaks.longest.max(run);
+        } else if date < to {
+            run = 0;
+        }
+    }
+    streaks.current = run;
+    streaks
+}
+
+/// Focus recorded by `device_id` since `since`, which is when a session
+/// started for its goal.
+pub fn session_focus(db: &Database, since: DateTime<Utc>, device_id: Option<&str>) -> Duration {
+    db.intervals
+        .iter()
+        .filter(|i| {
+            i.kind == IntervalType::Focus && i.end > since && i.device_id.as_deref() == device_id
+        })
+        .map(|i| i.end - i.start.max(since))
+        .sum()
+}
+
+/// Local midnight at the start of `date`, as UTC.
+pub fn local_midnight(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrendDirection {
+    Up,
+    Down,
+    Flat,
+}
+
+/// Daily focus over a rolling window of completed days.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FocusTrend {
+    /// Mean focus per day; days without data count as zero, days off are left out.
+    pub average: Duration,
+    /// Standard deviation of the daily focus.
+    pub std_dev: Duration,
+    /// Second half of the window compared to the first.
+    pub direction: TrendDirection,
+}
+
+/// Averages change by less than this fraction count as flat.
+const TREND_THRESHOLD: f64 = 0.1;
+
+/// Rolling average of focus over the `days` days before `today`. The window
+/// starts no earlier than the first recorded day, so a new database is not
+/// dragged down by days before tracking began. `None` without any such day.
+pub fn focus_trend(
+    daily_stats: &BTreeMap<NaiveDate, DayStats>,
+    today: NaiveDate,
+    days: i64,
+) -> Option<FocusTrend> {
+    let first_day = *daily_stats.keys().next()?;
+    let from = (today - Duration::days(days)).max(first_day);
+    let focus: Vec<f64> = from
+        .iter_days()
+        .take_while(|date| *date < today)
+        .filter(|date| daily_stats.get(date).is_none_or(|s| s.off.is_none()))
+        .map(|date| {
+            daily_stats
+                .get(&date)
+                .map_or(0.0, |s| s.total_focus.num_seconds() as f64)
+        })
+        .collect();
+    if focus.is_empty() {
+        return None;
+    }
+
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len().max(1) as f64;
+    let average = mean(&focus);
+    let variance = focus.iter().map(|f| (f - average).powi(2)).sum::<f64>() / focus.len() as f64;
+    let (first, second) = focus.split_at(focus.len() / 2);
+    let direction = if first.is_empty() {
+        TrendDirection::Flat
+    } else {
+        let (before, after) = (mean(first), mean(second));
+        if after > before * (1.0 + TREND_THRESHOLD) {
+            TrendDirection::Up
+        } else if after < before * (1.0 - TREND_THRESHOLD) {
+            TrendDirection::Down
+        } else {
+            TrendDirection::Flat
+        }
+    };
+
+    Some(FocusTrend {
+        average: Duration::seconds(average as i64),
+        std_dev: Duration::seconds(variance.sqrt() as i64),
+        direction,
+    })
+}
+
+fn update_summary(
+    summary: &mut SummaryStats,
+    kind: IntervalType,
+    duration: Duration,
+    options: &StatsOptions,
+) {
     match kind {
         IntervalType::Focus => {
             summary.total_focus += duration;
             summary.focus_count += 1;
+            if duration >= options.deep_work_min {
+                summary.total_deep_work += duration;
+                summary.deep_work_count += 1;
+            }
         }
         IntervalType::Idle => {
             summary.total_idle += duration;
             summary.idle_count += 1;
         }
+        IntervalType::Locked => summary.total_locked += duration,
+        IntervalType::Away => {}
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{Interval, IntervalType};
+    use crate::clock::{FixedClock, SystemClock};
+    use crate::models::{Interval, IntervalType, SessionRecord, SpaceSpan};
     use chrono::TimeZone;
 
     #[test]
@@ -116,16 +894,25 @@ mod tests {
                     start: base_time,
                     end: base_time + Duration::minutes(10),
                     kind: IntervalType::Focus,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
                 },
                 Interval {
                     start: base_time + Duration::minutes(20),
                     end: base_time + Duration::minutes(30),
                     kind: IntervalType::Focus,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
                 },
             ],
+            ..Default::default()
         };
 
-        let stats = calculate_stats(&db, Some(run_start));
+        let stats = calculate_stats(&db, Some(run_start), &StatsOptions::default(), &SystemClock);
 
         // Session should only have the second interval
         assert_eq!(stats.session_summary.focus_count, 1);
@@ -140,13 +927,804 @@ mod tests {
                 start: base_time,
                 end: base_time + Duration::seconds(1),
                 kind: IntervalType::Focus,
+                device_id: None,
+                power: None,
+                confidence: None,
+                intensity: None,
             }],
+            ..Default::default()
         };
 
         // Simulating a tick updating the end time
         db.intervals[0].end = base_time + Duration::seconds(10);
 
-        let stats = calculate_stats(&db, Some(base_time));
+        let stats = calculate_stats(&db, Some(base_time), &StatsOptions::default(), &SystemClock);
         assert_eq!(stats.session_summary.total_focus, Duration::seconds(10));
     }
+
+    fn device_interval(
+        device: &str,
+        kind: IntervalType,
+        start: DateTime<Utc>,
+        mins: i64,
+    ) -> Interval {
+        Interval {
+            start,
+            end: start + Duration::minutes(mins),
+            kind,
+            device_id: Some(device.to_string()),
+            power: None,
+            confidence: None,
+            intensity: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_devices_focus_union_idle_intersection() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let intervals = vec![
+            device_interval("laptop", IntervalType::Focus, t, 30),
+            device_interval("laptop", IntervalType::Idle, t + Duration::minutes(30), 30),
+            device_interval("desktop", IntervalType::Idle, t, 20),
+            device_interval(
+                "desktop",
+                IntervalType::Focus,
+                t + Duration::minutes(20),
+                20,
+            ),
+        ];
+
+        let merged = merge_devices(&intervals, IdleMerge::Intersection);
+
+        // Focus 10:00-10:40 (union), idle only 10:40-11:00
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].kind, IntervalType::Focus);
+        assert_eq!(merged[0].end - merged[0].start, Duration::minutes(40));
+        assert_eq!(merged[1].kind, IntervalType::Idle);
+        assert_eq!(merged[1].start, t + Duration::minutes(40));
+        assert_eq!(merged[1].end, t + Duration::minutes(60));
+    }
+
+    #[test]
+    fn test_locked_time_is_kept_apart_from_idle() {
+        let t = Local
+            .with_ymd_and_hms(2023, 1, 2, 10, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let db = Database {
+            intervals: vec![
+                device_interval("laptop", IntervalType::Focus, t, 30),
+                device_interval(
+                    "laptop",
+                    IntervalType::Locked,
+                    t + Duration::minutes(30),
+                    30,
+                ),
+                device_interval("desktop", IntervalType::Idle, t + Duration::minutes(20), 20),
+            ],
+            ..Default::default()
+        };
+
+        // The desktop's idle covers the start of the laptop's lock
+        let merged = merge_devices(&db.intervals, IdleMerge::Intersection);
+        let kinds: Vec<_> = merged.iter().map(|i| (i.kind, i.start)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (IntervalType::Focus, t),
+                (IntervalType::Idle, t + Duration::minutes(30)),
+                (IntervalType::Locked, t + Duration::minutes(40)),
+            ]
+        );
+
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &t);
+        let day = &stats.daily_stats[&t.with_timezone(&Local).date_naive()];
+        assert_eq!(day.total_locked, Duration::minutes(20));
+        assert_eq!(day.total_idle, Duration::minutes(10));
+        assert_eq!(day.idle_sessions, 1);
+    }
+
+    #[test]
+    fn test_session_focus_counts_this_device_since_the_start() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let db = Database {
+            intervals: vec![
+                device_interval("laptop", IntervalType::Focus, t, 30),
+                device_interval("laptop", IntervalType::Idle, t + Duration::minutes(30), 10),
+                device_interval("desktop", IntervalType::Focus, t, 60),
+                device_interval("laptop", IntervalType::Focus, t + Duration::minutes(40), 20),
+            ],
+            ..Default::default()
+        };
+
+        let since = t + Duration::minutes(20);
+        assert_eq!(
+            session_focus(&db, since, Some("laptop")),
+            Duration::minutes(30)
+        );
+        assert_eq!(session_focus(&db, since, None), Duration::zero());
+    }
+
+    #[test]
+    fn test_merge_devices_idle_union() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let intervals = vec![
+            device_interval("laptop", IntervalType::Focus, t, 30),
+            device_interval("desktop", IntervalType::Idle, t, 20),
+        ];
+
+        let merged = merge_devices(&intervals, IdleMerge::Union);
+
+        assert_eq!(merged.len(), 2);
+        let idle: Duration = merged
+            .iter()
+            .filter(|i| i.kind == IntervalType::Idle)
+            .map(|i| i.end - i.start)
+            .sum();
+        assert_eq!(idle, Duration::minutes(20));
+    }
+
+    #[test]
+    fn test_calculate_stats_does_not_double_count_devices() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let db = Database {
+            intervals: vec![
+                device_interval("laptop", IntervalType::Focus, t, 30),
+                device_interval(
+                    "desktop",
+                    IntervalType::Focus,
+                    t + Duration::minutes(10),
+                    30,
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let stats = calculate_stats(&db, Some(t), &StatsOptions::default(), &SystemClock);
+
+        assert_eq!(stats.session_summary.total_focus, Duration::minutes(40));
+        assert_eq!(stats.session_summary.focus_count, 1);
+    }
+
+    #[test]
+    fn test_forecast_focus_follows_history() {
+        let at = |day: u32, h: u32| {
+            Local
+                .with_ymd_and_hms(2024, 5, day, h, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let db = Database {
+            intervals: (10..13)
+                .map(|day| Interval {
+                    end: at(day, 15),
+                    ..Interval::new_at(IntervalType::Focus, at(day, 14))
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let now = at(13, 13);
+
+        let forecast =
+            |min_days| forecast_focus(&db, Duration::hours(2), now, at(13, 17), 28, min_days);
+        assert_eq!(forecast(3), Some(Duration::hours(3)));
+        assert_eq!(forecast(4), None);
+    }
+
+    #[test]
+    fn test_focus_eta_extrapolates_todays_pace() {
+        let at = |h: u32| {
+            Local
+                .with_ymd_and_hms(2024, 5, 13, h, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let db = Database {
+            intervals: vec![Interval {
+                end: at(12),
+                ..Interval::new_at(IntervalType::Focus, at(9))
+            }],
+            ..Default::default()
+        };
+
+        // 2h of focus in the 4h since 09:00: the remaining 2h take another 4h
+        assert_eq!(
+            focus_eta(&db, Duration::hours(2), Duration::hours(4), at(13)),
+            FocusEta::At(at(17))
+        );
+        assert_eq!(
+            focus_eta(&db, Duration::hours(4), Duration::hours(4), at(13)),
+            FocusEta::Reached
+        );
+        assert_eq!(
+            focus_eta(
+                &Database::default(),
+                Duration::zero(),
+                Duration::hours(4),
+                at(13)
+            ),
+            FocusEta::Unknown
+        );
+    }
+
+    #[test]
+    fn test_focus_profile_splits_slots() {
+        let start = Local
+            .with_ymd_and_hms(2023, 1, 2, 9, 15, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let db = Database {
+            intervals: vec![
+                Interval {
+                    start,
+                    end: start + Duration::minutes(60),
+                    kind: IntervalType::Focus,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+                Interval {
+                    start: start + Duration::minutes(60),
+                    end: start + Duration::minutes(70),
+                    kind: IntervalType::Idle,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let profile = focus_profile(&db, start - Duration::days(1), start + Duration::days(1));
+
+        assert_eq!(profile.days, 1);
+        assert_eq!(profile.focus_secs[18], 15 * 60); // 09:00-09:30
+        assert_eq!(profile.focus_secs[19], 30 * 60); // 09:30-10:00
+        assert_eq!(profile.focus_secs[20], 15 * 60); // 10:00-10:30
+        assert_eq!(profile.focus_ratio(19), 1.0);
+        assert_eq!(profile.hour_ratio(9), 0.75);
+        assert_eq!(profile.hour_ratio(10), 0.25);
+    }
+
+    #[test]
+    fn test_coverage_cells() {
+        let from = Utc.with_ymd_and_hms(2023, 1, 1, 9, 0, 0).unwrap();
+        let db = Database {
+            intervals: vec![
+                Interval {
+                    start: from,
+                    end: from + Duration::minutes(50),
+                    kind: IntervalType::Focus,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+                Interval {
+                    start: from + Duration::minutes(50),
+                    end: from + Duration::minutes(80),
+                    kind: IntervalType::Idle,
+                    device_id: None,
+                    power: None,
+                    confidence: Some(0.1),
+                    intensity: None,
+                },
+                // Too short to claim its cell
+                Interval {
+                    start: from + Duration::minutes(150),
+                    end: from + Duration::minutes(160),
+                    kind: IntervalType::Focus,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cells = coverage(
+            &db,
+            from,
+            from + Duration::hours(4),
+            4,
+            &StatsOptions::default(),
+        );
+        assert_eq!(cells, vec![Some(IntervalType::Focus), None, None, None]);
+
+        let cells = coverage(
+            &db,
+            from,
+            from + Duration::hours(2),
+            8,
+            &StatsOptions::default(),
+        );
+        assert_eq!(cells[3], Some(IntervalType::Idle));
+        assert_eq!(cells[4], Some(IntervalType::Idle));
+        assert_eq!(cells[5], None);
+
+        let borderline = borderline_coverage(
+            &db,
+            from,
+            from + Duration::hours(2),
+            8,
+            &StatsOptions::default(),
+        );
+        assert_eq!(borderline[0], None);
+        assert_eq!(borderline[4], Some(IntervalType::Idle));
+    }
+
+    #[test]
+    fn test_deep_work_counts_only_long_focus() {
+        let base_time = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let interval = |offset: i64, mins: i64, kind| Interval {
+            start: base_time + Duration::minutes(offset),
+            end: base_time + Duration::minutes(offset + mins),
+            kind,
+            device_id: None,
+            power: None,
+            confidence: None,
+            intensity: None,
+        };
+        let db = Database {
+            intervals: vec![
+                interval(0, 30, IntervalType::Focus),
+                interval(30, 5, IntervalType::Idle),
+                interval(35, 10, IntervalType::Focus),
+                interval(45, 25, IntervalType::Focus),
+            ],
+            ..Default::default()
+        };
+
+        let stats = calculate_stats(&db, Some(base_time), &StatsOptions::default(), &SystemClock);
+        assert_eq!(stats.session_summary.total_deep_work, Duration::minutes(55));
+        assert_eq!(stats.session_summary.deep_work_count, 2);
+        let day = stats.daily_stats.values().next().unwrap();
+        assert_eq!(day.deep_work_sessions, 2);
+
+        let options = StatsOptions {
+            deep_work_min: Duration::minutes(26),
+            ..Default::default()
+        };
+        let stats = calculate_stats(&db, Some(base_time), &options, &SystemClock);
+        assert_eq!(stats.session_summary.deep_work_count, 1);
+    }
+
+    #[test]
+    fn test_focus_onset() {
+        let at = |day: u32, h: u32, m: u32| {
+            Local
+                .with_ymd_and_hms(2024, 5, day, h, m, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let interval = |start: DateTime<Utc>, mins: i64, kind| Interval {
+            end: start + Duration::minutes(mins),
+            ..Interval::new_at(kind, start)
+        };
+        let mut db = Database {
+            intervals: vec![
+                // Monday: activity from 8:30, deep work from 9:15
+                interval(at(13, 8, 30), 10, IntervalType::Focus),
+                interval(at(13, 8, 40), 35, IntervalType::Idle),
+                interval(at(13, 9, 15), 40, IntervalType::Focus),
+                // Tuesday: deep work from 10:00, scheduled start 9:00
+                interval(at(14, 9, 45), 15, IntervalType::Focus),
+                interval(at(14, 10, 0), 30, IntervalType::Focus),
+                // Wednesday: no deep work
+                interval(at(15, 9, 0), 10, IntervalType::Focus),
+            ],
+            ..Default::default()
+        };
+        db.sessions.push(SessionRecord {
+            start: at(14, 8, 55),
+            end: None,
+            threshold_mins: 5,
+            start_time: chrono::NaiveTime::from_hms_opt(9, 0, 0),
+            end_time: None,
+            duration_mins: None,
+            device_id: None,
+        });
+
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &at(15, 12, 0));
+        let onset =
+            |day| stats.daily_stats[&at(day, 12, 0).with_timezone(&Local).date_naive()].focus_onset;
+        assert_eq!(onset(13), Some(Duration::minutes(45)));
+        assert_eq!(onset(14), Some(Duration::minutes(60)));
+        assert_eq!(onset(15), None);
+        assert_eq!(
+            average_focus_onset(&stats.daily_stats, stats.week_start, stats.today),
+            Some(Duration::seconds(52 * 60 + 30))
+        );
+    }
+
+    #[test]
+    fn test_focus_by_space() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let db = Database {
+            intervals: vec![Interval {
+                start: t,
+                end: t + Duration::minutes(60),
+                kind: IntervalType::Focus,
+                device_id: None,
+                power: None,
+                confidence: None,
+                intensity: None,
+            }],
+            spaces: vec![
+                SpaceSpan {
+                    start: t,
+                    end: t + Duration::minutes(20),
+                    space: 1,
+                },
+                SpaceSpan {
+                    start: t + Duration::minutes(20),
+                    end: t + Duration::minutes(50),
+                    space: 2,
+                },
+                SpaceSpan {
+                    start: t + Duration::minutes(50),
+                    end: t + Duration::minutes(90),
+                    space: 1,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let by_space = focus_by_space(&db, t + Duration::minutes(10), &StatsOptions::default());
+        assert_eq!(by_space[&1], Duration::minutes(20));
+        assert_eq!(by_space[&2], Duration::minutes(30));
+
+        let names = BTreeMap::from([("2".to_string(), "coding".to_string())]);
+        let day = t.with_timezone(&Local).date_naive();
+        let options = StatsOptions::default();
+        assert_eq!(
+            top_tag(&db, day, &options, &names),
+            Some("coding".to_string())
+        );
+        assert_eq!(
+            top_tag(&db, day.succ_opt().unwrap(), &options, &names),
+            None
+        );
+        assert_eq!(top_tag(&db, day, &options, &BTreeMap::new()), None);
+    }
+
+    #[test]
+    fn test_focus_blocks_and_last_active_day() {
+        let t = Local
+            .with_ymd_and_hms(2024, 5, 10, 10, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let interval = |offset: i64, mins: i64, kind| Interval {
+            start: t + Duration::minutes(offset),
+            end: t + Duration::minutes(offset + mins),
+            kind,
+            device_id: None,
+            power: None,
+            confidence: None,
+            intensity: None,
+        };
+        let db = Database {
+            intervals: vec![
+                interval(0, 60, IntervalType::Focus),
+                interval(60, 5, IntervalType::Idle),
+                interval(65, 5, IntervalType::Focus),
+                interval(3 * 24 * 60, 30, IntervalType::Focus),
+            ],
+            spaces: vec![
+                SpaceSpan {
+                    start: t,
+                    end: t + Duration::minutes(20),
+                    space: 1,
+                },
+                SpaceSpan {
+                    start: t + Duration::minutes(20),
+                    end: t + Duration::minutes(60),
+                    space: 2,
+                },
+            ],
+            ..Default::default()
+        };
+        let names = BTreeMap::from([
+            ("1".to_string(), "comms".to_string()),
+            ("2".to_string(), "coding".to_string()),
+        ]);
+        let friday = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+
+        assert_eq!(last_active_day(&db, monday), Some(friday));
+        assert_eq!(last_active_day(&db, friday), None);
+        let blocks = focus_blocks(
+            &db,
+            friday,
+            Duration::minutes(10),
+            &StatsOptions::default(),
+            &names,
+        );
+        assert_eq!(
+            blocks,
+            [FocusBlock {
+                start: t,
+                end: t + Duration::minutes(60),
+                tags: vec!["coding".to_string(), "comms".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_focus_by_power_source() {
+        let base_time = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let interval = |offset: i64, kind, power| Interval {
+            start: base_time + Duration::minutes(offset),
+            end: base_time + Duration::minutes(offset + 10),
+            kind,
+            device_id: None,
+            power,
+            confidence: None,
+            intensity: None,
+        };
+        let db = Database {
+            intervals: vec![
+                interval(0, IntervalType::Focus, Some(PowerSource::Battery)),
+                interval(10, IntervalType::Idle, Some(PowerSource::Battery)),
+                interval(20, IntervalType::Focus, Some(PowerSource::Ac)),
+                interval(30, IntervalType::Focus, None),
+            ],
+            ..Default::default()
+        };
+
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &SystemClock);
+        assert_eq!(stats.focus_on_battery, Duration::minutes(10));
+        assert_eq!(stats.focus_on_ac, Duration::minutes(10));
+    }
+
+    #[test]
+    fn test_today_rolls_over_at_midnight() {
+        let start = Local
+            .with_ymd_and_hms(2023, 3, 1, 23, 40, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let db = Database {
+            intervals: vec![Interval {
+                end: start + Duration::minutes(10),
+                ..Interval::new_at(IntervalType::Focus, start)
+            }],
+            ..Default::default()
+        };
+        let clock = FixedClock::new(start + Duration::minutes(15));
+        let options = StatsOptions::default();
+
+        let stats = calculate_stats(&db, None, &options, &clock);
+        assert_eq!(stats.today_summary.total_focus, Duration::minutes(10));
+
+        clock.advance(Duration::minutes(30));
+        let stats = calculate_stats(&db, None, &options, &clock);
+        assert_eq!(stats.today, NaiveDate::from_ymd_opt(2023, 3, 2).unwrap());
+        assert_eq!(stats.today_summary.total_focus, Duration::zero());
+    }
+
+    #[test]
+    fn test_ranked_days() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let day = |focus_mins, idle_sessions| DayStats {
+            total_focus: Duration::minutes(focus_mins),
+            idle_sessions,
+            ..Default::default()
+        };
+        let daily_stats = BTreeMap::from([
+            (date(1), day(60, 3)),
+            (date(2), day(240, 1)),
+            (date(3), day(60, 9)),
+            (date(20), day(480, 0)),
+        ]);
+        let march_1_to_10 = Period::Custom(date(1), date(10));
+        let dates = |days: Vec<(NaiveDate, &DayStats)>| -> Vec<NaiveDate> {
+            days.into_iter().map(|(date, _)| date).collect()
+        };
+
+        assert_eq!(
+            dates(ranked_days(&daily_stats, march_1_to_10, None, None)),
+            [date(1), date(2), date(3)]
+        );
+        // Ties keep their date order
+        assert_eq!(
+            dates(ranked_days(
+                &daily_stats,
+                march_1_to_10,
+                Some(DaySort::Focus),
+                None
+            )),
+            [date(2), date(1), date(3)]
+        );
+        assert_eq!(
+            dates(ranked_days(
+                &daily_stats,
+                march_1_to_10,
+                Some(DaySort::Interruptions),
+                Some(1)
+            )),
+            [date(3)]
+        );
+    }
+
+    #[test]
+    fn test_summaries_agree_with_the_intervals_they_cover() {
+        // Monday 2023-01-02 through Wednesday, plus the Sunday before
+        let at = |day, hour| {
+            Local
+                .with_ymd_and_hms(2023, 1, day, hour, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let interval = |start, mins, kind| Interval {
+            start,
+            end: start + Duration::minutes(mins),
+            kind,
+            device_id: None,
+            power: None,
+            confidence: None,
+            intensity: None,
+        };
+        let db = Database {
+            intervals: vec![
+                interval(at(1, 10), 40, IntervalType::Focus),
+                interval(at(2, 9), 30, IntervalType::Focus),
+                interval(at(2, 10), 5, IntervalType::Idle),
+                interval(at(3, 9), 10, IntervalType::Focus),
+                interval(at(4, 9), 25, IntervalType::Focus),
+            ],
+            ..Default::default()
+        };
+        let options = StatsOptions::default();
+        let stats = calculate_stats(&db, None, &options, &FixedClock::new(at(4, 12)));
+
+        // What adding up the intervals one by one gives
+        let expected = |from: DateTime<Utc>| {
+            let mut summary = SummaryStats::default();
+            for i in db.intervals.iter().filter(|i| i.start >= from) {
+                update_summary(&mut summary, i.kind, i.end - i.start, &options);
+            }
+            summary
+        };
+        assert_eq!(stats.week_summary, expected(at(2, 0)));
+        assert_eq!(stats.today_summary, expected(at(4, 0)));
+        assert_eq!(stats.week_summary.total_focus, Duration::minutes(65));
+        assert_eq!(stats.week_summary.deep_work_count, 2);
+    }
+
+    #[test]
+    fn test_hourly_interruptions() {
+        let base_time = Local
+            .with_ymd_and_hms(2023, 1, 2, 11, 15, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let db = Database {
+            intervals: vec![
+                Interval {
+                    start: base_time,
+                    end: base_time + Duration::minutes(5),
+                    kind: IntervalType::Idle,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+                Interval {
+                    start: base_time + Duration::minutes(10),
+                    end: base_time + Duration::minutes(20),
+                    kind: IntervalType::Focus,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+                Interval {
+                    start: base_time + Duration::minutes(30),
+                    end: base_time + Duration::minutes(35),
+                    kind: IntervalType::Idle,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+                Interval {
+                    start: base_time + Duration::hours(4),
+                    end: base_time + Duration::hours(4) + Duration::minutes(5),
+                    kind: IntervalType::Idle,
+                    device_id: None,
+                    power: None,
+                    confidence: None,
+                    intensity: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &SystemClock);
+        assert_eq!(stats.hourly_interruptions[11], 2);
+        assert_eq!(stats.hourly_interruptions[15], 1);
+        assert_eq!(stats.hourly_interruptions.iter().sum::<u32>(), 3);
+    }
+
+    #[test]
+    fn test_focus_trend() {
+        let today = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+        let day = |hours: i64| DayStats {
+            total_focus: Duration::hours(hours),
+            ..Default::default()
+        };
+        // Four days of 2h, then four of 4h; today is ignored
+        let mut daily: BTreeMap<NaiveDate, DayStats> = (1..=8)
+            .map(|n| (today - Duration::days(n), day(if n <= 4 { 4 } else { 2 })))
+            .collect();
+        daily.insert(today, day(10));
+
+        let trend = focus_trend(&daily, today, 7).unwrap();
+        // The window starts at the first recorded day when that is later
+        let trend_28 = focus_trend(&daily, today, 28).unwrap();
+
+        assert_eq!(trend.direction, TrendDirection::Up);
+        assert_eq!(trend.average, Duration::seconds((3 * 2 + 4 * 4) * 3600 / 7));
+        assert_eq!(trend_28.average, Duration::hours(3));
+        assert_eq!(trend_28.std_dev, Duration::hours(1));
+        assert_eq!(focus_trend(&BTreeMap::new(), today, 7), None);
+    }
+
+    #[test]
+    fn test_utilization_counts_worked_days() {
+        let date = |d: u32| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let day = |focus: i64, idle: i64| DayStats {
+            total_focus: Duration::hours(focus),
+            total_idle: Duration::hours(idle),
+            ..Default::default()
+        };
+        let mut daily = BTreeMap::from([
+            (date(3), day(6, 1)),
+            (date(4), day(2, 2)),
+            (date(5), day(0, 0)),
+        ]);
+        daily.entry(date(6)).or_default().off = Some("sick".to_string());
+        let workday = Duration::hours(8);
+
+        assert_eq!(utilization(&daily, date(3), date(3), workday), Some(0.75));
+        // Days without activity and days off are not held against the week
+        assert_eq!(utilization(&daily, date(3), date(9), workday), Some(0.5));
+        assert_eq!(utilization(&daily, date(5), date(6), workday), None);
+    }
+
+    #[test]
+    fn test_days_off_are_left_out() {
+        let date = |d: u32| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let hour = Duration::hours(1);
+        let at = |d: u32| local_midnight(date(d)).unwrap() + Duration::hours(9);
+        let mut db = Database {
+            intervals: [3, 4, 10]
+                .into_iter()
+                .map(|d| Interval {
+                    end: at(d) + hour,
+                    ..Interval::new_at(IntervalType::Focus, at(d))
+                })
+                .collect(),
+            days_off: [(date(5), "vacation".to_string())].into(),
+            ..Default::default()
+        };
+        db.mark_off(date(6), date(7), "vacation");
+        let now = at(10) + Duration::hours(2);
+        let stats = calculate_stats(&db, None, &StatsOptions::default(), &now);
+
+        assert_eq!(stats.daily_stats[&date(6)].off.as_deref(), Some("vacation"));
+        // The weekend and the week off neither extend nor break the streak
+        let mut daily = stats.daily_stats.clone();
+        for d in [8, 9] {
+            daily.entry(date(d)).or_default().off = Some("weekend".to_string());
+        }
+        let streaks = goal_streaks(&daily, date(3), date(10), hour);
+        assert_eq!(streaks.current, 3);
+        // Only the two completed working days count towards the average
+        let trend = focus_trend(&daily, date(10), 7).unwrap();
+        assert_eq!(trend.average, hour);
+    }
 }
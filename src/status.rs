@@ -0,0 +1,107 @@
+use crate::clock::Clock;
+use crate::config::KindsConfig;
+use crate::models::{Database, IntervalType};
+use crate::stats::{calculate_stats, StatsOptions};
+use crate::utils::{color_argb, format_minutes};
+use serde::Serialize;
+
+/// Icon shown by status bars; its color carries the state.
+const ICON: &str = "●";
+
+/// A one-line view of the tracker for status bars and scripts.
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub running: bool,
+    /// Kind of the newest interval while a session runs.
+    pub state: Option<IntervalType>,
+    pub label: String,
+    /// 0xAARRGGBB, as SketchyBar expects.
+    pub color: String,
+    pub focus_today_secs: i64,
+    pub focus_today: String,
+}
+
+impl Status {
+    /// Build the status from the saved database. The state follows the newest
+    /// interval, so it can lag a running session by one save interval.
+    pub fn new(
+        db: &Database,
+        running: bool,
+        options: &StatsOptions,
+        kinds: &KindsConfig,
+        clock: &dyn Clock,
+    ) -> Self {
+        let state = running
+            .then(|| db.intervals.iter().max_by_key(|i| i.end).map(|i| i.kind))
+            .flatten();
+        let focus = calculate_stats(db, None, options, clock)
+            .today_summary
+            .total_focus;
+        let (label, color) = match state {
+            Some(kind) => (kinds.label(kind).to_string(), kinds.color(kind)),
+            None => ("Not running".to_string(), kinds.color(IntervalType::Away)),
+        };
+        Self {
+            running,
+            state,
+            label,
+            color: color_argb(color),
+            focus_today_secs: focus.num_seconds(),
+            focus_today: format_minutes(focus),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        format!("{} · {} focus today", self.label, self.focus_today)
+    }
+
+    /// Item properties for `sketchybar --set`, quoted for `eval`.
+    pub fn sketchybar(&self) -> String {
+        format!(
+            "icon={} icon.color={} label='{}'",
+            ICON, self.color, self.focus_today
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Interval;
+    use chrono::{Duration, Local, TimeZone, Utc};
+
+    #[test]
+    fn test_status_follows_newest_interval() {
+        let start = Local
+            .with_ymd_and_hms(2024, 5, 13, 9, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let db = Database {
+            intervals: vec![
+                Interval {
+                    end: start + Duration::minutes(83),
+                    ..Interval::new_at(IntervalType::Focus, start)
+                },
+                Interval {
+                    end: start + Duration::minutes(90),
+                    ..Interval::new_at(IntervalType::Idle, start + Duration::minutes(83))
+                },
+            ],
+            ..Default::default()
+        };
+        let now = start + Duration::minutes(90);
+        let kinds = KindsConfig::default();
+
+        let status = Status::new(&db, true, &StatsOptions::default(), &kinds, &now);
+        assert_eq!(status.state, Some(IntervalType::Idle));
+        assert_eq!(status.text(), "Idle · 1h 23m focus today");
+        assert_eq!(
+            status.sketchybar(),
+            "icon=● icon.color=0xffcdcd00 label='1h 23m'"
+        );
+
+        let status = Status::new(&db, false, &StatsOptions::default(), &kinds, &now);
+        assert_eq!(status.state, None);
+        assert_eq!(status.label, "Not running");
+    }
+}
@@ -1,27 +1,74 @@
-use crate::models::Database;
-use anyhow::Result;
+use crate::config::DatabaseConfig;
+use crate::error::{self, Error};
+use crate::fixups;
+use crate::models::{ChangePlan, Database, DbMeta, Dirty, Interval, SpaceSpan, DB_SCHEMA_VERSION};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Marker file next to the executable that switches on portable mode.
+pub const PORTABLE_MARKER: &str = "neflo.portable";
+/// Data directory used in portable mode, relative to the executable.
+const PORTABLE_DATA_DIR: &str = "neflo-data";
+
+/// The latest interval, written on its own so that extending it does not
+/// require rewriting the whole database.
+#[derive(Serialize, Deserialize)]
+struct Tail {
+    interval: Interval,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    space: Option<SpaceSpan>,
+}
+
+/// The database as written to disk, headed by metadata about the writer.
+#[derive(Serialize)]
+struct Written<'a> {
+    meta: DbMeta,
+    #[serde(flatten)]
+    db: &'a Database,
+}
 
 #[derive(Clone)]
 pub struct Storage {
     path: PathBuf,
+    /// Recorded in the metadata of every save.
+    pub device: Option<String>,
+    /// OS user loading and saving; databases owned by someone else are refused.
+    pub user: Option<String>,
+    /// Print what each save would change instead of writing (`--dry-run`).
+    pub dry_run: bool,
+    /// The serialized database when nothing should touch the filesystem.
+    /// Clones share it, like they share a file.
+    memory: Option<Arc<Mutex<Option<String>>>>,
 }
 
 impl Storage {
-    pub fn get_base_dir() -> Result<PathBuf> {
-        let mut path =
-            dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-        path.push(".neflo");
+    /// Resolve the data directory, defaulting to `~/.neflo` when no override is given.
+    pub fn get_base_dir(data_dir: Option<PathBuf>) -> Result<PathBuf> {
+        let path = match data_dir {
+            Some(dir) => dir,
+            None => dirs::home_dir()
+                .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
+                .join(".neflo"),
+        };
         if !path.exists() {
             fs::create_dir_all(&path)?;
         }
         Ok(path)
     }
 
-    pub fn new() -> Result<Self> {
-        let path = Self::get_base_dir()?;
-        Ok(Self::from_path(path.join("db.json")))
+    /// The data directory beside `exe` when portable mode is forced or a
+    /// [`PORTABLE_MARKER`] sits next to it.
+    pub fn portable_dir(exe: &Path, force: bool) -> Option<PathBuf> {
+        let exe_dir = exe.parent()?;
+        (force || exe_dir.join(PORTABLE_MARKER).exists()).then(|| exe_dir.join(PORTABLE_DATA_DIR))
+    }
+
+    pub fn new(base_dir: &Path) -> Self {
+        Self::from_path(base_dir.join("db.json"))
     }
 
     pub fn from_path(path: PathBuf) -> Self {
@@ -30,25 +77,232 @@ impl Storage {
                 let _ = fs::create_dir_all(parent);
             }
         }
-        Self { path }
+        Self {
+            path,
+            device: None,
+            user: None,
+            dry_run: false,
+            memory: None,
+        }
+    }
+
+    /// Storage that keeps the database in memory and never touches the
+    /// filesystem.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            path: PathBuf::new(),
+            device: None,
+            user: None,
+            dry_run: false,
+            memory: Some(Arc::default()),
+        }
     }
 
     pub fn load(&self) -> Result<Database> {
+        let mut db = self.load_saved()?;
+        fixups::apply(&mut db);
+        Ok(db)
+    }
+
+    /// The database as saved, before pending fixups.
+    fn load_saved(&self) -> Result<Database> {
+        if let Some(memory) = &self.memory {
+            return match memory.lock().unwrap().as_deref() {
+                Some(data) => self.check_owner(self.parse(data)?),
+                None => Ok(Self::created()),
+            };
+        }
         if !self.path.exists() {
-            return Ok(Database::default());
+            return Ok(Self::created());
+        }
+        let data = fs::read_to_string(&self.path).map_err(|e| error::io(&self.path, e))?;
+        let mut db = self.check_owner(self.parse(&data)?)?;
+        self.apply_tail(&mut db)?;
+        Ok(db)
+    }
+
+    fn parse(&self, data: &str) -> Result<Database> {
+        serde_json::from_str(data).map_err(|source| {
+            Error::CorruptDatabase {
+                path: self.path.clone(),
+                source,
+            }
+            .into()
+        })
+    }
+
+    /// Refuse a database that belongs to another OS user, so that a data
+    /// directory on a shared disk never mixes two people's time.
+    fn check_owner(&self, db: Database) -> Result<Database> {
+        if let (Some(owner), Some(user)) = (&db.meta.owner, &self.user) {
+            if owner != user {
+                bail!(
+                    "{} belongs to user '{}', not '{}'; point --data-dir at your own data directory",
+                    self.path.display(),
+                    owner,
+                    user
+                );
+            }
         }
-        let data = fs::read_to_string(&self.path)?;
-        let db = serde_json::from_str(&data)?;
         Ok(db)
     }
 
     pub fn save(&self, db: &Database) -> Result<()> {
-        let data = serde_json::to_string_pretty(db)?;
+        if self.dry_run {
+            println!("{}", ChangePlan::between(&self.load_saved()?, db));
+            return Ok(());
+        }
+        let data = serde_json::to_string_pretty(&Written {
+            meta: DbMeta {
+                schema_version: DB_SCHEMA_VERSION,
+                created_at: db.meta.created_at,
+                app_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                device: self.device.clone().or_else(|| db.meta.device.clone()),
+                owner: db.meta.owner.clone().or_else(|| self.user.clone()),
+                fixups: db.meta.fixups,
+            },
+            db,
+        })?;
+        if let Some(memory) = &self.memory {
+            *memory.lock().unwrap() = Some(data);
+            return Ok(());
+        }
         let tmp_path = self.path.with_extension("tmp");
-        fs::write(&tmp_path, &data)?;
-        fs::rename(&tmp_path, &self.path)?;
+        fs::write(&tmp_path, &data).map_err(|e| error::io(&tmp_path, e))?;
+        fs::rename(&tmp_path, &self.path).map_err(|e| error::io(&self.path, e))?;
+        let tail_path = self.tail_path();
+        if tail_path.exists() {
+            fs::remove_file(tail_path)?;
+        }
+        Ok(())
+    }
+
+    /// Persist only the latest interval. `load` applies it on top of the last
+    /// full save, so this is enough when nothing but its `end` changed.
+    pub fn save_tail(&self, db: &Database) -> Result<()> {
+        if self.memory.is_some() || self.dry_run {
+            return self.save(db);
+        }
+        let Some(interval) = db.intervals.last() else {
+            return Ok(());
+        };
+        let data = serde_json::to_string(&Tail {
+            interval: interval.clone(),
+            space: db.spaces.last().cloned(),
+        })?;
+        let tail_path = self.tail_path();
+        let tmp_path = tail_path.with_extension("tmp");
+        fs::write(&tmp_path, data).map_err(|e| error::io(&tmp_path, e))?;
+        fs::rename(&tmp_path, &tail_path).map_err(|e| error::io(&tail_path, e))?;
         Ok(())
     }
+
+    /// Write whatever `db.dirty` says is pending and mark it clean.
+    pub fn save_dirty(&self, db: &mut Database) -> Result<()> {
+        match db.dirty {
+            Dirty::Clean => {}
+            Dirty::Tail => self.save_tail(db)?,
+            Dirty::Full => self.save(db)?,
+        }
+        db.dirty = Dirty::Clean;
+        Ok(())
+    }
+
+    /// Bytes of the last full save, not counting the tail file; 0 before the first.
+    pub fn size(&self) -> u64 {
+        match &self.memory {
+            Some(memory) => memory
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map_or(0, |d| d.len() as u64),
+            None => fs::metadata(&self.path).map_or(0, |m| m.len()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where intervals removed by a reset are kept for `neflo undo`; in-memory
+    /// storage has none.
+    pub fn trash_dir(&self) -> Option<PathBuf> {
+        self.memory
+            .is_none()
+            .then(|| self.path.with_file_name("trash"))
+    }
+
+    /// An empty database for a file that does not exist yet.
+    fn created() -> Database {
+        Database {
+            meta: DbMeta {
+                schema_version: DB_SCHEMA_VERSION,
+                created_at: Some(Utc::now()),
+                fixups: fixups::LATEST,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn tail_path(&self) -> PathBuf {
+        self.path.with_extension("tail.json")
+    }
+
+    fn apply_tail(&self, db: &mut Database) -> Result<()> {
+        let tail_path = self.tail_path();
+        if !tail_path.exists() {
+            return Ok(());
+        }
+        let tail: Tail = serde_json::from_str(&fs::read_to_string(tail_path)?)?;
+        if let Some(interval) = db.intervals.iter_mut().rev().find(|i| {
+            i.start == tail.interval.start
+                && i.kind == tail.interval.kind
+                && i.device_id == tail.interval.device_id
+        }) {
+            if tail.interval.end >= interval.end {
+                interval.end = tail.interval.end;
+                interval.confidence = tail.interval.confidence;
+                interval.intensity = tail.interval.intensity;
+            }
+        }
+        if let Some(tail_span) = tail.space {
+            if let Some(span) = db
+                .spaces
+                .iter_mut()
+                .rev()
+                .find(|s| s.start == tail_span.start && s.space == tail_span.space)
+            {
+                span.end = span.end.max(tail_span.end);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a database of `bytes` holding `intervals` is past the `[database]`
+/// warning levels, with what to do about it; `None` while it is not.
+pub fn quota_warning(limits: &DatabaseConfig, bytes: u64, intervals: usize) -> Option<String> {
+    let megabytes = bytes as f64 / 1_000_000.0;
+    let over = if megabytes > limits.warn_mb as f64 {
+        format!(
+            "The database is {:.1} MB, over database.warn_mb ({} MB)",
+            megabytes, limits.warn_mb
+        )
+    } else if intervals > limits.warn_intervals {
+        format!(
+            "The database holds {} intervals, over database.warn_intervals ({})",
+            intervals, limits.warn_intervals
+        )
+    } else {
+        return None;
+    };
+    Some(format!(
+        "{}; every save rewrites all of it. Archive old data with `neflo export` and \
+         remove it with `neflo edit delete`, or raise the limit.",
+        over
+    ))
 }
 
 #[cfg(test)]
@@ -77,6 +331,179 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_quota_warning() -> Result<()> {
+        let storage = Storage::in_memory();
+        assert_eq!(storage.size(), 0);
+        storage.save(&Database::default())?;
+        assert!(storage.size() > 0);
+
+        let limits = DatabaseConfig {
+            warn_mb: 2,
+            warn_intervals: 1000,
+        };
+        assert_eq!(quota_warning(&limits, 2_000_000, 1000), None);
+        let large = quota_warning(&limits, 2_500_000, 10).unwrap();
+        assert!(large.starts_with("The database is 2.5 MB, over database.warn_mb (2 MB)"));
+        let many = quota_warning(&limits, 1000, 1001).unwrap();
+        assert!(many.contains("1001 intervals"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_tail_extends_last_interval() -> Result<()> {
+        let dir = tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let start = Utc::now();
+
+        let mut db = Database::default();
+        db.intervals
+            .push(Interval::new_at(IntervalType::Focus, start));
+        storage.save(&db)?;
+
+        db.intervals[0].end = start + chrono::Duration::minutes(10);
+        db.mark_dirty(Dirty::Tail);
+        storage.save_dirty(&mut db)?;
+        assert_eq!(db.dirty, Dirty::Clean);
+        assert!(dir.path().join("db.tail.json").exists());
+
+        let loaded = storage.load()?;
+        assert_eq!(
+            loaded.intervals[0].end,
+            start + chrono::Duration::minutes(10)
+        );
+
+        // A full save folds the tail back into the database file
+        storage.save(&loaded)?;
+        assert!(!dir.path().join("db.tail.json").exists());
+        assert_eq!(
+            storage.load()?.intervals[0].end,
+            start + chrono::Duration::minutes(10)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_dirty_skips_clean() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("db.json");
+        let storage = Storage::from_path(db_path.clone());
+
+        let mut db = Database::default();
+        storage.save_dirty(&mut db)?;
+        assert!(!db_path.exists());
+
+        db.mark_dirty(Dirty::Full);
+        storage.save_dirty(&mut db)?;
+        assert!(db_path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_base_dir_override() -> Result<()> {
+        let dir = tempdir()?;
+        let data_dir = dir.path().join("custom");
+
+        let base_dir = Storage::get_base_dir(Some(data_dir.clone()))?;
+        assert_eq!(base_dir, data_dir);
+        assert!(data_dir.is_dir());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_portable_dir() -> Result<()> {
+        let dir = tempdir()?;
+        let exe = dir.path().join("neflo");
+
+        assert_eq!(Storage::portable_dir(&exe, false), None);
+        assert_eq!(
+            Storage::portable_dir(&exe, true),
+            Some(dir.path().join("neflo-data"))
+        );
+
+        fs::write(dir.path().join(PORTABLE_MARKER), "")?;
+        assert_eq!(
+            Storage::portable_dir(&exe, false),
+            Some(dir.path().join("neflo-data"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_writes_metadata() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("db.json");
+        fs::write(&db_path, r#"{"intervals": []}"#)?;
+        let mut storage = Storage::from_path(db_path.clone());
+        storage.device = Some("work-laptop".to_string());
+
+        // Files from before the metadata existed, which then get every fixup
+        let legacy = storage.load()?;
+        assert_eq!(
+            legacy.meta,
+            DbMeta {
+                fixups: fixups::LATEST,
+                ..Default::default()
+            }
+        );
+
+        fs::remove_file(&db_path)?;
+        let db = storage.load()?;
+        let created_at = db.meta.created_at;
+        assert!(created_at.is_some());
+        storage.save(&db)?;
+
+        let raw: serde_json::Value = serde_json::from_str(&fs::read_to_string(&db_path)?)?;
+        assert_eq!(raw["meta"]["schema_version"], DB_SCHEMA_VERSION);
+        assert_eq!(raw["meta"]["app_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(raw["meta"]["device"], "work-laptop");
+        assert_eq!(storage.load()?.meta.created_at, created_at);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_refuses_database_of_another_user() -> Result<()> {
+        let dir = tempdir()?;
+        let mut alice = Storage::from_path(dir.path().join("db.json"));
+        alice.user = Some("alice".to_string());
+        alice.save(&alice.load()?)?;
+        assert_eq!(alice.load()?.meta.owner.as_deref(), Some("alice"));
+
+        let mut bob = alice.clone();
+        bob.user = Some("bob".to_string());
+        let err = bob.load().unwrap_err().to_string();
+        assert!(
+            err.contains("belongs to user 'alice', not 'bob'"),
+            "{}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory_shared_between_clones() -> Result<()> {
+        let storage = Storage::in_memory();
+        assert!(storage.load()?.intervals.is_empty());
+        assert_eq!(storage.trash_dir(), None);
+
+        let mut db = Database::default();
+        db.intervals
+            .push(Interval::new_at(IntervalType::Focus, Utc::now()));
+        db.mark_dirty(Dirty::Tail);
+        storage.clone().save_dirty(&mut db)?;
+
+        assert_eq!(storage.load()?.intervals.len(), 1);
+        assert_eq!(Storage::in_memory().load()?.intervals.len(), 0);
+
+        Ok(())
+    }
+
     #[test]
     fn test_storage_load_nonexistent() -> Result<()> {
         let dir = tempdir()?;
@@ -88,4 +515,41 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_dry_run_never_writes() -> Result<()> {
+        let dir = tempdir()?;
+        let storage = Storage::new(dir.path());
+        let mut db = storage.load()?;
+        db.intervals
+            .push(Interval::new_at(IntervalType::Focus, Utc::now()));
+        storage.save(&db)?;
+        let saved = fs::read_to_string(dir.path().join("db.json"))?;
+
+        let dry_run = Storage {
+            dry_run: true,
+            ..storage.clone()
+        };
+        db.intervals.clear();
+        dry_run.save(&db)?;
+        dry_run.save_tail(&db)?;
+        assert_eq!(fs::read_to_string(dir.path().join("db.json"))?, saved);
+        assert!(!storage.tail_path().exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_database_is_typed() -> Result<()> {
+        let dir = tempdir()?;
+        let storage = Storage::new(dir.path());
+        fs::write(dir.path().join("db.json"), "{ not json")?;
+
+        let err = storage.load().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::CorruptDatabase { path, .. }) if *path == storage.path
+        ));
+
+        Ok(())
+    }
 }
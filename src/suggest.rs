@@ -0,0 +1,139 @@
+use crate::models::Database;
+use crate::stats::{focus_profile, FocusProfile, SLOTS_PER_DAY};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+
+/// Slots whose average focus ratio reaches this value are considered good for deep work.
+const MIN_FOCUS_RATIO: f64 = 0.5;
+/// Shortest window worth suggesting, in half-hour slots.
+const MIN_WINDOW_SLOTS: usize = 2;
+/// Days of history required before making suggestions.
+pub const MIN_DAYS: u32 = 3;
+/// How far back the focus profile looks.
+pub const LOOKBACK_DAYS: i64 = 28;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Sum of the focus ratios of the slots in the window.
+    pub score: f64,
+}
+
+/// Suggest the two best deep-work windows from the last four weeks of data.
+pub fn suggest_from_history(db: &Database, now: DateTime<Utc>) -> Vec<FocusWindow> {
+    let profile = focus_profile(db, now - Duration::days(LOOKBACK_DAYS), now);
+    suggest_focus_blocks(&profile, 2)
+}
+
+/// Find up to `limit` windows of consecutive high-focus slots, best first.
+pub fn suggest_focus_blocks(profile: &FocusProfile, limit: usize) -> Vec<FocusWindow> {
+    if profile.days < MIN_DAYS {
+        return Vec::new();
+    }
+
+    let mut windows = Vec::new();
+    let mut slot = 0;
+    while slot < SLOTS_PER_DAY {
+        if profile.focus_ratio(slot) < MIN_FOCUS_RATIO {
+            slot += 1;
+            continue;
+        }
+        let start = slot;
+        let mut score = 0.0;
+        while slot < SLOTS_PER_DAY && profile.focus_ratio(slot) >= MIN_FOCUS_RATIO {
+            score += profile.focus_ratio(slot);
+            slot += 1;
+        }
+        if slot - start >= MIN_WINDOW_SLOTS {
+            windows.push(FocusWindow {
+                start: slot_time(start),
+                end: slot_time(slot),
+                score,
+            });
+        }
+    }
+
+    windows.sort_by(|a, b| b.score.total_cmp(&a.score));
+    windows.truncate(limit);
+    windows.sort_by_key(|w| w.start);
+    windows
+}
+
+fn slot_time(slot: usize) -> NaiveTime {
+    if slot >= SLOTS_PER_DAY {
+        return NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+    }
+    NaiveTime::from_hms_opt((slot / 2) as u32, (slot % 2) as u32 * 30, 0).unwrap()
+}
+
+/// Human-readable sentence describing `windows`.
+pub fn describe(windows: &[FocusWindow]) -> String {
+    let ranges: Vec<String> = windows
+        .iter()
+        .map(|w| format!("{}–{}", w.start.format("%H:%M"), w.end.format("%H:%M")))
+        .collect();
+    match ranges.len() {
+        0 => "Not enough consistent focus history yet to suggest deep-work blocks.".to_string(),
+        1 => format!("Your best uninterrupted window is {}.", ranges[0]),
+        _ => {
+            let (last, rest) = ranges.split_last().unwrap();
+            format!(
+                "Your best uninterrupted windows are {} and {}.",
+                rest.join(", "),
+                last
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile_with(focused_slots: &[(usize, i64)], days: u32) -> FocusProfile {
+        let mut focus_secs = [0i64; SLOTS_PER_DAY];
+        for (slot, secs) in focused_slots {
+            focus_secs[*slot] = *secs;
+        }
+        FocusProfile { focus_secs, days }
+    }
+
+    #[test]
+    fn test_suggest_picks_best_windows() {
+        let full = 5 * 1800;
+        let profile = profile_with(
+            &[
+                (18, full), // 09:00
+                (19, full),
+                (20, full),
+                (21, full), // until 11:00
+                (26, full), // 13:00, single slot: too short
+                (28, full), // 14:00
+                (29, full),
+                (30, full / 2 + 1), // until 15:30
+                (40, full),         // 20:00
+                (41, full / 2 + 1),
+            ],
+            5,
+        );
+
+        let windows = suggest_focus_blocks(&profile, 2);
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].start, NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(windows[0].end, NaiveTime::from_hms_opt(11, 0, 0).unwrap());
+        assert_eq!(windows[1].start, NaiveTime::from_hms_opt(14, 0, 0).unwrap());
+        assert_eq!(windows[1].end, NaiveTime::from_hms_opt(15, 30, 0).unwrap());
+        assert_eq!(
+            describe(&windows),
+            "Your best uninterrupted windows are 09:00–11:00 and 14:00–15:30."
+        );
+    }
+
+    #[test]
+    fn test_suggest_requires_history() {
+        let profile = profile_with(&[(18, 1800), (19, 1800)], 1);
+        assert!(suggest_focus_blocks(&profile, 2).is_empty());
+        assert!(describe(&[]).starts_with("Not enough"));
+    }
+}
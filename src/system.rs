@@ -1,4 +1,189 @@
-pub fn get_idle_time() -> f64 {
+use crate::models::PowerSource;
+use chrono::{DateTime, Utc};
+use std::time::{Duration as StdDuration, Instant};
+
+/// How long an idle source may return exactly the same value, while the
+/// clock moves on, before it is considered frozen.
+pub const FROZEN_SECS: u64 = 30;
+
+/// Where idle time is read from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdleSource {
+    /// CGEventSourceSecondsSinceLastEventType, the default on macOS.
+    EventSource,
+    /// IOKit's HIDIdleTime, used when CGEventSource misbehaves.
+    HidIdleTime,
+    /// The desktop's idle monitor over D-Bus, in a Wayland session on Linux,
+    /// where applications cannot watch input themselves.
+    Wayland,
+    /// No idle detection on this platform; idle time always reads zero.
+    Unavailable,
+}
+
+impl IdleSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdleSource::EventSource => "CGEventSource",
+            IdleSource::HidIdleTime => "IOKit HIDIdleTime",
+            IdleSource::Wayland => "D-Bus idle monitor",
+            IdleSource::Unavailable => "none",
+        }
+    }
+}
+
+/// One idle time reading, for showing whether detection works.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdleSample {
+    pub source: IdleSource,
+    pub idle: StdDuration,
+    pub at: DateTime<Utc>,
+}
+
+/// Reads idle time, falling back from CGEventSource to IOKit HIDIdleTime when
+/// the former returns anomalous values: negative, not a number, or the same
+/// value for longer than `FROZEN_SECS` (some setups report 0 forever). Real
+/// idle time grows, and input resets it, so it never stands still.
+pub struct IdleDetector {
+    source: IdleSource,
+    /// The last reading and when it was first returned.
+    unchanged: Option<(f64, Instant)>,
+    /// The current source is frozen and there is nothing left to fall back to.
+    frozen: bool,
+    /// The monitor that answered at startup, with `IdleSource::Wayland`.
+    dbus: Option<DbusMonitor>,
+}
+
+impl IdleDetector {
+    pub fn new() -> Self {
+        let dbus = (!cfg!(target_os = "macos") && wayland_session(|var| std::env::var_os(var)))
+            .then(dbus_monitor)
+            .flatten();
+        Self {
+            source: if cfg!(target_os = "macos") {
+                IdleSource::EventSource
+            } else if dbus.is_some() {
+                IdleSource::Wayland
+            } else {
+                IdleSource::Unavailable
+            },
+            unchanged: None,
+            frozen: false,
+            dbus,
+        }
+    }
+
+    pub fn source(&self) -> IdleSource {
+        self.source
+    }
+
+    /// The D-Bus idle monitor read in a Wayland session.
+    pub fn dbus_monitor(&self) -> Option<DbusMonitor> {
+        self.dbus
+    }
+
+    /// Whether idle time is stuck with no fallback left, so it cannot be trusted.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Time since the last user input.
+    pub fn idle_time(&mut self) -> StdDuration {
+        let now = Instant::now();
+        let secs = if let (IdleSource::Wayland, Some(monitor)) = (self.source, self.dbus) {
+            self.read(now, || dbus_idle_time(monitor))
+        } else {
+            self.sample(now, event_source_idle_time, hid_idle_time)
+        };
+        StdDuration::try_from_secs_f64(secs).unwrap_or_default()
+    }
+
+    fn sample(
+        &mut self,
+        now: Instant,
+        event_source: impl Fn() -> Option<f64>,
+        hid: impl Fn() -> Option<f64>,
+    ) -> f64 {
+        if self.source == IdleSource::EventSource {
+            let value = event_source().unwrap_or(f64::NAN);
+            let frozen = self.watch(now, value);
+            if value.is_finite() && value >= 0.0 && !frozen {
+                self.frozen = false;
+                return value;
+            }
+            match hid() {
+                Some(idle) => {
+                    self.source = IdleSource::HidIdleTime;
+                    self.unchanged = None;
+                    return idle;
+                }
+                None => {
+                    self.frozen = frozen;
+                    return value.max(0.0);
+                }
+            }
+        }
+        match self.source {
+            IdleSource::HidIdleTime => self.read(now, hid),
+            _ => 0.0,
+        }
+    }
+
+    /// Read a source there is no fallback from, which is frozen once it
+    /// stands still.
+    fn read(&mut self, now: Instant, source: impl Fn() -> Option<f64>) -> f64 {
+        let value = source().unwrap_or(0.0);
+        self.frozen = self.watch(now, value);
+        value
+    }
+
+    /// Track `value` and report whether it has not changed for `FROZEN_SECS`.
+    fn watch(&mut self, now: Instant, value: f64) -> bool {
+        match self.unchanged {
+            Some((last, since)) if last == value => {
+                now - since >= StdDuration::from_secs(FROZEN_SECS)
+            }
+            _ => {
+                self.unchanged = Some((value, now));
+                false
+            }
+        }
+    }
+}
+
+/// How far the wall clock may run ahead of the monotonic clock between two
+/// samples, from clock adjustments, before the difference counts as sleep.
+const SLEEP_TOLERANCE_SECS: u64 = 2;
+
+/// Tells how long the machine slept between samples. The monotonic clock
+/// (mach absolute time on macOS, CLOCK_MONOTONIC on Linux) stops while the
+/// machine sleeps and the wall clock does not, so the wall clock runs ahead
+/// by the time spent asleep.
+#[derive(Default)]
+pub struct SleepDetector {
+    last: Option<(Instant, DateTime<Utc>)>,
+}
+
+impl SleepDetector {
+    /// Time spent asleep since the previous sample, zero for the first one.
+    pub fn slept(&mut self, now: Instant, wall: DateTime<Utc>) -> StdDuration {
+        let slept = match self.last {
+            Some((then, then_wall)) => (wall - then_wall)
+                .to_std()
+                .unwrap_or_default()
+                .saturating_sub(now - then),
+            None => StdDuration::ZERO,
+        };
+        self.last = Some((now, wall));
+        if slept >= StdDuration::from_secs(SLEEP_TOLERANCE_SECS) {
+            slept
+        } else {
+            StdDuration::ZERO
+        }
+    }
+}
+
+/// Idle seconds as reported by CGEventSource; `None` where it does not exist.
+pub fn event_source_idle_time() -> Option<f64> {
     #[cfg(target_os = "macos")]
     {
         #[link(name = "CoreGraphics", kind = "framework")]
@@ -7,11 +192,489 @@ pub fn get_idle_time() -> f64 {
         }
         // kCGEventSourceStateCombinedSessionState = 0
         // kCGAnyInputEventType = u32::MAX
-        unsafe { CGEventSourceSecondsSinceLastEventType(0, u32::MAX) }
+        Some(unsafe { CGEventSourceSecondsSinceLastEventType(0, u32::MAX) })
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        None
+    }
+}
+
+/// Idle seconds from the HIDIdleTime property of IOKit's IOHIDSystem; needs no
+/// permissions. `None` if it cannot be read.
+pub fn hid_idle_time() -> Option<f64> {
+    #[cfg(target_os = "macos")]
+    {
+        use std::ffi::c_void;
+        use std::ptr;
+
+        type CFTypeRef = *const c_void;
+
+        #[link(name = "IOKit", kind = "framework")]
+        extern "C" {
+            fn IOServiceMatching(name: *const libc::c_char) -> *mut c_void;
+            fn IOServiceGetMatchingService(main_port: u32, matching: *mut c_void) -> u32;
+            fn IORegistryEntryCreateCFProperty(
+                entry: u32,
+                key: CFTypeRef,
+                allocator: CFTypeRef,
+                options: u32,
+            ) -> CFTypeRef;
+            fn IOObjectRelease(object: u32) -> i32;
+        }
+        #[link(name = "CoreFoundation", kind = "framework")]
+        extern "C" {
+            fn CFStringCreateWithCString(
+                allocator: CFTypeRef,
+                c_str: *const libc::c_char,
+                encoding: u32,
+            ) -> CFTypeRef;
+            fn CFGetTypeID(cf: CFTypeRef) -> usize;
+            fn CFNumberGetTypeID() -> usize;
+            fn CFNumberGetValue(number: CFTypeRef, the_type: isize, value: *mut c_void) -> bool;
+            fn CFRelease(cf: CFTypeRef);
+        }
+        const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+        const CF_NUMBER_SINT64_TYPE: isize = 4;
+
+        // SAFETY: every object created here is released before returning, and
+        // IOServiceGetMatchingService consumes the matching dictionary.
+        unsafe {
+            // kIOMainPortDefault = 0
+            let service =
+                IOServiceGetMatchingService(0, IOServiceMatching(c"IOHIDSystem".as_ptr()));
+            if service == 0 {
+                return None;
+            }
+            let key = CFStringCreateWithCString(
+                ptr::null(),
+                c"HIDIdleTime".as_ptr(),
+                CF_STRING_ENCODING_UTF8,
+            );
+            let value = IORegistryEntryCreateCFProperty(service, key, ptr::null(), 0);
+            CFRelease(key);
+            IOObjectRelease(service);
+            if value.is_null() {
+                return None;
+            }
+            let mut nanos: i64 = 0;
+            let ok = CFGetTypeID(value) == CFNumberGetTypeID()
+                && CFNumberGetValue(
+                    value,
+                    CF_NUMBER_SINT64_TYPE,
+                    &mut nanos as *mut i64 as *mut c_void,
+                );
+            CFRelease(value);
+            ok.then(|| nanos as f64 / 1e9)
+        }
     }
     #[cfg(not(target_os = "macos"))]
     {
-        // Fallback for non-macOS systems (e.g. for development/testing on Linux)
-        0.0
+        None
+    }
+}
+
+/// Whether this is a Wayland session, judged by the variables the session
+/// sets for its clients.
+pub fn wayland_session(var: impl Fn(&str) -> Option<std::ffi::OsString>) -> bool {
+    var("XDG_SESSION_TYPE").is_some_and(|kind| kind == "wayland")
+        || var("WAYLAND_DISPLAY").is_some()
+}
+
+/// A desktop idle monitor on the D-Bus session bus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DbusMonitor {
+    /// GNOME's Mutter IdleMonitor.
+    Mutter,
+    /// The org.freedesktop.ScreenSaver interface of KDE and others.
+    ScreenSaver,
+}
+
+impl DbusMonitor {
+    const ALL: [DbusMonitor; 2] = [DbusMonitor::Mutter, DbusMonitor::ScreenSaver];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DbusMonitor::Mutter => "org.gnome.Mutter.IdleMonitor",
+            DbusMonitor::ScreenSaver => "org.freedesktop.ScreenSaver",
+        }
+    }
+
+    /// The object path and method that return idle milliseconds.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn method(&self) -> [&'static str; 2] {
+        match self {
+            DbusMonitor::Mutter => [
+                "/org/gnome/Mutter/IdleMonitor/Core",
+                "org.gnome.Mutter.IdleMonitor.GetIdletime",
+            ],
+            DbusMonitor::ScreenSaver => [
+                "/org/freedesktop/ScreenSaver",
+                "org.freedesktop.ScreenSaver.GetSessionIdleTime",
+            ],
+        }
+    }
+}
+
+/// The first idle monitor on the D-Bus session bus that answers, looked up
+/// once so that each sample asks only that one.
+pub fn dbus_monitor() -> Option<DbusMonitor> {
+    DbusMonitor::ALL
+        .into_iter()
+        .find(|monitor| dbus_idle_time(*monitor).is_some())
+}
+
+/// Idle seconds from `monitor`. `None` outside Linux or if it does not answer.
+pub fn dbus_idle_time(monitor: DbusMonitor) -> Option<f64> {
+    #[cfg(target_os = "linux")]
+    {
+        let [path, method] = monitor.method();
+        let output = std::process::Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                &format!("--dest={}", monitor.name()),
+                path,
+                method,
+            ])
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| dbus_reply_millis(&String::from_utf8_lossy(&output.stdout)))?
+            .map(|millis| millis as f64 / 1000.0)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = monitor;
+        None
+    }
+}
+
+/// The unsigned integer in a `dbus-send --print-reply` reply, such as
+/// `uint64 5120` on the line after the header.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn dbus_reply_millis(reply: &str) -> Option<u64> {
+    reply.lines().find_map(|line| {
+        let (kind, value) = line.trim().split_once(' ')?;
+        kind.starts_with("uint").then(|| value.parse().ok())?
+    })
+}
+
+/// Identifier of the active Mission Control space; `None` where spaces are not
+/// supported.
+pub fn active_space() -> Option<u64> {
+    #[cfg(target_os = "macos")]
+    {
+        #[link(name = "CoreGraphics", kind = "framework")]
+        extern "C" {
+            fn CGSMainConnectionID() -> i32;
+            fn CGSGetActiveSpace(connection: i32) -> u64;
+        }
+        // Private but long-stable CoreGraphics SPI; 0 means no space could be determined
+        let space = unsafe { CGSGetActiveSpace(CGSMainConnectionID()) };
+        (space != 0).then_some(space)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        None
+    }
+}
+
+/// Whether the screen is being shared or a presentation is running, judged by
+/// a process keeping the display awake (`pmset -g assertions`), as Zoom,
+/// Teams, Keynote and PowerPoint do while sharing or presenting. Always
+/// `false` outside macOS.
+pub fn presenting() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("pmset")
+            .args(["-g", "assertions"])
+            .output()
+            .is_ok_and(|output| holds_display_awake(&String::from_utf8_lossy(&output.stdout)))
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        false
+    }
+}
+
+/// Keystrokes and mouse clicks so far, from CoreGraphics' event counters:
+/// how many, never which keys or where. Needs no permissions. The count
+/// wraps around eventually. `None` outside macOS.
+pub fn input_event_count() -> Option<u64> {
+    #[cfg(target_os = "macos")]
+    {
+        #[link(name = "CoreGraphics", kind = "framework")]
+        extern "C" {
+            fn CGEventSourceCounterForEventType(state: i32, event_type: u32) -> u32;
+        }
+        // kCGEventSourceStateCombinedSessionState = 0; kCGEventLeftMouseDown = 1,
+        // kCGEventRightMouseDown = 3, kCGEventKeyDown = 10
+        let count = |event_type| unsafe { CGEventSourceCounterForEventType(0, event_type) } as u64;
+        Some(count(10) + count(1) + count(3))
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        None
+    }
+}
+
+/// Whether nobody can be using this session: the lock screen is showing, or
+/// fast user switching has put another user's session (or the login window)
+/// on the display. Idle time read from CoreGraphics is not reliable then.
+/// Always `false` outside macOS, and outside a GUI session.
+pub fn screen_locked() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        use std::ffi::c_void;
+        use std::ptr;
+
+        type CFTypeRef = *const c_void;
+
+        #[link(name = "CoreGraphics", kind = "framework")]
+        extern "C" {
+            fn CGSessionCopyCurrentDictionary() -> CFTypeRef;
+        }
+        #[link(name = "CoreFoundation", kind = "framework")]
+        extern "C" {
+            fn CFStringCreateWithCString(
+                allocator: CFTypeRef,
+                c_str: *const libc::c_char,
+                encoding: u32,
+            ) -> CFTypeRef;
+            fn CFDictionaryGetValue(dict: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+            fn CFBooleanGetValue(boolean: CFTypeRef) -> bool;
+            fn CFRelease(cf: CFTypeRef);
+        }
+        const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+
+        // SAFETY: the dictionary and keys created here are released before
+        // returning; values got from the dictionary are not owned.
+        let (locked, on_console) = unsafe {
+            let session = CGSessionCopyCurrentDictionary();
+            if session.is_null() {
+                return false;
+            }
+            let flag = |name: &std::ffi::CStr| {
+                let key =
+                    CFStringCreateWithCString(ptr::null(), name.as_ptr(), CF_STRING_ENCODING_UTF8);
+                let value = CFDictionaryGetValue(session, key);
+                CFRelease(key);
+                (!value.is_null()).then(|| CFBooleanGetValue(value))
+            };
+            // The lock key is only present while the screen is locked
+            let flags = (
+                flag(c"CGSSessionScreenIsLocked").unwrap_or(false),
+                flag(c"kCGSSessionOnConsoleKey").unwrap_or(true),
+            );
+            CFRelease(session);
+            flags
+        };
+        locked || !on_console
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        false
+    }
+}
+
+/// Whether the screen saver is running. Without a lock it does not keep
+/// anyone out, so the time counts as idle rather than locked. Always `false`
+/// outside macOS.
+pub fn screen_saver_running() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("pgrep")
+            .args(["-xq", "ScreenSaverEngine"])
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        false
+    }
+}
+
+/// Whether `pmset -g assertions` output lists a process holding a
+/// PreventUserIdleDisplaySleep assertion. The summary at the top lists every
+/// assertion type with a count, so only the per-process lines are considered.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn holds_display_awake(assertions: &str) -> bool {
+    assertions.lines().any(|line| {
+        line.trim_start().starts_with("pid ") && line.contains("PreventUserIdleDisplaySleep")
+    })
+}
+
+/// Login name of the user running neflo.
+pub fn user_name() -> String {
+    // SAFETY: getpwuid returns null or a pointer to static storage, whose name
+    // is copied out before any other call could overwrite it.
+    let name = unsafe {
+        let passwd = libc::getpwuid(libc::getuid());
+        (!passwd.is_null() && !(*passwd).pw_name.is_null()).then(|| {
+            std::ffi::CStr::from_ptr((*passwd).pw_name)
+                .to_string_lossy()
+                .into_owned()
+        })
+    };
+    name.or_else(|| std::env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Host name of this machine, used as the default device id.
+pub fn device_name() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for `buf.len()` bytes and gethostname NUL-terminates on success.
+    let res = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if res != 0 {
+        return "unknown".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Whether the machine currently runs on battery or AC power; `None` if unknown
+/// (e.g. desktops without a battery).
+pub fn power_source() -> Option<PowerSource> {
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        if text.contains("'Battery Power'") {
+            Some(PowerSource::Battery)
+        } else if text.contains("'AC Power'") {
+            Some(PowerSource::Ac)
+        } else {
+            None
+        }
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let supplies = std::fs::read_dir("/sys/class/power_supply").ok()?;
+        let mut has_battery = false;
+        for supply in supplies.flatten() {
+            let path = supply.path();
+            let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+            match kind.trim() {
+                "Mains"
+                    if std::fs::read_to_string(path.join("online"))
+                        .is_ok_and(|v| v.trim() == "1") =>
+                {
+                    return Some(PowerSource::Ac);
+                }
+                "Battery" => has_battery = true,
+                _ => {}
+            }
+        }
+        has_battery.then_some(PowerSource::Battery)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sleep_detector() {
+        let mut detector = SleepDetector::default();
+        let t0 = Instant::now();
+        let wall = Utc::now();
+        assert_eq!(detector.slept(t0, wall), StdDuration::ZERO);
+
+        // Both clocks moved on together
+        let t1 = t0 + StdDuration::from_secs(1);
+        let wall = wall + chrono::Duration::seconds(1);
+        assert_eq!(detector.slept(t1, wall), StdDuration::ZERO);
+
+        // The wall clock ran 5 minutes ahead while the machine slept
+        let t2 = t1 + StdDuration::from_secs(1);
+        let wall = wall + chrono::Duration::seconds(301);
+        assert_eq!(detector.slept(t2, wall), StdDuration::from_secs(300));
+
+        // A small clock adjustment or a step back is no sleep
+        let t3 = t2 + StdDuration::from_secs(1);
+        assert_eq!(
+            detector.slept(t3, wall + chrono::Duration::milliseconds(1500)),
+            StdDuration::ZERO
+        );
+        let t4 = t3 + StdDuration::from_secs(1);
+        assert_eq!(
+            detector.slept(t4, wall - chrono::Duration::seconds(60)),
+            StdDuration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_falls_back_when_event_source_is_frozen() {
+        for stuck in [0.0, 7.25] {
+            let mut detector = IdleDetector::new();
+            detector.source = IdleSource::EventSource;
+            let t0 = Instant::now();
+            let hid = || Some(42.0);
+
+            assert_eq!(detector.sample(t0, || Some(3.5), hid), 3.5);
+            assert_eq!(detector.sample(t0, || Some(stuck), hid), stuck);
+            let later = t0 + StdDuration::from_secs(FROZEN_SECS);
+            assert_eq!(detector.sample(later, || Some(stuck), hid), 42.0);
+            assert_eq!(detector.source(), IdleSource::HidIdleTime);
+            assert!(!detector.is_frozen());
+        }
+    }
+
+    #[test]
+    fn test_wayland_detection_and_dbus_reply() {
+        let session = |vars: &'static [(&'static str, &'static str)]| {
+            wayland_session(move |name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.into())
+            })
+        };
+        assert!(session(&[("XDG_SESSION_TYPE", "wayland")]));
+        assert!(session(&[("WAYLAND_DISPLAY", "wayland-0")]));
+        assert!(!session(&[("XDG_SESSION_TYPE", "x11")]));
+        assert!(!session(&[]));
+
+        let mutter = "method return time=1718000000.1 sender=:1.20 -> destination=:1.99 serial=812 reply_serial=2\n   uint64 5120\n";
+        assert_eq!(dbus_reply_millis(mutter), Some(5120));
+        let kde = "method return time=1718000000.1 sender=:1.20 -> destination=:1.99 serial=44 reply_serial=2\n   uint32 90000\n";
+        assert_eq!(dbus_reply_millis(kde), Some(90000));
+        assert_eq!(
+            dbus_reply_millis("Error org.freedesktop.DBus.Error.ServiceUnknown"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_display_assertion_means_presenting() {
+        let idle = "Assertion status system-wide:\n   PreventUserIdleDisplaySleep    0\nListed by owning process:\n   pid 88(coreaudiod): [0x0001] 00:01:00 PreventUserIdleSystemSleep named: \"audio\"\n";
+        assert!(!holds_display_awake(idle));
+        let sharing = format!(
+            "{}   pid 412(zoom.us): [0x0002] 00:12:04 PreventUserIdleDisplaySleep named: \"Zoom is sharing screen\"\n",
+            idle
+        );
+        assert!(holds_display_awake(&sharing));
+    }
+
+    #[test]
+    fn test_frozen_without_fallback() {
+        let mut detector = IdleDetector::new();
+        detector.source = IdleSource::EventSource;
+        assert_eq!(detector.sample(Instant::now(), || Some(-1.0), || None), 0.0);
+        assert_eq!(detector.source(), IdleSource::EventSource);
+
+        let t0 = Instant::now();
+        detector.sample(t0, || Some(5.0), || None);
+        assert!(!detector.is_frozen());
+        let later = t0 + StdDuration::from_secs(FROZEN_SECS);
+        assert_eq!(detector.sample(later, || Some(5.0), || None), 5.0);
+        assert!(detector.is_frozen());
+        detector.sample(later, || Some(0.5), || None);
+        assert!(!detector.is_frozen());
     }
 }
@@ -1,11 +1,69 @@
-use crate::models::{Database, Interval, IntervalType};
+use crate::audio::AudioCues;
+use crate::blocker::Blocker;
+use crate::clock::{Clock, SystemClock};
+use crate::holidays::Holidays;
+use crate::inbox::Inbox;
+use crate::models::{
+    Database, Dirty, Gap, Interval, IntervalType, PowerSource, SessionRecord, SpaceSpan,
+};
+use crate::mqtt::MqttPublisher;
+use crate::notify::Notifier;
+use crate::reminder::IdleReminder;
+use crate::scheduler::Scheduler;
+use crate::stats::{calculate_stats, goal_streaks, session_focus, StatsOptions};
 use crate::storage::Storage;
+use crate::trash;
+use crate::utils::{chrono_duration, format_minutes, resolve_local};
+use crate::websocket::EventStream;
 use anyhow::Result;
-use chrono::{DateTime, Local, NaiveTime, Utc};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration as StdDuration;
+
+/// What `reset` removes.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ResetScope {
+    /// Time recorded by the running session
+    Session,
+    /// Time recorded since local midnight
+    Today,
+    /// All recorded intervals
+    All,
+}
+
+/// Forward clock jumps longer than this between two ticks (e.g. waking from
+/// sleep) are recorded as `Away` instead of being attributed to focus or idle.
+const CLOCK_JUMP_THRESHOLD_SECS: i64 = 60;
+
+/// Default for `Tracker::gap_threshold`.
+const GAP_SECS: i64 = 10;
+
+/// How often the inbox directory is checked for events from external tools.
+const INBOX_POLL_SECS: i64 = 5;
+const SAVE_SECS: i64 = 30;
+/// Used instead of `SAVE_SECS` and sampling on every loop iteration when the
+/// battery saver is on and the machine runs on battery.
+const BATTERY_SAVE_SECS: i64 = 300;
+const BATTERY_TICK_SECS: i64 = 5;
+/// How often today's focus is compared with `Tracker::daily_goal`.
+const DAILY_GOAL_CHECK_SECS: i64 = 30;
+
+/// How `update_db` reacts when a transition would leave overlapping intervals.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapMode {
+    /// Silently resolve overlaps, letting the newest interval win.
+    #[default]
+    Lenient,
+    /// Panic in debug builds when an overlap is produced; resolve as lenient
+    /// in release builds.
+    Strict,
+}
 
 pub struct Tracker {
     pub storage: Storage,
-    pub threshold_secs: f64,
+    /// Idle time at or above this counts as idle.
+    pub threshold: StdDuration,
     pub db: Database,
     pub last_kind_seen: Option<IntervalType>,
     pub state_start: DateTime<Utc>,
@@ -15,6 +73,66 @@ pub struct Tracker {
     pub duration: Option<chrono::Duration>,
     pub run_start_time: DateTime<Utc>,
     pub session_ended_saved: bool,
+    /// Focus to reach this session, from `neflo start --goal`.
+    pub session_goal: Option<chrono::Duration>,
+    /// The session goal was reached and announced.
+    pub session_goal_met: bool,
+    /// Today's focus goal (`daily_goal_mins`), celebrated when reached.
+    pub daily_goal: Option<chrono::Duration>,
+    /// When this session saw today's goal reached, and the goal streak that
+    /// day extended.
+    pub daily_goal_met: Option<(DateTime<Utc>, u32)>,
+    /// The day whose goal is known to be reached, announced or not, and
+    /// when it was last checked.
+    daily_goal_day: Option<NaiveDate>,
+    last_daily_goal_check: Option<DateTime<Utc>>,
+    pub overlap_mode: OverlapMode,
+    /// Time without a sample after which a new interval is started and the
+    /// missing stretch kept as a `Gap`.
+    pub gap_threshold: chrono::Duration,
+    pub last_tick: Option<DateTime<Utc>>,
+    pub scheduler: Scheduler,
+    /// Recorded on every new interval; `None` leaves intervals untagged.
+    pub device_id: Option<String>,
+    pub stats_options: StatsOptions,
+    pub inbox: Option<Inbox>,
+    pub last_inbox_poll: DateTime<Utc>,
+    pub audio: Option<AudioCues>,
+    /// Current power source, recorded on every new interval.
+    pub power: Option<PowerSource>,
+    pub battery_saver: bool,
+    /// Seconds between idle-time samples; keyboard input is still polled every 100ms.
+    pub sample_secs: i64,
+    /// Active desktop space, sampled by the UI loop.
+    pub space: Option<u64>,
+    /// Screen sharing or a presentation is active, sampled by the UI loop when
+    /// enabled; such time counts as focus even without input.
+    pub presenting: bool,
+    /// The lock screen is showing, or another user's session is; sampled by
+    /// the UI loop. Such time is recorded as locked whatever the idle time.
+    pub screen_locked: bool,
+    /// The screen saver is running without a lock; sampled by the UI loop.
+    /// Such time is idle whatever the idle time.
+    pub screen_saver: bool,
+    /// Time the machine slept since the previous tick, sampled by the UI loop.
+    pub slept: StdDuration,
+    pub idle_reminder: Option<IdleReminder>,
+    pub notifier: Notifier,
+    pub mqtt: Option<MqttPublisher>,
+    pub websocket: Option<EventStream>,
+    pub blocker: Option<Blocker>,
+    /// Public holidays calendar, marked as days off.
+    pub holidays: Option<Holidays>,
+    /// Start of this device's latest interval and the longest idle time
+    /// sampled during it, which its confidence is derived from.
+    pub peak_idle: Option<(DateTime<Utc>, StdDuration)>,
+    /// Input event counter, sampled by the UI loop with `track_intensity`.
+    pub input_events: Option<u64>,
+    /// The counter at the previous tick, and this device's latest focus
+    /// interval with the events counted during it.
+    last_input_events: Option<u64>,
+    interval_events: Option<(DateTime<Utc>, u64)>,
+    pub clock: Box<dyn Clock + Send>,
 }
 
 impl Tracker {
@@ -24,9 +142,28 @@ impl Tracker {
         start_time: Option<String>,
         end_time: Option<String>,
         duration: Option<String>,
+    ) -> Result<Self> {
+        Self::with_clock(
+            storage,
+            threshold_mins,
+            start_time,
+            end_time,
+            duration,
+            Box::new(SystemClock),
+        )
+    }
+
+    /// Like `new`, reading the time from `clock` instead of the system clock.
+    pub fn with_clock(
+        storage: Storage,
+        threshold_mins: u64,
+        start_time: Option<String>,
+        end_time: Option<String>,
+        duration: Option<String>,
+        clock: Box<dyn Clock + Send>,
     ) -> Result<Self> {
         let db = storage.load()?;
-        let now = Utc::now();
+        let now = clock.now();
 
         let parsed_start_time = start_time
             .map(|s| NaiveTime::parse_from_str(&s, "%H:%M"))
@@ -43,7 +180,7 @@ impl Tracker {
 
         let mut tracker = Self {
             storage,
-            threshold_secs: (threshold_mins * 60) as f64,
+            threshold: StdDuration::from_secs(threshold_mins * 60),
             db,
             last_kind_seen: None,
             state_start: now,
@@ -53,104 +190,602 @@ impl Tracker {
             duration: parsed_duration,
             run_start_time: now,
             session_ended_saved: false,
+            overlap_mode: OverlapMode::default(),
+            gap_threshold: chrono::Duration::seconds(GAP_SECS),
+            last_tick: None,
+            scheduler: Scheduler::default(),
+            device_id: None,
+            stats_options: StatsOptions::default(),
+            inbox: None,
+            last_inbox_poll: now,
+            audio: None,
+            power: None,
+            battery_saver: false,
+            sample_secs: 1,
+            space: None,
+            presenting: false,
+            screen_locked: false,
+            screen_saver: false,
+            slept: StdDuration::ZERO,
+            idle_reminder: None,
+            notifier: Notifier::default(),
+            mqtt: None,
+            websocket: None,
+            blocker: None,
+            holidays: None,
+            peak_idle: None,
+            input_events: None,
+            last_input_events: None,
+            interval_events: None,
+            session_goal: None,
+            session_goal_met: false,
+            daily_goal: None,
+            daily_goal_met: None,
+            daily_goal_day: None,
+            last_daily_goal_check: None,
+            clock,
         };
         tracker.prune_old_data();
         Ok(tracker)
     }
 
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
     pub fn should_track(&self, now: DateTime<Utc>) -> bool {
+        self.should_track_in(&Local, now)
+    }
+
+    pub fn should_stop(&self, now: DateTime<Utc>) -> bool {
+        self.should_stop_in(&Local, now)
+    }
+
+    // The schedule is compared as instants rather than wall-clock times, so
+    // that clocks falling back do not restart a session that already ended.
+    fn should_track_in<Tz: TimeZone>(&self, tz: &Tz, now: DateTime<Utc>) -> bool {
         if self.duration.is_some() {
             return true;
         }
         if let Some(st) = self.start_time {
-            if now.with_timezone(&Local).time() < st {
+            let today = now.with_timezone(tz).date_naive();
+            // A scheduled session takes days off
+            if now < resolve_local(tz, today, st) || self.db.days_off.contains_key(&today) {
                 return false;
             }
         }
         true
     }
 
-    pub fn should_stop(&self, now: DateTime<Utc>) -> bool {
+    fn should_stop_in<Tz: TimeZone>(&self, tz: &Tz, now: DateTime<Utc>) -> bool {
         if let Some(duration) = self.duration {
             if now - self.run_start_time >= duration {
                 return true;
             }
         } else if let Some(et) = self.end_time {
-            if now.with_timezone(&Local).time() >= et {
+            let today = now.with_timezone(tz).date_naive();
+            if now >= resolve_local(tz, today, et) {
                 return true;
             }
         }
         false
     }
 
-    pub fn tick(&mut self, idle_time: f64, now: DateTime<Utc>) -> Result<()> {
-        let current_kind = if idle_time >= self.threshold_secs {
+    /// When the session is set to end, if it has a duration or an end time
+    /// still ahead today.
+    pub fn planned_end(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if let Some(duration) = self.duration {
+            return Some(self.run_start_time + duration);
+        }
+        let end_time = self.end_time?;
+        let end = resolve_local(&Local, now.with_timezone(&Local).date_naive(), end_time);
+        (end > now).then_some(end)
+    }
+
+    pub fn tick(&mut self, idle_time: StdDuration, now: DateTime<Utc>) -> Result<()> {
+        let mut clock_reset = false;
+        if let Some(last_tick) = self.last_tick {
+            if now < last_tick {
+                if last_tick - now <= chrono::Duration::seconds(CLOCK_JUMP_THRESHOLD_SECS) {
+                    // The clock moved back a little (e.g. NTP correction). Intervals stay closed
+                    // at the last known time; recording resumes once the clock passes it again.
+                    return Ok(());
+                }
+                // Set back further, waiting for the clock to catch up would drop every sample
+                // until then. Recording starts over at `now` instead, and what was recorded
+                // after it is trimmed as an overlap.
+                self.db.gaps.push(Gap {
+                    start: now,
+                    end: last_tick,
+                    device_id: self.device_id.clone(),
+                });
+                self.last_kind_seen = None;
+                clock_reset = true;
+            } else if !self.slept.is_zero()
+                || now - last_tick > chrono::Duration::seconds(CLOCK_JUMP_THRESHOLD_SECS)
+            {
+                // After sleep, however short, the intervals are cut at the last
+                // sample before it instead of being extended across it
+                self.record_away(last_tick, now);
+            }
+        }
+        self.last_tick = Some(now);
+        self.slept = StdDuration::ZERO;
+
+        let current_kind = if self.screen_locked {
+            IntervalType::Locked
+        } else if self.screen_saver || (idle_time >= self.threshold && !self.presenting) {
             IntervalType::Idle
         } else {
             IntervalType::Focus
         };
 
+        if clock_reset {
+            self.db.intervals.push(Interval {
+                device_id: self.device_id.clone(),
+                power: self.power,
+                ..Interval::new_at(current_kind, now)
+            });
+            self.db.resolve_overlaps();
+            self.db.mark_dirty(Dirty::Full);
+        }
+
         // Update database
         self.update_db(current_kind, idle_time, now);
+        // Nobody is working behind the screen saver, however short the idle time
+        self.update_confidence(if self.screen_saver {
+            self.threshold * 2
+        } else {
+            idle_time
+        });
+        self.update_intensity();
+        self.update_space(now);
+
+        // Optional integrations must not keep the tick from recording and
+        // saving; their errors are reported once it has
+        let mut failures = Vec::new();
 
         // Handle state transition
+        let previous_kind = self.last_kind_seen;
         if Some(current_kind) != self.last_kind_seen {
             self.state_start = now;
             self.last_kind_seen = Some(current_kind);
-            self.storage.save(&self.db)?;
+            self.storage.save_dirty(&mut self.db)?;
             self.last_save = now;
+            if let Some(mqtt) = &mut self.mqtt {
+                failures.extend(mqtt.publish_state(current_kind).err());
+            }
+            if let Some(websocket) = &mut self.websocket {
+                websocket.publish_state(current_kind, now);
+            }
+        }
+        if let Some(websocket) = &mut self.websocket {
+            if websocket.stats_due(now) {
+                let stats =
+                    calculate_stats(&self.db, None, &self.stats_options, self.clock.as_ref());
+                websocket.publish_stats(current_kind, self.state_start, &stats.today_summary, now);
+            }
+        }
+
+        if let Some(blocker) = &mut self.blocker {
+            failures.extend(blocker.update(current_kind, now - self.state_start).err());
+        }
+
+        if let Some(audio) = &mut self.audio {
+            let cue = audio.cue_for(
+                previous_kind,
+                current_kind,
+                self.state_start.with_timezone(&Local),
+                now.with_timezone(&Local),
+            );
+            if let Some(cue) = cue {
+                failures.extend(audio.play(cue).err());
+            }
         }
 
-        // Save every 30 seconds
-        if now - self.last_save > chrono::Duration::seconds(30) {
+        if let Some(reminder) = &mut self.idle_reminder {
+            if let Some(message) = reminder.check(idle_time, now.with_timezone(&Local)) {
+                failures.extend(
+                    self.notifier
+                        .notify(&message, now.with_timezone(&Local))
+                        .err(),
+                );
+            }
+        }
+        if let (Some(goal), false) = (self.session_goal, self.session_goal_met) {
+            if session_focus(&self.db, self.run_start_time, self.device_id.as_deref()) >= goal {
+                self.session_goal_met = true;
+                let message = format!("Session goal of {} focus reached", format_minutes(goal));
+                failures.extend(
+                    self.notifier
+                        .notify(&message, now.with_timezone(&Local))
+                        .err(),
+                );
+            }
+        }
+        failures.extend(self.check_daily_goal(now).err());
+        failures.extend(self.notifier.flush(now.with_timezone(&Local)).err());
+
+        // Pick up events dropped by external tools
+        if now - self.last_inbox_poll >= chrono::Duration::seconds(INBOX_POLL_SECS) {
+            self.last_inbox_poll = now;
+            if let Some(inbox) = &self.inbox {
+                if inbox.ingest(&mut self.db)?.changed() {
+                    self.db.mark_dirty(Dirty::Full);
+                    self.storage.save_dirty(&mut self.db)?;
+                    self.last_save = now;
+                }
+            }
+        }
+
+        // Save periodically, skipping the write when nothing changed
+        if now - self.last_save > self.save_interval() {
             self.prune_old_data();
-            self.storage.save(&self.db)?;
+            self.storage.save_dirty(&mut self.db)?;
             self.last_save = now;
+            if let Some(mqtt) = &mut self.mqtt {
+                let stats =
+                    calculate_stats(&self.db, None, &self.stats_options, self.clock.as_ref());
+                failures.extend(mqtt.publish_today(&stats.today_summary).err());
+            }
         }
 
+        combined(failures)
+    }
+
+    /// Announce today's focus reaching `daily_goal`, with the goal streak,
+    /// once a day. A goal already reached when the session started is not
+    /// announced again.
+    fn check_daily_goal(&mut self, now: DateTime<Utc>) -> Result<()> {
+        let Some(goal) = self.daily_goal else {
+            return Ok(());
+        };
+        let today = now.with_timezone(&Local).date_naive();
+        let first_check = self.last_daily_goal_check.is_none();
+        if self.daily_goal_day == Some(today)
+            || self
+                .last_daily_goal_check
+                .is_some_and(|t| now - t < chrono::Duration::seconds(DAILY_GOAL_CHECK_SECS))
+        {
+            return Ok(());
+        }
+        self.last_daily_goal_check = Some(now);
+        let stats = calculate_stats(&self.db, None, &self.stats_options, &now);
+        if stats
+            .daily_stats
+            .get(&today)
+            .is_none_or(|day| day.off.is_some() || day.total_focus < goal)
+        {
+            return Ok(());
+        }
+        self.daily_goal_day = Some(today);
+        if first_check {
+            return Ok(());
+        }
+        let first = stats.daily_stats.keys().next().copied().unwrap_or(today);
+        let streak = goal_streaks(&stats.daily_stats, first, today, goal).current;
+        self.daily_goal_met = Some((now, streak));
+        let message = match streak {
+            0 | 1 => format!("Daily goal of {} focus reached!", format_minutes(goal)),
+            days => format!(
+                "Daily goal of {} focus reached, {} days in a row!",
+                format_minutes(goal),
+                days
+            ),
+        };
+        self.notifier.notify(&message, now.with_timezone(&Local))
+    }
+
+    /// Whether the screen is locked or the screen saver is running, so that
+    /// idle time is not read.
+    pub fn screen_inactive(&self) -> bool {
+        self.screen_locked || self.screen_saver
+    }
+
+    /// Persist the settings of this session, unless it is a restarted session
+    /// that is already recorded.
+    pub fn record_session(&mut self) {
+        if self.current_session().is_some() {
+            return;
+        }
+        self.db.sessions.push(SessionRecord {
+            start: self.run_start_time,
+            end: None,
+            threshold_mins: self.threshold.as_secs() / 60,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            duration_mins: self.duration.map(|d| d.num_minutes()),
+            device_id: self.device_id.clone(),
+        });
+        self.db.mark_dirty(Dirty::Full);
+    }
+
+    /// Close the record of this session.
+    pub fn end_session(&mut self, now: DateTime<Utc>) {
+        if let Some(session) = self.current_session() {
+            session.end = Some(now);
+            self.db.mark_dirty(Dirty::Full);
+        }
+    }
+
+    fn current_session(&mut self) -> Option<&mut SessionRecord> {
+        let (start, device_id) = (self.run_start_time, &self.device_id);
+        self.db
+            .sessions
+            .iter_mut()
+            .rev()
+            .find(|s| s.start == start && s.device_id == *device_id)
+    }
+
+    /// Lift a site block, e.g. when the session ends.
+    pub fn release_blocker(&mut self) -> Result<()> {
+        match &mut self.blocker {
+            Some(blocker) => blocker.release(),
+            None => Ok(()),
+        }
+    }
+
+    /// Run periodic background jobs such as the scheduled weekly report and
+    /// the holidays refresh.
+    pub fn run_scheduled_tasks(&mut self, now: DateTime<Utc>) -> Result<()> {
+        self.scheduler
+            .poll(&self.db, now.with_timezone(&Local).naive_local())?;
+        if let Some(holidays) = &mut self.holidays {
+            if holidays.refresh(&mut self.db, now)? {
+                self.db.mark_dirty(Dirty::Full);
+            }
+        }
         Ok(())
     }
 
-    pub fn reset(&mut self) -> Result<()> {
-        self.db.intervals.clear();
-        self.storage.save(&self.db)?;
+    /// Record an unobserved period (sleep, forward clock jump) and force the next
+    /// sample to be handled as a fresh state.
+    pub fn record_away(&mut self, from: DateTime<Utc>, to: DateTime<Utc>) {
+        let mut away = Interval::new_at(IntervalType::Away, from);
+        away.end = to;
+        away.device_id = self.device_id.clone();
+        self.db.intervals.push(away);
+        self.db.resolve_overlaps();
+        self.db.mark_dirty(Dirty::Full);
+        self.last_kind_seen = None;
+    }
+
+    /// Replace the in-memory database with what another process saved, for read-only
+    /// observers. The status follows the newest interval while it is still open.
+    pub fn reload(&mut self, now: DateTime<Utc>) -> Result<()> {
+        self.db = self.storage.load()?;
+        self.last_kind_seen = self
+            .db
+            .intervals
+            .iter()
+            .max_by_key(|i| i.end)
+            .filter(|i| now - i.end <= chrono::Duration::seconds(CLOCK_JUMP_THRESHOLD_SECS))
+            .map(|i| i.kind);
         Ok(())
     }
 
+    fn saving_battery(&self) -> bool {
+        self.battery_saver && self.power == Some(PowerSource::Battery)
+    }
+
+    fn save_interval(&self) -> chrono::Duration {
+        chrono::Duration::seconds(if self.saving_battery() {
+            BATTERY_SAVE_SECS
+        } else {
+            SAVE_SECS
+        })
+    }
+
+    /// Minimum time between samples.
+    pub fn tick_interval(&self) -> chrono::Duration {
+        chrono::Duration::seconds(if self.saving_battery() {
+            self.sample_secs.max(BATTERY_TICK_SECS)
+        } else {
+            self.sample_secs
+        })
+    }
+
+    /// Remove the intervals in `scope`, moving them to the trash so the reset
+    /// can be undone. Returns the number of intervals affected.
+    pub fn reset(&mut self, scope: ResetScope, now: DateTime<Utc>) -> Result<usize> {
+        let since = match scope {
+            ResetScope::Session => Some(self.run_start_time),
+            ResetScope::Today => now
+                .with_timezone(&Local)
+                .date_naive()
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(Local)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            ResetScope::All => None,
+        };
+        let removed = self.db.remove_intervals_since(since);
+        let count = removed.len();
+        let keep = count > 0 && !self.storage.dry_run;
+        if let (true, Some(trash_dir)) = (keep, self.storage.trash_dir()) {
+            let entry = trash::Entry {
+                intervals: removed,
+                ..Default::default()
+            };
+            trash::save(&trash_dir, "reset", &entry, now)?;
+        }
+        self.storage.save(&self.db)?;
+        Ok(count)
+    }
+
     pub fn prune_old_data(&mut self) {
-        let thirty_days_ago = Utc::now() - chrono::Duration::days(30);
+        let thirty_days_ago = self.clock.now() - chrono::Duration::days(30);
+        let before = self.db.intervals.len();
         self.db.intervals.retain(|i| i.end > thirty_days_ago);
+        let spaces_before = self.db.spaces.len();
+        self.db.spaces.retain(|s| s.end > thirty_days_ago);
+        let markers_before = self.db.markers.len();
+        self.db.markers.retain(|m| m.at > thirty_days_ago);
+        let gaps_before = self.db.gaps.len();
+        self.db.gaps.retain(|g| g.end > thirty_days_ago);
+        let sessions_before = self.db.sessions.len();
+        self.db
+            .sessions
+            .retain(|s| s.end.unwrap_or(s.start) > thirty_days_ago);
+        if self.db.intervals.len() != before
+            || self.db.spaces.len() != spaces_before
+            || self.db.markers.len() != markers_before
+            || self.db.gaps.len() != gaps_before
+            || self.db.sessions.len() != sessions_before
+        {
+            self.db.mark_dirty(Dirty::Full);
+        }
+    }
+
+    /// Fold `idle_time` into the confidence of this device's latest interval.
+    fn update_confidence(&mut self, idle_time: StdDuration) {
+        let device_id = &self.device_id;
+        let Some(interval) = self
+            .db
+            .intervals
+            .iter_mut()
+            .rev()
+            .find(|i| i.device_id == *device_id)
+        else {
+            return;
+        };
+        let peak = match self.peak_idle {
+            Some((start, peak)) if start == interval.start => peak.max(idle_time),
+            _ => idle_time,
+        };
+        self.peak_idle = Some((interval.start, peak));
+        interval.confidence = confidence(interval.kind, peak, self.threshold);
+    }
+
+    /// Add the input events since the last tick to this device's latest
+    /// interval, if it is focus, and store its events per minute.
+    fn update_intensity(&mut self) {
+        let Some(count) = self.input_events else {
+            return;
+        };
+        // The counter wraps around; that tick counts nothing
+        let new = self
+            .last_input_events
+            .map_or(0, |last| count.saturating_sub(last));
+        self.last_input_events = Some(count);
+        let device_id = &self.device_id;
+        let mut intervals = self
+            .db
+            .intervals
+            .iter_mut()
+            .rev()
+            .filter(|i| i.device_id == *device_id);
+        let Some(latest) = intervals.next() else {
+            return;
+        };
+        if latest.kind != IntervalType::Focus {
+            // Focus turned into idle when the idle start was backdated
+            latest.intensity = None;
+        }
+        let focus = match self.interval_events {
+            _ if latest.kind == IntervalType::Focus => latest,
+            // Idle may have cut the previous focus short, which changes its rate
+            Some((start, _)) => match intervals.next().filter(|i| i.start == start) {
+                Some(previous) => previous,
+                None => return,
+            },
+            None => return,
+        };
+        let events = match self.interval_events {
+            Some((start, events)) if start == focus.start => events + new,
+            _ => new,
+        };
+        self.interval_events = Some((focus.start, events));
+        let minutes = (focus.end - focus.start).num_seconds() as f32 / 60.0;
+        focus.intensity = (minutes > 0.0).then(|| events as f32 / minutes);
+    }
+
+    /// Extend the current space span, or split the timeline where the space
+    /// changed: the previous span runs up to `now` and the new one starts
+    /// there, so time before the switch is never credited to the new space.
+    fn update_space(&mut self, now: DateTime<Utc>) {
+        let Some(space) = self.space else {
+            return;
+        };
+        let contiguous = self
+            .db
+            .spaces
+            .last()
+            .is_some_and(|last| now >= last.end && now - last.end <= self.gap_threshold);
+        match self.db.spaces.last_mut() {
+            Some(last) if contiguous && last.space == space => {
+                last.end = now;
+                self.db.mark_dirty(Dirty::Tail);
+            }
+            last => {
+                if let Some(last) = last.filter(|_| contiguous) {
+                    last.end = now;
+                }
+                self.db.spaces.push(SpaceSpan {
+                    start: now,
+                    end: now,
+                    space,
+                });
+                self.db.mark_dirty(Dirty::Full);
+            }
+        }
     }
 
     pub fn update_db(
         &mut self,
         current_kind: IntervalType,
-        idle_time: f64,
+        idle_time: StdDuration,
         now: chrono::DateTime<Utc>,
     ) {
         let db = &mut self.db;
-        let gap_threshold = chrono::Duration::seconds(10);
+        let device_id = self.device_id.clone();
+        let power = self.power;
+        let new_at = |kind: IntervalType, at: DateTime<Utc>| Interval {
+            device_id: device_id.clone(),
+            power,
+            ..Interval::new_at(kind, at)
+        };
 
-        if db.intervals.is_empty() {
-            db.intervals.push(Interval::new_at(current_kind, now));
+        // Only this device's latest interval can be extended
+        let Some(last_idx) = db.intervals.iter().rposition(|i| i.device_id == device_id) else {
+            db.intervals.push(new_at(current_kind, now));
+            db.mark_dirty(Dirty::Full);
             return;
-        }
-
-        let last_idx = db.intervals.len() - 1;
+        };
 
-        // If it's been a long time since the last update, start a new interval
-        if now - db.intervals[last_idx].end > gap_threshold {
-            db.intervals.push(Interval::new_at(current_kind, now));
+        // If it's been a long time since the last update, or we are coming back from an
+        // unobserved period, start a new interval
+        let last = &db.intervals[last_idx];
+        if now - last.end > self.gap_threshold || last.kind == IntervalType::Away {
+            // Time since an earlier run is not a gap
+            if last.kind != IntervalType::Away && last.end >= self.run_start_time {
+                db.gaps.push(Gap {
+                    start: last.end,
+                    end: now,
+                    device_id: device_id.clone(),
+                });
+            }
+            db.intervals.push(new_at(current_kind, now));
+            db.mark_dirty(Dirty::Full);
             return;
         }
 
         if db.intervals[last_idx].kind == current_kind {
             db.intervals[last_idx].end = now;
+            // The tail file only covers the newest interval overall
+            if last_idx == db.intervals.len() - 1 {
+                db.mark_dirty(Dirty::Tail);
+            } else {
+                db.mark_dirty(Dirty::Full);
+            }
         } else {
+            db.mark_dirty(Dirty::Full);
             // Transition
-            if current_kind == IntervalType::Idle {
+            if current_kind == IntervalType::Idle
+                && db.intervals[last_idx].kind == IntervalType::Focus
+            {
                 // Focus -> Idle
-                let idle_start = now - chrono::Duration::seconds(idle_time as i64);
+                let idle_start = now - chrono_duration(idle_time);
 
                 if idle_start <= db.intervals[last_idx].start {
                     // Backdated idle start is before or at the start of the current Focus interval.
@@ -160,43 +795,471 @@ impl Tracker {
                 } else {
                     // Split the interval
                     db.intervals[last_idx].end = idle_start;
-                    let mut new_interval = Interval::new_at(IntervalType::Idle, now);
+                    let mut new_interval = new_at(IntervalType::Idle, now);
                     new_interval.start = idle_start;
                     new_interval.end = now;
                     db.intervals.push(new_interval);
                 }
             } else {
-                // Idle -> Focus
+                // Idle -> Focus, or the screen locking or unlocking, from now on.
+                // Idle after the lock is not backdated into the locked time
                 db.intervals[last_idx].end = now;
-                db.intervals
-                    .push(Interval::new_at(IntervalType::Focus, now));
+                db.intervals.push(new_at(current_kind, now));
             }
         }
 
-        // Cleanup: remove 0 or negative duration intervals if any (shouldn't really happen but for safety)
-        db.intervals.retain(|i| i.end >= i.start);
+        if self.overlap_mode == OverlapMode::Strict {
+            debug_assert!(
+                db.is_consistent(),
+                "update_db left overlapping or inverted intervals at {}",
+                now
+            );
+        }
+
+        // Cleanup: drop negative duration intervals and trim overlaps, e.g. after the clock moved backwards
+        db.resolve_overlaps();
+    }
+}
+
+/// A single error for `failures`, if there are any.
+fn combined(mut failures: Vec<anyhow::Error>) -> Result<()> {
+    match failures.len() {
+        0 => Ok(()),
+        1 => Err(failures.remove(0)),
+        _ => Err(anyhow::anyhow!(failures
+            .iter()
+            .map(|err| format!("{:#}", err))
+            .collect::<Vec<_>>()
+            .join("; "))),
     }
 }
 
+/// How far the longest idle time sampled during an interval stayed from the
+/// threshold, relative to it: 1 for focus without a gap in input, for idle
+/// of at least twice the threshold and for locked time, 0 right at the
+/// threshold. Rounded to two decimals; `None` for away time.
+pub fn confidence(
+    kind: IntervalType,
+    peak_idle: StdDuration,
+    threshold: StdDuration,
+) -> Option<f32> {
+    let ratio = peak_idle.div_duration_f64(threshold);
+    let margin = match kind {
+        IntervalType::Focus => 1.0 - ratio,
+        IntervalType::Idle => ratio - 1.0,
+        // Nobody can be working behind the lock screen
+        IntervalType::Locked => 1.0,
+        IntervalType::Away => return None,
+    };
+    Some(((margin.clamp(0.0, 1.0) * 100.0).round() / 100.0) as f32)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::FixedClock;
+    use crate::inbox::InboxEvent;
+    use crate::stats::focus_by_space;
     use crate::storage::Storage;
-    use chrono::TimeZone;
-    use std::path::PathBuf;
+    use chrono::{FixedOffset, LocalResult, NaiveDateTime, Offset, TimeZone};
+    use proptest::prelude::*;
+
+    fn secs(secs: u64) -> StdDuration {
+        StdDuration::from_secs(secs)
+    }
 
-    fn setup_tracker(path: PathBuf) -> Tracker {
-        let storage = Storage::from_path(path);
+    fn setup_tracker(storage: Storage) -> Tracker {
         Tracker::new(storage, 5, None, None, None).unwrap() // 5 mins threshold
     }
 
+    /// A time zone whose clocks change once, at `at` (UTC).
+    #[derive(Debug, Clone, Copy)]
+    struct OneChange {
+        at: NaiveDateTime,
+        before: FixedOffset,
+        after: FixedOffset,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct OneChangeOffset(OneChange, FixedOffset);
+
+    impl Offset for OneChangeOffset {
+        fn fix(&self) -> FixedOffset {
+            self.1
+        }
+    }
+
+    impl TimeZone for OneChange {
+        type Offset = OneChangeOffset;
+
+        fn from_offset(offset: &OneChangeOffset) -> Self {
+            offset.0
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<OneChangeOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(
+            &self,
+            local: &NaiveDateTime,
+        ) -> LocalResult<OneChangeOffset> {
+            let fits = |offset: FixedOffset| {
+                let utc = *local - chrono::Duration::seconds(offset.local_minus_utc() as i64);
+                (self.offset_from_utc_datetime(&utc).1 == offset).then_some(offset)
+            };
+            match (fits(self.before), fits(self.after)) {
+                (Some(first), Some(second)) => LocalResult::Ambiguous(
+                    OneChangeOffset(*self, first),
+                    OneChangeOffset(*self, second),
+                ),
+                (Some(offset), None) | (None, Some(offset)) => {
+                    LocalResult::Single(OneChangeOffset(*self, offset))
+                }
+                (None, None) => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> OneChangeOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> OneChangeOffset {
+            let offset = if *utc < self.at {
+                self.before
+            } else {
+                self.after
+            };
+            OneChangeOffset(*self, offset)
+        }
+    }
+
+    fn hours_east(hours: i32) -> FixedOffset {
+        FixedOffset::east_opt(hours * 3600).unwrap()
+    }
+
+    fn scheduled(start: &str, end: &str) -> Tracker {
+        Tracker::new(
+            Storage::in_memory(),
+            5,
+            Some(start.to_string()),
+            Some(end.to_string()),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_session_goal_is_met_when_session_focus_reaches_it() -> Result<()> {
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let clock = FixedClock::new(t1);
+        let mut tracker = Tracker::with_clock(
+            Storage::in_memory(),
+            5,
+            None,
+            None,
+            None,
+            Box::new(clock.clone()),
+        )?;
+        tracker.db = Database::default();
+        tracker.notifier = Notifier::from_config(&crate::config::NotificationsConfig {
+            enabled: false,
+            ..Default::default()
+        })?;
+        tracker.session_goal = Some(chrono::Duration::minutes(2));
+
+        for secs in (0..120).step_by(5) {
+            tracker.tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(secs))?;
+        }
+        assert!(!tracker.session_goal_met);
+        tracker.tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(120))?;
+        assert!(tracker.session_goal_met);
+        Ok(())
+    }
+
+    #[test]
+    fn test_daily_goal_is_celebrated_once() -> Result<()> {
+        let t1 = Local
+            .with_ymd_and_hms(2023, 1, 2, 10, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut tracker = Tracker::with_clock(
+            Storage::in_memory(),
+            5,
+            None,
+            None,
+            None,
+            Box::new(FixedClock::new(t1)),
+        )?;
+        tracker.db = Database::default();
+        // Yesterday's goal was met too
+        let mut yesterday = Interval::new_at(IntervalType::Focus, t1 - chrono::Duration::days(1));
+        yesterday.end = yesterday.start + chrono::Duration::minutes(5);
+        tracker.db.intervals.push(yesterday);
+        tracker.notifier = Notifier::from_config(&crate::config::NotificationsConfig {
+            enabled: false,
+            ..Default::default()
+        })?;
+        tracker.daily_goal = Some(chrono::Duration::minutes(2));
+
+        for secs in (0..=150).step_by(5) {
+            tracker.tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(secs))?;
+        }
+        let (at, streak) = tracker.daily_goal_met.unwrap();
+        assert!(at >= t1 + chrono::Duration::minutes(2));
+        assert_eq!(streak, 2);
+
+        for secs in (155..=300).step_by(5) {
+            tracker.tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(secs))?;
+        }
+        assert_eq!(tracker.daily_goal_met, Some((at, streak)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_daily_goal_met_before_the_session_is_not_announced() -> Result<()> {
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 2, 10, 0, 0).unwrap();
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let mut earlier = Interval::new_at(IntervalType::Focus, t1 - chrono::Duration::minutes(10));
+        earlier.end = t1 - chrono::Duration::minutes(5);
+        tracker.db.intervals.push(earlier);
+        tracker.daily_goal = Some(chrono::Duration::minutes(2));
+
+        tracker.check_daily_goal(t1)?;
+        tracker.check_daily_goal(t1 + chrono::Duration::minutes(1))?;
+        assert_eq!(tracker.daily_goal_met, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule_across_spring_forward() {
+        // 02:00 local becomes 03:00; 02:30 never happens
+        let zone = OneChange {
+            at: Utc
+                .with_ymd_and_hms(2024, 3, 31, 1, 0, 0)
+                .unwrap()
+                .naive_utc(),
+            before: hours_east(1),
+            after: hours_east(2),
+        };
+        let jump = Utc.with_ymd_and_hms(2024, 3, 31, 1, 0, 0).unwrap();
+        let just_before = jump - chrono::Duration::seconds(1);
+
+        let tracker = scheduled("02:30", "02:30");
+        assert!(!tracker.should_stop_in(&zone, just_before));
+        assert!(tracker.should_stop_in(&zone, jump));
+        assert!(!tracker.should_track_in(&zone, just_before));
+        assert!(tracker.should_track_in(&zone, jump));
+        assert_eq!(
+            resolve_local(&zone, jump.date_naive(), "02:30".parse().unwrap()),
+            jump
+        );
+    }
+
+    #[test]
+    fn test_schedule_across_fall_back() {
+        // 03:00 local becomes 02:00; 02:00-03:00 happens twice
+        let zone = OneChange {
+            at: Utc
+                .with_ymd_and_hms(2024, 10, 27, 1, 0, 0)
+                .unwrap()
+                .naive_utc(),
+            before: hours_east(2),
+            after: hours_east(1),
+        };
+        let first = Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0).unwrap();
+        let second = Utc.with_ymd_and_hms(2024, 10, 27, 1, 30, 0).unwrap();
+
+        let tracker = scheduled("02:30", "02:30");
+        assert!(!tracker.should_stop_in(&zone, first - chrono::Duration::seconds(1)));
+        assert!(tracker.should_stop_in(&zone, first));
+        // The second 02:10 comes after the end; the session must not resume
+        assert!(tracker.should_stop_in(&zone, second - chrono::Duration::minutes(20)));
+        assert!(tracker.should_stop_in(&zone, second));
+
+        assert!(tracker.should_track_in(&zone, first));
+        assert!(tracker.should_track_in(&zone, second - chrono::Duration::minutes(20)));
+    }
+
+    #[test]
+    fn test_confidence_tracks_peak_idle_per_interval() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let at = |secs| t1 + chrono::Duration::seconds(secs);
+        let sample = |tracker: &mut Tracker, idle: StdDuration, at_secs| {
+            let kind = if idle >= tracker.threshold {
+                IntervalType::Idle
+            } else {
+                IntervalType::Focus
+            };
+            tracker.update_db(kind, idle, at(at_secs));
+            tracker.update_confidence(idle);
+        };
+
+        sample(&mut tracker, StdDuration::ZERO, 0);
+        assert_eq!(tracker.db.intervals[0].confidence, Some(1.0));
+        // A long pause in input makes the focus interval a close call
+        sample(&mut tracker, secs(270), 5);
+        sample(&mut tracker, secs(1), 10);
+        assert_eq!(tracker.db.intervals[0].confidence, Some(0.1));
+        assert!(tracker.db.intervals[0].is_borderline());
+
+        sample(&mut tracker, secs(450), 460);
+        let idle = tracker.db.intervals.last().unwrap();
+        assert_eq!(idle.kind, IntervalType::Idle);
+        assert_eq!(idle.confidence, Some(0.5));
+        assert_eq!(tracker.db.intervals[0].confidence, Some(0.1));
+        assert_eq!(
+            confidence(IntervalType::Idle, secs(900), secs(300)),
+            Some(1.0)
+        );
+        assert_eq!(confidence(IntervalType::Away, secs(900), secs(300)), None);
+    }
+
+    #[test]
+    fn test_intensity_counts_input_events_per_minute_of_focus() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+        let at = |n: i64| t1 + chrono::Duration::seconds(5 * n);
+
+        // Events before the first sample are not counted
+        tracker.input_events = Some(1000);
+        tracker.tick(secs(0), t1).unwrap();
+        for n in 1..=120 {
+            tracker.input_events = Some(1000 + 10 * n as u64);
+            tracker.tick(secs(0), at(n)).unwrap();
+        }
+        assert_eq!(tracker.db.intervals[0].intensity, Some(120.0));
+
+        // Idle backdated into the focus leaves it shorter, with the same events
+        for n in 121..=126 {
+            tracker
+                .tick(secs(305 + 5 * (n - 121) as u64), at(n))
+                .unwrap();
+        }
+        assert_eq!(tracker.db.intervals[0].end, at(60));
+        assert_eq!(tracker.db.intervals[0].intensity, Some(240.0));
+        assert_eq!(tracker.db.intervals[1].intensity, None);
+
+        // The next focus interval starts afresh; a wrapped counter counts nothing
+        tracker.input_events = Some(1000);
+        tracker.tick(secs(0), at(127)).unwrap();
+        for n in 128..=139 {
+            tracker.input_events = Some(1000 + 3 * (n - 127) as u64);
+            tracker.tick(secs(0), at(n)).unwrap();
+        }
+        assert_eq!(tracker.db.intervals[2].intensity, Some(36.0));
+    }
+
+    #[test]
+    fn test_presenting_counts_as_focus() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+
+        tracker.presenting = true;
+        tracker.tick(secs(600), t1).unwrap();
+        assert_eq!(tracker.last_kind_seen, Some(IntervalType::Focus));
+        tracker.presenting = false;
+        tracker
+            .tick(secs(601), t1 + chrono::Duration::seconds(1))
+            .unwrap();
+        assert_eq!(tracker.last_kind_seen, Some(IntervalType::Idle));
+    }
+
+    #[test]
+    fn test_screen_lock_is_recorded_as_locked_immediately() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+        tracker.tick(secs(0), t1).unwrap();
+
+        // Fast user switching can leave idle time near zero
+        let t2 = t1 + chrono::Duration::seconds(5);
+        tracker.screen_locked = true;
+        tracker.presenting = true;
+        tracker.tick(secs(0), t2).unwrap();
+        assert_eq!(tracker.last_kind_seen, Some(IntervalType::Locked));
+        assert_eq!(tracker.db.intervals[0].end, t2);
+        assert_eq!(tracker.db.intervals[1].start, t2);
+        assert_eq!(tracker.db.intervals[1].confidence, Some(1.0));
+
+        tracker.screen_locked = false;
+        tracker
+            .tick(secs(0), t2 + chrono::Duration::seconds(1))
+            .unwrap();
+        assert_eq!(tracker.last_kind_seen, Some(IntervalType::Focus));
+        let kinds: Vec<_> = tracker.db.intervals.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                IntervalType::Focus,
+                IntervalType::Locked,
+                IntervalType::Focus
+            ]
+        );
+    }
+
+    #[test]
+    fn test_idle_after_unlocking_keeps_the_locked_time() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+        tracker.screen_locked = true;
+        tracker.tick(secs(0), t1).unwrap();
+
+        // Unlocked without input, e.g. by a watch: idle time reaches back
+        // past the lock
+        let t2 = t1 + chrono::Duration::seconds(2);
+        tracker
+            .tick(secs(0), t1 + chrono::Duration::seconds(1))
+            .unwrap();
+        tracker.screen_locked = false;
+        tracker.tick(secs(1800), t2).unwrap();
+        let kinds: Vec<_> = tracker
+            .db
+            .intervals
+            .iter()
+            .map(|i| (i.kind, i.start, i.end))
+            .collect();
+        assert_eq!(
+            kinds,
+            [(IntervalType::Locked, t1, t2), (IntervalType::Idle, t2, t2)]
+        );
+    }
+
+    #[test]
+    fn test_unlocked_screen_saver_is_idle() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+
+        tracker.screen_saver = true;
+        tracker.tick(secs(0), t1).unwrap();
+        assert_eq!(tracker.last_kind_seen, Some(IntervalType::Idle));
+        assert_eq!(tracker.db.intervals[0].confidence, Some(1.0));
+    }
+
     #[test]
     fn test_update_db_initial() {
-        let mut tracker = setup_tracker(PathBuf::from("dummy"));
+        let mut tracker = setup_tracker(Storage::in_memory());
         tracker.db = Database::default();
         let now = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
 
-        tracker.update_db(IntervalType::Focus, 0.0, now);
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, now);
 
         assert_eq!(tracker.db.intervals.len(), 1);
         assert_eq!(tracker.db.intervals[0].kind, IntervalType::Focus);
@@ -206,13 +1269,13 @@ mod tests {
 
     #[test]
     fn test_update_db_continuous() {
-        let mut tracker = setup_tracker(PathBuf::from("dummy"));
+        let mut tracker = setup_tracker(Storage::in_memory());
         tracker.db = Database::default();
         let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
         let t2 = t1 + chrono::Duration::seconds(5);
 
-        tracker.update_db(IntervalType::Focus, 0.0, t1);
-        tracker.update_db(IntervalType::Focus, 0.0, t2);
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t1);
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t2);
 
         assert_eq!(tracker.db.intervals.len(), 1);
         assert_eq!(tracker.db.intervals[0].start, t1);
@@ -221,20 +1284,20 @@ mod tests {
 
     #[test]
     fn test_update_db_transition_focus_to_idle_backdated() {
-        let mut tracker = setup_tracker(PathBuf::from("dummy"));
+        let mut tracker = setup_tracker(Storage::in_memory());
         tracker.db = Database::default();
         let start = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
         let mut now = start;
 
         // Focus for 300s, updating every 5s to stay under gap_threshold
         for _ in 0..60 {
-            tracker.update_db(IntervalType::Focus, 0.0, now);
-            now = now + chrono::Duration::seconds(5);
+            tracker.update_db(IntervalType::Focus, StdDuration::ZERO, now);
+            now += chrono::Duration::seconds(5);
         }
 
         // Now at 10:05:00, we detect 300s idle.
         // idle_start = 10:05:00 - 300s = 10:00:00.
-        tracker.update_db(IntervalType::Idle, 300.0, now);
+        tracker.update_db(IntervalType::Idle, secs(300), now);
 
         assert_eq!(tracker.db.intervals.len(), 1);
         assert_eq!(tracker.db.intervals[0].kind, IntervalType::Idle);
@@ -244,20 +1307,20 @@ mod tests {
 
     #[test]
     fn test_update_db_transition_focus_to_idle_split() {
-        let mut tracker = setup_tracker(PathBuf::from("dummy"));
+        let mut tracker = setup_tracker(Storage::in_memory());
         tracker.db = Database::default();
         let start = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
         let mut now = start;
 
         // Focus for 600s, updating every 5s
         for _ in 0..120 {
-            tracker.update_db(IntervalType::Focus, 0.0, now);
-            now = now + chrono::Duration::seconds(5);
+            tracker.update_db(IntervalType::Focus, StdDuration::ZERO, now);
+            now += chrono::Duration::seconds(5);
         }
 
         // Now at 10:10:00, we detect 300s idle.
         // idle_start = 10:10:00 - 300s = 10:05:00.
-        tracker.update_db(IntervalType::Idle, 300.0, now);
+        tracker.update_db(IntervalType::Idle, secs(300), now);
 
         assert_eq!(tracker.db.intervals.len(), 2);
         assert_eq!(tracker.db.intervals[0].kind, IntervalType::Focus);
@@ -275,13 +1338,13 @@ mod tests {
 
     #[test]
     fn test_update_db_transition_idle_to_focus() {
-        let mut tracker = setup_tracker(PathBuf::from("dummy"));
+        let mut tracker = setup_tracker(Storage::in_memory());
         tracker.db = Database::default();
         let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
         let t2 = t1 + chrono::Duration::seconds(300);
 
-        tracker.update_db(IntervalType::Idle, 300.0, t1);
-        tracker.update_db(IntervalType::Focus, 0.0, t2);
+        tracker.update_db(IntervalType::Idle, secs(300), t1);
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t2);
 
         assert_eq!(tracker.db.intervals.len(), 2);
         assert_eq!(tracker.db.intervals[0].kind, IntervalType::Idle);
@@ -291,22 +1354,59 @@ mod tests {
 
     #[test]
     fn test_update_db_gap() {
-        let mut tracker = setup_tracker(PathBuf::from("dummy"));
+        let mut tracker = setup_tracker(Storage::in_memory());
         tracker.db = Database::default();
         let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
         let t2 = t1 + chrono::Duration::seconds(60); // 1 min gap (threshold is 10s)
+        tracker.run_start_time = t1;
 
-        tracker.update_db(IntervalType::Focus, 0.0, t1);
-        tracker.update_db(IntervalType::Focus, 0.0, t2);
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t1);
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t2);
 
         assert_eq!(tracker.db.intervals.len(), 2);
         assert_eq!(tracker.db.intervals[0].start, t1);
         assert_eq!(tracker.db.intervals[1].start, t2);
+        assert_eq!(
+            tracker.db.gaps,
+            [Gap {
+                start: t1,
+                end: t2,
+                device_id: None
+            }]
+        );
+
+        // A longer threshold bridges the same pause
+        tracker.gap_threshold = chrono::Duration::seconds(60);
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t2 + chrono::Duration::seconds(60),
+        );
+        assert_eq!(tracker.db.intervals.len(), 2);
+        assert_eq!(tracker.db.gaps.len(), 1);
+    }
+
+    #[test]
+    fn test_time_before_the_session_is_no_gap() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t1);
+
+        tracker.run_start_time = t1 + chrono::Duration::hours(1);
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            tracker.run_start_time,
+        );
+
+        assert_eq!(tracker.db.intervals.len(), 2);
+        assert!(tracker.db.gaps.is_empty());
     }
 
     #[test]
     fn test_prune_old_data() {
-        let mut tracker = setup_tracker(PathBuf::from("dummy"));
+        let mut tracker = setup_tracker(Storage::in_memory());
         tracker.db = Database::default();
 
         let old_date = Utc::now() - chrono::Duration::days(31);
@@ -332,9 +1432,33 @@ mod tests {
         assert_eq!(tracker.db.intervals[0].start, recent_date);
     }
 
+    #[test]
+    fn test_prune_follows_injected_clock() {
+        let t = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let clock = FixedClock::new(t);
+        let storage = Storage::in_memory();
+        let mut tracker =
+            Tracker::with_clock(storage, 5, None, None, None, Box::new(clock.clone())).unwrap();
+        tracker.db = Database::default();
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t);
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t + chrono::Duration::minutes(1),
+        );
+
+        clock.advance(chrono::Duration::days(30));
+        tracker.prune_old_data();
+        assert_eq!(tracker.db.intervals.len(), 1);
+
+        clock.advance(chrono::Duration::minutes(2));
+        tracker.prune_old_data();
+        assert!(tracker.db.intervals.is_empty());
+    }
+
     #[test]
     fn test_should_track_start_time() {
-        let storage = Storage::from_path(PathBuf::from("dummy"));
+        let storage = Storage::in_memory();
         let st = Some("09:00".to_string());
         let tracker = Tracker::new(storage, 5, st, None, None).unwrap();
 
@@ -361,9 +1485,31 @@ mod tests {
         assert!(tracker.should_track(t2));
     }
 
+    #[test]
+    fn test_scheduled_session_skips_days_off() {
+        let storage = Storage::in_memory();
+        let mut tracker = Tracker::new(storage, 5, Some("09:00".to_string()), None, None).unwrap();
+        let today = Local::now().date_naive();
+        let ten = today
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc);
+
+        tracker
+            .db
+            .add_holidays([(today, "Public holiday".to_string())]);
+        assert!(!tracker.should_track(ten));
+
+        // Unscheduled sessions record as usual
+        tracker.start_time = None;
+        assert!(tracker.should_track(ten));
+    }
+
     #[test]
     fn test_should_stop_end_time() {
-        let storage = Storage::from_path(PathBuf::from("dummy"));
+        let storage = Storage::in_memory();
         let et = Some("17:00".to_string());
         let tracker = Tracker::new(storage, 5, None, et, None).unwrap();
 
@@ -392,7 +1538,7 @@ mod tests {
 
     #[test]
     fn test_should_stop_duration() {
-        let storage = Storage::from_path(PathBuf::from("dummy"));
+        let storage = Storage::in_memory();
         let duration = Some("1h".to_string());
         let mut tracker = Tracker::new(storage, 5, None, None, duration).unwrap();
 
@@ -405,7 +1551,7 @@ mod tests {
 
     #[test]
     fn test_duration_prevails_over_start_time() {
-        let storage = Storage::from_path(PathBuf::from("dummy"));
+        let storage = Storage::in_memory();
         let st = Some("09:00".to_string());
         let duration = Some("1h".to_string());
         let tracker = Tracker::new(storage, 5, st, None, duration).unwrap();
@@ -421,4 +1567,526 @@ mod tests {
             .with_timezone(&Utc);
         assert!(tracker.should_track(t1));
     }
+
+    #[test]
+    fn test_update_db_clock_backwards_no_overlap() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t1);
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(5),
+        );
+        tracker.update_db(
+            IntervalType::Idle,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(10),
+        );
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(2),
+        );
+
+        assert!(tracker.db.is_consistent());
+        assert_eq!(
+            tracker.db.intervals.last().unwrap().start,
+            t1 + chrono::Duration::seconds(2)
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overlapping")]
+    fn test_update_db_strict_mode_panics_on_overlap() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        tracker.overlap_mode = OverlapMode::Strict;
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t1);
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(5),
+        );
+        tracker.update_db(
+            IntervalType::Idle,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(10),
+        );
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(2),
+        );
+    }
+
+    #[test]
+    fn test_tick_ignores_backward_clock_jump() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = setup_tracker(Storage::from_path(dir.path().join("db.json")));
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+
+        tracker.tick(StdDuration::ZERO, t1).unwrap();
+        tracker
+            .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(5))
+            .unwrap();
+        // NTP correction moves the clock 30s back
+        tracker
+            .tick(StdDuration::ZERO, t1 - chrono::Duration::seconds(25))
+            .unwrap();
+
+        assert_eq!(tracker.db.intervals.len(), 1);
+        assert_eq!(tracker.db.intervals[0].start, t1);
+        assert_eq!(
+            tracker.db.intervals[0].end,
+            t1 + chrono::Duration::seconds(5)
+        );
+
+        // Recording resumes once the clock has caught up
+        tracker
+            .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(8))
+            .unwrap();
+        assert_eq!(tracker.db.intervals.len(), 1);
+        assert_eq!(
+            tracker.db.intervals[0].end,
+            t1 + chrono::Duration::seconds(8)
+        );
+
+        assert!(tracker.db.gaps.is_empty());
+
+        // The clock is set back an hour: recording starts over instead of
+        // waiting an hour for it to catch up
+        let t2 = t1 - chrono::Duration::hours(1);
+        tracker.tick(StdDuration::ZERO, t2).unwrap();
+        tracker
+            .tick(StdDuration::ZERO, t2 + chrono::Duration::seconds(5))
+            .unwrap();
+        assert_eq!(
+            tracker.db.gaps,
+            [Gap {
+                start: t2,
+                end: t1 + chrono::Duration::seconds(8),
+                device_id: None
+            }]
+        );
+        // The interval recorded past the new time is trimmed away as an overlap
+        assert_eq!(tracker.db.intervals.len(), 1);
+        assert_eq!(tracker.db.intervals[0].kind, IntervalType::Focus);
+        assert_eq!(tracker.db.intervals[0].start, t2);
+        assert_eq!(
+            tracker.db.intervals[0].end,
+            t2 + chrono::Duration::seconds(5)
+        );
+    }
+
+    #[test]
+    fn test_tick_records_away_on_forward_clock_jump() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = setup_tracker(Storage::from_path(dir.path().join("db.json")));
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let wake = t1 + chrono::Duration::hours(3);
+
+        tracker.tick(StdDuration::ZERO, t1).unwrap();
+        tracker
+            .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(5))
+            .unwrap();
+        // Machine wakes from sleep; the system reports a long idle time
+        tracker.tick(secs(10800), wake).unwrap();
+
+        let intervals = &tracker.db.intervals;
+        assert_eq!(intervals.len(), 3);
+        assert_eq!(intervals[0].kind, IntervalType::Focus);
+        assert_eq!(intervals[0].end, t1 + chrono::Duration::seconds(5));
+        assert_eq!(intervals[1].kind, IntervalType::Away);
+        assert_eq!(intervals[1].start, t1 + chrono::Duration::seconds(5));
+        assert_eq!(intervals[1].end, wake);
+        assert_eq!(intervals[2].kind, IntervalType::Idle);
+        assert_eq!(intervals[2].start, wake);
+        assert_eq!(tracker.state_start, wake);
+    }
+
+    #[test]
+    fn test_tick_cuts_intervals_at_short_sleep() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let wake = t1 + chrono::Duration::seconds(12);
+
+        tracker.tick(StdDuration::ZERO, t1).unwrap();
+        tracker
+            .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(2))
+            .unwrap();
+        // Asleep for 9 of the 10 seconds, within the gap threshold
+        tracker.slept = secs(9);
+        tracker.tick(StdDuration::ZERO, wake).unwrap();
+        tracker
+            .tick(StdDuration::ZERO, wake + chrono::Duration::seconds(1))
+            .unwrap();
+
+        let intervals = &tracker.db.intervals;
+        assert_eq!(intervals.len(), 3);
+        assert_eq!(intervals[0].kind, IntervalType::Focus);
+        assert_eq!(intervals[0].end, t1 + chrono::Duration::seconds(2));
+        assert_eq!(intervals[1].kind, IntervalType::Away);
+        assert_eq!(intervals[1].end, wake);
+        assert_eq!(intervals[2].kind, IntervalType::Focus);
+        assert_eq!(intervals[2].start, wake);
+        assert_eq!(intervals[2].end, wake + chrono::Duration::seconds(1));
+        assert!(tracker.db.gaps.is_empty());
+        assert_eq!(tracker.slept, StdDuration::ZERO);
+    }
+
+    #[test]
+    fn test_update_db_ignores_other_devices() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        tracker.device_id = Some("laptop".to_string());
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t1);
+        let mut other = Interval::new_at(IntervalType::Idle, t1);
+        other.end = t1 + chrono::Duration::seconds(30);
+        other.device_id = Some("desktop".to_string());
+        tracker.db.intervals.push(other);
+
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(5),
+        );
+
+        assert_eq!(tracker.db.intervals.len(), 2);
+        assert_eq!(
+            tracker.db.intervals[0].end,
+            t1 + chrono::Duration::seconds(5)
+        );
+        assert_eq!(tracker.db.intervals[0].device_id.as_deref(), Some("laptop"));
+        assert_eq!(
+            tracker.db.intervals[1].end,
+            t1 + chrono::Duration::seconds(30)
+        );
+    }
+
+    #[test]
+    fn test_reload_follows_other_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db.json");
+        let mut writer = setup_tracker(Storage::from_path(db_path.clone()));
+        let mut observer = setup_tracker(Storage::from_path(db_path));
+        let t1 = Utc::now();
+
+        writer.tick(StdDuration::ZERO, t1).unwrap();
+        observer.reload(t1).unwrap();
+        assert_eq!(observer.db.intervals.len(), 1);
+        assert_eq!(observer.last_kind_seen, Some(IntervalType::Focus));
+
+        // Nothing written for a while: the session is not running
+        observer.reload(t1 + chrono::Duration::minutes(5)).unwrap();
+        assert_eq!(observer.last_kind_seen, None);
+    }
+
+    #[test]
+    fn test_battery_saver_records_power_and_saves_less() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = setup_tracker(Storage::from_path(dir.path().join("db.json")));
+        tracker.db = Database::default();
+        tracker.power = Some(PowerSource::Battery);
+        tracker.battery_saver = true;
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+
+        tracker.tick(StdDuration::ZERO, t1).unwrap();
+        assert_eq!(tracker.db.intervals[0].power, Some(PowerSource::Battery));
+        assert_eq!(tracker.tick_interval(), chrono::Duration::seconds(5));
+
+        for secs in (5..=60).step_by(5) {
+            tracker
+                .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(secs))
+                .unwrap();
+        }
+        assert_eq!(tracker.db.dirty, Dirty::Tail);
+
+        tracker.power = Some(PowerSource::Ac);
+        assert_eq!(tracker.tick_interval(), chrono::Duration::seconds(1));
+        tracker
+            .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(61))
+            .unwrap();
+        assert_eq!(tracker.db.dirty, Dirty::Clean);
+    }
+
+    #[test]
+    fn test_space_switches_do_not_split_intervals() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+
+        tracker.space = Some(1);
+        tracker.update_db(IntervalType::Focus, StdDuration::ZERO, t1);
+        tracker.update_space(t1);
+        tracker.update_space(t1 + chrono::Duration::seconds(5));
+        tracker.space = Some(2);
+        tracker.update_db(
+            IntervalType::Focus,
+            StdDuration::ZERO,
+            t1 + chrono::Duration::seconds(10),
+        );
+        tracker.update_space(t1 + chrono::Duration::seconds(10));
+
+        assert_eq!(tracker.db.intervals.len(), 1);
+        assert_eq!(tracker.db.spaces.len(), 2);
+        assert_eq!(tracker.db.spaces[0].end, t1 + chrono::Duration::seconds(10));
+        assert_eq!(
+            tracker.db.spaces[1].start,
+            t1 + chrono::Duration::seconds(10)
+        );
+        assert_eq!(tracker.db.spaces[1].space, 2);
+    }
+
+    #[test]
+    fn test_rapid_space_switches_split_at_each_change() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        let at = |secs| t1 + chrono::Duration::seconds(secs);
+
+        for (secs, space) in [(0, 1), (1, 2), (2, 1), (3, 1), (4, 3), (5, 3)] {
+            tracker.space = Some(space);
+            tracker.update_db(IntervalType::Focus, StdDuration::ZERO, at(secs));
+            tracker.update_space(at(secs));
+        }
+
+        let spans: Vec<_> = tracker
+            .db
+            .spaces
+            .iter()
+            .map(|s| (s.start, s.end, s.space))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (at(0), at(1), 1),
+                (at(1), at(2), 2),
+                (at(2), at(4), 1),
+                (at(4), at(5), 3)
+            ]
+        );
+        assert_eq!(tracker.db.intervals.len(), 1);
+        let by_space = focus_by_space(&tracker.db, t1, &StatsOptions::default());
+        assert_eq!(by_space[&1], chrono::Duration::seconds(3));
+        assert_eq!(by_space[&2], chrono::Duration::seconds(1));
+        assert_eq!(by_space[&3], chrono::Duration::seconds(1));
+    }
+
+    #[test]
+    fn test_continuous_focus_saves_only_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db.json");
+        let mut tracker = setup_tracker(Storage::from_path(db_path.clone()));
+        tracker.db = Database::default();
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+
+        tracker.tick(StdDuration::ZERO, t1).unwrap();
+        let full = std::fs::read_to_string(&db_path).unwrap();
+
+        for secs in 1..=35 {
+            tracker
+                .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(secs))
+                .unwrap();
+        }
+
+        // Ticks after the save at 31s are pending as a tail update
+        assert_eq!(tracker.db.dirty, Dirty::Tail);
+        assert_eq!(std::fs::read_to_string(&db_path).unwrap(), full);
+        let loaded = tracker.storage.load().unwrap();
+        assert_eq!(loaded.intervals.len(), 1);
+        assert_eq!(loaded.intervals[0].end, t1 + chrono::Duration::seconds(31));
+    }
+
+    #[test]
+    fn test_record_session_once_per_run() {
+        let mut tracker = setup_tracker(Storage::in_memory());
+        tracker.db = Database::default();
+        tracker.device_id = Some("laptop".to_string());
+
+        tracker.record_session();
+        // A restarted session keeps its start and must not be recorded twice
+        tracker.record_session();
+        let end = tracker.run_start_time + chrono::Duration::hours(1);
+        tracker.end_session(end);
+
+        assert_eq!(tracker.db.sessions.len(), 1);
+        assert_eq!(tracker.db.sessions[0].threshold_mins, 5);
+        assert_eq!(tracker.db.sessions[0].end, Some(end));
+        assert_eq!(tracker.db.dirty, Dirty::Full);
+    }
+
+    #[test]
+    fn test_tick_ingests_inbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = setup_tracker(Storage::from_path(dir.path().join("db.json")));
+        tracker.db = Database::default();
+        let inbox = Inbox::new(dir.path().join("inbox")).unwrap();
+        std::fs::write(
+            dir.path().join("inbox").join("event.json"),
+            r#"{"type": "note", "date": "2023-01-01", "text": "from a script"}"#,
+        )
+        .unwrap();
+        tracker.inbox = Some(inbox);
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        tracker.last_inbox_poll = t1;
+
+        tracker
+            .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(1))
+            .unwrap();
+        assert!(tracker.db.journal.is_empty());
+
+        tracker
+            .tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(6))
+            .unwrap();
+        assert_eq!(tracker.db.journal.len(), 1);
+        assert_eq!(tracker.storage.load().unwrap().journal.len(), 1);
+    }
+
+    #[test]
+    fn test_submitted_note_survives_the_next_save() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let mut tracker = setup_tracker(storage.clone());
+        tracker.db = Database::default();
+        tracker.inbox = Some(Inbox::new(dir.path().join("inbox"))?);
+        let t1 = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+        tracker.last_inbox_poll = t1;
+        tracker.tick(StdDuration::ZERO, t1)?;
+
+        // What `neflo journal` does while this session holds the lock
+        let date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        Inbox::new(dir.path().join("inbox"))?.submit(&[InboxEvent::Note {
+            date,
+            text: "shipped the importer".to_string(),
+        }])?;
+        for secs in (5..=60).step_by(5) {
+            tracker.tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(secs))?;
+        }
+        // A transition saves the whole database
+        tracker.tick(secs(600), t1 + chrono::Duration::seconds(65))?;
+
+        assert_eq!(storage.load()?.journal[&date], "shipped the importer");
+        Ok(())
+    }
+
+    #[test]
+    fn test_failing_blocker_does_not_stop_saving() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let mut tracker = setup_tracker(storage.clone());
+        tracker.db = Database::default();
+        tracker.blocker = Some(Blocker::from_config(&crate::config::BlockerConfig {
+            sites: vec!["news.ycombinator.com".to_string()],
+            hosts_file: dir.path().join("hosts").to_string_lossy().to_string(),
+            ..Default::default()
+        }));
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+
+        assert!(tracker.tick(StdDuration::ZERO, t1).is_err());
+        // The failure was reported; later ticks save as usual
+        for secs in (5..=40).step_by(5) {
+            tracker.tick(StdDuration::ZERO, t1 + chrono::Duration::seconds(secs))?;
+        }
+        assert!(storage.load()?.intervals[0].end > t1 + chrono::Duration::seconds(SAVE_SECS));
+        Ok(())
+    }
+
+    #[test]
+    fn test_integration_errors_do_not_stop_the_inbox_or_saving() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let mut tracker = setup_tracker(storage.clone());
+        tracker.db = Database::default();
+        tracker.inbox = Some(Inbox::new(dir.path().join("inbox"))?);
+        // Fails on every transition where mosquitto_pub is not installed
+        tracker.mqtt = Some(MqttPublisher::new(&Default::default(), "test")?);
+        let t1 = Utc::now();
+        tracker.last_inbox_poll = t1;
+        tracker.last_save = t1;
+
+        let date = t1.with_timezone(&Local).date_naive();
+        Inbox::new(dir.path().join("inbox"))?.submit(&[InboxEvent::Note {
+            date,
+            text: "published or not".to_string(),
+        }])?;
+        for secs in (0..=10).step_by(5) {
+            // Idle and focus in turn, so every tick publishes a transition
+            let idle = if secs % 10 == 0 { 0 } else { 600 };
+            let _ = tracker.tick(
+                StdDuration::from_secs(idle),
+                t1 + chrono::Duration::seconds(secs),
+            );
+        }
+        assert_eq!(storage.load()?.journal[&date], "published or not");
+        Ok(())
+    }
+
+    fn tick_strategy() -> impl Strategy<Value = (bool, u64, i64)> {
+        // (is_idle, idle seconds, clock step in seconds; negative steps model clock corrections)
+        (any::<bool>(), 0u64..900, -30i64..60)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_update_db_intervals_ordered_and_disjoint(
+            ticks in prop::collection::vec(tick_strategy(), 1..200)
+        ) {
+            let mut tracker = setup_tracker(Storage::in_memory());
+            tracker.db = Database::default();
+            let mut now = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+
+            for (is_idle, idle_secs, step) in ticks {
+                now += chrono::Duration::seconds(step);
+                let kind = if is_idle { IntervalType::Idle } else { IntervalType::Focus };
+                tracker.update_db(kind, secs(idle_secs), now);
+
+                prop_assert_eq!(tracker.db.validate(), []);
+                for pair in tracker.db.intervals.windows(2) {
+                    prop_assert!(pair[0].start <= pair[1].start);
+                }
+            }
+        }
+
+        #[test]
+        fn prop_update_db_conserves_time(
+            ticks in prop::collection::vec((any::<bool>(), 0u64..900, 0i64..=10), 1..200)
+        ) {
+            // Steps within the gap threshold leave no gaps, so the intervals
+            // cover exactly the time from the first tick to the last
+            let mut tracker = setup_tracker(Storage::in_memory());
+            tracker.db = Database::default();
+            let first = Utc.with_ymd_and_hms(2023, 1, 1, 10, 0, 0).unwrap();
+            let mut now = first;
+
+            for (i, (is_idle, idle_secs, step)) in ticks.into_iter().enumerate() {
+                if i > 0 {
+                    now += chrono::Duration::seconds(step);
+                }
+                let kind = if is_idle { IntervalType::Idle } else { IntervalType::Focus };
+                tracker.update_db(kind, secs(idle_secs), now);
+            }
+
+            prop_assert_eq!(tracker.db.validate(), []);
+            let recorded: chrono::Duration =
+                tracker.db.intervals.iter().map(|i| i.end - i.start).sum();
+            prop_assert_eq!(recorded, now - first);
+        }
+    }
 }
@@ -0,0 +1,104 @@
+use crate::models::{Interval, SpaceSpan};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a reset or bulk edit took out of the database, for `neflo undo` to
+/// put back.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Entry {
+    /// Intervals removed; merged back on undo.
+    #[serde(default)]
+    pub intervals: Vec<Interval>,
+    /// Space spans as they were before a retag; put back over the retagged ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spaces: Vec<SpaceSpan>,
+}
+
+/// A trash file: an entry, or the bare intervals older versions wrote.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Intervals(Vec<Interval>),
+    Entry(Entry),
+}
+
+/// Keep `entry`, taken out by `action` (e.g. `reset`), in a timestamped file
+/// under `dir`.
+pub fn save(dir: &Path, action: &str, entry: &Entry, now: DateTime<Utc>) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "{}-{}.json",
+        action,
+        now.format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    fs::write(&path, serde_json::to_string_pretty(entry)?)?;
+    Ok(path)
+}
+
+/// The newest trash file and its entry, left in place.
+pub fn latest(dir: &Path) -> Result<Option<(PathBuf, Entry)>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(None);
+    };
+    // File names sort by the time after the action
+    let timestamp = |path: &PathBuf| {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        stem.split_once('-')
+            .map_or(stem.to_string(), |(_, time)| time.to_string())
+    };
+    let latest = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .max_by_key(timestamp);
+    let Some(path) = latest else {
+        return Ok(None);
+    };
+    let entry = match serde_json::from_str(&fs::read_to_string(&path)?)? {
+        Stored::Intervals(intervals) => Entry {
+            intervals,
+            ..Default::default()
+        },
+        Stored::Entry(entry) => entry,
+    };
+    Ok(Some((path, entry)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::IntervalType;
+    use chrono::{Duration, TimeZone};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_latest_returns_newest_first() -> Result<()> {
+        let dir = tempdir()?;
+        let t = Utc.with_ymd_and_hms(2024, 5, 13, 10, 0, 0).unwrap();
+        let interval = |minutes| Interval {
+            end: t + Duration::minutes(minutes),
+            ..Interval::new_at(IntervalType::Focus, t)
+        };
+        let entry = |minutes| Entry {
+            intervals: vec![interval(minutes)],
+            ..Default::default()
+        };
+        // Written by an older version, as bare intervals
+        fs::write(
+            dir.path().join("reset-20240513T090000.000Z.json"),
+            serde_json::to_string(&[interval(1)])?,
+        )?;
+        save(dir.path(), "reset", &entry(5), t)?;
+        save(dir.path(), "delete", &entry(10), t + Duration::hours(1))?;
+
+        for minutes in [10, 5, 1] {
+            let (path, latest) = latest(dir.path())?.unwrap();
+            assert_eq!(latest, entry(minutes));
+            fs::remove_file(path)?;
+        }
+        assert!(latest(dir.path())?.is_none());
+        Ok(())
+    }
+}
@@ -1,10 +1,23 @@
+use crate::breaks::{self, BreakRule};
+use crate::budget::{self, Evaluation, Standing, Target};
+use crate::chart;
+use crate::config::{DatabaseConfig, KindsConfig};
+use crate::logs::Log;
 use crate::models::IntervalType;
-use crate::stats::{calculate_stats, SummaryStats};
-use crate::system::get_idle_time;
-use crate::tracker::Tracker;
-use crate::utils::format_duration;
-use anyhow::Result;
-use chrono::{Duration, Local, Utc};
+use crate::period::Period;
+use crate::session::{self, Command, SessionEnd, Update};
+use crate::stats::{
+    borderline_coverage, calculate_stats, coverage, focus_by_tag, forecast_focus, local_midnight,
+    session_focus, utilization, Stats, SummaryStats,
+};
+use crate::suggest::{describe, suggest_from_history, LOOKBACK_DAYS, MIN_DAYS};
+use crate::system::{IdleSample, IdleSource, FROZEN_SECS};
+use crate::tracker::{ResetScope, Tracker};
+use crate::utils::{
+    big_text, format_bar, format_duration, format_minutes, format_percent, BIG_TEXT_ROWS,
+};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
 use crossterm::{
     event::{self, Event, KeyCode},
     execute,
@@ -15,13 +28,128 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{BarChart, Block, Borders, List, ListItem, Paragraph},
     Frame, Terminal,
 };
+use std::collections::{BTreeMap, VecDeque};
 use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
 use std::time::Duration as StdDuration;
 
-pub fn run_tui(tracker: &mut Tracker) -> Result<()> {
+/// How many errors the log keeps before dropping the oldest.
+const ERROR_LOG_CAPACITY: usize = 50;
+/// How long the latest error stays visible in the footer.
+const TOAST_SECS: i64 = 10;
+/// How often a read-only observer reloads the database written by the running session.
+const OBSERVE_POLL_SECS: i64 = 2;
+/// Redraw at least this often so the clock and current-state counter keep moving.
+const REDRAW_SECS: i64 = 1;
+/// How often today's focus forecast is recomputed from the history.
+const FORECAST_SECS: i64 = 180;
+/// Cells in the session goal bar of the SESSION block.
+const GOAL_BAR_WIDTH: usize = 10;
+/// Missed samples after which idle detection is reported as stalled.
+const STALLED_SAMPLES: i32 = 3;
+/// How long the footer celebrates reaching today's focus goal.
+const GOAL_BANNER_SECS: i64 = 60;
+
+/// Which chart is shown in the lower half of the dashboard.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ChartView {
+    #[default]
+    Weekly,
+    Hourly,
+}
+
+impl ChartView {
+    fn toggle(self) -> Self {
+        match self {
+            ChartView::Weekly => ChartView::Hourly,
+            ChartView::Hourly => ChartView::Weekly,
+        }
+    }
+}
+
+/// Ring buffer of recent errors, so a failed save does not end the session.
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    entries: VecDeque<(DateTime<Local>, String)>,
+}
+
+impl ErrorLog {
+    pub fn push(&mut self, at: DateTime<Local>, err: &anyhow::Error) {
+        if self.entries.len() == ERROR_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((at, format!("{:#}", err)));
+    }
+
+    /// The latest error, if it is recent enough to show as a toast.
+    pub fn toast(&self, now: DateTime<Local>) -> Option<&str> {
+        self.entries
+            .back()
+            .filter(|(at, _)| now - *at < Duration::seconds(TOAST_SECS))
+            .map(|(_, msg)| msg.as_str())
+    }
+}
+
+/// State of the dashboard that is not part of the tracker.
+#[derive(Debug, Default)]
+pub struct UiState {
+    pub view: ChartView,
+    pub show_errors: bool,
+    pub errors: ErrorLog,
+    /// Show the idle detection panel instead of the chart.
+    pub show_idle: bool,
+    /// The latest idle time reading of the tracking thread.
+    pub idle_sample: Option<IdleSample>,
+    /// Read-only view of a session running elsewhere.
+    pub observe: bool,
+    pub kinds: KindsConfig,
+    /// Close the dashboard this long after the session ends.
+    pub exit_after: Option<Duration>,
+    /// Waiting for the user to pick what 'r' should reset.
+    pub reset_prompt: bool,
+    /// When an automatic exit is due, set once the session has ended.
+    pub exit_at: Option<DateTime<Utc>>,
+    pub targets: Vec<Target>,
+    /// Tags for `targets`, keyed by desktop space id.
+    pub space_names: BTreeMap<String, String>,
+    pub breaks: Vec<BreakRule>,
+    /// Idle detection is stuck with no fallback, so nothing is recorded.
+    pub idle_frozen: bool,
+    pub chart_scale: chart::Scale,
+    /// Poll for screen sharing so that presenting counts as focus.
+    pub detect_presenting: bool,
+    /// Count input events, for the intensity of focus.
+    pub track_intensity: bool,
+    /// Paint borderline intervals in the coverage bar with a lighter block.
+    pub shade_borderline: bool,
+    /// Scheduled working time per day; focus is shown as a share of it.
+    pub workday: Option<Duration>,
+    /// Session log, written by the tracking thread.
+    pub log: Option<Log>,
+    /// Warn once when the database outgrows these levels.
+    pub quota: Option<DatabaseConfig>,
+    /// Show only the header and a large timer of the current state.
+    pub compact: bool,
+}
+
+impl UiState {
+    /// Keep a failed operation in the error log instead of ending the loop.
+    fn report(&mut self, result: Result<()>) {
+        if let Err(err) = result {
+            self.errors.push(Local::now(), &err);
+        }
+    }
+}
+
+/// Run the dashboard. With `ui.observe`, nothing is recorded or saved; the database
+/// is reloaded periodically to follow a session running in another process.
+pub fn run_tui(tracker: &mut Tracker, state_dir: &Path, ui: UiState) -> Result<SessionEnd> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -29,74 +157,395 @@ pub fn run_tui(tracker: &mut Tracker) -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_loop(&mut terminal, tracker);
+    let res = if ui.observe {
+        observe_loop(&mut terminal, tracker, ui)
+    } else {
+        run_loop(&mut terminal, tracker, state_dir, ui)
+    };
 
     // restore terminal
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen,)?;
     terminal.show_cursor()?;
 
-    if let Err(err) = res {
-        println!("{:?}", err)
+    match res {
+        Ok(end) => Ok(end),
+        Err(err) => {
+            println!("{:?}", err);
+            Ok(SessionEnd::Quit)
+        }
     }
-
-    Ok(())
 }
 
 fn run_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     tracker: &mut Tracker,
-) -> Result<()> {
+    state_dir: &Path,
+    mut ui: UiState,
+) -> Result<SessionEnd> {
+    let (commands, command_rx) = mpsc::channel();
+    let (update_tx, updates) = mpsc::channel();
+    let options = session::Options {
+        detect_presenting: ui.detect_presenting,
+        track_intensity: ui.track_intensity,
+        exit_after: ui.exit_after,
+        log: ui.log.clone(),
+        quota: ui.quota.clone(),
+    };
+    let tracker = &Mutex::new(tracker);
+    thread::scope(|scope| {
+        scope.spawn(move || session::run(tracker, state_dir, options, command_rx, update_tx));
+        let end = ui_loop(terminal, tracker, &mut ui, &commands, &updates);
+        // Stop the tracking thread unless it ended the session itself
+        let _ = commands.send(Command::Quit);
+        end
+    })
+}
+
+/// Input and drawing for a session recorded by `session::run` on another thread.
+fn ui_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    tracker: &Mutex<&mut Tracker>,
+    ui: &mut UiState,
+    commands: &Sender<Command>,
+    updates: &Receiver<Update>,
+) -> Result<SessionEnd> {
+    let mut redraw = Redraw::default();
     loop {
-        terminal.draw(|f| draw(f, tracker))?;
+        // While the tracker is busy (e.g. saving), keep the last frame and stay responsive
+        if let Ok(tracker) = tracker.try_lock() {
+            redraw.draw(terminal, &tracker, ui)?;
+        }
 
         if event::poll(StdDuration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
+                redraw.request();
+                if ui.reset_prompt {
+                    ui.reset_prompt = false;
+                    let scope = match key.code {
+                        KeyCode::Char('s') => Some(ResetScope::Session),
+                        KeyCode::Char('t') => Some(ResetScope::Today),
+                        KeyCode::Char('a') => Some(ResetScope::All),
+                        _ => None,
+                    };
+                    if let Some(scope) = scope {
+                        commands.send(Command::Reset(scope))?;
+                    }
+                    continue;
+                }
                 match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Char('r') => tracker.reset()?,
+                    KeyCode::Char('q') => return Ok(SessionEnd::Quit),
+                    KeyCode::Char('r') => ui.reset_prompt = true,
+                    KeyCode::Char('h') => ui.view = ui.view.toggle(),
+                    KeyCode::Char('e') => ui.show_errors = !ui.show_errors,
+                    KeyCode::Char('d') => ui.show_idle = !ui.show_idle,
                     _ => {}
                 }
             }
         }
 
-        let now = Utc::now();
-        if tracker.should_stop(now) {
-            if !tracker.session_ended_saved {
-                tracker.storage.save(&tracker.db)?;
-                tracker.session_ended_saved = true;
+        loop {
+            match updates.try_recv() {
+                Ok(Update::Changed) => redraw.data_changed(),
+                Ok(Update::Error(err)) => ui.errors.push(Local::now(), &err),
+                Ok(Update::IdleFrozen(frozen)) => {
+                    ui.idle_frozen = frozen;
+                    redraw.request();
+                }
+                Ok(Update::IdleSampled(sample)) => ui.idle_sample = Some(sample),
+                Ok(Update::ExitAt(at)) => ui.exit_at = Some(at),
+                Ok(Update::End(end)) => return Ok(end),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => bail!("The tracking thread stopped"),
             }
-        } else if tracker.should_track(now) {
-            let idle_time = get_idle_time();
-            tracker.tick(idle_time, now)?;
         }
     }
 }
 
-pub fn draw(frame: &mut Frame, tracker: &Tracker) {
+fn observe_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    tracker: &mut Tracker,
+    mut ui: UiState,
+) -> Result<SessionEnd> {
+    let mut last_reload = tracker.now();
+    let mut redraw = Redraw::default();
+    loop {
+        redraw.draw(terminal, tracker, &ui)?;
+
+        if event::poll(StdDuration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                redraw.request();
+                match key.code {
+                    KeyCode::Char('q') => return Ok(SessionEnd::Quit),
+                    KeyCode::Char('h') => ui.view = ui.view.toggle(),
+                    KeyCode::Char('e') => ui.show_errors = !ui.show_errors,
+                    _ => {}
+                }
+            }
+        }
+
+        let now = tracker.now();
+        if now - last_reload >= Duration::seconds(OBSERVE_POLL_SECS) {
+            last_reload = now;
+            ui.report(tracker.reload(now));
+            redraw.data_changed();
+        }
+    }
+}
+
+/// Everything a frame shows, as of a single instant, so that no two panels
+/// disagree about the time.
+pub struct Snapshot {
+    pub now: DateTime<Utc>,
+    pub stats: Stats,
+    pub targets: Vec<Evaluation>,
+    /// Projected focus for the rest of today; refreshed less often than the rest.
+    pub forecast: Option<Duration>,
+    /// Focus so far toward the session goal, and the goal.
+    pub session_goal: Option<(Duration, Duration)>,
+}
+
+impl Snapshot {
+    pub fn take(tracker: &Tracker, ui: &UiState, now: DateTime<Utc>) -> Self {
+        let stats = calculate_stats(
+            &tracker.db,
+            Some(tracker.run_start_time),
+            &tracker.stats_options,
+            &now,
+        );
+        let targets = if ui.targets.is_empty() {
+            Vec::new()
+        } else {
+            let by_tag = focus_by_tag(
+                &tracker.db,
+                Period::Week(stats.today).start(),
+                &tracker.stats_options,
+                &ui.space_names,
+            );
+            budget::evaluate(&ui.targets, &stats.week_summary, &by_tag)
+        };
+        let session_goal = tracker.session_goal.map(|goal| {
+            let focus = session_focus(
+                &tracker.db,
+                tracker.run_start_time,
+                tracker.device_id.as_deref(),
+            );
+            (focus, goal)
+        });
+        Self {
+            now,
+            stats,
+            targets,
+            forecast: None,
+            session_goal,
+        }
+    }
+
+    /// Today's focus by the end of the session, or of the day, if the rest of
+    /// it goes like the same hours usually do.
+    fn forecast(&self, tracker: &Tracker) -> Option<Duration> {
+        let end_of_day = local_midnight(self.stats.today.succ_opt()?)?;
+        let until = tracker
+            .planned_end(self.now)
+            .map_or(end_of_day, |end| end.min(end_of_day));
+        forecast_focus(
+            &tracker.db,
+            self.stats.today_summary.total_focus,
+            self.now,
+            until,
+            LOOKBACK_DAYS,
+            MIN_DAYS,
+        )
+    }
+}
+
+/// Decides when the dashboard is redrawn: after input, after the data changed,
+/// and otherwise once a second so the clocks keep moving. Stats are only
+/// recomputed after the data changed or the date rolled over, and the
+/// forecast every `FORECAST_SECS`.
+#[derive(Default)]
+struct Redraw {
+    snapshot: Option<Snapshot>,
+    stale: bool,
+    drawn_at: Option<DateTime<Utc>>,
+    /// The latest forecast and when it was computed.
+    forecast: Option<(DateTime<Utc>, Option<Duration>)>,
+}
+
+impl Redraw {
+    /// The database changed; recompute stats on the next frame.
+    fn data_changed(&mut self) {
+        self.stale = true;
+    }
+
+    /// Draw on the next iteration, e.g. after a key press.
+    fn request(&mut self) {
+        self.drawn_at = None;
+    }
+
+    fn draw(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+        tracker: &Tracker,
+        ui: &UiState,
+    ) -> Result<()> {
+        let now = tracker.now();
+        let due = self.stale
+            || self
+                .drawn_at
+                .is_none_or(|t| now - t >= Duration::seconds(REDRAW_SECS));
+        if !due {
+            return Ok(());
+        }
+        let today = now.with_timezone(&Local).date_naive();
+        let snapshot = match self.snapshot.take() {
+            Some(snapshot) if !self.stale && snapshot.stats.today == today => {
+                Snapshot { now, ..snapshot }
+            }
+            _ => Snapshot::take(tracker, ui, now),
+        };
+        let snapshot = self.snapshot.insert(snapshot);
+        let forecast = match self.forecast {
+            Some((at, forecast))
+                if now - at < Duration::seconds(FORECAST_SECS)
+                    && at.with_timezone(&Local).date_naive() == today =>
+            {
+                forecast
+            }
+            _ => {
+                let forecast = snapshot.forecast(tracker);
+                self.forecast = Some((now, forecast));
+                forecast
+            }
+        };
+        snapshot.forecast = forecast;
+        self.stale = false;
+        terminal.draw(|f| draw(f, tracker, ui, snapshot))?;
+        self.drawn_at = Some(now);
+        Ok(())
+    }
+}
+
+pub fn draw(frame: &mut Frame, tracker: &Tracker, ui: &UiState, snapshot: &Snapshot) {
+    if ui.compact {
+        return draw_compact(frame, tracker, ui, snapshot.now);
+    }
+    let stats = &snapshot.stats;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3), // Header
+            Constraint::Length(3), // Coverage
             Constraint::Length(9), // Stats
             Constraint::Min(0),    // Chart
             Constraint::Length(3), // Footer
         ])
         .split(frame.size());
 
-    draw_header(frame, chunks[0], tracker);
-    draw_stats(frame, chunks[1], tracker);
-    draw_chart(frame, chunks[2], tracker);
-    draw_footer(frame, chunks[3]);
+    draw_header(
+        frame,
+        chunks[0],
+        tracker,
+        snapshot.now,
+        &ui.kinds,
+        ui.exit_at,
+    );
+    draw_coverage(frame, chunks[1], tracker, snapshot.now, ui);
+    draw_stats(frame, chunks[2], snapshot, ui);
+    if ui.show_errors {
+        draw_error_log(frame, chunks[3], &ui.errors);
+    } else if ui.show_idle {
+        draw_idle_detection(frame, chunks[3], tracker, ui, snapshot.now);
+    } else {
+        match ui.view {
+            ChartView::Weekly => draw_chart(frame, chunks[3], stats, ui),
+            ChartView::Hourly => draw_hourly_chart(frame, chunks[3], tracker, snapshot, &ui.kinds),
+        }
+    }
+    let break_hint = match tracker.last_kind_seen {
+        Some(IntervalType::Idle) => breaks::suggest(
+            &ui.breaks,
+            tracker
+                .db
+                .intervals
+                .iter()
+                .rev()
+                .filter(|i| i.device_id == tracker.device_id),
+        ),
+        _ => None,
+    };
+    let goal_banner = tracker
+        .daily_goal_met
+        .filter(|(at, _)| snapshot.now - *at < Duration::seconds(GOAL_BANNER_SECS))
+        .map(|(_, streak)| match streak {
+            0 | 1 => "Daily focus goal reached, well done!".to_string(),
+            days => format!("Daily focus goal reached, {} days in a row!", days),
+        });
+    draw_footer(frame, chunks[4], ui, goal_banner, break_hint);
+}
+
+/// The header above a timer of the current state in large digits, for a
+/// small always-on window or a sidecar display.
+fn draw_compact(frame: &mut Frame, tracker: &Tracker, ui: &UiState, now: DateTime<Utc>) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(frame.size());
+    draw_header(frame, chunks[0], tracker, now, &ui.kinds, ui.exit_at);
+
+    let (timer, color) = match tracker.last_kind_seen {
+        Some(kind) if tracker.should_track(now) => {
+            let secs = (now - tracker.state_start).num_seconds().max(0);
+            let timer = if secs >= 3600 {
+                format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+            } else {
+                format!("{:02}:{:02}", secs / 60, secs % 60)
+            };
+            (timer, ui.kinds.color(kind))
+        }
+        _ => ("--:--".to_string(), Color::DarkGray),
+    };
+    let area = chunks[1];
+    let rows = big_text(&timer);
+    // Too small for the large digits: plain text still fits
+    let lines: Vec<Line> = if area.height as usize >= BIG_TEXT_ROWS + 2
+        && area.width as usize >= rows[0].chars().count() + 2
+    {
+        rows.into_iter().map(Line::from).collect()
+    } else {
+        vec![Line::from(timer)]
+    };
+    let padding = (area.height.saturating_sub(2) as usize).saturating_sub(lines.len()) / 2;
+    let lines: Vec<Line> = std::iter::repeat_n(Line::from(""), padding)
+        .chain(lines)
+        .collect();
+    let timer = Paragraph::new(lines)
+        .style(Style::default().fg(color).add_modifier(Modifier::BOLD))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(timer, area);
 }
 
-fn draw_header(frame: &mut Frame, area: Rect, tracker: &Tracker) {
-    let now_utc = Utc::now();
-    let now_local = Local::now();
+fn draw_header(
+    frame: &mut Frame,
+    area: Rect,
+    tracker: &Tracker,
+    now_utc: DateTime<Utc>,
+    kinds: &KindsConfig,
+    exit_at: Option<DateTime<Utc>>,
+) {
+    let now_local = now_utc.with_timezone(&Local);
 
     let status_text = if tracker.should_stop(now_utc) {
+        let text = match exit_at {
+            Some(exit_at) => format!(
+                "SESSION ENDED (exiting in {}s)",
+                (exit_at - now_utc).num_seconds().max(0)
+            ),
+            None => "SESSION ENDED".to_string(),
+        };
         Span::styled(
-            "SESSION ENDED",
+            text,
             Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
         )
     } else if !tracker.should_track(now_utc) {
@@ -110,20 +559,17 @@ fn draw_header(frame: &mut Frame, area: Rect, tracker: &Tracker) {
                 .add_modifier(Modifier::BOLD),
         )
     } else if let Some(kind) = tracker.last_kind_seen {
-        match kind {
-            IntervalType::Focus => Span::styled(
-                "IN FLOW",
-                Style::default()
-                    .fg(Color::Green)
-                    .add_modifier(Modifier::BOLD),
-            ),
-            IntervalType::Idle => Span::styled(
-                "IDLE",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            ),
-        }
+        let status = match (kind, &kinds.style(kind).label) {
+            (_, Some(label)) => label.to_uppercase(),
+            (IntervalType::Focus, None) => "IN FLOW".to_string(),
+            (_, None) => kinds.label(kind).to_uppercase(),
+        };
+        Span::styled(
+            status,
+            Style::default()
+                .fg(kinds.color(kind))
+                .add_modifier(Modifier::BOLD),
+        )
     } else {
         Span::raw("STARTING...")
     };
@@ -165,9 +611,111 @@ fn draw_header(frame: &mut Frame, area: Rect, tracker: &Tracker) {
     frame.render_widget(header, area);
 }
 
-fn draw_stats(frame: &mut Frame, area: Rect, tracker: &Tracker) {
-    let stats = calculate_stats(&tracker.db, Some(tracker.run_start_time));
+/// Today's working hours as one bar, painted by what was recorded in each cell.
+fn draw_coverage(
+    frame: &mut Frame,
+    area: Rect,
+    tracker: &Tracker,
+    now: DateTime<Utc>,
+    ui: &UiState,
+) {
+    let kinds = &ui.kinds;
+    let today = now.with_timezone(&Local).date_naive();
+    let start_time = tracker.start_time.unwrap_or(NaiveTime::MIN);
+    let local_at = |time: NaiveTime| {
+        Local
+            .from_local_datetime(&today.and_time(time))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+    };
+    let from = local_at(start_time).unwrap_or(now);
+    let to = match tracker.end_time {
+        Some(end_time) if end_time > start_time => local_at(end_time).unwrap_or(now),
+        _ => local_at(NaiveTime::MIN)
+            .map(|t| t + Duration::days(1))
+            .unwrap_or(now),
+    };
 
+    let cells = area.width.saturating_sub(2) as usize;
+    let borderline = if ui.shade_borderline {
+        borderline_coverage(&tracker.db, from, to, cells, &tracker.stats_options)
+    } else {
+        Vec::new()
+    };
+    let spans: Vec<Span> = coverage(&tracker.db, from, to, cells, &tracker.stats_options)
+        .into_iter()
+        .enumerate()
+        .map(|(cell, kind)| {
+            let cell_start = from + (to - from) * cell as i32 / cells as i32;
+            let cell_end = from + (to - from) * (cell + 1) as i32 / cells as i32;
+            let marked = tracker
+                .db
+                .markers
+                .iter()
+                .any(|m| m.at >= cell_start && m.at < cell_end);
+            match kind {
+                _ if marked => Span::styled(
+                    "◆",
+                    Style::default()
+                        .fg(Color::Magenta)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Some(IntervalType::Away) => {
+                    Span::styled("▒", Style::default().fg(kinds.color(IntervalType::Away)))
+                }
+                Some(kind) if borderline.get(cell) == Some(&Some(kind)) => {
+                    Span::styled("▓", Style::default().fg(kinds.color(kind)))
+                }
+                Some(kind) => Span::styled("█", Style::default().fg(kinds.color(kind))),
+                None if cell_start > now => Span::styled("·", Style::default().fg(Color::DarkGray)),
+                // Nothing recorded while the day was running: tracker not running
+                None => Span::styled("░", Style::default().fg(Color::Red)),
+            }
+        })
+        .collect();
+
+    let end_label = match tracker.end_time {
+        Some(end_time) if end_time > start_time => end_time.format("%H:%M").to_string(),
+        _ => "24:00".to_string(),
+    };
+    let title = format!(" Today {}–{} ", start_time.format("%H:%M"), end_label);
+    let bar = Paragraph::new(Line::from(spans))
+        .block(Block::default().title(title).borders(Borders::ALL));
+    frame.render_widget(bar, area);
+}
+
+fn draw_stats(frame: &mut Frame, area: Rect, snapshot: &Snapshot, ui: &UiState) {
+    let stats = &snapshot.stats;
+    let kinds = &ui.kinds;
+    let utilization_line = |from| {
+        let share = utilization(&stats.daily_stats, from, stats.today, ui.workday?)?;
+        Some(format!(
+            "  Utilization: {} of schedule",
+            format_percent(share)
+        ))
+    };
+    let mut today_lines: Vec<String> = utilization_line(stats.today).into_iter().collect();
+    if let Some(forecast) = snapshot.forecast {
+        today_lines.push(format!(
+            "  On pace for {} focus today",
+            format_minutes(forecast)
+        ));
+    }
+    let week_lines: Vec<String> = utilization_line(stats.week_start).into_iter().collect();
+    let session_lines: Vec<String> = snapshot
+        .session_goal
+        .map(|(focus, goal)| {
+            let bar = format_bar(focus.num_seconds(), goal.num_seconds(), GOAL_BAR_WIDTH);
+            format!(
+                "  Goal: [{:<width$}] {} / {}",
+                bar,
+                format_minutes(focus.min(goal)),
+                format_minutes(goal),
+                width = GOAL_BAR_WIDTH
+            )
+        })
+        .into_iter()
+        .collect();
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
@@ -177,12 +725,44 @@ fn draw_stats(frame: &mut Frame, area: Rect, tracker: &Tracker) {
         ])
         .split(area);
 
-    draw_summary_block(frame, chunks[0], " SESSION ", &stats.session_summary);
-    draw_summary_block(frame, chunks[1], " TODAY ", &stats.today_summary);
-    draw_summary_block(frame, chunks[2], " WEEK ", &stats.week_summary);
+    draw_summary_block(
+        frame,
+        chunks[0],
+        " SESSION ",
+        &stats.session_summary,
+        &[],
+        &session_lines,
+        kinds,
+    );
+    draw_summary_block(
+        frame,
+        chunks[1],
+        " TODAY ",
+        &stats.today_summary,
+        &[],
+        &today_lines,
+        kinds,
+    );
+    draw_summary_block(
+        frame,
+        chunks[2],
+        " WEEK ",
+        &stats.week_summary,
+        &snapshot.targets,
+        &week_lines,
+        kinds,
+    );
 }
 
-fn draw_summary_block(frame: &mut Frame, area: Rect, title: &str, summary: &SummaryStats) {
+fn draw_summary_block(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    summary: &SummaryStats,
+    targets: &[Evaluation],
+    extra: &[String],
+    kinds: &KindsConfig,
+) {
     let mut lines = Vec::new();
 
     let avg_focus = if summary.focus_count > 0 {
@@ -196,8 +776,15 @@ fn draw_summary_block(frame: &mut Frame, area: Rect, title: &str, summary: &Summ
         Duration::zero()
     };
 
+    // Pad both labels to the same width so the values line up
+    let focus_label = kinds.label(IntervalType::Focus);
+    let idle_label = kinds.label(IntervalType::Idle);
+    let width = focus_label.chars().count().max(idle_label.chars().count()) + 1;
     lines.push(Line::from(vec![
-        Span::styled("  Focus:", Style::default().fg(Color::Green)),
+        Span::styled(
+            format!("  {:<width$}", format!("{}:", focus_label)),
+            Style::default().fg(kinds.color(IntervalType::Focus)),
+        ),
         Span::raw(format!(
             " {} (Avg: {})",
             format_duration(summary.total_focus.num_seconds()),
@@ -206,7 +793,10 @@ fn draw_summary_block(frame: &mut Frame, area: Rect, title: &str, summary: &Summ
     ]));
 
     lines.push(Line::from(vec![
-        Span::styled("  Idle:  ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!("  {:<width$}", format!("{}:", idle_label)),
+            Style::default().fg(kinds.color(IntervalType::Idle)),
+        ),
         Span::raw(format!(
             " {} (Avg: {})",
             format_duration(summary.total_idle.num_seconds()),
@@ -218,6 +808,30 @@ fn draw_summary_block(frame: &mut Frame, area: Rect, title: &str, summary: &Summ
         "  Interruptions: {}",
         summary.idle_count
     )));
+    lines.push(Line::raw(format!(
+        "  Deep work: {} ({} sessions)",
+        format_duration(summary.total_deep_work.num_seconds()),
+        summary.deep_work_count
+    )));
+    lines.extend(extra.iter().map(|line| Line::raw(line.clone())));
+    for evaluation in targets {
+        let color = match evaluation.standing {
+            Standing::Met => Color::Green,
+            Standing::Under => Color::Yellow,
+            Standing::Over => Color::Red,
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("  {} ", evaluation.standing.symbol()),
+                Style::default().fg(color),
+            ),
+            Span::raw(format!(
+                "{}: {}",
+                evaluation.target.describe(),
+                evaluation.progress()
+            )),
+        ]));
+    }
 
     let block = Block::default()
         .title(Span::styled(
@@ -229,11 +843,22 @@ fn draw_summary_block(frame: &mut Frame, area: Rect, title: &str, summary: &Summ
     frame.render_widget(para, area);
 }
 
-fn draw_chart(frame: &mut Frame, area: Rect, tracker: &Tracker) {
-    let stats = calculate_stats(&tracker.db, Some(tracker.run_start_time));
-
+fn draw_chart(frame: &mut Frame, area: Rect, stats: &Stats, ui: &UiState) {
+    let kinds = &ui.kinds;
     let chart_block = Block::default()
-        .title(" Activity - Current Week (Focus: Green, Idle: Yellow) ")
+        .title(Line::from(vec![
+            Span::raw(" Activity - Current Week ("),
+            Span::styled(
+                kinds.label(IntervalType::Focus),
+                Style::default().fg(kinds.color(IntervalType::Focus)),
+            ),
+            Span::raw(", "),
+            Span::styled(
+                kinds.label(IntervalType::Idle),
+                Style::default().fg(kinds.color(IntervalType::Idle)),
+            ),
+            Span::raw(") "),
+        ]))
         .borders(Borders::ALL);
     let inner_area = chart_block.inner(area);
     frame.render_widget(chart_block, area);
@@ -242,21 +867,8 @@ fn draw_chart(frame: &mut Frame, area: Rect, tracker: &Tracker) {
         return;
     }
 
-    // Get current week (Monday to Sunday)
-    let mut days_data = Vec::new();
-    let mut max_total_secs = 1;
-
-    for i in 0..7 {
-        let date = stats.week_start + Duration::days(i);
-        let day_stats = stats.daily_stats.get(&date).cloned().unwrap_or_default();
-        let focus_secs = day_stats.total_focus.num_seconds();
-        let idle_secs = day_stats.total_idle.num_seconds();
-        let total_secs = focus_secs + idle_secs;
-        if total_secs > max_total_secs {
-            max_total_secs = total_secs;
-        }
-        days_data.push((date.format("%a").to_string(), focus_secs, idle_secs));
-    }
+    let bars = chart::week_bars(stats);
+    let full = chart::full_bar_secs(&bars, ui.chart_scale);
 
     let columns = Layout::default()
         .direction(Direction::Horizontal)
@@ -271,9 +883,7 @@ fn draw_chart(frame: &mut Frame, area: Rect, tracker: &Tracker) {
         ])
         .split(inner_area);
 
-    for (i, (label, focus, idle)) in days_data.into_iter().enumerate() {
-        let col_area = columns[i];
-
+    for (bar, col_area) in bars.iter().zip(columns.iter().copied()) {
         let bar_label_split = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -288,10 +898,10 @@ fn draw_chart(frame: &mut Frame, area: Rect, tracker: &Tracker) {
         let label_area = bar_label_split[2];
 
         // Draw Focus value
-        if focus > 0 {
+        if bar.focus_secs > 0 {
             frame.render_widget(
-                Paragraph::new(format_duration(focus))
-                    .style(Style::default().fg(Color::Green))
+                Paragraph::new(chart::label(bar, ui.chart_scale))
+                    .style(Style::default().fg(kinds.color(IntervalType::Focus)))
                     .alignment(ratatui::layout::Alignment::Center),
                 value_area,
             );
@@ -309,15 +919,13 @@ fn draw_chart(frame: &mut Frame, area: Rect, tracker: &Tracker) {
 
         // Draw label
         frame.render_widget(
-            Paragraph::new(label).alignment(ratatui::layout::Alignment::Center),
+            Paragraph::new(bar.label.as_str()).alignment(ratatui::layout::Alignment::Center),
             label_area,
         );
 
         // Draw bar
         if centered_bar_area.height > 0 {
-            let total_height = centered_bar_area.height as i64;
-            let focus_height = (focus * total_height / max_total_secs) as u16;
-            let idle_height = (idle * total_height / max_total_secs) as u16;
+            let (focus_height, idle_height) = chart::stack(bar, full, centered_bar_area.height);
 
             let remaining_height = centered_bar_area
                 .height
@@ -333,18 +941,331 @@ fn draw_chart(frame: &mut Frame, area: Rect, tracker: &Tracker) {
                 .split(centered_bar_area);
 
             if idle_height > 0 {
-                frame.render_widget(Block::default().bg(Color::Yellow), bar_chunks[1]);
+                frame.render_widget(
+                    Block::default().bg(kinds.color(IntervalType::Idle)),
+                    bar_chunks[1],
+                );
             }
             if focus_height > 0 {
-                frame.render_widget(Block::default().bg(Color::Green), bar_chunks[2]);
+                frame.render_widget(
+                    Block::default().bg(kinds.color(IntervalType::Focus)),
+                    bar_chunks[2],
+                );
             }
         }
     }
 }
 
-fn draw_footer(frame: &mut Frame, area: Rect) {
-    let help = Paragraph::new("Press 'q' to quit | 'r' to reset | Neflo TUI v0.1.0")
+fn draw_hourly_chart(
+    frame: &mut Frame,
+    area: Rect,
+    tracker: &Tracker,
+    snapshot: &Snapshot,
+    kinds: &KindsConfig,
+) {
+    let stats = &snapshot.stats;
+    let chart_block = Block::default()
+        .title(" Interruptions by Hour - All Recorded Days ")
+        .borders(Borders::ALL);
+    let inner_area = chart_block.inner(area);
+    frame.render_widget(chart_block, area);
+
+    if inner_area.height < 3 || inner_area.width < 24 {
+        return;
+    }
+
+    let split = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(inner_area);
+    let suggestion = describe(&suggest_from_history(&tracker.db, snapshot.now));
+    frame.render_widget(
+        Paragraph::new(suggestion)
+            .style(Style::default().fg(Color::Cyan))
+            .alignment(ratatui::layout::Alignment::Center),
+        split[1],
+    );
+
+    let labels: Vec<String> = (0..24).map(|h| format!("{:02}", h)).collect();
+    let data: Vec<(&str, u64)> = labels
+        .iter()
+        .zip(stats.hourly_interruptions.iter())
+        .map(|(label, count)| (label.as_str(), *count as u64))
+        .collect();
+
+    // 24 bars separated by a single-cell gap
+    let bar_width = ((inner_area.width.saturating_sub(23)) / 24).max(1);
+    let chart = BarChart::default()
+        .data(&data)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(kinds.color(IntervalType::Idle)))
+        .value_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(kinds.color(IntervalType::Idle)),
+        );
+    frame.render_widget(chart, split[0]);
+}
+
+fn draw_error_log(frame: &mut Frame, area: Rect, errors: &ErrorLog) {
+    let items: Vec<ListItem> = if errors.entries.is_empty() {
+        vec![ListItem::new("No errors so far.")]
+    } else {
+        errors
+            .entries
+            .iter()
+            .rev()
+            .map(|(at, msg)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        at.format("%H:%M:%S ").to_string(),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw(msg.as_str()),
+                ]))
+            })
+            .collect()
+    };
+    let list = List::new(items).block(
+        Block::default()
+            .title(" Error Log (newest first, 'e' to close) ")
+            .borders(Borders::ALL),
+    );
+    frame.render_widget(list, area);
+}
+
+/// How well idle detection is working, and the colour to show it in.
+fn idle_health(tracker: &Tracker, ui: &UiState, now: DateTime<Utc>) -> (String, Color) {
+    let sample = ui.idle_sample.as_ref();
+    match sample {
+        _ if ui.idle_frozen => (
+            format!(
+                "Frozen: the same reading for {}s and no fallback; recording paused",
+                FROZEN_SECS
+            ),
+            Color::Red,
+        ),
+        Some(sample) if sample.source == IdleSource::Unavailable => (
+            "Unavailable on this platform: idle time always reads 0, so all time counts as focus"
+                .to_string(),
+            Color::Red,
+        ),
+        _ if tracker.screen_inactive() => (
+            "Not read while the screen saver, the lock screen or another user's session is showing"
+                .to_string(),
+            Color::DarkGray,
+        ),
+        _ if !tracker.should_track(now) => (
+            "Not sampling outside the schedule".to_string(),
+            Color::DarkGray,
+        ),
+        None => ("Waiting for the first reading".to_string(), Color::Yellow),
+        Some(sample) if now - sample.at > tracker.tick_interval() * STALLED_SAMPLES => (
+            format!(
+                "Stalled: no reading for {}",
+                format_duration((now - sample.at).num_seconds())
+            ),
+            Color::Red,
+        ),
+        Some(sample) if sample.source == IdleSource::HidIdleTime => (
+            format!("OK, fell back from {}", IdleSource::EventSource.name()),
+            Color::Yellow,
+        ),
+        Some(_) => ("OK".to_string(), Color::Green),
+    }
+}
+
+fn draw_idle_detection(
+    frame: &mut Frame,
+    area: Rect,
+    tracker: &Tracker,
+    ui: &UiState,
+    now: DateTime<Utc>,
+) {
+    let (health, color) = idle_health(tracker, ui, now);
+    let reading = match &ui.idle_sample {
+        Some(sample) => format!(
+            "{} idle, read {} ago",
+            format_duration(sample.idle.as_secs() as i64),
+            format_duration((now - sample.at).num_seconds().max(0))
+        ),
+        None => "none yet".to_string(),
+    };
+    let source = ui.idle_sample.map_or("-", |sample| sample.source.name());
+    let lines = vec![
+        Line::from(vec![
+            Span::raw("  Status:       "),
+            Span::styled(health, Style::default().fg(color)),
+        ]),
+        Line::from(format!("  Source:       {}", source)),
+        Line::from(format!("  Last reading: {}", reading)),
+    ];
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .title(" Idle Detection ('d' to close) ")
+            .borders(Borders::ALL),
+    );
+    frame.render_widget(panel, area);
+}
+
+fn draw_footer(
+    frame: &mut Frame,
+    area: Rect,
+    ui: &UiState,
+    goal_banner: Option<String>,
+    break_hint: Option<String>,
+) {
+    let help = match (ui.errors.toast(Local::now()), goal_banner, break_hint) {
+        _ if ui.reset_prompt => Paragraph::new(
+            "Reset what? 's' this session | 't' today | 'a' everything | any other key to cancel",
+        )
+        .style(Style::default().fg(Color::Black).bg(Color::Yellow)),
+        (Some(msg), _, _) => Paragraph::new(format!("Error: {} ('e' for details)", msg))
+            .style(Style::default().fg(Color::White).bg(Color::Red)),
+        _ if ui.idle_frozen => {
+            Paragraph::new("Idle detection is frozen; recording paused ('e' for details)")
+                .style(Style::default().fg(Color::White).bg(Color::Red))
+        }
+        (None, Some(banner), _) => Paragraph::new(banner).style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        ),
+        (None, None, Some(hint)) => {
+            Paragraph::new(hint).style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        }
+        (None, None, None) if ui.observe => Paragraph::new(
+            "OBSERVING (read-only) | Press 'q' to quit | 'h' to toggle hourly view | 'e' for errors",
+        ),
+        (None, None, None) => Paragraph::new(
+            "Press 'q' to quit | 'r' to reset | 'h' to toggle hourly view | 'e' for errors | 'd' for idle detection | Neflo TUI v0.1.0",
+        ),
+    };
+    let help = help
         .block(Block::default().borders(Borders::ALL))
         .alignment(ratatui::layout::Alignment::Center);
     frame.render_widget(help, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_log_drops_oldest_and_expires_toast() {
+        let mut log = ErrorLog::default();
+        let t0 = Local::now();
+        for i in 0..ERROR_LOG_CAPACITY + 5 {
+            log.push(t0, &anyhow::anyhow!("error {}", i));
+        }
+        assert_eq!(log.entries.len(), ERROR_LOG_CAPACITY);
+        assert_eq!(log.entries.front().unwrap().1, "error 5");
+
+        assert_eq!(
+            log.toast(t0 + Duration::seconds(1)),
+            Some(format!("error {}", ERROR_LOG_CAPACITY + 4).as_str())
+        );
+        assert_eq!(log.toast(t0 + Duration::seconds(TOAST_SECS)), None);
+    }
+
+    #[test]
+    fn test_frame_shows_snapshot_time() -> Result<()> {
+        use crate::clock::FixedClock;
+        use crate::storage::Storage;
+        use chrono::TimeZone;
+        use ratatui::backend::TestBackend;
+
+        let dir = tempfile::tempdir()?;
+        let t = Local.with_ymd_and_hms(2024, 5, 13, 10, 0, 0).unwrap();
+        let clock = FixedClock::new(t.with_timezone(&Utc));
+        let storage = Storage::from_path(dir.path().join("db.json"));
+        let tracker = Tracker::with_clock(storage, 5, None, None, None, Box::new(clock.clone()))?;
+        let snapshot = Snapshot::take(&tracker, &UiState::default(), tracker.now());
+
+        // The tracker's clock moving on does not leak into a frame already taken
+        clock.advance(Duration::minutes(1));
+        let mut terminal = Terminal::new(TestBackend::new(120, 40))?;
+        terminal.draw(|f| draw(f, &tracker, &UiState::default(), &snapshot))?;
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("2024-05-13 10:00:00"));
+        assert_eq!(snapshot.stats.today, t.date_naive());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_layout_shows_the_current_state_timer() -> Result<()> {
+        use crate::clock::FixedClock;
+        use crate::storage::Storage;
+        use ratatui::backend::TestBackend;
+
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2024, 5, 13, 8, 0, 0).unwrap());
+        let mut tracker =
+            Tracker::with_clock(Storage::in_memory(), 5, None, None, None, Box::new(clock))?;
+        let now = tracker.now();
+        tracker.last_kind_seen = Some(IntervalType::Focus);
+        tracker.state_start = now - Duration::seconds(65);
+        let ui = UiState {
+            compact: true,
+            ..Default::default()
+        };
+        let snapshot = Snapshot::take(&tracker, &ui, now);
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 12))?;
+        terminal.draw(|f| draw(f, &tracker, &ui, &snapshot))?;
+        let buffer = terminal.backend().buffer();
+        let rows: Vec<String> = (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol())
+                    .collect()
+            })
+            .collect();
+        assert!(rows[1].contains("IN FLOW"));
+        let screen = rows.join("\n");
+        for row in big_text("01:05") {
+            assert!(screen.contains(&row));
+        }
+        assert!(!screen.contains("TODAY"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_health_flags_stalled_and_missing_detection() -> Result<()> {
+        use crate::clock::FixedClock;
+        use crate::storage::Storage;
+
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2024, 5, 13, 8, 0, 0).unwrap());
+        let tracker =
+            Tracker::with_clock(Storage::in_memory(), 5, None, None, None, Box::new(clock))?;
+        let now = tracker.now();
+        let mut ui = UiState::default();
+        assert_eq!(idle_health(&tracker, &ui, now).1, Color::Yellow);
+
+        let sample = IdleSample {
+            source: IdleSource::EventSource,
+            idle: StdDuration::from_secs(12),
+            at: now,
+        };
+        ui.idle_sample = Some(sample);
+        assert_eq!(idle_health(&tracker, &ui, now).0, "OK");
+        let (health, color) = idle_health(&tracker, &ui, now + Duration::seconds(60));
+        assert_eq!(health, "Stalled: no reading for 1m");
+        assert_eq!(color, Color::Red);
+
+        ui.idle_sample = Some(IdleSample {
+            source: IdleSource::Unavailable,
+            ..sample
+        });
+        assert!(idle_health(&tracker, &ui, now).0.starts_with("Unavailable"));
+        Ok(())
+    }
+}
@@ -1,10 +1,99 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use self_update::cargo_crate_version;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
-/// Check for and apply updates from GitHub.
-pub fn update() -> Result<()> {
+/// Dropped in the state directory by `self-update --restart` for a running session to pick up.
+const RESTART_REQUEST_FILE: &str = "restart.request";
+/// Carries the session start across the restart so duration and session stats continue.
+const SESSION_START_ENV: &str = "NEFLO_SESSION_START";
+/// Directory in the data directory holding the binary replaced by the last
+/// update, as `neflo-<version>`.
+const PREVIOUS_DIR: &str = "previous";
+/// Log of updates and rollbacks in the data directory.
+const UPDATES_LOG: &str = "updates.log";
+/// Where release assets are downloaded from, by tag and asset name.
+const RELEASE_DOWNLOADS: &str = "https://github.com/impulia/neuroflow/releases/download";
+
+/// A package manager that owns the installed binary; replacing the binary
+/// behind its back breaks its bookkeeping.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackageManager {
+    Homebrew,
+    Nix,
+}
+
+impl PackageManager {
+    /// Guess from where `exe` lives, after following symlinks.
+    pub fn owning(exe: &Path) -> Option<Self> {
+        let path = fs::canonicalize(exe).unwrap_or_else(|_| exe.to_path_buf());
+        let path = path.to_string_lossy();
+        if path.starts_with("/nix/store/") || path.contains("/.nix-profile/") {
+            Some(PackageManager::Nix)
+        } else if path.contains("/Cellar/")
+            || path.starts_with("/opt/homebrew/")
+            || path.starts_with("/home/linuxbrew/")
+        {
+            Some(PackageManager::Homebrew)
+        } else {
+            None
+        }
+    }
+
+    /// How to update through the package manager instead.
+    pub fn instructions(&self) -> &'static str {
+        match self {
+            PackageManager::Homebrew => "run `brew upgrade neflo`",
+            PackageManager::Nix => {
+                "update it through Nix, e.g. `nix profile upgrade` or your system configuration"
+            }
+        }
+    }
+}
+
+/// Check for and apply updates from GitHub, keeping the binary at `exe` so the
+/// update can be rolled back. Returns whether a new version was installed.
+pub fn update(data_dir: &Path, exe: &Path) -> Result<bool> {
     println!("Checking for updates...");
 
+    // Copied before the update replaces it; kept only if it does
+    let staged = stage_copy(data_dir, exe)?;
+    match install(exe) {
+        Ok(Some(version)) => {
+            keep_previous(data_dir, &staged, cargo_crate_version!())?;
+            log(data_dir, "update", cargo_crate_version!(), &version)?;
+            Ok(true)
+        }
+        Ok(None) => {
+            fs::remove_file(&staged)?;
+            Ok(false)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&staged);
+            Err(err)
+        }
+    }
+}
+
+/// Install the latest release, returning its version if it is newer. A
+/// delta against the running version is tried first.
+fn install(exe: &Path) -> Result<Option<String>> {
+    match install_delta(exe) {
+        Ok(Some(version)) => return Ok(Some(version)),
+        Ok(None) => {}
+        Err(err) => println!(
+            "Delta update failed ({:#}); downloading the full release.",
+            err
+        ),
+    }
+
+    let target = release_target(std::env::consts::OS, std::env::consts::ARCH)?;
     let status = self_update::backends::github::Update::configure()
         .repo_owner("impulia")
         .repo_name("neuroflow")
@@ -12,15 +101,338 @@ pub fn update() -> Result<()> {
         .show_download_progress(true)
         .current_version(cargo_crate_version!())
         .no_confirm(true)
-        .target("macos")
+        .target(&target)
         .build()?
         .update()?;
 
     if status.updated() {
         println!("Successfully updated to version {}!", status.version());
+        Ok(Some(status.version().to_string()))
     } else {
         println!("Already up to date (version {})!", status.version());
+        Ok(None)
+    }
+}
+
+/// The platform part of the release archive names, e.g. `macos-aarch64` in
+/// `neflo-macos-aarch64.tar.gz`, for `os` and `arch` as in [`std::env::consts`].
+fn release_target(os: &str, arch: &str) -> Result<String> {
+    match (os, arch) {
+        ("macos" | "linux", "aarch64" | "x86_64") => Ok(format!("{}-{}", os, arch)),
+        _ => anyhow::bail!("No releases are built for {} on {}", os, arch),
+    }
+}
+
+/// Name of the release asset patching `from` into `to` on `target`.
+fn delta_name(target: &str, from: &str, to: &str) -> String {
+    format!("neflo-{}-{}-to-{}.bsdiff", target, from, to)
+}
+
+/// Name of the release asset holding the SHA-256 of the binary released for `target`.
+fn checksum_name(target: &str) -> String {
+    format!("neflo-{}.sha256", target)
+}
+
+/// Patch `exe` up to the latest release with its bsdiff asset from the running
+/// version, if the release has one. Both the running and the patched binary
+/// must match the SHA-256 published with their release, otherwise nothing is
+/// installed and the full release is downloaded instead. Returns the version
+/// installed.
+fn install_delta(exe: &Path) -> Result<Option<String>> {
+    let releases = self_update::backends::github::ReleaseList::configure()
+        .repo_owner("impulia")
+        .repo_name("neuroflow")
+        .build()?
+        .fetch()?;
+    let Some(latest) = releases.first() else {
+        return Ok(None);
+    };
+    if !self_update::version::bump_is_greater(cargo_crate_version!(), &latest.version)? {
+        return Ok(None);
+    }
+    let Some(current) = releases
+        .iter()
+        .find(|release| release.version == cargo_crate_version!())
+    else {
+        return Ok(None);
+    };
+    let target = release_target(std::env::consts::OS, std::env::consts::ARCH)?;
+    let name = delta_name(&target, cargo_crate_version!(), &latest.version);
+    let checksum = checksum_name(&target);
+    let has_asset = |release: &self_update::update::Release, name: &str| {
+        release.assets.iter().any(|asset| asset.name == name)
+    };
+    if !has_asset(latest, &name) || !has_asset(latest, &checksum) || !has_asset(current, &checksum)
+    {
+        return Ok(None);
+    }
+
+    let old_sha = String::from_utf8(download(&current.version, &checksum, false)?)?;
+    let new_sha = String::from_utf8(download(&latest.version, &checksum, false)?)?;
+    println!("Downloading {}...", name);
+    let delta = download(&latest.version, &name, true)?;
+    let Some(patched) = apply_delta(&fs::read(exe)?, &delta, &old_sha, &new_sha)? else {
+        println!("Delta does not match this binary; downloading the full release.");
+        return Ok(None);
+    };
+
+    // Staged next to `exe` so the final rename stays on one file system
+    let staged = exe.with_file_name(".neflo.delta");
+    fs::write(&staged, patched)?;
+    fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&staged, exe).with_context(|| format!("Failed to replace {}", exe.display()))?;
+    println!("Successfully updated to version {}!", latest.version);
+    Ok(Some(latest.version.clone()))
+}
+
+/// Download the release asset `name` of `version`.
+fn download(version: &str, name: &str, show_progress: bool) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    self_update::Download::from_url(&format!("{}/v{}/{}", RELEASE_DOWNLOADS, version, name))
+        .show_progress(show_progress)
+        .download_to(&mut buf)?;
+    Ok(buf)
+}
+
+/// Patch `old` with `delta`, or `None` if `old` is not the binary the delta was
+/// made from or the result is not the binary it was made to, by their
+/// published SHA-256 as written by `sha256sum`.
+fn apply_delta(old: &[u8], delta: &[u8], old_sha: &str, new_sha: &str) -> Result<Option<Vec<u8>>> {
+    if !sha256_matches(old, old_sha) {
+        return Ok(None);
     }
+    let mut new = Vec::new();
+    bsdiff::patch(old, &mut &delta[..], &mut new).context("Invalid delta")?;
+    Ok(sha256_matches(&new, new_sha).then_some(new))
+}
 
+fn sha256_matches(bytes: &[u8], published: &str) -> bool {
+    let hash = format!("{:x}", Sha256::digest(bytes));
+    published
+        .split_whitespace()
+        .next()
+        .is_some_and(|expected| expected.eq_ignore_ascii_case(&hash))
+}
+
+/// Put the binary replaced by the last update back at `exe`, keeping the
+/// current one, which is `current`, so the rollback can itself be undone.
+/// Returns the version restored.
+pub fn rollback(data_dir: &Path, exe: &Path, current: &str) -> Result<String> {
+    let (backup, version) = previous(data_dir)?
+        .context("No previous version kept; one is kept by the next self-update")?;
+    let staged_current = stage_copy(data_dir, exe)?;
+    // Staged next to `exe` so the final rename stays on one file system
+    let staged_exe = exe.with_file_name(".neflo.rollback");
+    fs::copy(&backup, &staged_exe)?;
+    fs::rename(&staged_exe, exe).with_context(|| format!("Failed to replace {}", exe.display()))?;
+    keep_previous(data_dir, &staged_current, current)?;
+    log(data_dir, "rollback", current, &version)?;
+    Ok(version)
+}
+
+/// The kept binary and its version, if any.
+fn previous(data_dir: &Path) -> Result<Option<(PathBuf, String)>> {
+    let dir = data_dir.join(PREVIOUS_DIR);
+    if !dir.exists() {
+        return Ok(None);
+    }
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(version) = name.strip_prefix("neflo-") {
+            let version = version.to_string();
+            return Ok(Some((path, version)));
+        }
+    }
+    Ok(None)
+}
+
+/// Copy `exe` into the previous-version directory under a temporary name.
+fn stage_copy(data_dir: &Path, exe: &Path) -> Result<PathBuf> {
+    let dir = data_dir.join(PREVIOUS_DIR);
+    fs::create_dir_all(&dir)?;
+    let staged = dir.join("staged");
+    fs::copy(exe, &staged).with_context(|| format!("Failed to copy {}", exe.display()))?;
+    Ok(staged)
+}
+
+/// Make `staged` the kept binary for `version`, replacing the one kept before.
+fn keep_previous(data_dir: &Path, staged: &Path, version: &str) -> Result<()> {
+    if let Some((old, _)) = previous(data_dir)? {
+        fs::remove_file(old)?;
+    }
+    fs::rename(
+        staged,
+        data_dir
+            .join(PREVIOUS_DIR)
+            .join(format!("neflo-{}", version)),
+    )?;
+    Ok(())
+}
+
+fn log(data_dir: &Path, action: &str, from: &str, to: &str) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(data_dir.join(UPDATES_LOG))?;
+    writeln!(
+        file,
+        "{} {} {} -> {}",
+        Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        action,
+        from,
+        to
+    )?;
+    Ok(())
+}
+
+/// Ask the session whose state lives in `state_dir` to restart into the binary at `exe`.
+pub fn request_restart(state_dir: &Path, exe: &Path) -> Result<()> {
+    fs::write(
+        state_dir.join(RESTART_REQUEST_FILE),
+        exe.to_string_lossy().as_bytes(),
+    )?;
     Ok(())
 }
+
+/// Consume a pending restart request, returning the binary to restart into.
+pub fn take_restart_request(state_dir: &Path) -> Result<Option<PathBuf>> {
+    let path = state_dir.join(RESTART_REQUEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let exe = fs::read_to_string(&path)?;
+    fs::remove_file(&path)?;
+    Ok(Some(PathBuf::from(exe.trim())))
+}
+
+/// Replace this process with `exe`, keeping the command line and the session start.
+/// Only returns if the exec failed.
+pub fn restart(exe: &Path, session_start: DateTime<Utc>) -> Result<()> {
+    let err = Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .env(SESSION_START_ENV, session_start.to_rfc3339())
+        .exec();
+    Err(err).with_context(|| format!("Failed to restart into {}", exe.display()))
+}
+
+/// The session start handed over by [`restart`], if this process is a restarted session.
+pub fn resumed_session_start() -> Option<DateTime<Utc>> {
+    let value = std::env::var(SESSION_START_ENV).ok()?;
+    std::env::remove_var(SESSION_START_ENV);
+    DateTime::parse_from_rfc3339(&value)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_restart_request_round_trip() -> Result<()> {
+        let dir = tempdir()?;
+        assert_eq!(take_restart_request(dir.path())?, None);
+
+        request_restart(dir.path(), Path::new("/usr/local/bin/neflo"))?;
+        assert_eq!(
+            take_restart_request(dir.path())?,
+            Some(PathBuf::from("/usr/local/bin/neflo"))
+        );
+        // Consumed by the first read
+        assert_eq!(take_restart_request(dir.path())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_delta() -> Result<()> {
+        let old = b"neflo 0.2.0 binary".repeat(100);
+        let new = b"neflo 0.3.0 binary!".repeat(100);
+        let mut delta = Vec::new();
+        bsdiff::diff(&old, &new, &mut delta)?;
+
+        let sha = |bytes: &[u8]| format!("{:x}  neflo\n", Sha256::digest(bytes));
+        let (old_sha, new_sha) = (sha(&old), sha(&new));
+
+        assert_eq!(
+            apply_delta(&old, &delta, &old_sha, &new_sha)?,
+            Some(new.clone())
+        );
+        assert!(apply_delta(&old, b"garbage", &old_sha, &new_sha).is_err());
+        // Not the published result, e.g. a corrupted or tampered delta
+        assert_eq!(apply_delta(&old, &delta, &old_sha, &old_sha)?, None);
+        assert_eq!(
+            delta_name("macos-aarch64", "0.2.0", "0.3.0"),
+            "neflo-macos-aarch64-0.2.0-to-0.3.0.bsdiff"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_delta_on_a_different_base_falls_back() -> Result<()> {
+        let old = b"neflo 0.2.0 binary".repeat(100);
+        let new = b"neflo 0.3.0 binary!".repeat(100);
+        let mut delta = Vec::new();
+        bsdiff::diff(&old, &new, &mut delta)?;
+        let sha = |bytes: &[u8]| format!("{:x}", Sha256::digest(bytes));
+
+        // A locally built or already patched binary of the same version
+        let other = b"neflo 0.2.0 local build".repeat(100);
+        assert_eq!(apply_delta(&other, &delta, &sha(&old), &sha(&new))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_release_target() -> Result<()> {
+        assert_eq!(release_target("macos", "aarch64")?, "macos-aarch64");
+        assert_eq!(release_target("linux", "x86_64")?, "linux-x86_64");
+        assert!(release_target("windows", "x86_64").is_err());
+        assert!(release_target("freebsd", "x86_64").is_err());
+        assert!(release_target("linux", "riscv64").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_manager_from_path() {
+        let owning = |path: &str| PackageManager::owning(Path::new(path));
+        assert_eq!(
+            owning("/opt/homebrew/Cellar/neflo/0.2.0/bin/neflo"),
+            Some(PackageManager::Homebrew)
+        );
+        assert_eq!(
+            owning("/usr/local/Cellar/neflo/0.2.0/bin/neflo"),
+            Some(PackageManager::Homebrew)
+        );
+        assert_eq!(
+            owning("/nix/store/abc123-neflo-0.2.0/bin/neflo"),
+            Some(PackageManager::Nix)
+        );
+        assert_eq!(owning("/Users/anna/.cargo/bin/neflo"), None);
+    }
+
+    #[test]
+    fn test_rollback_swaps_binaries() -> Result<()> {
+        let dir = tempdir()?;
+        let exe = dir.path().join("neflo");
+        assert!(rollback(dir.path(), &exe, "0.3.0").is_err());
+
+        // As left by an update from 0.2.0 to 0.3.0
+        fs::write(&exe, "0.2.0")?;
+        let staged = stage_copy(dir.path(), &exe)?;
+        fs::write(&exe, "0.3.0")?;
+        keep_previous(dir.path(), &staged, "0.2.0")?;
+
+        assert_eq!(rollback(dir.path(), &exe, "0.3.0")?, "0.2.0");
+        assert_eq!(fs::read_to_string(&exe)?, "0.2.0");
+        // The rolled back version is kept in turn
+        assert_eq!(
+            fs::read_to_string(dir.path().join("previous/neflo-0.3.0"))?,
+            "0.3.0"
+        );
+        assert!(!dir.path().join("previous/neflo-0.2.0").exists());
+        let log = fs::read_to_string(dir.path().join(UPDATES_LOG))?;
+        assert!(log.trim_end().ends_with(" rollback 0.3.0 -> 0.2.0"));
+        Ok(())
+    }
+}
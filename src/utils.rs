@@ -1,3 +1,7 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use ratatui::style::Color;
+use std::path::PathBuf;
+
 pub fn format_duration(seconds: i64) -> String {
     let days = seconds / 86400;
     let hours = (seconds % 86400) / 3600;
@@ -21,6 +25,210 @@ pub fn format_duration(seconds: i64) -> String {
     parts.join(" ")
 }
 
+/// `duration` in whole minutes, e.g. "1h 35m".
+pub fn format_minutes(duration: Duration) -> String {
+    match duration.num_minutes() {
+        0 => "0m".to_string(),
+        minutes => format_duration(minutes * 60),
+    }
+}
+
+/// The ISO 8601 week `date` falls in, as a year-week key, e.g. "2024-W09".
+/// Days around New Year can belong to a week of the neighbouring year.
+pub fn year_week(date: NaiveDate) -> String {
+    date.format("%G-W%V").to_string()
+}
+
+/// When console output is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// When writing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output written to stdout.
+    pub fn enabled(self) -> bool {
+        use std::io::IsTerminal;
+        self.enabled_for(
+            std::io::stdout().is_terminal(),
+            std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+        )
+    }
+
+    fn enabled_for(self, terminal: bool, no_color: bool) -> bool {
+        match self {
+            ColorChoice::Auto => terminal && !no_color,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// `text` in `color` for a terminal, using ANSI escape codes; unchanged when
+/// `enabled` is false or the color is the terminal's default.
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+    let code = match color {
+        Color::Black => "30".to_string(),
+        Color::Red => "31".to_string(),
+        Color::Green => "32".to_string(),
+        Color::Yellow => "33".to_string(),
+        Color::Blue => "34".to_string(),
+        Color::Magenta => "35".to_string(),
+        Color::Cyan => "36".to_string(),
+        Color::Gray => "37".to_string(),
+        Color::DarkGray => "90".to_string(),
+        Color::LightRed => "91".to_string(),
+        Color::LightGreen => "92".to_string(),
+        Color::LightYellow => "93".to_string(),
+        Color::LightBlue => "94".to_string(),
+        Color::LightMagenta => "95".to_string(),
+        Color::LightCyan => "96".to_string(),
+        Color::White => "97".to_string(),
+        Color::Indexed(index) => format!("38;5;{}", index),
+        Color::Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
+        Color::Reset => return text.to_string(),
+    };
+    if !enabled || text.is_empty() {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// Rows of the block glyphs drawn by `big_text`.
+pub const BIG_TEXT_ROWS: usize = 5;
+
+/// `text` in block glyphs five rows high, for reading from across the room.
+/// Digits and ':' have glyphs; anything else is left blank.
+pub fn big_text(text: &str) -> Vec<String> {
+    let glyph = |c: char| -> [&str; BIG_TEXT_ROWS] {
+        match c {
+            '0' => ["███", "█ █", "█ █", "█ █", "███"],
+            '1' => ["  █", "  █", "  █", "  █", "  █"],
+            '2' => ["███", "  █", "███", "█  ", "███"],
+            '3' => ["███", "  █", "███", "  █", "███"],
+            '4' => ["█ █", "█ █", "███", "  █", "  █"],
+            '5' => ["███", "█  ", "███", "  █", "███"],
+            '6' => ["███", "█  ", "███", "█ █", "███"],
+            '7' => ["███", "  █", "  █", "  █", "  █"],
+            '8' => ["███", "█ █", "███", "█ █", "███"],
+            '9' => ["███", "█ █", "███", "  █", "███"],
+            ':' => [" ", "█", " ", "█", " "],
+            _ => [" "; BIG_TEXT_ROWS],
+        }
+    };
+    (0..BIG_TEXT_ROWS)
+        .map(|row| {
+            text.chars()
+                .map(|c| glyph(c)[row])
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Expand a leading `~` to the user's home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+/// `duration` as a chrono duration, saturating at the longest one it can hold.
+pub fn chrono_duration(duration: std::time::Duration) -> Duration {
+    Duration::from_std(duration).unwrap_or(Duration::MAX)
+}
+
+/// `ratio` as a whole percentage, e.g. "62%".
+pub fn format_percent(ratio: f64) -> String {
+    format!("{:.0}%", ratio * 100.0)
+}
+
+/// Render a horizontal bar of `width` cells proportional to `value / max`.
+pub fn format_bar(value: i64, max: i64, width: usize) -> String {
+    if max <= 0 || value <= 0 {
+        return String::new();
+    }
+    let filled = ((value as f64 / max as f64) * width as f64).round() as usize;
+    "█".repeat(filled.clamp(1, width))
+}
+
+/// A terminal color as opaque 0xAARRGGBB, using the xterm palette for named colors.
+pub fn color_argb(color: Color) -> String {
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0x00, 0x00, 0x00),
+        Color::Red => (0xcd, 0x00, 0x00),
+        Color::Green => (0x00, 0xcd, 0x00),
+        Color::Yellow => (0xcd, 0xcd, 0x00),
+        Color::Blue => (0x00, 0x00, 0xee),
+        Color::Magenta => (0xcd, 0x00, 0xcd),
+        Color::Cyan => (0x00, 0xcd, 0xcd),
+        Color::Gray => (0xe5, 0xe5, 0xe5),
+        Color::DarkGray => (0x7f, 0x7f, 0x7f),
+        Color::LightRed => (0xff, 0x00, 0x00),
+        Color::LightGreen => (0x00, 0xff, 0x00),
+        Color::LightYellow => (0xff, 0xff, 0x00),
+        Color::LightBlue => (0x5c, 0x5c, 0xff),
+        Color::LightMagenta => (0xff, 0x00, 0xff),
+        Color::LightCyan => (0x00, 0xff, 0xff),
+        Color::White | Color::Reset | Color::Indexed(_) => (0xff, 0xff, 0xff),
+    };
+    format!("0xff{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// A color from `color_argb` as a CSS hex color, e.g. "#00cd00".
+pub fn color_hex(argb: &str) -> String {
+    format!("#{}", argb.get(4..).unwrap_or("ffffff"))
+}
+
+/// Whether `time` falls in the daily window from `start` to `end`, which wraps
+/// past midnight when `end` is earlier, e.g. 22:00-07:00.
+pub fn in_daily_window(time: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        time >= start && time < end
+    } else {
+        time >= start || time < end
+    }
+}
+
+/// `time` on `date` in `tz`, resolved the same way on every day: a time the
+/// clocks pass twice (falling back) is its first occurrence, and a time they
+/// skip (springing forward) is the moment they jump.
+pub fn resolve_local<Tz: TimeZone>(tz: &Tz, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    let local = date.and_time(time);
+    // Gaps are whole minutes and no longer than a day
+    (0..=24 * 60)
+        .find_map(|minute| {
+            tz.from_local_datetime(&(local + Duration::minutes(minute)))
+                .earliest()
+        })
+        .map_or_else(|| local.and_utc(), |t| t.with_timezone(&Utc))
+}
+
+/// The weeks of the month containing `date`, Monday first; days outside the
+/// month are `None`.
+pub fn month_weeks(date: NaiveDate) -> Vec<[Option<NaiveDate>; 7]> {
+    let first = date.with_day(1).unwrap_or(date);
+    let mut day = first - Duration::days(first.weekday().num_days_from_monday() as i64);
+    let mut weeks = Vec::new();
+    while day.month() == first.month() || day < first {
+        let mut week = [None; 7];
+        for slot in &mut week {
+            *slot = (day.month() == first.month()).then_some(day);
+            day += Duration::days(1);
+        }
+        weeks.push(week);
+    }
+    weeks
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +245,55 @@ mod tests {
         assert_eq!(format_duration(86400), "1d");
         assert_eq!(format_duration(86400 + 3600 + 60 + 1), "1d 1h 1m 1s");
     }
+
+    #[test]
+    fn test_format_bar() {
+        assert_eq!(format_bar(0, 10, 10), "");
+        assert_eq!(format_bar(5, 0, 10), "");
+        assert_eq!(format_bar(10, 10, 10), "██████████");
+        assert_eq!(format_bar(5, 10, 10), "█████");
+        assert_eq!(format_bar(1, 1000, 10), "█");
+    }
+
+    #[test]
+    fn test_year_week() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(year_week(date(2024, 3, 1)), "2024-W09");
+        assert_eq!(year_week(date(2024, 12, 30)), "2025-W01");
+        assert_eq!(year_week(date(2021, 1, 3)), "2020-W53");
+    }
+
+    #[test]
+    fn test_color_choice_and_paint() {
+        assert!(ColorChoice::Auto.enabled_for(true, false));
+        assert!(!ColorChoice::Auto.enabled_for(true, true));
+        assert!(!ColorChoice::Auto.enabled_for(false, false));
+        assert!(ColorChoice::Always.enabled_for(false, true));
+        assert!(!ColorChoice::Never.enabled_for(true, false));
+
+        assert_eq!(paint("focus", Color::Green, true), "\x1b[32mfocus\x1b[0m");
+        assert_eq!(
+            paint("█", Color::Rgb(255, 0, 16), true),
+            "\x1b[38;2;255;0;16m█\x1b[0m"
+        );
+        assert_eq!(paint("focus", Color::Green, false), "focus");
+    }
+
+    #[test]
+    fn test_big_text() {
+        let rows = big_text("1:05");
+        assert_eq!(rows.len(), BIG_TEXT_ROWS);
+        assert_eq!(rows[0], "  █   ███ ███");
+        assert_eq!(rows[1], "  █ █ █ █ █  ");
+        assert!(rows.iter().all(|row| row.chars().count() == 13));
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        assert_eq!(expand_tilde("/tmp/x.md"), PathBuf::from("/tmp/x.md"));
+        assert_eq!(expand_tilde("notes/x.md"), PathBuf::from("notes/x.md"));
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(expand_tilde("~/x.md"), home.join("x.md"));
+        }
+    }
 }
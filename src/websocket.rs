@@ -0,0 +1,197 @@
+use crate::config::WebSocketConfig;
+use crate::models::IntervalType;
+use crate::stats::SummaryStats;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, WebSocket};
+
+/// Seconds between `stats` events.
+const STATS_SECS: i64 = 60;
+/// How long a client may take to complete the handshake or read an event.
+const CLIENT_TIMEOUT_SECS: u64 = 2;
+/// How often the server thread checks for new connections between events.
+const ACCEPT_POLL_MILLIS: u64 = 100;
+
+/// Streams state transitions and per-minute totals as JSON events to
+/// WebSocket clients of `ws://<bind>:<port>/ws`. Clients that fall behind or
+/// disconnect are dropped. Clients are served from a thread of their own, so
+/// a slow one never holds up recording.
+pub struct EventStream {
+    events: Sender<Value>,
+    /// Connected clients, as counted by the server thread.
+    listeners: Arc<AtomicUsize>,
+    next_stats: Option<DateTime<Utc>>,
+}
+
+impl EventStream {
+    pub fn bind(config: &WebSocketConfig) -> Result<Self> {
+        let listener = TcpListener::bind((config.bind.as_str(), config.port))
+            .with_context(|| format!("Failed to listen on {}:{}", config.bind, config.port))?;
+        listener.set_nonblocking(true)?;
+        let (events, received) = mpsc::channel();
+        let listeners = Arc::new(AtomicUsize::new(0));
+        let counter = listeners.clone();
+        thread::spawn(move || serve(listener, received, &counter));
+        Ok(Self {
+            events,
+            listeners,
+            next_stats: None,
+        })
+    }
+
+    pub fn publish_state(&mut self, kind: IntervalType, since: DateTime<Utc>) {
+        self.broadcast(&state_event(kind, since));
+    }
+
+    /// Send today's totals; the next are due a minute later.
+    pub fn publish_stats(
+        &mut self,
+        kind: IntervalType,
+        since: DateTime<Utc>,
+        today: &SummaryStats,
+        now: DateTime<Utc>,
+    ) {
+        self.next_stats = Some(now + Duration::seconds(STATS_SECS));
+        self.broadcast(&stats_event(kind, since, today, now));
+    }
+
+    /// Whether a `stats` event is due; totals are only computed for listeners.
+    pub fn stats_due(&self, now: DateTime<Utc>) -> bool {
+        self.listeners.load(Ordering::Relaxed) > 0 && self.next_stats.is_none_or(|next| now >= next)
+    }
+
+    fn broadcast(&mut self, event: &Value) {
+        let _ = self.events.send(event.clone());
+    }
+}
+
+/// Send each event to every client, and greet new clients with the latest
+/// state, until the stream is dropped.
+fn serve(listener: TcpListener, events: Receiver<Value>, listeners: &AtomicUsize) {
+    let mut clients: Vec<WebSocket<TcpStream>> = Vec::new();
+    let mut state = None;
+    loop {
+        match events.recv_timeout(std::time::Duration::from_millis(ACCEPT_POLL_MILLIS)) {
+            Ok(event) => {
+                clients.retain_mut(|client| send(client, &event));
+                if event["type"] == "state" {
+                    state = Some(event);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        while let Ok((stream, _)) = listener.accept() {
+            if let Some(mut client) = handshake(stream) {
+                if state.as_ref().is_none_or(|state| send(&mut client, state)) {
+                    clients.push(client);
+                }
+            }
+        }
+        listeners.store(clients.len(), Ordering::Relaxed);
+    }
+}
+
+// The callback's error type is set by tungstenite
+#[allow(clippy::result_large_err)]
+fn handshake(stream: TcpStream) -> Option<WebSocket<TcpStream>> {
+    let timeout = Some(std::time::Duration::from_secs(CLIENT_TIMEOUT_SECS));
+    stream.set_nonblocking(false).ok()?;
+    stream.set_read_timeout(timeout).ok()?;
+    stream.set_write_timeout(timeout).ok()?;
+    let only_ws = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if request.uri().path() == "/ws" {
+            Ok(response)
+        } else {
+            let mut not_found = ErrorResponse::new(Some("Not found".to_string()));
+            *not_found.status_mut() = StatusCode::NOT_FOUND;
+            Err(not_found)
+        }
+    };
+    tungstenite::accept_hdr(stream, only_ws).ok()
+}
+
+fn send(client: &mut WebSocket<TcpStream>, event: &Value) -> bool {
+    client.send(Message::text(event.to_string())).is_ok()
+}
+
+fn state_name(kind: IntervalType) -> String {
+    format!("{:?}", kind).to_lowercase()
+}
+
+fn state_event(kind: IntervalType, since: DateTime<Utc>) -> Value {
+    json!({
+        "type": "state",
+        "state": state_name(kind),
+        "since": since.to_rfc3339(),
+    })
+}
+
+fn stats_event(
+    kind: IntervalType,
+    since: DateTime<Utc>,
+    today: &SummaryStats,
+    now: DateTime<Utc>,
+) -> Value {
+    json!({
+        "type": "stats",
+        "state": state_name(kind),
+        "since": since.to_rfc3339(),
+        "state_mins": (now - since).num_minutes(),
+        "focus_mins": today.total_focus.num_minutes(),
+        "idle_mins": today.total_idle.num_minutes(),
+        "deep_work_mins": today.total_deep_work.num_minutes(),
+        "interruptions": today.idle_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_clients_get_state_and_stats() -> Result<()> {
+        // Reserve a free port, then let the stream take it
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let mut stream = EventStream::bind(&WebSocketConfig {
+            bind: "127.0.0.1".to_string(),
+            port,
+        })?;
+        let since = "2024-05-13T09:00:00Z".parse::<DateTime<Utc>>()?;
+
+        let client = std::thread::spawn(move || -> Result<Vec<Value>> {
+            let (mut socket, _) = tungstenite::connect(format!("ws://127.0.0.1:{}/ws", port))?;
+            (0..2)
+                .map(|_| Ok(serde_json::from_str(socket.read()?.to_text()?)?))
+                .collect()
+        });
+        stream.publish_state(IntervalType::Focus, since);
+        let today = SummaryStats {
+            total_focus: Duration::minutes(42),
+            ..Default::default()
+        };
+        let now = since + Duration::minutes(42);
+        while !stream.stats_due(now) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        stream.publish_stats(IntervalType::Focus, since, &today, now);
+        assert!(!stream.stats_due(now + Duration::seconds(30)));
+
+        let events = client.join().unwrap()?;
+        assert_eq!(events[0]["type"], "state");
+        assert_eq!(events[0]["state"], "focus");
+        assert_eq!(events[1]["type"], "stats");
+        assert_eq!(events[1]["state_mins"], 42);
+        assert_eq!(events[1]["focus_mins"], 42);
+        Ok(())
+    }
+}
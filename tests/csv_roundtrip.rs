@@ -0,0 +1,92 @@
+//! End-to-end checks that `neflo import` restores exactly what `neflo export` wrote.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const DB: &str = r#"{
+  "intervals": [
+    {
+      "start": "2023-01-01T10:00:00.123456789Z",
+      "end": "2023-01-01T10:25:00Z",
+      "kind": "Focus",
+      "device_id": "laptop, \"13 inch\""
+    },
+    {
+      "start": "2023-01-01T10:25:00Z",
+      "end": "2023-01-01T10:30:00Z",
+      "kind": "Idle"
+    },
+    {
+      "start": "2023-01-01T10:30:00Z",
+      "end": "2023-01-01T12:00:00Z",
+      "kind": "Away",
+      "device_id": "laptop"
+    }
+  ]
+}"#;
+
+fn neflo(data_dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_neflo"))
+        .arg("--data-dir")
+        .arg(data_dir)
+        .args(args)
+        .output()
+        .expect("failed to run neflo");
+    assert!(
+        output.status.success(),
+        "neflo {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn intervals(data_dir: &Path) -> serde_json::Value {
+    let data = fs::read_to_string(data_dir.join("db.json")).unwrap();
+    let db: serde_json::Value = serde_json::from_str(&data).unwrap();
+    db["intervals"].clone()
+}
+
+#[test]
+fn export_import_round_trip() {
+    let source = tempfile::tempdir().unwrap();
+    let target = tempfile::tempdir().unwrap();
+    fs::write(source.path().join("db.json"), DB).unwrap();
+
+    let csv_path = source.path().join("export.csv");
+    neflo(
+        source.path(),
+        &["export", "--output", csv_path.to_str().unwrap()],
+    );
+    let summary = neflo(target.path(), &["import", csv_path.to_str().unwrap()]);
+
+    assert!(summary.contains("Imported 3 of 3 intervals"));
+    assert_eq!(intervals(target.path()), intervals(source.path()));
+}
+
+#[test]
+fn import_is_idempotent() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("db.json"), DB).unwrap();
+
+    let csv = neflo(dir.path(), &["export"]);
+    let csv_path = dir.path().join("export.csv");
+    fs::write(&csv_path, csv).unwrap();
+
+    let summary = neflo(dir.path(), &["import", csv_path.to_str().unwrap()]);
+
+    assert!(summary.contains("Imported 0 of 3 intervals (3 duplicates skipped)"));
+    assert_eq!(intervals(dir.path()).as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn schema_lists_columns_and_kinds() {
+    let dir = tempfile::tempdir().unwrap();
+    let schema = neflo(dir.path(), &["schema"]);
+
+    for column in ["start", "end", "kind", "device_id"] {
+        assert!(schema.contains(column));
+    }
+    assert!(schema.contains("Focus, Idle, Locked, Away"));
+}